@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
 use pai_core::SourceKind;
 use std::path::PathBuf;
 
@@ -15,10 +15,31 @@ pub struct Cli {
     #[arg(short = 'd', value_name = "PATH", global = true)]
     pub db_path: Option<PathBuf>,
 
+    /// Increase log verbosity (-v for debug, -vv for trace)
+    #[arg(short = 'v', action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Only log errors
+    #[arg(short = 'q', conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Log output format (default: human, or $PAI_LOG_FORMAT)
+    #[arg(long, value_name = "FORMAT", global = true)]
+    pub log_format: Option<LogFormat>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+/// Log output formats
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines, coloured when stderr is a terminal
+    Human,
+    /// One JSON object per line, for journald, Loki and similar collectors
+    Json,
+}
+
 #[derive(Parser, Debug)]
 pub struct ExportOpts {
     /// Filter by source kind
@@ -41,13 +62,182 @@ pub struct ExportOpts {
     #[arg(short = 'q', value_name = "PATTERN")]
     pub query: Option<String>,
 
-    /// Output format
+    /// Output format: json, ndjson, rss, markdown, csv or tsv
     #[arg(short = 'f', value_name = "FORMAT", default_value = "json")]
     pub format: String,
 
-    /// Output file (default: stdout)
+    /// Output file (default: stdout); the output directory for markdown
     #[arg(short = 'o', value_name = "FILE")]
     pub output: Option<PathBuf>,
+
+    /// Front matter style for markdown exports
+    #[arg(long, value_name = "STYLE", default_value = "yaml")]
+    pub front_matter: FrontMatter,
+
+    /// Comma-separated columns for csv and tsv exports (e.g. id,title,url,word_count,domain)
+    #[arg(long, value_name = "COLUMNS")]
+    pub columns: Option<String>,
+}
+
+/// Front matter written at the top of exported Markdown files
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrontMatter {
+    /// `---` delimited YAML, as used by Hugo and Jekyll
+    Yaml,
+    /// `+++` delimited TOML, laid out for Zola
+    Toml,
+}
+
+/// `pai source` subcommands, which edit the `[sources]` section of config.toml
+#[derive(Subcommand, Debug)]
+pub enum SourceCommand {
+    /// Add a source from its URL, Bluesky handle or DID
+    Add {
+        /// Publication URL, Bluesky profile URL, handle or DID
+        #[arg(value_name = "URL")]
+        url: String,
+
+        /// Source kind, for URLs on custom domains (default: detected from the URL)
+        #[arg(short = 'k', value_name = "KIND")]
+        kind: Option<SourceKind>,
+
+        /// Source ID for Leaflet, BearBlog and feeds (default: the subdomain, or the host for feeds)
+        #[arg(long, value_name = "ID")]
+        id: Option<String>,
+
+        /// Add the source without fetching it first
+        #[arg(long)]
+        no_verify: bool,
+    },
+
+    /// List configured sources with their item counts and last sync
+    List,
+
+    /// Remove a source
+    Remove {
+        /// Source ID or URL
+        #[arg(value_name = "ID")]
+        id: String,
+
+        /// Only match sources of this kind
+        #[arg(short = 'k', value_name = "KIND")]
+        kind: Option<SourceKind>,
+    },
+
+    /// Enable a source so `pai sync` fetches it
+    Enable {
+        /// Source ID or URL
+        #[arg(value_name = "ID")]
+        id: String,
+
+        /// Only match sources of this kind
+        #[arg(short = 'k', value_name = "KIND")]
+        kind: Option<SourceKind>,
+    },
+
+    /// Disable a source without removing it
+    Disable {
+        /// Source ID or URL
+        #[arg(value_name = "ID")]
+        id: String,
+
+        /// Only match sources of this kind
+        #[arg(short = 'k', value_name = "KIND")]
+        kind: Option<SourceKind>,
+    },
+
+    /// Add every feed in an OPML file, detecting Substack, Leaflet, BearBlog and Bluesky feeds
+    Import {
+        /// OPML file exported from a feed reader
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+
+    /// Write every configured source as a list of feeds to subscribe to
+    Export {
+        /// Output format
+        #[arg(short = 'f', value_name = "FORMAT", default_value = "opml")]
+        format: SourceFormat,
+
+        /// Output file (default: stdout)
+        #[arg(short = 'o', value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Formats for `pai source export`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceFormat {
+    /// OPML 2.0, which feed readers import
+    Opml,
+}
+
+/// `pai import` subcommands, for history the source APIs no longer return
+#[derive(Subcommand, Debug)]
+pub enum ImportCommand {
+    /// Import the posts in a Bluesky repository export (.car)
+    Bluesky {
+        /// CAR file from "Export my data" in the Bluesky app, or com.atproto.sync.getRepo
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Handle to store the posts under (default: the handle in [sources.bluesky])
+        #[arg(long, value_name = "HANDLE")]
+        handle: Option<String>,
+    },
+
+    /// Import the published posts in a Substack data export (.zip)
+    Substack {
+        /// Zip file from the publication's Settings → Exports
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Publication URL (default: the base_url in [sources.substack])
+        #[arg(long, value_name = "URL")]
+        url: Option<String>,
+    },
+}
+
+/// `pai db` subcommands, for maintaining the SQLite database
+#[derive(Subcommand, Debug)]
+pub enum DbCommand {
+    /// Copy the database to a new file; safe while `pai serve` or a sync is running
+    Backup {
+        /// Backup file to create
+        #[arg(short = 'o', value_name = "FILE")]
+        output: PathBuf,
+    },
+
+    /// Replace the database with a backup made by `pai db backup`
+    Restore {
+        /// Backup file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Replace a database that already exists
+        #[arg(short = 'f')]
+        force: bool,
+    },
+
+    /// Rebuild the database file to give back the space of deleted items
+    Vacuum,
+
+    /// Refresh query planner statistics and merge full-text search indexes
+    Optimize,
+}
+
+/// `pai config` subcommands
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Validate config.toml, reporting unknown keys, duplicate source IDs and malformed URLs
+    Check,
+
+    /// Print the effective config, with defaults filled in and the database resolved
+    Show {
+        /// Print secrets (webhook secrets, the CORS dev key, database passwords) instead of masking them
+        #[arg(long)]
+        show_secrets: bool,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -67,6 +257,38 @@ pub enum Commands {
         source_id: Option<String>,
     },
 
+    /// Validate or print the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Manage the sources in config.toml
+    Source {
+        #[command(subcommand)]
+        command: SourceCommand,
+    },
+
+    /// Import older items from a Bluesky or Substack archive; safe to re-run
+    Import {
+        #[command(subcommand)]
+        command: ImportCommand,
+    },
+
+    /// Delete items that the [retention] rules no longer keep
+    Prune {
+        /// Show what would be deleted without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// List the Webmentions sent for links in synced items, newest first
+    Webmentions {
+        /// Maximum number of Webmentions to display
+        #[arg(short = 'n', value_name = "NUMBER", default_value = "20")]
+        limit: usize,
+    },
+
     /// Inspect stored items
     List {
         /// Filter by source kind
@@ -88,21 +310,68 @@ pub enum Commands {
         /// Filter items by substring in title/summary
         #[arg(short = 'q', value_name = "PATTERN")]
         query: Option<String>,
+
+        /// Comma-separated table columns, each optionally with a width (e.g. published_at,title:80,domain)
+        #[arg(long, value_name = "COLUMNS")]
+        columns: Option<String>,
+    },
+
+    /// Show one item with its metadata and content
+    Show {
+        /// Item ID, unique ID prefix, URL, or row number from the last `pai list`
+        #[arg(value_name = "ITEM")]
+        reference: String,
     },
 
+    /// Open an item's URL in the browser
+    Open {
+        /// Item ID, unique ID prefix, URL, or row number from the last `pai list`
+        #[arg(value_name = "ITEM")]
+        reference: String,
+    },
+
+    /// Browse the local index in an interactive terminal UI
+    Tui,
+
     /// Produce feeds or export files
     Export(ExportOpts),
 
+    /// Render a static site with feeds and a sitemap
+    #[command(name = "build-site")]
+    BuildSite {
+        /// Output directory
+        #[arg(short = 'o', value_name = "DIR")]
+        output: PathBuf,
+
+        /// Public base URL of the site (default: [server] public_url)
+        #[arg(short = 'u', value_name = "URL")]
+        base_url: Option<String>,
+
+        /// Items per index page
+        #[arg(short = 'n', value_name = "NUMBER", default_value = "20")]
+        per_page: usize,
+
+        /// Template directory (default: [server] templates)
+        #[arg(short = 't', value_name = "DIR")]
+        templates: Option<PathBuf>,
+    },
+
     /// Self-host HTTP API
     Serve {
-        /// Address to bind HTTP server to
-        #[arg(short = 'a', value_name = "ADDRESS", default_value = "127.0.0.1:8080")]
-        address: String,
+        /// Address to listen on: host:port or unix:/path/to.sock [default: [server] listen, or 127.0.0.1:8080]
+        #[arg(short = 'a', value_name = "ADDRESS")]
+        address: Option<String>,
     },
 
-    /// Verify database schema and print statistics
+    /// Verify database schema and integrity and print statistics
     DbCheck,
 
+    /// Back up, restore or compact the database
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+
     /// Initialize configuration file
     Init {
         /// Force overwrite existing config
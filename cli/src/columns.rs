@@ -0,0 +1,377 @@
+use crate::html::{tokenize, Token};
+use crate::{format_published_display, truncate_for_column};
+use pai_core::{Item, PaiError};
+use std::io::{self, Write};
+
+/// An `Item` field, or a value derived from one, that `pai list` and tabular exports can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Id,
+    SourceKind,
+    SourceId,
+    Author,
+    Title,
+    Summary,
+    Url,
+    ContentHtml,
+    Tags,
+    PublishedAt,
+    CreatedAt,
+    /// Words in the content, or in the summary when there is no content
+    WordCount,
+    /// Host name of the item's URL
+    Domain,
+}
+
+const COLUMNS: [Column; 13] = [
+    Column::Id,
+    Column::SourceKind,
+    Column::SourceId,
+    Column::Author,
+    Column::Title,
+    Column::Summary,
+    Column::Url,
+    Column::ContentHtml,
+    Column::Tags,
+    Column::PublishedAt,
+    Column::CreatedAt,
+    Column::WordCount,
+    Column::Domain,
+];
+
+/// Columns written by `pai export -f csv`/`tsv` without `--columns`
+pub const DEFAULT_EXPORT_COLUMNS: &str =
+    "id,source_kind,source_id,author,title,summary,url,tags,published_at,created_at";
+
+/// Columns shown by `pai list` without `--columns`
+pub const DEFAULT_LIST_COLUMNS: &str = "published_at:19,source_kind:9,source_id:24,title:60";
+
+impl Column {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::SourceKind => "source_kind",
+            Self::SourceId => "source_id",
+            Self::Author => "author",
+            Self::Title => "title",
+            Self::Summary => "summary",
+            Self::Url => "url",
+            Self::ContentHtml => "content_html",
+            Self::Tags => "tags",
+            Self::PublishedAt => "published_at",
+            Self::CreatedAt => "created_at",
+            Self::WordCount => "word_count",
+            Self::Domain => "domain",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        let name = match name.trim().to_ascii_lowercase().as_str() {
+            "kind" => "source_kind".to_string(),
+            "source" => "source_id".to_string(),
+            "content" => "content_html".to_string(),
+            "published" => "published_at".to_string(),
+            "created" => "created_at".to_string(),
+            "words" => "word_count".to_string(),
+            other => other.to_string(),
+        };
+        COLUMNS.into_iter().find(|column| column.name() == name)
+    }
+
+    /// Table header in `pai list`
+    fn label(self) -> &'static str {
+        match self {
+            Self::Id => "ID",
+            Self::SourceKind => "Kind",
+            Self::SourceId => "Source",
+            Self::Author => "Author",
+            Self::Title => "Title",
+            Self::Summary => "Summary",
+            Self::Url => "URL",
+            Self::ContentHtml => "Content",
+            Self::Tags => "Tags",
+            Self::PublishedAt => "Published",
+            Self::CreatedAt => "Created",
+            Self::WordCount => "Words",
+            Self::Domain => "Domain",
+        }
+    }
+
+    fn default_width(self) -> usize {
+        match self {
+            Self::SourceKind => 9,
+            Self::PublishedAt | Self::CreatedAt => 19,
+            Self::WordCount => 6,
+            Self::SourceId | Self::Author | Self::Domain => 24,
+            Self::Id | Self::Tags => 30,
+            Self::Url => 50,
+            Self::Title | Self::Summary | Self::ContentHtml => 60,
+        }
+    }
+
+    /// Raw value, as written to exports
+    pub fn value(self, item: &Item) -> String {
+        match self {
+            Self::Id => item.id.clone(),
+            Self::SourceKind => item.source_kind.to_string(),
+            Self::SourceId => item.source_id.clone(),
+            Self::Author => item.author.clone().unwrap_or_default(),
+            Self::Title => item.title.clone().unwrap_or_default(),
+            Self::Summary => item.summary.clone().unwrap_or_default(),
+            Self::Url => item.url.clone(),
+            Self::ContentHtml => item.content_html.clone().unwrap_or_default(),
+            Self::Tags => item.tags.join(", "),
+            Self::PublishedAt => item.published_at.clone(),
+            Self::CreatedAt => item.created_at.clone(),
+            Self::WordCount => word_count(item).to_string(),
+            Self::Domain => domain(&item.url).unwrap_or_default(),
+        }
+    }
+
+    /// Value shown in the `pai list` table, on a single line
+    fn display(self, item: &Item) -> String {
+        let value = match self {
+            Self::Title => item
+                .title
+                .as_deref()
+                .or(item.summary.as_deref())
+                .unwrap_or(&item.url)
+                .to_string(),
+            Self::PublishedAt => format_published_display(&item.published_at),
+            Self::CreatedAt => format_published_display(&item.created_at),
+            other => other.value(item),
+        };
+        value.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// A selected column and its width in the `pai list` table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnSpec {
+    pub column: Column,
+    pub width: usize,
+}
+
+/// Parses a comma-separated column list such as `published_at,title:80,domain`
+///
+/// A `:N` suffix sets the column's width in `pai list`; exports ignore widths.
+pub fn parse_columns(spec: &str) -> Result<Vec<ColumnSpec>, PaiError> {
+    let mut columns = Vec::new();
+    for part in spec.split(',').filter(|part| !part.trim().is_empty()) {
+        let (name, width) = match part.split_once(':') {
+            Some((name, width)) => {
+                let width = width
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|width| *width > 0)
+                    .ok_or_else(|| {
+                        PaiError::InvalidArgument(format!("Invalid width in column '{part}'. Use name:N with N > 0."))
+                    })?;
+                (name, Some(width))
+            }
+            None => (part, None),
+        };
+
+        let column = Column::parse(name).ok_or_else(|| {
+            let names: Vec<&str> = COLUMNS.iter().map(|column| column.name()).collect();
+            PaiError::InvalidArgument(format!(
+                "Unknown column '{}'. Expected one of: {}",
+                name.trim(),
+                names.join(", ")
+            ))
+        })?;
+        columns.push(ColumnSpec { column, width: width.unwrap_or_else(|| column.default_width()) });
+    }
+
+    if columns.is_empty() {
+        return Err(PaiError::InvalidArgument("No columns selected".to_string()));
+    }
+    Ok(columns)
+}
+
+fn word_count(item: &Item) -> usize {
+    match (&item.content_html, &item.summary) {
+        (Some(html), _) => tokenize(html)
+            .iter()
+            .map(|token| match token {
+                Token::Text(text) => text.split_whitespace().count(),
+                Token::Tag { .. } => 0,
+            })
+            .sum(),
+        (None, Some(summary)) => summary.split_whitespace().count(),
+        (None, None) => 0,
+    }
+}
+
+fn domain(url: &str) -> Option<String> {
+    Some(url::Url::parse(url).ok()?.host_str()?.to_string())
+}
+
+/// Writes `items` as CSV (RFC 4180) or TSV with a header row
+///
+/// CSV rows end in CRLF and quote fields holding the delimiter, quotes or line breaks. TSV fields cannot hold tabs or
+/// line breaks, so those are written as `\t`, `\n` and `\r`, and backslashes as `\\`. In both, a field starting with
+/// `=`, `+`, `-` or `@` gets a leading `'`, so spreadsheets show it as text instead of running it as a formula.
+pub fn write_delimited(
+    items: &[Item], columns: &[ColumnSpec], delimiter: char, writer: &mut dyn Write,
+) -> io::Result<()> {
+    let tsv = delimiter == '\t';
+    let escape = |value: &str| {
+        let value = defuse_formula(value);
+        if tsv {
+            escape_tsv(&value)
+        } else {
+            escape_csv(&value, delimiter)
+        }
+    };
+    let (separator, line_end) = (delimiter.to_string(), if tsv { "\n" } else { "\r\n" });
+
+    let header: Vec<String> = columns.iter().map(|spec| escape(spec.column.name())).collect();
+    write!(writer, "{}{line_end}", header.join(&separator))?;
+    for item in items {
+        let row: Vec<String> = columns.iter().map(|spec| escape(&spec.column.value(item))).collect();
+        write!(writer, "{}{line_end}", row.join(&separator))?;
+    }
+    Ok(())
+}
+
+fn defuse_formula(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{value}").into()
+    } else {
+        value.into()
+    }
+}
+
+fn escape_csv(value: &str, delimiter: char) -> String {
+    if value.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape_tsv(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Writes the `pai list` table with the selected columns
+///
+/// Rows are numbered from 1 so `pai show` and `pai open` can refer to them.
+pub fn write_items_table<W: Write>(items: &[Item], columns: &[ColumnSpec], writer: &mut W) -> io::Result<()> {
+    let index_width = items.len().to_string().len();
+    let header: Vec<String> = std::iter::once(format!("{:>index_width$}", "#"))
+        .chain(columns.iter().map(|spec| {
+            format!(
+                "{:<width$}",
+                truncate_for_column(spec.column.label(), spec.width),
+                width = spec.width
+            )
+        }))
+        .collect();
+    let header = format!("| {} |", header.join(" | "));
+    let separator = "-".repeat(header.chars().count());
+
+    writeln!(writer, "{separator}")?;
+    writeln!(writer, "{header}")?;
+    writeln!(writer, "{separator}")?;
+
+    for (index, item) in items.iter().enumerate() {
+        let cells: Vec<String> = std::iter::once(format!("{:>index_width$}", index + 1))
+            .chain(columns.iter().map(|spec| {
+                let value = truncate_for_column(&spec.column.display(item), spec.width);
+                format!("{value:<width$}", width = spec.width)
+            }))
+            .collect();
+        writeln!(writer, "| {} |", cells.join(" | "))?;
+    }
+
+    writeln!(writer, "{separator}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pai_core::SourceKind;
+
+    fn item() -> Item {
+        Item::test("https://me.substack.com/p/post")
+            .with_source(SourceKind::Substack, "me.substack.com")
+            .with_title("Commas, \"quotes\"")
+            .with_summary("Line one\nline\ttwo")
+            .with_url("https://user@me.substack.com:443/p/post?x=1")
+            .with_content_html("<p>Three <em>short</em> words</p>")
+            .with_tags(&["a", "b"])
+    }
+
+    #[test]
+    fn parses_columns_with_aliases_and_widths() {
+        let columns = parse_columns("kind, title:80,words").unwrap();
+        assert_eq!(columns[0], ColumnSpec { column: Column::SourceKind, width: 9 });
+        assert_eq!(columns[1], ColumnSpec { column: Column::Title, width: 80 });
+        assert_eq!(columns[2].column, Column::WordCount);
+
+        assert!(parse_columns("title:0").is_err());
+        let err = parse_columns("nope").unwrap_err();
+        assert!(err.to_string().contains("Unknown column 'nope'"));
+        assert!(parse_columns(DEFAULT_EXPORT_COLUMNS).is_ok());
+        assert!(parse_columns(DEFAULT_LIST_COLUMNS).is_ok());
+    }
+
+    #[test]
+    fn derives_word_count_and_domain() {
+        assert_eq!(Column::WordCount.value(&item()), "3");
+        assert_eq!(Column::Domain.value(&item()), "me.substack.com");
+        assert_eq!(Column::Tags.value(&item()), "a, b");
+    }
+
+    #[test]
+    fn csv_and_tsv_escape_fields() {
+        let columns = parse_columns("title,summary,domain").unwrap();
+
+        let mut csv = Vec::new();
+        write_delimited(&[item()], &columns, ',', &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "title,summary,domain\r\n\"Commas, \"\"quotes\"\"\",\"Line one\nline\ttwo\",me.substack.com\r\n"
+        );
+
+        let mut tsv = Vec::new();
+        write_delimited(&[item()], &columns, '\t', &mut tsv).unwrap();
+        assert_eq!(
+            String::from_utf8(tsv).unwrap(),
+            "title\tsummary\tdomain\nCommas, \"quotes\"\tLine one\\nline\\ttwo\tme.substack.com\n"
+        );
+    }
+
+    #[test]
+    fn delimited_fields_never_start_a_formula() {
+        let columns = parse_columns("title,summary,author,domain").unwrap();
+        let item = item()
+            .with_title("=HYPERLINK(\"https://evil.example\", \"x\")")
+            .with_summary("+1")
+            .with_author("@me")
+            .with_url("not a url");
+
+        let mut csv = Vec::new();
+        write_delimited(&[item], &columns, ',', &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap().lines().nth(1).unwrap(),
+            "\"'=HYPERLINK(\"\"https://evil.example\"\", \"\"x\"\")\",'+1,'@me,"
+        );
+    }
+
+    #[test]
+    fn table_uses_selected_columns_and_widths() {
+        let mut buffer = Vec::new();
+        write_items_table(&[item()], &parse_columns("domain:10,words").unwrap(), &mut buffer).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        assert!(output.contains("| # | Domain     | Words  |"));
+        assert!(output.contains("| 1 | me.subs... | 3      |"));
+    }
+}
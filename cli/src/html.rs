@@ -0,0 +1,333 @@
+/// A piece of HTML: decoded text or a tag
+#[derive(Debug, PartialEq, Eq)]
+pub enum Token<'a> {
+    Text(String),
+    Tag {
+        /// Lowercase tag name
+        name: String,
+        closing: bool,
+        /// Source between `<` and `>`, for reading attributes
+        source: &'a str,
+    },
+}
+
+/// Splits `html` into text and tags, dropping `<script>` and `<style>` elements
+pub fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        push_text(&mut tokens, &rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            push_text(&mut tokens, &rest[start..]);
+            return tokens;
+        };
+        let source = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+
+        let (name, closing) = tag_name(source);
+        if !closing && matches!(name.as_str(), "script" | "style") {
+            let close = format!("</{name}");
+            rest = rest
+                .to_ascii_lowercase()
+                .find(&close)
+                .and_then(|index| rest[index..].find('>').map(|end| &rest[index + end + 1..]))
+                .unwrap_or("");
+            continue;
+        }
+        tokens.push(Token::Tag { name, closing, source });
+    }
+    push_text(&mut tokens, rest);
+
+    tokens
+}
+
+/// Inline markup that Markdown keeps and plain text drops
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Strong,
+    Emphasis,
+    Strikethrough,
+    /// Inline `<code>`, outside `<pre>`
+    Code,
+}
+
+/// How a list item is marked: a bullet, or its number in an ordered list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    Bullet,
+    Number(usize),
+}
+
+/// An output format for [`walk`], which calls it for the structure it finds in HTML content
+pub trait Sink {
+    /// Text outside `<pre>`, with its whitespace still as in the source
+    fn text(&mut self, text: &str);
+    /// Text inside `<pre>`, to be kept verbatim
+    fn preformatted(&mut self, text: &str);
+    /// A paragraph or other block starts or ends
+    fn block(&mut self);
+    fn line_break(&mut self);
+    fn rule(&mut self);
+    fn heading(&mut self, level: usize, closing: bool);
+    fn style(&mut self, style: Style);
+    fn pre(&mut self, closing: bool);
+    /// A list opens or closes, leaving `depth` lists open
+    fn list(&mut self, closing: bool, depth: usize);
+    /// A list item starts inside `depth` open lists
+    fn list_item(&mut self, marker: Marker, depth: usize);
+    fn end_list_item(&mut self);
+    fn quote(&mut self, closing: bool);
+    /// An anchor with an `href` opens or closes; anchors without one only keep their text
+    fn link(&mut self, href: &str, closing: bool);
+    fn image(&mut self, src: &str, alt: Option<&str>);
+}
+
+/// Walks the HTML found in feed content, numbering ordered lists and pairing anchors with their
+/// `href` for `sink`
+///
+/// Handles paragraphs, headings, emphasis, links, images, code, lists and blockquotes; other tags
+/// are dropped and their text kept.
+pub fn walk(html: &str, sink: &mut impl Sink) {
+    // Open lists, with the next number for ordered ones
+    let mut lists: Vec<Option<usize>> = Vec::new();
+    // Open anchors; `None` for those without an `href`
+    let mut links: Vec<Option<String>> = Vec::new();
+    let mut pre = false;
+
+    for token in tokenize(html) {
+        let (name, closing, source) = match token {
+            Token::Text(text) if pre => {
+                sink.preformatted(&text);
+                continue;
+            }
+            Token::Text(text) => {
+                sink.text(&text);
+                continue;
+            }
+            Token::Tag { name, closing, source } => (name, closing, source),
+        };
+        match (name.as_str(), closing) {
+            ("p" | "div" | "section" | "article" | "header" | "footer" | "figure" | "figcaption", _) => sink.block(),
+            ("br", _) => sink.line_break(),
+            ("hr", _) => sink.rule(),
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", _) => sink.heading(name[1..].parse().unwrap_or(1), closing),
+            ("strong" | "b", _) => sink.style(Style::Strong),
+            ("em" | "i", _) => sink.style(Style::Emphasis),
+            ("del" | "s", _) => sink.style(Style::Strikethrough),
+            ("code", _) if !pre => sink.style(Style::Code),
+            ("pre", _) => {
+                pre = !closing;
+                sink.pre(closing);
+            }
+            ("ul" | "ol", false) => {
+                lists.push((name == "ol").then_some(1));
+                sink.list(false, lists.len());
+            }
+            ("ul" | "ol", true) => {
+                lists.pop();
+                sink.list(true, lists.len());
+            }
+            ("li", false) => {
+                let marker = match lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        Marker::Number(*number - 1)
+                    }
+                    _ => Marker::Bullet,
+                };
+                sink.list_item(marker, lists.len());
+            }
+            ("li", true) => sink.end_list_item(),
+            ("blockquote", _) => sink.quote(closing),
+            ("a", false) => {
+                let href = attribute(source, "href");
+                if let Some(href) = &href {
+                    sink.link(href, false);
+                }
+                links.push(href);
+            }
+            ("a", true) => {
+                if let Some(Some(href)) = links.pop() {
+                    sink.link(&href, true);
+                }
+            }
+            ("img", false) => {
+                if let Some(src) = attribute(source, "src") {
+                    sink.image(&src, attribute(source, "alt").as_deref());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn push_text(tokens: &mut Vec<Token<'_>>, raw: &str) {
+    if !raw.is_empty() {
+        tokens.push(Token::Text(decode_entities(raw)));
+    }
+}
+
+/// Lowercase tag name and whether it is a closing tag
+fn tag_name(tag: &str) -> (String, bool) {
+    let tag = tag.trim();
+    let (closing, tag) = match tag.strip_prefix('/') {
+        Some(rest) => (true, rest),
+        None => (false, tag),
+    };
+    let name = tag
+        .split(|c: char| c.is_whitespace() || c == '/')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    (name, closing)
+}
+
+/// Value of a quoted or bare attribute in a tag's source
+pub fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut search = 0;
+    while let Some(found) = lower[search..].find(name) {
+        let start = search + found;
+        search = start + name.len();
+        let preceded_by_space = lower[..start].ends_with(char::is_whitespace);
+        let rest = lower[search..].trim_start();
+        if !preceded_by_space || !rest.starts_with('=') {
+            continue;
+        }
+
+        let value_start = tag.len() - rest.len() + 1;
+        let value = tag[value_start..].trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or_default(),
+            _ => value
+                .split(|c: char| c.is_whitespace() || c == '>')
+                .next()
+                .unwrap_or_default(),
+        };
+        return Some(decode_entities(value));
+    }
+    None
+}
+
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        });
+
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes_tags_text_and_attributes() {
+        let tokens = tokenize(r#"<p class=x>a &amp; b<script>alert("<p>")</script><A HREF='/x?a=1&amp;b=2'>c</a></p>"#);
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(tokens[1], Token::Text("a & b".to_string()));
+        let Token::Tag { name, closing, source } = &tokens[2] else { panic!("expected a tag") };
+        assert_eq!((name.as_str(), *closing), ("a", false));
+        assert_eq!(attribute(source, "href").as_deref(), Some("/x?a=1&b=2"));
+        assert_eq!(attribute(source, "title"), None);
+        assert!(matches!(&tokens[5], Token::Tag { name, closing: true, .. } if name == "p"));
+        assert_eq!(decode_entities("&#8217;&#x2014;&bogus;"), "\u{2019}\u{2014}&bogus;");
+    }
+
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl Sink for Events {
+        fn text(&mut self, text: &str) {
+            self.0.push(format!("text {text}"));
+        }
+        fn preformatted(&mut self, text: &str) {
+            self.0.push(format!("pre {text}"));
+        }
+        fn block(&mut self) {}
+        fn line_break(&mut self) {}
+        fn rule(&mut self) {}
+        fn heading(&mut self, _level: usize, _closing: bool) {}
+        fn style(&mut self, style: Style) {
+            self.0.push(format!("{style:?}"));
+        }
+        fn pre(&mut self, _closing: bool) {}
+        fn list(&mut self, closing: bool, depth: usize) {
+            self.0.push(format!("list {closing} {depth}"));
+        }
+        fn list_item(&mut self, marker: Marker, depth: usize) {
+            self.0.push(format!("{marker:?} {depth}"));
+        }
+        fn end_list_item(&mut self) {}
+        fn quote(&mut self, _closing: bool) {}
+        fn link(&mut self, href: &str, closing: bool) {
+            self.0.push(format!("link {href} {closing}"));
+        }
+        fn image(&mut self, src: &str, alt: Option<&str>) {
+            self.0.push(format!("image {src} {alt:?}"));
+        }
+    }
+
+    #[test]
+    fn walks_lists_links_and_preformatted_text() {
+        let mut events = Events::default();
+        walk(
+            r#"<ol><li><a name=x>a</a><ul><li><a href="/b">b</a></li></ul></li><li>c</li></ol><pre><code>d</code></pre><img src=e.png>"#,
+            &mut events,
+        );
+        assert_eq!(
+            events.0,
+            [
+                "list false 1",
+                "Number(1) 1",
+                "text a",
+                "list false 2",
+                "Bullet 2",
+                "link /b false",
+                "text b",
+                "link /b true",
+                "list true 1",
+                "Number(2) 1",
+                "text c",
+                "list true 0",
+                "pre d",
+                "image e.png None",
+            ]
+        );
+    }
+}
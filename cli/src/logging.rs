@@ -0,0 +1,72 @@
+use crate::app::LogFormat;
+use std::io::IsTerminal;
+use tracing_subscriber::EnvFilter;
+
+/// Environment variable holding an `EnvFilter` directive string (e.g. `debug` or `pai_core=trace`)
+pub const LOG_ENV: &str = "PAI_LOG";
+
+/// Environment variable selecting the log format when `--log-format` is not given
+pub const LOG_FORMAT_ENV: &str = "PAI_LOG_FORMAT";
+
+/// Installs the global tracing subscriber
+///
+/// `-v`/`-q` take precedence over `PAI_LOG`, which takes precedence over the default of `info` for
+/// the pai crates and `warn` for dependencies. Logs always go to stderr so command output on
+/// stdout (tables, exports, manpages) stays pipeable.
+pub fn init(verbose: u8, quiet: bool, format: Option<LogFormat>) {
+    let env_directives = std::env::var(LOG_ENV).ok().filter(|value| !value.trim().is_empty());
+    let mut invalid_env = None;
+
+    let filter = match (quiet, verbose, env_directives) {
+        (true, _, _) => EnvFilter::new(default_directives("error")),
+        (false, 1, _) => EnvFilter::new(default_directives("debug")),
+        (false, 2.., _) => EnvFilter::new(default_directives("trace")),
+        (false, 0, Some(directives)) => EnvFilter::try_new(&directives).unwrap_or_else(|_| {
+            invalid_env = Some(directives);
+            EnvFilter::new(default_directives("info"))
+        }),
+        (false, 0, None) => EnvFilter::new(default_directives("info")),
+    };
+
+    let format = format.or_else(format_from_env).unwrap_or(LogFormat::Human);
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_target(false);
+
+    let _ = match format {
+        LogFormat::Json => builder.json().with_current_span(true).with_span_list(true).try_init(),
+        LogFormat::Human if std::io::stderr().is_terminal() => builder.without_time().try_init(),
+        LogFormat::Human => builder.with_ansi(false).try_init(),
+    };
+
+    if let Some(directives) = invalid_env {
+        tracing::warn!("Ignoring invalid {LOG_ENV} value {directives:?}");
+    }
+}
+
+fn default_directives(level: &str) -> String {
+    format!("warn,pai={level},pai_core={level},pai_server={level}")
+}
+
+fn format_from_env() -> Option<LogFormat> {
+    let value = std::env::var(LOG_FORMAT_ENV).ok()?;
+    match value.trim().to_ascii_lowercase().as_str() {
+        "json" => Some(LogFormat::Json),
+        "human" | "" => Some(LogFormat::Human),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_directives_scope_level_to_pai_crates() {
+        let directives = default_directives("debug");
+        assert!(directives.starts_with("warn,"));
+        assert!(directives.contains("pai_core=debug"));
+        assert!(EnvFilter::try_new(&directives).is_ok());
+    }
+}
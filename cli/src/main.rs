@@ -1,35 +1,57 @@
 mod app;
+mod columns;
+mod html;
+mod logging;
+mod markdown;
 mod paths;
+mod show;
+mod site;
+mod sources;
+mod tui;
 
-use app::{Cli, Commands, ExportOpts};
+use app::{Cli, Commands, ConfigCommand, DbCommand, ExportOpts, ImportCommand, SourceCommand, SourceFormat};
 use chrono::{DateTime, Duration, Utc};
 use clap::Parser;
+use columns::ColumnSpec;
 use owo_colors::OwoColorize;
-use pai_core::{Config, Item, ListFilter, PaiError, SourceKind};
+use pai_core::{Config, ConfigReport, IssueLevel, Item, ListFilter, PaiError, SourceKind, StatsGroup, Storage};
 use pai_server::SqliteStorage;
+use paths::Backend;
 use rss::{Channel, ChannelBuilder, ItemBuilder};
 use std::fs;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-const PUBLISHED_WIDTH: usize = 19;
-const KIND_WIDTH: usize = 9;
-const SOURCE_WIDTH: usize = 24;
-const TITLE_WIDTH: usize = 60;
 const MAN_PAGE: &str = include_str!(env!("PAI_MAN_PAGE"));
 
 fn main() {
     let cli = Cli::parse();
+    logging::init(cli.verbose, cli.quiet, cli.log_format);
 
     let result = match cli.command {
         Commands::Sync { all, kind, source_id } => handle_sync(cli.config_dir, cli.db_path, all, kind, source_id),
-        Commands::List { kind, source_id, limit, since, query } => {
-            handle_list(cli.db_path, kind, source_id, limit, since, query)
+        Commands::Config { command } => handle_config(cli.config_dir, cli.db_path, command),
+        Commands::Source { command } => handle_source(cli.config_dir, cli.db_path, command),
+        Commands::Import { command } => handle_import(cli.config_dir, cli.db_path, command),
+        Commands::Prune { dry_run } => handle_prune(cli.config_dir, cli.db_path, dry_run),
+        Commands::Webmentions { limit } => handle_webmentions(cli.config_dir, cli.db_path, limit),
+        Commands::List { kind, source_id, limit, since, query, columns } => sqlite_path(cli.config_dir, cli.db_path)
+            .and_then(|db_path| handle_list(db_path, kind, source_id, limit, since, query, columns)),
+        Commands::Show { reference } => handle_show(cli.config_dir, cli.db_path, reference),
+        Commands::Open { reference } => handle_open(cli.config_dir, cli.db_path, reference),
+        Commands::Tui => sqlite_path(cli.config_dir, cli.db_path).and_then(|db_path| tui::run(&db_path)),
+        Commands::Export(opts) => {
+            sqlite_path(cli.config_dir, cli.db_path).and_then(|db_path| handle_export(db_path, opts))
+        }
+        Commands::BuildSite { output, base_url, per_page, templates } => {
+            handle_build_site(cli.config_dir, cli.db_path, output, base_url, per_page, templates)
+        }
+        Commands::Serve { address } => handle_serve(cli.config_dir, cli.db_path, address),
+        Commands::DbCheck => sqlite_path(cli.config_dir, cli.db_path).and_then(handle_db_check),
+        Commands::Db { command } => {
+            sqlite_path(cli.config_dir, cli.db_path).and_then(|db_path| handle_db(db_path, command))
         }
-        Commands::Export(opts) => handle_export(cli.db_path, opts),
-        Commands::Serve { address } => handle_serve(cli.db_path, address),
-        Commands::DbCheck => handle_db_check(cli.db_path),
         Commands::Init { force } => handle_init(cli.config_dir, force),
         Commands::Man { output, install, install_dir } => handle_man(output, install, install_dir),
         Commands::CfInit { output_dir, dry_run } => handle_cf_init(output_dir, dry_run),
@@ -45,38 +67,404 @@ fn handle_sync(
     config_dir: Option<PathBuf>, db_path: Option<PathBuf>, _all: bool, kind: Option<SourceKind>,
     source_id: Option<String>,
 ) -> Result<(), PaiError> {
-    let db_path = paths::resolve_db_path(db_path)?;
-    let config_dir = paths::resolve_config_dir(config_dir)?;
+    let report = check_config(config_dir.clone())?;
+    if report.layers.is_empty() {
+        return Err(PaiError::Config(format!(
+            "No config file at {}; create one with `pai init` or `pai source add`, or set PAI_* variables",
+            paths::resolve_config_dir(config_dir)?.join("config.toml").display()
+        )));
+    }
+    let config = report.into_config()?;
 
-    let storage = SqliteStorage::new(db_path)?;
+    let storage = open_storage(&paths::resolve_backend(db_path, &config.database)?)?;
+    let count = pai_core::sync_all_sources(&config, storage.as_ref(), kind, source_id.as_deref())?;
 
-    let config_path = config_dir.join("config.toml");
-    let config = if config_path.exists() {
-        Config::from_file(&config_path)?
+    if count == 0 {
+        tracing::info!("No sources synced (check your config or filters)");
     } else {
-        println!(
-            "{} No config file found, using default configuration",
-            "Warning:".yellow()
-        );
-        Config::default()
-    };
+        tracing::info!(sources = count, "Synced {count} source(s)");
+    }
 
-    let count = pai_core::sync_all_sources(&config, &storage, kind, source_id.as_deref())?;
+    if config.retention.after_sync {
+        let pruned = config.retention.prune(storage.as_ref(), false)?;
+        if !pruned.is_empty() {
+            tracing::info!(items = pruned.len(), "Pruned {} item(s)", pruned.len());
+        }
+    }
 
-    if count == 0 {
-        println!("{} No sources synced (check your config or filters)", "Info:".cyan());
+    Ok(())
+}
+
+fn handle_prune(config_dir: Option<PathBuf>, db_path: Option<PathBuf>, dry_run: bool) -> Result<(), PaiError> {
+    let config = load_config(config_dir)?;
+    if config.retention.rules.is_empty() {
+        return Err(PaiError::Config(
+            "No retention rules are configured; add [[retention.rules]] to config.toml".to_string(),
+        ));
+    }
+
+    let storage = open_storage(&paths::resolve_backend(db_path, &config.database)?)?;
+    let pruned = config.retention.prune(storage.as_ref(), dry_run)?;
+
+    let mut sources: Vec<(SourceKind, &str, usize)> = Vec::new();
+    for item in &pruned {
+        match sources.last_mut() {
+            Some((kind, id, count)) if *kind == item.source_kind && *id == item.source_id => *count += 1,
+            _ => sources.push((item.source_kind, &item.source_id, 1)),
+        }
+    }
+
+    if dry_run {
+        for item in &pruned {
+            println!(
+                "{} {} {}",
+                item.published_at.bright_black(),
+                format!("[{}:{}]", item.source_kind, item.source_id).cyan(),
+                item.title.as_deref().unwrap_or(&item.url)
+            );
+        }
+        tracing::info!(items = pruned.len(), "Would delete {} item(s)", pruned.len());
     } else {
-        println!("{} Synced {}", "Success:".green(), format!("{count} source(s)").bold());
+        tracing::info!(items = pruned.len(), "Deleted {} item(s)", pruned.len());
+    }
+    for (kind, id, count) in sources {
+        tracing::info!(%kind, id, items = count, "{kind}:{id}: {count}");
+    }
+    Ok(())
+}
+
+fn handle_import(
+    config_dir: Option<PathBuf>, db_path: Option<PathBuf>, command: ImportCommand,
+) -> Result<(), PaiError> {
+    let config = load_config(config_dir)?;
+    let read = |file: &Path| {
+        fs::read(file).map_err(|e| PaiError::InvalidArgument(format!("Failed to read {}: {e}", file.display())))
+    };
+
+    let (source, items) = match command {
+        ImportCommand::Bluesky { file, handle } => {
+            let handle = handle
+                .or_else(|| config.sources.bluesky.as_ref().map(|bluesky| bluesky.handle.clone()))
+                .ok_or_else(|| {
+                    PaiError::InvalidArgument(
+                        "No [sources.bluesky] is configured; pass the handle with --handle".to_string(),
+                    )
+                })?;
+            let handle = handle.trim_start_matches('@').to_string();
+            (handle.clone(), pai_core::read_bluesky_repo(&read(&file)?, &handle)?)
+        }
+        ImportCommand::Substack { file, url } => {
+            let substack = match url {
+                Some(url) => pai_core::SubstackConfig { enabled: true, base_url: url },
+                None => config.sources.substack.clone().ok_or_else(|| {
+                    PaiError::InvalidArgument(
+                        "No [sources.substack] is configured; pass the publication URL with --url".to_string(),
+                    )
+                })?,
+            };
+            (
+                substack.source_id(),
+                pai_core::read_substack_export(&read(&file)?, &substack)?,
+            )
+        }
+    };
+
+    let storage = open_storage(&paths::resolve_backend(db_path, &config.database)?)?;
+    // Stored items come from a sync or an earlier import and are at least as fresh as the archive
+    let items_read = items.len();
+    let mut new_items = Vec::new();
+    for item in items {
+        if storage.get_item(&item.id)?.is_none() {
+            new_items.push(item);
+        }
+    }
+    storage.insert_or_replace_items(&new_items)?;
+
+    tracing::info!(
+        items = new_items.len(),
+        skipped = items_read - new_items.len(),
+        "Imported {} new item(s) from {source}",
+        new_items.len()
+    );
+    Ok(())
+}
+
+fn handle_config(
+    config_dir: Option<PathBuf>, db_path: Option<PathBuf>, command: ConfigCommand,
+) -> Result<(), PaiError> {
+    let report = check_config(config_dir.clone())?;
+
+    match command {
+        ConfigCommand::Check => {
+            if report.layers.is_empty() {
+                return Err(PaiError::Config(format!(
+                    "No config file at {}; create one with `pai init`",
+                    paths::resolve_config_dir(config_dir)?.join("config.toml").display()
+                )));
+            }
+            for layer in &report.layers {
+                println!("{} {layer}", "Checked:".bright_black());
+            }
+            for issue in &report.issues {
+                let level = match issue.level {
+                    IssueLevel::Error => "error:".red().bold().to_string(),
+                    IssueLevel::Warning => "warning:".yellow().bold().to_string(),
+                };
+                println!("{level} {issue}");
+            }
+
+            let errors = report
+                .issues
+                .iter()
+                .filter(|issue| issue.level == IssueLevel::Error)
+                .count();
+            let warnings = report.issues.len() - errors;
+            if errors > 0 {
+                return Err(PaiError::Config(format!(
+                    "Config has {errors} error(s) and {warnings} warning(s)"
+                )));
+            }
+            println!("{} config is valid ({warnings} warning(s))", "OK:".green().bold());
+            Ok(())
+        }
+        ConfigCommand::Show { show_secrets } => {
+            let db_override = db_path.clone();
+            let layers = report.layers.clone();
+            let mut config = report.into_config()?;
+            match paths::resolve_backend(db_path, &config.database)? {
+                Backend::Sqlite(path) => config.database.path = Some(path.display().to_string()),
+                Backend::Postgres(url) => config.database.url = Some(url),
+            }
+            if !show_secrets {
+                mask_secrets(&mut config);
+            }
+
+            let rendered =
+                toml::to_string(&config).map_err(|e| PaiError::Config(format!("Failed to render config: {e}")))?;
+            println!("# Effective configuration, from:");
+            println!("#   defaults");
+            for layer in layers {
+                println!("#   {layer}");
+            }
+            if let Some(path) = db_override {
+                println!("#   -d {}", path.display());
+            }
+            print!("{rendered}");
+            Ok(())
+        }
+    }
+}
+
+/// Replaces secrets in `config` so it can be printed
+fn mask_secrets(config: &mut Config) {
+    const MASK: &str = "***";
+    if let Some(url) = &config.database.url {
+        config.database.url = Some(pai_server::redact_url(url));
     }
+    if config.cors.dev_key.is_some() {
+        config.cors.dev_key = Some(MASK.to_string());
+    }
+    for webhook in &mut config.webhooks {
+        if webhook.secret.is_some() {
+            webhook.secret = Some(MASK.to_string());
+        }
+        // Slack and Discord webhook URLs carry their token in the path
+        if let Ok(mut url) = url::Url::parse(&webhook.url) {
+            if url.path() != "/" || url.query().is_some() {
+                url.set_path(MASK);
+                url.set_query(None);
+                webhook.url = url.to_string();
+            }
+        }
+    }
+}
+
+fn handle_source(
+    config_dir: Option<PathBuf>, db_path: Option<PathBuf>, command: SourceCommand,
+) -> Result<(), PaiError> {
+    let config_path = paths::resolve_config_dir(config_dir.clone())?.join("config.toml");
+    let mut document = sources::ConfigDocument::open(&config_path)?;
+
+    let (verb, source) = match command {
+        SourceCommand::List => return handle_source_list(config_dir, db_path, &document),
+        SourceCommand::Import { file } => return handle_source_import(&config_path, document, &file),
+        SourceCommand::Export { format, output } => return handle_source_export(config_dir, format, output),
+        SourceCommand::Add { url, kind, id, no_verify } => {
+            let source = sources::detect_source(&url, kind, id)?;
+            document.add(&source)?;
+            if !no_verify {
+                tracing::info!(url = %source.url, "Fetching {}", source.url);
+                let count = sources::verify_source(&source).map_err(|e| {
+                    PaiError::Fetch(format!(
+                        "{} could not be fetched ({e}); use --no-verify to add it anyway",
+                        source.url
+                    ))
+                })?;
+                tracing::info!(items = count, "Fetched {count} item(s)");
+            }
+            ("Added", source)
+        }
+        SourceCommand::Remove { id, kind } => ("Removed", document.remove(&id, kind)?),
+        SourceCommand::Enable { id, kind } => ("Enabled", document.set_enabled(&id, kind, true)?),
+        SourceCommand::Disable { id, kind } => ("Disabled", document.set_enabled(&id, kind, false)?),
+    };
 
+    document.save()?;
+    tracing::info!(
+        kind = %source.kind,
+        id = %source.id,
+        "{verb} {} source {} in {}",
+        source.kind,
+        source.id,
+        config_path.display()
+    );
+    Ok(())
+}
+
+fn handle_source_import(
+    config_path: &Path, mut document: sources::ConfigDocument, file: &Path,
+) -> Result<(), PaiError> {
+    let opml = fs::read_to_string(file)
+        .map_err(|e| PaiError::InvalidArgument(format!("Failed to read {}: {e}", file.display())))?;
+    let results = document.import_opml(&opml)?;
+    if results.is_empty() {
+        return Err(PaiError::InvalidArgument(format!(
+            "{} has no outlines with an xmlUrl",
+            file.display()
+        )));
+    }
+
+    let mut added = 0;
+    for result in &results {
+        match result {
+            sources::Imported::Added(source) => {
+                added += 1;
+                tracing::info!(kind = %source.kind, id = %source.id, "Added {} source {}", source.kind, source.id);
+            }
+            sources::Imported::Skipped(url, reason) => tracing::info!(%url, "Skipped {url} ({reason})"),
+        }
+    }
+    if added > 0 {
+        document.save()?;
+    }
+
+    tracing::info!(
+        added,
+        "Imported {added} of {} feeds into {}",
+        results.len(),
+        config_path.display()
+    );
+    Ok(())
+}
+
+fn handle_source_export(
+    config_dir: Option<PathBuf>, format: SourceFormat, output: Option<PathBuf>,
+) -> Result<(), PaiError> {
+    let config = load_config(config_dir)?;
+    let configured = config.sources.configured();
+    let content = match format {
+        SourceFormat::Opml => sources::to_opml(&configured),
+    };
+
+    let mut writer = create_output_writer(output.as_ref())?;
+    writer.write_all(content.as_bytes())?;
+    writer.flush()?;
+
+    if let Some(path) = output {
+        tracing::info!(
+            sources = configured.len(),
+            "Exported {} sources to {}",
+            configured.len(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+fn handle_source_list(
+    config_dir: Option<PathBuf>, db_path: Option<PathBuf>, document: &sources::ConfigDocument,
+) -> Result<(), PaiError> {
+    let configured = document.sources()?;
+    if configured.is_empty() {
+        println!("{}", "No sources configured".yellow());
+        println!("{} Add one with {}", "Hint:".yellow(), "pai source add <URL>".bold());
+        return Ok(());
+    }
+
+    let config = load_config(config_dir)?;
+    let storage = open_storage(&paths::resolve_backend(db_path, &config.database)?)?;
+    let stats = storage.get_stats(StatsGroup::Source)?;
+    let statuses = storage.get_sync_status()?;
+
+    println!(
+        "{}",
+        format!(
+            "{:<9} {:<32} {:<8} {:>6}  {}",
+            "KIND", "ID", "ENABLED", "ITEMS", "LAST SYNC"
+        )
+        .bold()
+    );
+    for source in configured {
+        let count = stats
+            .iter()
+            .find(|stat| stat.source_kind == source.kind && stat.source_id.as_deref() == Some(source.id.as_str()))
+            .map_or(0, |stat| stat.count);
+        let status = statuses
+            .iter()
+            .find(|status| status.source_kind == source.kind && status.source_id == source.id);
+        let last_sync = match status {
+            None => "never".bright_black().to_string(),
+            Some(status) if status.last_error.is_some() => {
+                format!("{} {}", format_published_display(&status.last_run_at), "(failed)".red())
+            }
+            Some(status) => format_published_display(&status.last_run_at),
+        };
+        let enabled = format!("{:<8}", if source.enabled { "yes" } else { "no" });
+        let enabled = if source.enabled { enabled.green().to_string() } else { enabled.bright_black().to_string() };
+
+        println!(
+            "{:<9} {:<32} {enabled} {count:>6}  {last_sync}",
+            source.kind.to_string(),
+            truncate_for_column(&source.id, 32),
+        );
+    }
+    Ok(())
+}
+
+fn handle_webmentions(config_dir: Option<PathBuf>, db_path: Option<PathBuf>, limit: usize) -> Result<(), PaiError> {
+    let limit = ensure_positive_limit(limit)?;
+    let config = load_config(config_dir)?;
+    let storage = open_storage(&paths::resolve_backend(db_path, &config.database)?)?;
+    let sent = storage.list_sent_webmentions(limit)?;
+    if sent.is_empty() {
+        println!("{}", "No Webmentions sent".yellow());
+        return Ok(());
+    }
+
+    println!("{}", format!("{:<16} {:>6}  {}", "SENT", "STATUS", "TARGET").bold());
+    for sent in sent {
+        let status = format!(
+            "{:>6}",
+            sent.status_code.map_or("-".to_string(), |code| code.to_string())
+        );
+        let status = if sent.error.is_none() { status.green().to_string() } else { status.red().to_string() };
+        println!(
+            "{:<16} {status}  {}",
+            format_published_display(&sent.sent_at),
+            sent.target
+        );
+        if let Some(error) = sent.error {
+            println!("{:<16} {:>6}  {}", "", "", error.bright_black());
+        }
+    }
     Ok(())
 }
 
 fn handle_list(
-    db_path: Option<PathBuf>, kind: Option<SourceKind>, source_id: Option<String>, limit: usize, since: Option<String>,
-    query: Option<String>,
+    db_path: PathBuf, kind: Option<SourceKind>, source_id: Option<String>, limit: usize, since: Option<String>,
+    query: Option<String>, columns: Option<String>,
 ) -> Result<(), PaiError> {
-    let db_path = paths::resolve_db_path(db_path)?;
+    let columns = columns::parse_columns(columns.as_deref().unwrap_or(columns::DEFAULT_LIST_COLUMNS))?;
     let storage = SqliteStorage::new(db_path)?;
 
     let since = normalize_since_input(since)?;
@@ -86,7 +474,7 @@ fn handle_list(
 
     let filter = ListFilter { source_kind: kind, source_id, limit: Some(limit), since, query };
 
-    let items = pai_core::Storage::list_items(&storage, &filter)?;
+    let items = storage.list_items(&filter)?;
 
     if items.is_empty() {
         println!("{}", "No items found".yellow());
@@ -95,71 +483,357 @@ fn handle_list(
 
     println!("{} {}", "Found".cyan(), format!("{} item(s)", items.len()).bold());
     println!();
-    render_items_table(&items)?;
+    render_items_table(&items, &columns)?;
+    save_last_list(&items);
 
     Ok(())
 }
 
-fn handle_export(db_path: Option<PathBuf>, opts: ExportOpts) -> Result<(), PaiError> {
-    let db_path = paths::resolve_db_path(db_path)?;
+/// Records the listed IDs so `pai show` and `pai open` accept row numbers
+fn save_last_list(items: &[Item]) {
+    let result = paths::last_list_path().and_then(|path| {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let ids: String = items.iter().map(|item| format!("{}\n", item.id)).collect();
+        fs::write(&path, ids)?;
+        Ok(())
+    });
+    if let Err(e) = result {
+        tracing::debug!(error = %e, "Failed to record the last list");
+    }
+}
+
+/// IDs from the last `pai list`, or none if it was never run
+fn load_last_list() -> Vec<String> {
+    paths::last_list_path()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|ids| ids.lines().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn handle_show(config_dir: Option<PathBuf>, db_path: Option<PathBuf>, reference: String) -> Result<(), PaiError> {
+    let config = load_config(config_dir)?;
+    let storage = open_storage(&paths::resolve_backend(db_path, &config.database)?)?;
+    let item = show::resolve_item(storage.as_ref(), &reference, &load_last_list())?;
+
+    if let Some(title) = &item.title {
+        println!("{}", title.bold());
+        println!();
+    }
+
+    let published = format_published_display(&item.published_at);
+    let kind = item.source_kind.to_string();
+    let tags = item.tags.join(", ");
+    let fields = [
+        ("ID", Some(item.id.as_str())),
+        ("Kind", Some(kind.as_str())),
+        ("Source", Some(item.source_id.as_str())),
+        ("Author", item.author.as_deref()),
+        ("URL", Some(item.url.as_str())),
+        ("Published", Some(published.as_str())),
+        ("Created", Some(item.created_at.as_str())),
+        ("Tags", Some(tags.as_str()).filter(|tags| !tags.is_empty())),
+    ];
+    for (label, value) in fields {
+        if let Some(value) = value {
+            println!("{} {value}", format!("{label:<10}").bright_black());
+        }
+    }
+
+    let width = show::text_width();
+    let body = match (&item.content_html, &item.summary) {
+        (Some(html), _) if !html.trim().is_empty() => show::render_html(html, width),
+        (_, Some(summary)) => show::wrap_text(summary, width),
+        _ => String::new(),
+    };
+    if !body.is_empty() {
+        println!();
+        println!("{body}");
+    }
+
+    Ok(())
+}
+
+fn handle_open(config_dir: Option<PathBuf>, db_path: Option<PathBuf>, reference: String) -> Result<(), PaiError> {
+    let config = load_config(config_dir)?;
+    let storage = open_storage(&paths::resolve_backend(db_path, &config.database)?)?;
+    let item = show::resolve_item(storage.as_ref(), &reference, &load_last_list())?;
+
+    tracing::info!(id = %item.id, "Opening {}", item.url);
+    show::open_in_browser(&item.url)
+}
+
+fn handle_export(db_path: PathBuf, opts: ExportOpts) -> Result<(), PaiError> {
     let storage = SqliteStorage::new(db_path)?;
 
-    let ExportOpts { kind, source_id, limit, since, query, format, output } = opts;
+    let ExportOpts { kind, source_id, limit, since, query, format, output, front_matter, columns } = opts;
     let since = normalize_since_input(since)?;
     let limit = ensure_optional_limit(limit)?;
     let source_id = normalize_optional_string(source_id);
     let query = normalize_optional_string(query);
 
     let filter = ListFilter { source_kind: kind, source_id, limit, since, query };
-    let items = pai_core::Storage::list_items(&storage, &filter)?;
+    let items = storage.list_items(&filter)?;
 
     let export_format = ExportFormat::from_str(&format)?;
+    let columns = match (&export_format, columns) {
+        (ExportFormat::Csv | ExportFormat::Tsv, columns) => {
+            columns::parse_columns(columns.as_deref().unwrap_or(columns::DEFAULT_EXPORT_COLUMNS))?
+        }
+        (_, Some(_)) => {
+            return Err(PaiError::InvalidArgument(
+                "--columns only applies to csv and tsv exports".to_string(),
+            ))
+        }
+        (_, None) => Vec::new(),
+    };
+    if let ExportFormat::Markdown = export_format {
+        let output = output.ok_or_else(|| {
+            PaiError::InvalidArgument("Markdown export writes a directory tree; pass it with -o".to_string())
+        })?;
+        let report = markdown::export_markdown(&items, &output, front_matter)?;
+        tracing::info!(
+            written = report.written,
+            unchanged = report.unchanged,
+            "Exported {} item(s) to {}: {} written, {} unchanged",
+            items.len(),
+            output.display(),
+            report.written,
+            report.unchanged
+        );
+        return Ok(());
+    }
+
     let mut writer = create_output_writer(output.as_ref())?;
-    export_items(&items, export_format, writer.as_mut())?;
+    export_items(&items, export_format, &columns, writer.as_mut())?;
 
     match output {
-        Some(path) => println!(
-            "{} Exported {} item(s) to {}",
-            "Success:".green(),
+        Some(path) => tracing::info!(
+            items = items.len(),
+            "Exported {} item(s) to {}",
             items.len(),
             path.display()
         ),
-        None => println!("{} Exported {} item(s) to stdout", "Success:".green(), items.len()),
+        None => tracing::info!(items = items.len(), "Exported {} item(s) to stdout", items.len()),
     }
 
     Ok(())
 }
 
-fn handle_serve(db_path: Option<PathBuf>, address: String) -> Result<(), PaiError> {
-    let db_path = paths::resolve_db_path(db_path)?;
-    let config_path = paths::resolve_config_dir(None)?.join("config.toml");
-    let config = if config_path.exists() { Config::from_file(&config_path)? } else { Config::default() };
-    pai_server::serve(config, db_path, &address)
+fn handle_build_site(
+    config_dir: Option<PathBuf>, db_path: Option<PathBuf>, output: PathBuf, base_url: Option<String>, per_page: usize,
+    templates: Option<PathBuf>,
+) -> Result<(), PaiError> {
+    let config = load_config(config_dir)?;
+
+    let base_url = normalize_optional_string(base_url)
+        .or_else(|| config.server.public_url.clone())
+        .ok_or_else(|| {
+            PaiError::InvalidArgument(
+                "A base URL is required for feeds and the sitemap; pass -u or set [server] public_url".to_string(),
+            )
+        })?;
+    let options = site::SiteOptions {
+        output,
+        base_url,
+        per_page: ensure_positive_limit(per_page)?,
+        templates: templates.or_else(|| config.server.templates.as_ref().map(PathBuf::from)),
+    };
+
+    let storage = open_storage(&paths::resolve_backend(db_path, &config.database)?)?;
+    let report = site::build_site(storage.as_ref(), &options)?;
+
+    tracing::info!(
+        written = report.written,
+        unchanged = report.unchanged,
+        removed = report.removed,
+        "Built site in {}: {} written, {} unchanged, {} removed",
+        options.output.display(),
+        report.written,
+        report.unchanged,
+        report.removed
+    );
+
+    Ok(())
 }
 
-fn handle_db_check(db_path: Option<PathBuf>) -> Result<(), PaiError> {
-    let db_path = paths::resolve_db_path(db_path)?;
+fn handle_serve(
+    config_dir: Option<PathBuf>, db_path: Option<PathBuf>, address: Option<String>,
+) -> Result<(), PaiError> {
+    let mut config = load_config(config_dir)?;
+    if let Some(address) = address {
+        config.server.listen = Some(address);
+    }
+
+    match paths::resolve_backend(db_path, &config.database)? {
+        Backend::Sqlite(path) => {
+            let storage = SqliteStorage::new(&path)?;
+            pai_server::serve(config, storage, path.display().to_string())
+        }
+        #[cfg(feature = "postgres")]
+        Backend::Postgres(url) => {
+            let storage = pai_server::PostgresStorage::connect(&url)?;
+            pai_server::serve(config, storage, pai_server::redact_url(&url))
+        }
+        #[cfg(not(feature = "postgres"))]
+        Backend::Postgres(_) => Err(postgres_unavailable()),
+    }
+}
+
+/// Loads the layered config, or defaults when no file or `PAI_*` variable sets anything
+fn load_config(config_dir: Option<PathBuf>) -> Result<Config, PaiError> {
+    check_config(config_dir)?.into_config()
+}
+
+/// Merges and validates the system config, the user's config.toml and config.d/, and `PAI_*` variables
+fn check_config(config_dir: Option<PathBuf>) -> Result<ConfigReport, PaiError> {
+    Ok(Config::check_layered(&paths::config_paths(config_dir)?, |name| {
+        std::env::var(name).ok()
+    }))
+}
+
+/// Resolves the SQLite file for commands that only read the local database
+fn sqlite_path(config_dir: Option<PathBuf>, db_path: Option<PathBuf>) -> Result<PathBuf, PaiError> {
+    paths::resolve_db_path(db_path, &load_config(config_dir)?.database)
+}
+
+/// Opens the storage backend selected by [`paths::resolve_backend`]
+fn open_storage(backend: &Backend) -> Result<Box<dyn Storage>, PaiError> {
+    match backend {
+        Backend::Sqlite(path) => Ok(Box::new(SqliteStorage::new(path)?)),
+        #[cfg(feature = "postgres")]
+        Backend::Postgres(url) => Ok(Box::new(pai_server::PostgresStorage::connect(url)?)),
+        #[cfg(not(feature = "postgres"))]
+        Backend::Postgres(_) => Err(postgres_unavailable()),
+    }
+}
+
+#[cfg(not(feature = "postgres"))]
+fn postgres_unavailable() -> PaiError {
+    PaiError::Config("PostgreSQL support is not enabled; rebuild pai with `--features postgres`".to_string())
+}
+
+fn handle_db_check(db_path: PathBuf) -> Result<(), PaiError> {
     let storage = SqliteStorage::new(db_path)?;
 
     println!("{}", "Verifying database schema...".cyan());
     storage.verify_schema()?;
-    println!("{} {}\n", "Schema verification:".green(), "OK".bold());
+    println!("{} {}", "Schema verification:".green(), "OK".bold());
+
+    println!("{}", "Checking database integrity...".cyan());
+    let problems = storage.integrity_check()?;
+    if !problems.is_empty() {
+        for problem in &problems {
+            println!("  {}", problem.red());
+        }
+        return Err(PaiError::Storage(format!(
+            "Integrity check found {} problem(s); restore a backup with `pai db restore`",
+            problems.len()
+        )));
+    }
+    println!("{} {}\n", "Integrity check:".green(), "OK".bold());
 
     println!("{}", "Database statistics:".cyan().bold());
+    let size = storage.size()?;
+    println!(
+        "  {}: {} ({} free)",
+        "Size".bright_black(),
+        format_size(size.bytes).bold(),
+        format_size(size.free_bytes)
+    );
     let total = storage.count_items()?;
     println!("  {}: {}", "Total items".bright_black(), total.to_string().bold());
 
-    let stats = storage.get_stats()?;
+    let stats = storage.get_stats(StatsGroup::Kind)?;
     if !stats.is_empty() {
         println!("\n{}", "Items by source:".cyan().bold());
-        for (source_kind, count) in stats {
-            println!("  {}: {}", source_kind.bright_black(), count.to_string().bold());
+        for stat in stats {
+            println!(
+                "  {}: {}",
+                stat.source_kind.to_string().bright_black(),
+                stat.count.to_string().bold()
+            );
         }
     }
 
     Ok(())
 }
 
+fn handle_db(db_path: PathBuf, command: DbCommand) -> Result<(), PaiError> {
+    let exists = db_path.exists();
+    if let DbCommand::Restore { file, force } = &command {
+        if exists && !*force {
+            return Err(PaiError::InvalidArgument(format!(
+                "{} already exists; pass -f to replace it with {}",
+                db_path.display(),
+                file.display()
+            )));
+        }
+    } else if !exists {
+        return Err(PaiError::InvalidArgument(format!(
+            "No database at {}",
+            db_path.display()
+        )));
+    }
+    let storage = SqliteStorage::new(&db_path)?;
+
+    match command {
+        DbCommand::Backup { output } => {
+            storage.backup(&output)?;
+            tracing::info!("Backed up {} to {}", db_path.display(), output.display());
+        }
+        DbCommand::Restore { file, .. } => {
+            let version = storage.restore(&file)?;
+            let items = storage.count_items()?;
+            tracing::info!(
+                schema_version = version,
+                items,
+                "Restored {} from {} ({items} items)",
+                db_path.display(),
+                file.display()
+            );
+        }
+        DbCommand::Vacuum => {
+            let before = storage.size()?;
+            storage.vacuum()?;
+            let after = storage.size()?;
+            tracing::info!(
+                before = before.bytes,
+                after = after.bytes,
+                "Vacuumed {}: {} → {}",
+                db_path.display(),
+                format_size(before.bytes),
+                format_size(after.bytes)
+            );
+        }
+        DbCommand::Optimize => {
+            let fts_tables = storage.optimize()?;
+            tracing::info!("Analyzed {}", db_path.display());
+            for table in fts_tables {
+                tracing::info!(%table, "Optimized FTS index {table}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `bytes` in the largest binary unit that keeps it at or above 1
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
 fn handle_init(config_dir: Option<PathBuf>, force: bool) -> Result<(), PaiError> {
     let config_dir = paths::resolve_config_dir(config_dir)?;
     let config_path = config_dir.join("config.toml");
@@ -476,6 +1150,9 @@ enum ExportFormat {
     Json,
     Ndjson,
     Rss,
+    Markdown,
+    Csv,
+    Tsv,
 }
 
 impl FromStr for ExportFormat {
@@ -486,8 +1163,11 @@ impl FromStr for ExportFormat {
             "json" => Ok(Self::Json),
             "ndjson" => Ok(Self::Ndjson),
             "rss" => Ok(Self::Rss),
+            "markdown" | "md" => Ok(Self::Markdown),
+            "csv" => Ok(Self::Csv),
+            "tsv" => Ok(Self::Tsv),
             other => Err(PaiError::InvalidArgument(format!(
-                "Unsupported export format '{other}'. Expected json, ndjson, or rss."
+                "Unsupported export format '{other}'. Expected json, ndjson, rss, markdown, csv, or tsv."
             ))),
         }
     }
@@ -507,11 +1187,20 @@ fn create_output_writer(path: Option<&PathBuf>) -> Result<Box<dyn Write>, PaiErr
     }
 }
 
-fn export_items(items: &[Item], format: ExportFormat, writer: &mut dyn Write) -> Result<(), PaiError> {
+fn export_items(
+    items: &[Item], format: ExportFormat, columns: &[ColumnSpec], writer: &mut dyn Write,
+) -> Result<(), PaiError> {
     match format {
         ExportFormat::Json => write_json(items, writer)?,
         ExportFormat::Ndjson => write_ndjson(items, writer)?,
         ExportFormat::Rss => write_rss(items, writer)?,
+        ExportFormat::Csv => columns::write_delimited(items, columns, ',', writer)?,
+        ExportFormat::Tsv => columns::write_delimited(items, columns, '\t', writer)?,
+        ExportFormat::Markdown => {
+            return Err(PaiError::InvalidArgument(
+                "Markdown export writes a directory tree, not a single stream".to_string(),
+            ))
+        }
     }
 
     writer.flush().map_err(PaiError::Io)
@@ -626,43 +1315,9 @@ fn truncate_for_column(value: &str, max_chars: usize) -> String {
     truncated
 }
 
-fn render_items_table(items: &[Item]) -> Result<(), PaiError> {
+fn render_items_table(items: &[Item], columns: &[ColumnSpec]) -> Result<(), PaiError> {
     let mut stdout = io::stdout();
-    write_items_table(items, &mut stdout).map_err(PaiError::Io)
-}
-
-fn write_items_table<W: Write>(items: &[Item], writer: &mut W) -> io::Result<()> {
-    let header = format!(
-        "| {published:<pub_width$} | {kind:<kind_width$} | {source:<source_width$} | {title:<title_width$} |",
-        published = "Published",
-        kind = "Kind",
-        source = "Source",
-        title = "Title",
-        pub_width = PUBLISHED_WIDTH,
-        kind_width = KIND_WIDTH,
-        source_width = SOURCE_WIDTH,
-        title_width = TITLE_WIDTH,
-    );
-    let separator = "-".repeat(header.len());
-
-    writeln!(writer, "{separator}")?;
-    writeln!(writer, "{header}")?;
-    writeln!(writer, "{}", separator.clone())?;
-
-    for item in items {
-        let published = truncate_for_column(&format_published_display(&item.published_at), PUBLISHED_WIDTH);
-        let kind = truncate_for_column(&item.source_kind.to_string(), KIND_WIDTH);
-        let source = truncate_for_column(&item.source_id, SOURCE_WIDTH);
-        let title_text = item.title.as_deref().or(item.summary.as_deref()).unwrap_or(&item.url);
-        let title = truncate_for_column(title_text, TITLE_WIDTH);
-
-        let row = format!(
-            "| {published:<PUBLISHED_WIDTH$} | {kind:<KIND_WIDTH$} | {source:<SOURCE_WIDTH$} | {title:<TITLE_WIDTH$} |",
-        );
-        writeln!(writer, "{row}")?;
-    }
-
-    writeln!(writer, "{separator}")
+    columns::write_items_table(items, columns, &mut stdout).map_err(PaiError::Io)
 }
 
 #[cfg(test)]
@@ -670,19 +1325,24 @@ mod tests {
     use super::*;
     use chrono::TimeZone;
 
+    #[test]
+    fn verbose_flag_is_accepted_after_the_subcommand() {
+        let cli = Cli::try_parse_from(["pai", "sync", "-vv"]).unwrap();
+        assert_eq!(cli.verbose, 2);
+        assert!(matches!(cli.command, Commands::Sync { .. }));
+
+        let cli = Cli::try_parse_from(["pai", "-v", "list", "-q", "rust"]).unwrap();
+        assert_eq!(cli.verbose, 1);
+        assert!(!cli.quiet);
+    }
+
     fn sample_item() -> Item {
-        Item {
-            id: "sample-id".to_string(),
-            source_kind: SourceKind::Substack,
-            source_id: "patternmatched.substack.com".to_string(),
-            author: Some("Pattern Matched".to_string()),
-            title: Some("Test entry".to_string()),
-            summary: Some("Summary".to_string()),
-            url: "https://patternmatched.substack.com/p/test".to_string(),
-            content_html: None,
-            published_at: "2024-01-01T00:00:00Z".to_string(),
-            created_at: "2024-01-01T00:00:00Z".to_string(),
-        }
+        Item::test("sample-id")
+            .with_source(SourceKind::Substack, "patternmatched.substack.com")
+            .with_author("Pattern Matched")
+            .with_title("Test entry")
+            .with_summary("Summary")
+            .with_url("https://patternmatched.substack.com/p/test")
     }
 
     #[test]
@@ -713,13 +1373,17 @@ mod tests {
             ExportFormat::Ndjson
         ));
         assert!(matches!(ExportFormat::from_str("rss").unwrap(), ExportFormat::Rss));
+        assert!(matches!(
+            ExportFormat::from_str("markdown").unwrap(),
+            ExportFormat::Markdown
+        ));
         assert!(ExportFormat::from_str("invalid").is_err());
     }
 
     #[test]
     fn json_export_serializes_items() {
         let mut buffer = Vec::new();
-        export_items(&[sample_item()], ExportFormat::Json, &mut buffer).unwrap();
+        export_items(&[sample_item()], ExportFormat::Json, &[], &mut buffer).unwrap();
         let output = String::from_utf8(buffer).unwrap();
         assert!(output.trim_start().starts_with('['));
         assert!(output.contains("sample-id"));
@@ -728,7 +1392,7 @@ mod tests {
     #[test]
     fn ndjson_export_serializes_items() {
         let mut buffer = Vec::new();
-        export_items(&[sample_item()], ExportFormat::Ndjson, &mut buffer).unwrap();
+        export_items(&[sample_item()], ExportFormat::Ndjson, &[], &mut buffer).unwrap();
         let output = String::from_utf8(buffer).unwrap();
         assert!(output.lines().next().unwrap().contains("sample-id"));
     }
@@ -745,7 +1409,8 @@ mod tests {
     #[test]
     fn table_writer_emits_rows() {
         let mut buffer = Vec::new();
-        write_items_table(&[sample_item()], &mut buffer).unwrap();
+        let columns = columns::parse_columns(columns::DEFAULT_LIST_COLUMNS).unwrap();
+        columns::write_items_table(&[sample_item()], &columns, &mut buffer).unwrap();
         let output = String::from_utf8(buffer).unwrap();
         assert!(output.contains("Published"));
         assert!(output.contains("patternmatched"));
@@ -757,6 +1422,13 @@ mod tests {
         assert_eq!(truncated, "abcde...");
     }
 
+    #[test]
+    fn format_size_picks_binary_units() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(4096), "4.0 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 + 512 * 1024), "5.5 MiB");
+    }
+
     #[test]
     fn manpage_contains_name_section() {
         assert!(MAN_PAGE.contains("NAME"));
@@ -0,0 +1,345 @@
+use crate::app::FrontMatter;
+use crate::html::{self, Marker, Sink, Style};
+use crate::site::write_if_changed;
+use chrono::{DateTime, Datelike};
+use pai_core::{Item, PaiError};
+use pai_server::item_slug;
+use std::path::{Path, PathBuf};
+
+/// Files touched by a Markdown export
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MarkdownReport {
+    pub written: usize,
+    pub unchanged: usize,
+}
+
+/// Writes one Markdown file per item to `{source_kind}/{yyyy}/{slug}.md` under `output`
+///
+/// Slugs come from the item ID, so re-exporting rewrites only files whose content changed.
+pub fn export_markdown(items: &[Item], output: &Path, front_matter: FrontMatter) -> Result<MarkdownReport, PaiError> {
+    let mut report = MarkdownReport::default();
+    for item in items {
+        let path = output.join(item_path(item));
+        if write_if_changed(&path, render_item(item, front_matter).as_bytes())? {
+            report.written += 1;
+        } else {
+            report.unchanged += 1;
+        }
+    }
+    Ok(report)
+}
+
+/// Path of an item's file relative to the export directory
+fn item_path(item: &Item) -> PathBuf {
+    let year = DateTime::parse_from_rfc3339(&item.published_at)
+        .map(|date| date.year().to_string())
+        .unwrap_or_else(|_| "undated".to_string());
+    PathBuf::from(item.source_kind.to_string())
+        .join(year)
+        .join(format!("{}.md", item_slug(&item.id)))
+}
+
+fn render_item(item: &Item, front_matter: FrontMatter) -> String {
+    let body = match (&item.content_html, &item.summary) {
+        (Some(html), _) => html_to_markdown(html),
+        (None, Some(summary)) => summary.trim().to_string(),
+        (None, None) => String::new(),
+    };
+
+    let header = match front_matter {
+        FrontMatter::Yaml => yaml_front_matter(item),
+        FrontMatter::Toml => toml_front_matter(item),
+    };
+    format!("{header}\n{body}\n")
+}
+
+/// Quotes `value` as a double-quoted string, which YAML and TOML share with JSON
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "\"\"".to_string())
+}
+
+fn title(item: &Item) -> &str {
+    item.title.as_deref().or(item.summary.as_deref()).unwrap_or(&item.url)
+}
+
+fn yaml_front_matter(item: &Item) -> String {
+    let mut lines = vec![
+        "---".to_string(),
+        format!("title: {}", quote(title(item))),
+        format!("date: {}", quote(&item.published_at)),
+        format!("id: {}", quote(&item.id)),
+        format!("url: {}", quote(&item.url)),
+        format!("source: {}", quote(&item.source_kind.to_string())),
+        format!("source_id: {}", quote(&item.source_id)),
+    ];
+    if let Some(author) = &item.author {
+        lines.push(format!("author: {}", quote(author)));
+    }
+    lines.push(format!("published_at: {}", quote(&item.published_at)));
+    let tags: Vec<String> = item.tags.iter().map(|tag| quote(tag)).collect();
+    lines.push(format!("tags: [{}]", tags.join(", ")));
+    lines.push("---".to_string());
+    lines.join("\n") + "\n"
+}
+
+/// TOML front matter laid out for Zola: custom fields under `[extra]`, tags as a taxonomy
+fn toml_front_matter(item: &Item) -> String {
+    // Zola wants `date` as a TOML datetime; fall back to a string for unparseable values.
+    let date = match DateTime::parse_from_rfc3339(&item.published_at) {
+        Ok(date) => date.to_rfc3339(),
+        Err(_) => quote(&item.published_at),
+    };
+    let mut lines = vec![
+        "+++".to_string(),
+        format!("title = {}", quote(title(item))),
+        format!("date = {date}"),
+        String::new(),
+        "[extra]".to_string(),
+        format!("id = {}", quote(&item.id)),
+        format!("url = {}", quote(&item.url)),
+        format!("source = {}", quote(&item.source_kind.to_string())),
+        format!("source_id = {}", quote(&item.source_id)),
+    ];
+    if let Some(author) = &item.author {
+        lines.push(format!("author = {}", quote(author)));
+    }
+    lines.push(format!("published_at = {}", quote(&item.published_at)));
+    lines.push(String::new());
+    lines.push("[taxonomies]".to_string());
+    let tags: Vec<String> = item.tags.iter().map(|tag| quote(tag)).collect();
+    lines.push(format!("tags = [{}]", tags.join(", ")));
+    lines.push("+++".to_string());
+    lines.join("\n") + "\n"
+}
+
+/// Converts the HTML found in feed content to Markdown
+///
+/// Handles the tags [`html::walk`] does; other tags are dropped and their text kept.
+pub fn html_to_markdown(html: &str) -> String {
+    let mut converter = Converter::default();
+    html::walk(html, &mut converter);
+    converter.finish()
+}
+
+#[derive(Default)]
+struct Converter {
+    out: String,
+    /// Output offsets where open blockquotes start
+    quotes: Vec<usize>,
+    pre: bool,
+}
+
+impl Sink for Converter {
+    fn text(&mut self, text: &str) {
+        for (index, word) in text.split_whitespace().enumerate() {
+            let at_line_start = self.out.is_empty() || self.out.ends_with('\n');
+            let needs_space = index > 0 || text.starts_with(char::is_whitespace);
+            if needs_space && !at_line_start && !self.out.ends_with(' ') {
+                self.out.push(' ');
+            }
+            self.out.push_str(&escape_markdown(word));
+        }
+        if text.ends_with(char::is_whitespace) && !text.trim().is_empty() {
+            self.out.push(' ');
+        }
+    }
+
+    fn preformatted(&mut self, text: &str) {
+        self.out.push_str(text);
+    }
+
+    fn block(&mut self) {
+        if self.pre {
+            return;
+        }
+        self.trim_trailing_spaces();
+        if self.out.is_empty() {
+            return;
+        }
+        while !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn line_break(&mut self) {
+        self.trim_trailing_spaces();
+        self.out.push_str("  \n");
+    }
+
+    fn rule(&mut self) {
+        self.block();
+        self.out.push_str("---");
+        self.block();
+    }
+
+    fn heading(&mut self, level: usize, closing: bool) {
+        self.block();
+        if !closing {
+            self.out.push_str(&"#".repeat(level));
+            self.out.push(' ');
+        }
+    }
+
+    fn style(&mut self, style: Style) {
+        self.out.push_str(match style {
+            Style::Strong => "**",
+            Style::Emphasis => "*",
+            Style::Strikethrough => "~~",
+            Style::Code => "`",
+        });
+    }
+
+    fn pre(&mut self, closing: bool) {
+        if closing {
+            self.pre = false;
+            if !self.out.ends_with('\n') {
+                self.out.push('\n');
+            }
+            self.out.push_str("```");
+            self.block();
+        } else {
+            self.block();
+            self.out.push_str("```\n");
+            self.pre = true;
+        }
+    }
+
+    fn list(&mut self, closing: bool, depth: usize) {
+        // Only the outermost list is set apart from the text around it
+        let outermost = if closing { depth == 0 } else { depth == 1 };
+        if outermost {
+            self.block();
+        }
+    }
+
+    fn list_item(&mut self, marker: Marker, depth: usize) {
+        self.trim_trailing_spaces();
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        let marker = match marker {
+            Marker::Number(number) => format!("{number}."),
+            Marker::Bullet => "-".to_string(),
+        };
+        self.out
+            .push_str(&format!("{}{marker} ", "  ".repeat(depth.saturating_sub(1))));
+    }
+
+    fn end_list_item(&mut self) {}
+
+    fn quote(&mut self, closing: bool) {
+        if !closing {
+            self.block();
+            self.quotes.push(self.out.len());
+        } else if let Some(start) = self.quotes.pop() {
+            let quoted: Vec<String> = self.out[start..]
+                .trim_end()
+                .lines()
+                .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {line}") })
+                .collect();
+            self.out.truncate(start);
+            self.out.push_str(&quoted.join("\n"));
+            self.block();
+        }
+    }
+
+    fn link(&mut self, href: &str, closing: bool) {
+        if closing {
+            self.trim_trailing_spaces();
+            self.out.push_str(&format!("]({href})"));
+        } else {
+            self.out.push('[');
+        }
+    }
+
+    fn image(&mut self, src: &str, alt: Option<&str>) {
+        self.out
+            .push_str(&format!("![{}]({src})", escape_markdown(alt.unwrap_or_default())));
+    }
+}
+
+impl Converter {
+    fn trim_trailing_spaces(&mut self) {
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+    }
+
+    fn finish(self) -> String {
+        self.out.trim().to_string()
+    }
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pai_core::SourceKind;
+    use tempfile::tempdir;
+
+    fn item() -> Item {
+        Item::test("https://me.substack.com/p/hello-world")
+            .with_source(SourceKind::Substack, "me.substack.com")
+            .with_author("Me")
+            .with_title("Hello \"World\"")
+            .with_summary("Summary")
+            .with_url("https://me.substack.com/p/hello-world")
+            .with_content_html("<p>Hi <em>there</em></p>")
+            .with_tags(&["rust"])
+            .with_published_at("2024-03-04T05:06:07Z")
+    }
+
+    #[test]
+    fn converts_common_html() {
+        let html = r#"<h2>Title</h2><p>Hello <strong>world</strong> &amp; <a href="https://x.example/?a=1&amp;b=2">a link</a>.<br>Next_line</p>
+<ul><li>one</li><li>two<ol><li>nested</li></ol></li></ul>
+<blockquote><p>quoted</p><p>twice</p></blockquote>
+<pre><code>let x = 1;
+let y = 2;</code></pre><script>alert(1)</script><p><img src="/a.png" alt="pic"></p>"#;
+
+        assert_eq!(
+            html_to_markdown(html),
+            "## Title\n\nHello **world** & [a link](https://x.example/?a=1&b=2).  \nNext\\_line\n\n- one\n- two\n  1. nested\n\n\
+             > quoted\n>\n> twice\n\n```\nlet x = 1;\nlet y = 2;\n```\n\n![pic](/a.png)"
+        );
+    }
+
+    #[test]
+    fn renders_front_matter() {
+        let yaml = render_item(&item(), FrontMatter::Yaml);
+        assert!(yaml.starts_with("---\ntitle: \"Hello \\\"World\\\"\"\n"));
+        assert!(yaml.contains("source: \"substack\"\n"));
+        assert!(yaml.contains("tags: [\"rust\"]\n---\n\nHi *there*\n"));
+
+        let toml = render_item(&item(), FrontMatter::Toml);
+        assert!(toml.starts_with("+++\n"));
+        assert!(toml.contains("date = 2024-03-04T05:06:07+00:00\n"));
+        assert!(toml.contains("[taxonomies]\ntags = [\"rust\"]\n+++\n"));
+    }
+
+    #[test]
+    fn export_only_rewrites_changed_files() {
+        let dir = tempdir().unwrap();
+        let report = export_markdown(&[item()], dir.path(), FrontMatter::Yaml).unwrap();
+        assert_eq!(report, MarkdownReport { written: 1, unchanged: 0 });
+        let path = dir.path().join(item_path(&item()));
+        assert!(path.starts_with(dir.path().join("substack/2024")));
+        assert!(path.to_string_lossy().ends_with(".md"));
+
+        let report = export_markdown(&[item()], dir.path(), FrontMatter::Yaml).unwrap();
+        assert_eq!(report, MarkdownReport { written: 0, unchanged: 1 });
+
+        let changed = item().with_title("Renamed");
+        let report = export_markdown(&[changed], dir.path(), FrontMatter::Yaml).unwrap();
+        assert_eq!(report, MarkdownReport { written: 1, unchanged: 0 });
+    }
+}
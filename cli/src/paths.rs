@@ -1,17 +1,50 @@
-use pai_core::{PaiError, Result};
+use pai_core::{ConfigPaths, DatabaseConfig, PaiError, Result};
 use std::path::PathBuf;
 
+/// Storage backend selected from the command line and config
+#[derive(Debug, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite(PathBuf),
+    Postgres(String),
+}
+
+/// Resolves which storage backend to use
+///
+/// Priority order:
+/// 1. Explicit SQLite path provided via `-d` flag
+/// 2. `[database] url` from config (`postgres://` or `postgresql://`)
+/// 3. Default SQLite path from [`resolve_db_path`]
+pub fn resolve_backend(explicit_path: Option<PathBuf>, database: &DatabaseConfig) -> Result<Backend> {
+    if explicit_path.is_none() {
+        if let Some(url) = database.url.as_deref().map(str::trim).filter(|url| !url.is_empty()) {
+            if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+                return Ok(Backend::Postgres(url.to_string()));
+            }
+            return Err(PaiError::Config(format!(
+                "Unsupported database url '{url}': expected a postgres:// or postgresql:// URL"
+            )));
+        }
+    }
+
+    resolve_db_path(explicit_path, database).map(Backend::Sqlite)
+}
+
 /// Resolves the database file path, with XDG fallback
 ///
 /// Priority order:
 /// 1. Explicit path provided via `-d` flag
-/// 2. $XDG_DATA_HOME/pai/pai.db
-/// 3. $HOME/.local/share/pai/pai.db
-pub fn resolve_db_path(explicit_path: Option<PathBuf>) -> Result<PathBuf> {
+/// 2. `[database] path` from config
+/// 3. $XDG_DATA_HOME/pai/pai.db
+/// 4. $HOME/.local/share/pai/pai.db
+pub fn resolve_db_path(explicit_path: Option<PathBuf>, database: &DatabaseConfig) -> Result<PathBuf> {
     if let Some(path) = explicit_path {
         return Ok(path);
     }
 
+    if let Some(path) = database.path.as_deref().map(str::trim).filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+
     if let Some(data_home) = dirs::data_dir() {
         return Ok(data_home.join("pai").join("pai.db"));
     }
@@ -41,6 +74,29 @@ pub fn resolve_config_dir(explicit_dir: Option<PathBuf>) -> Result<PathBuf> {
     ))
 }
 
+/// Config files to layer for [`pai_core::Config::load_layered`]
+///
+/// The system file is only read on Unix; `-C` replaces the user directory.
+pub fn config_paths(explicit_dir: Option<PathBuf>) -> Result<ConfigPaths> {
+    Ok(ConfigPaths {
+        system: cfg!(unix).then(|| PathBuf::from(pai_core::SYSTEM_CONFIG_PATH)),
+        user_dir: Some(resolve_config_dir(explicit_dir)?),
+    })
+}
+
+/// Resolves where `pai list` records the IDs it printed
+///
+/// Uses $XDG_STATE_HOME/pai/last-list, falling back to the cache directory
+/// on platforms without a state directory.
+pub fn last_list_path() -> Result<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::cache_dir)
+        .map(|dir| dir.join("pai").join("last-list"))
+        .ok_or_else(|| {
+            PaiError::Config("Unable to determine state directory: no XDG_STATE_HOME or HOME set".to_string())
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -49,19 +105,49 @@ mod tests {
     #[test]
     fn resolve_db_path_with_explicit() {
         let explicit = Some(PathBuf::from("/custom/path/db.sqlite"));
-        let result = resolve_db_path(explicit).unwrap();
+        let result = resolve_db_path(explicit, &DatabaseConfig::default()).unwrap();
         assert_eq!(result, Path::new("/custom/path/db.sqlite"));
     }
 
     #[test]
     fn resolve_db_path_falls_back() {
-        let result = resolve_db_path(None);
+        let result = resolve_db_path(None, &DatabaseConfig::default());
         assert!(result.is_ok());
 
         let path = result.unwrap();
         assert!(path.ends_with("pai/pai.db"));
     }
 
+    #[test]
+    fn resolve_db_path_uses_config() {
+        let database = DatabaseConfig { path: Some("/srv/pai/pai.db".to_string()), ..Default::default() };
+        assert_eq!(resolve_db_path(None, &database).unwrap(), Path::new("/srv/pai/pai.db"));
+        assert_eq!(
+            resolve_db_path(Some(PathBuf::from("/tmp/pai.db")), &database).unwrap(),
+            Path::new("/tmp/pai.db")
+        );
+    }
+
+    #[test]
+    fn resolve_backend_prefers_postgres_url() {
+        let database = DatabaseConfig { url: Some("postgres://localhost/pai".to_string()), ..Default::default() };
+        let backend = resolve_backend(None, &database).unwrap();
+        assert_eq!(backend, Backend::Postgres("postgres://localhost/pai".to_string()));
+    }
+
+    #[test]
+    fn resolve_backend_explicit_path_wins() {
+        let database = DatabaseConfig { url: Some("postgres://localhost/pai".to_string()), ..Default::default() };
+        let backend = resolve_backend(Some(PathBuf::from("/tmp/pai.db")), &database).unwrap();
+        assert_eq!(backend, Backend::Sqlite(PathBuf::from("/tmp/pai.db")));
+    }
+
+    #[test]
+    fn resolve_backend_rejects_unknown_scheme() {
+        let database = DatabaseConfig { url: Some("mysql://localhost/pai".to_string()), ..Default::default() };
+        assert!(resolve_backend(None, &database).is_err());
+    }
+
     #[test]
     fn resolve_config_dir_with_explicit() {
         let explicit = Some(PathBuf::from("/custom/config"));
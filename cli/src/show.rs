@@ -0,0 +1,398 @@
+use crate::html::{self, Marker, Sink, Style};
+use pai_core::{Item, PaiError, Storage};
+use std::process::Command;
+
+/// Candidates listed when an ID prefix is ambiguous
+const AMBIGUOUS_LIMIT: usize = 5;
+
+/// Finds the item `reference` points at
+///
+/// Tries, in order: an exact ID, an item URL, a row number from the last `pai list`
+/// (`last_list` holds its IDs), then a unique ID prefix.
+pub fn resolve_item(storage: &dyn Storage, reference: &str, last_list: &[String]) -> Result<Item, PaiError> {
+    let reference = reference.trim();
+    if let Some(item) = storage.get_item(reference)? {
+        return Ok(item);
+    }
+    if let Some(item) = storage.get_item_by_url(reference)? {
+        return Ok(item);
+    }
+
+    if let Ok(row) = reference.parse::<usize>() {
+        if let Some(id) = row.checked_sub(1).and_then(|index| last_list.get(index)) {
+            return storage.get_item(id)?.ok_or_else(|| {
+                PaiError::InvalidArgument(format!("Row {row} of the last `pai list` ({id}) no longer exists"))
+            });
+        }
+    }
+
+    let mut matches = storage.find_items_by_id_prefix(reference, AMBIGUOUS_LIMIT + 1)?;
+    match matches.len() {
+        0 => Err(PaiError::InvalidArgument(format!("No item matches '{reference}'"))),
+        1 => Ok(matches.remove(0)),
+        count => {
+            let mut candidates: Vec<&str> = matches
+                .iter()
+                .take(AMBIGUOUS_LIMIT)
+                .map(|item| item.id.as_str())
+                .collect();
+            if count > AMBIGUOUS_LIMIT {
+                candidates.push("...");
+            }
+            Err(PaiError::InvalidArgument(format!(
+                "'{reference}' matches several items: {}",
+                candidates.join(", ")
+            )))
+        }
+    }
+}
+
+/// Launches `url` with `$BROWSER`, or the platform's default opener
+///
+/// Only `http(s)` URLs are opened, since item URLs come from the sources and a `file:` or custom
+/// scheme URL would hand them a local program to run.
+pub fn open_in_browser(url: &str) -> Result<(), PaiError> {
+    check_web_url(url)?;
+
+    let browser = std::env::var("BROWSER")
+        .ok()
+        .and_then(|value| value.split(':').next().map(str::trim).map(String::from))
+        .filter(|browser| !browser.is_empty());
+
+    let mut command = match browser {
+        Some(browser) => Command::new(browser),
+        None if cfg!(target_os = "macos") => Command::new("open"),
+        // Not `cmd /C start`, which would interpret `&` and friends in the URL as shell syntax
+        None if cfg!(windows) => {
+            let mut command = Command::new("rundll32");
+            command.arg("url.dll,FileProtocolHandler");
+            command
+        }
+        None => Command::new("xdg-open"),
+    };
+
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command.arg(url).status().map_err(|e| {
+        PaiError::Config(format!(
+            "Failed to launch {program}: {e}. Set $BROWSER to choose a browser."
+        ))
+    })?;
+    if !status.success() {
+        return Err(PaiError::Config(format!(
+            "{program} exited with {status} while opening {url}"
+        )));
+    }
+    Ok(())
+}
+
+fn check_web_url(url: &str) -> Result<(), PaiError> {
+    match url::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(()),
+        _ => Err(PaiError::InvalidArgument(format!(
+            "Refusing to open '{url}': only http and https URLs are opened"
+        ))),
+    }
+}
+
+/// Terminal width for wrapped text: `$COLUMNS`, clamped to 40..=100, or 80
+pub fn text_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse::<usize>().ok())
+        .map(|columns| columns.clamp(40, 100))
+        .unwrap_or(80)
+}
+
+/// Renders HTML content as plain text wrapped to `width`
+///
+/// Links and images are numbered `[n]` in the text and listed after it.
+pub fn render_html(html: &str, width: usize) -> String {
+    let mut renderer = TextRenderer::default();
+    html::walk(html, &mut renderer);
+    renderer.flush();
+
+    let mut output = String::new();
+    let mut previous: Option<&Block> = None;
+    for block in &renderer.blocks {
+        match previous {
+            Some(previous) if previous.list_item && block.list_item => output.push('\n'),
+            Some(_) => output.push_str("\n\n"),
+            None => {}
+        }
+        output.push_str(&block.render(width));
+        previous = Some(block);
+    }
+
+    if !renderer.links.is_empty() {
+        output.push_str("\n\nLinks:");
+        for (index, link) in renderer.links.iter().enumerate() {
+            output.push_str(&format!("\n[{}] {link}", index + 1));
+        }
+    }
+    output
+}
+
+/// Wraps plain text such as a summary, keeping its paragraphs
+pub fn wrap_text(text: &str, width: usize) -> String {
+    let paragraphs: Vec<String> = text
+        .split("\n\n")
+        .filter(|paragraph| !paragraph.trim().is_empty())
+        .map(|paragraph| wrap(paragraph, "", "", width))
+        .collect();
+    paragraphs.join("\n\n")
+}
+
+struct Block {
+    /// Prefix of the first line, e.g. a list marker
+    first: String,
+    /// Prefix of the following lines
+    rest: String,
+    text: String,
+    preformatted: bool,
+    /// Part of a list, so printed without blank lines between its neighbours
+    list_item: bool,
+}
+
+impl Block {
+    fn render(&self, width: usize) -> String {
+        if self.preformatted {
+            let lines: Vec<String> = self
+                .text
+                .trim_matches('\n')
+                .lines()
+                .map(|line| format!("{}{line}", self.rest))
+                .collect();
+            return lines.join("\n");
+        }
+        wrap(&self.text, &self.first, &self.rest, width)
+    }
+}
+
+/// Greedy word wrap, prefixing the first line with `first` and the others with `rest`
+fn wrap(text: &str, first: &str, rest: &str, width: usize) -> String {
+    let mut lines = Vec::new();
+    let mut line = first.to_string();
+    let mut line_has_words = false;
+
+    for word in text.split_whitespace() {
+        let fits = line.chars().count() + 1 + word.chars().count() <= width;
+        if line_has_words && !fits {
+            lines.push(std::mem::replace(&mut line, rest.to_string()));
+            line_has_words = false;
+        }
+        if line_has_words {
+            line.push(' ');
+        }
+        line.push_str(word);
+        line_has_words = true;
+    }
+    lines.push(line);
+    lines.join("\n")
+}
+
+#[derive(Default)]
+struct TextRenderer {
+    blocks: Vec<Block>,
+    text: String,
+    /// Number of open lists
+    lists: usize,
+    quote_depth: usize,
+    pre: bool,
+    /// List marker waiting for the next block
+    marker: Option<String>,
+    links: Vec<String>,
+}
+
+impl Sink for TextRenderer {
+    fn text(&mut self, text: &str) {
+        if text.starts_with(char::is_whitespace) && !self.text.is_empty() {
+            self.text.push(' ');
+        }
+        self.text
+            .push_str(&text.split_whitespace().collect::<Vec<_>>().join(" "));
+        if text.ends_with(char::is_whitespace) && !text.trim().is_empty() {
+            self.text.push(' ');
+        }
+    }
+
+    fn preformatted(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    fn block(&mut self) {
+        self.flush();
+    }
+
+    fn line_break(&mut self) {
+        self.flush();
+    }
+
+    fn rule(&mut self) {
+        self.flush();
+        self.text.push_str("───");
+        self.flush();
+    }
+
+    fn heading(&mut self, _level: usize, _closing: bool) {
+        self.flush();
+    }
+
+    fn style(&mut self, _style: Style) {}
+
+    fn pre(&mut self, closing: bool) {
+        self.flush();
+        self.pre = !closing;
+    }
+
+    fn list(&mut self, _closing: bool, depth: usize) {
+        self.flush();
+        self.lists = depth;
+    }
+
+    fn list_item(&mut self, marker: Marker, _depth: usize) {
+        self.flush();
+        self.marker = Some(match marker {
+            Marker::Number(number) => format!("{number}. "),
+            Marker::Bullet => "• ".to_string(),
+        });
+    }
+
+    fn end_list_item(&mut self) {
+        self.flush();
+    }
+
+    fn quote(&mut self, closing: bool) {
+        self.flush();
+        self.quote_depth = if closing { self.quote_depth.saturating_sub(1) } else { self.quote_depth + 1 };
+    }
+
+    fn link(&mut self, href: &str, closing: bool) {
+        let number = self.link_number(href);
+        if closing {
+            let trimmed = self.text.trim_end().len();
+            self.text.truncate(trimmed);
+            self.text.push_str(&format!("[{number}]"));
+        }
+    }
+
+    fn image(&mut self, src: &str, alt: Option<&str>) {
+        let alt = alt.filter(|alt| !alt.trim().is_empty());
+        let number = self.link_number(src);
+        self.text
+            .push_str(&format!("[image: {}][{number}]", alt.unwrap_or("untitled")));
+    }
+}
+
+impl TextRenderer {
+    fn link_number(&mut self, href: &str) -> usize {
+        match self.links.iter().position(|link| link == href) {
+            Some(index) => index + 1,
+            None => {
+                self.links.push(href.to_string());
+                self.links.len()
+            }
+        }
+    }
+
+    /// Ends the current block
+    fn flush(&mut self) {
+        let text = std::mem::take(&mut self.text);
+        if text.trim().is_empty() {
+            return;
+        }
+
+        let quote = "│ ".repeat(self.quote_depth);
+        let indent = "  ".repeat(self.lists.saturating_sub(1));
+        let in_list = self.lists > 0;
+        let (first, rest, list_item) = match self.marker.take() {
+            Some(marker) => {
+                let hanging = " ".repeat(marker.chars().count());
+                (
+                    format!("{quote}{indent}{marker}"),
+                    format!("{quote}{indent}{hanging}"),
+                    true,
+                )
+            }
+            // Text continuing a list item after a nested list or line break
+            None if in_list => {
+                let prefix = format!("{quote}{indent}  ");
+                (prefix.clone(), prefix, true)
+            }
+            None if self.pre => (String::new(), format!("{quote}    "), false),
+            None => (quote.clone(), quote, false),
+        };
+
+        self.blocks
+            .push(Block { first, rest, text, preformatted: self.pre, list_item });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pai_core::{MemoryStorage, SourceKind};
+
+    fn item(id: &str) -> Item {
+        Item::test(id)
+            .with_source(SourceKind::Bluesky, "me.bsky.social")
+            .with_url(&format!("https://bsky.app/{id}"))
+    }
+
+    #[test]
+    fn renders_wrapped_text_with_numbered_links() {
+        let html = r#"<p>Read <a href="https://a.example/post">this post</a> and then <a href="https://a.example/post">again</a>, it is long enough to wrap.</p>
+<ul><li>first</li><li>second<ol><li>nested</li></ol></li></ul><blockquote><p>quoted</p></blockquote><pre>  code
+  block</pre>"#;
+
+        assert_eq!(
+            render_html(html, 40),
+            "Read this post[1] and then again[1], it\nis long enough to wrap.\n\n\
+             • first\n• second\n  1. nested\n\n│ quoted\n\n      code\n      block\n\n\
+             Links:\n[1] https://a.example/post"
+        );
+        assert_eq!(wrap_text("one two three\n\nfour", 9), "one two\nthree\n\nfour");
+    }
+
+    #[test]
+    fn only_web_urls_are_opened() {
+        assert!(check_web_url("https://bsky.app/profile/me/post/1").is_ok());
+        assert!(check_web_url("HTTP://example.com/a?b=1&c=2").is_ok());
+        for url in [
+            "file:///etc/passwd",
+            "javascript:alert(1)",
+            "ms-settings:",
+            "calc.exe",
+            "",
+        ] {
+            assert!(open_in_browser(url).is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn resolves_ids_urls_rows_and_prefixes() {
+        let storage = MemoryStorage::new();
+        for id in [
+            "at://did:plc:a/post/1",
+            "at://did:plc:a/post/2",
+            "at://did:plc:b/post/1",
+        ] {
+            storage.insert_or_replace_item(&item(id)).unwrap();
+        }
+        let last_list = vec!["at://did:plc:b/post/1".to_string(), "at://gone".to_string()];
+
+        let resolve = |reference: &str| resolve_item(&storage, reference, &last_list);
+        assert_eq!(resolve("at://did:plc:a/post/2").unwrap().id, "at://did:plc:a/post/2");
+        assert_eq!(
+            resolve("https://bsky.app/at://did:plc:a/post/1").unwrap().id,
+            "at://did:plc:a/post/1"
+        );
+        assert_eq!(resolve("1").unwrap().id, "at://did:plc:b/post/1");
+        assert!(resolve("2").unwrap_err().to_string().contains("no longer exists"));
+        assert_eq!(resolve("at://did:plc:b").unwrap().id, "at://did:plc:b/post/1");
+        assert!(resolve("at://did:plc:a")
+            .unwrap_err()
+            .to_string()
+            .contains("matches several items"));
+        assert!(resolve("nothing").unwrap_err().to_string().contains("No item matches"));
+    }
+}
@@ -0,0 +1,445 @@
+use crate::build_rss_channel;
+use atom_syndication::{Category, Content, Entry, Feed, FixedDateTime, Link, Person, Text};
+use chrono::{DateTime, Utc};
+use pai_core::{Item, ListFilter, Mention, PaiError, Storage};
+use pai_server::{escape, item_slug, PageContext, Pagination, Templates};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const TITLE: &str = "Personal Activity Index";
+
+/// Lists the files written by the previous build, so pages that disappear can be removed
+const MANIFEST: &str = ".pai-site";
+
+/// Options for `pai build-site`
+pub struct SiteOptions {
+    pub output: PathBuf,
+    /// Public base URL of the site, used for feeds, permalinks and the sitemap
+    pub base_url: String,
+    pub per_page: usize,
+    pub templates: Option<PathBuf>,
+}
+
+/// What a build changed in the output directory
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BuildReport {
+    pub written: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+/// Renders every page of the site and writes those whose content changed
+pub fn build_site(storage: &dyn Storage, options: &SiteOptions) -> Result<BuildReport, PaiError> {
+    let templates = Templates::load(options.templates.as_deref())?;
+    let page = PageContext::static_site(&options.base_url);
+
+    let items = storage.list_items(&ListFilter::default())?;
+    let mut mentions = BTreeMap::new();
+    for item in &items {
+        mentions.insert(item.id.clone(), storage.list_mentions(&item.id)?);
+    }
+
+    let files = render_site(&templates, &page, &items, &mentions, options.per_page)?;
+    write_site(&options.output, &files)
+}
+
+/// Renders all files of the site, keyed by their path relative to the output directory
+fn render_site(
+    templates: &Templates, page: &PageContext, items: &[Item], mentions: &BTreeMap<String, Vec<Mention>>,
+    per_page: usize,
+) -> Result<BTreeMap<String, Vec<u8>>, PaiError> {
+    let mut files = BTreeMap::new();
+    let mut sitemap = Vec::new();
+
+    for section in sections(items) {
+        let pages: Vec<&[Item]> =
+            if section.items.is_empty() { vec![&[]] } else { section.items.chunks(per_page).collect() };
+        for (index, chunk) in pages.iter().enumerate() {
+            let pagination = Pagination {
+                newer: (index > 0).then(|| page.url(&page_path(&section.path, index))),
+                older: (index + 1 < pages.len()).then(|| page.url(&page_path(&section.path, index + 2))),
+            };
+            let path = page_path(&section.path, index + 1);
+            let html = templates.render_listing(&section.heading, chunk, &pagination, page);
+            sitemap.push((page.url(&path), chunk.iter().map(|item| item.created_at.clone()).max()));
+            files.insert(
+                format!("{path}index.html").trim_start_matches('/').to_string(),
+                html.into_bytes(),
+            );
+        }
+    }
+
+    for item in items {
+        let mentions = mentions.get(&item.id).map(Vec::as_slice).unwrap_or_default();
+        let html = templates.render_item(item, mentions, page);
+        sitemap.push((page.permalink(&item.id), Some(item.created_at.clone())));
+        files.insert(format!("item/{}/index.html", item_slug(&item.id)), html.into_bytes());
+    }
+
+    let latest = &items[..items.len().min(per_page)];
+    let mut channel = build_rss_channel(latest)?;
+    channel.set_link(page.url("/"));
+    files.insert("rss.xml".to_string(), channel.to_string().into_bytes());
+    files.insert("atom.xml".to_string(), atom_feed(latest, page).to_string().into_bytes());
+    files.insert("feed.json".to_string(), json_feed(latest, page)?);
+    files.insert("sitemap.xml".to_string(), render_sitemap(&sitemap).into_bytes());
+
+    Ok(files)
+}
+
+/// A paginated listing: the timeline, one source or one tag
+struct Section {
+    /// Site path of the first page, e.g. `/` or `/tag/rust/`
+    path: String,
+    heading: String,
+    items: Vec<Item>,
+}
+
+fn sections(items: &[Item]) -> Vec<Section> {
+    let mut sources: BTreeMap<String, Section> = BTreeMap::new();
+    let mut tags: BTreeMap<String, Section> = BTreeMap::new();
+
+    for item in items {
+        let path = format!("/source/{}/{}/", item.source_kind, slug(&item.source_id));
+        sources
+            .entry(path.clone())
+            .or_insert_with(|| Section {
+                path,
+                heading: format!("{} on {}", item.source_id, item.source_kind),
+                items: Vec::new(),
+            })
+            .items
+            .push(item.clone());
+
+        // Tags differing only in case or punctuation share a page.
+        let item_tags: BTreeSet<String> = item.tags.iter().map(|tag| slug(tag)).collect();
+        for tag_slug in item_tags {
+            let path = format!("/tag/{tag_slug}/");
+            let tag = item.tags.iter().find(|tag| slug(tag) == tag_slug).unwrap_or(&tag_slug);
+            tags.entry(path.clone())
+                .or_insert_with(|| Section { path, heading: format!("#{tag}"), items: Vec::new() })
+                .items
+                .push(item.clone());
+        }
+    }
+
+    let timeline = Section { path: "/".to_string(), heading: TITLE.to_string(), items: items.to_vec() };
+    std::iter::once(timeline)
+        .chain(sources.into_values())
+        .chain(tags.into_values())
+        .collect()
+}
+
+/// Site path of page `number` (1-based) of a listing
+fn page_path(first: &str, number: usize) -> String {
+    if number <= 1 {
+        first.to_string()
+    } else {
+        format!("{first}page/{number}/")
+    }
+}
+
+/// Lowercase `[a-z0-9.-]` form of a source ID or tag
+fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    match slug.trim_matches(|c| c == '-' || c == '.') {
+        "" => item_slug(text),
+        slug => slug.to_string(),
+    }
+}
+
+fn parse_date(value: &str) -> Option<FixedDateTime> {
+    DateTime::parse_from_rfc3339(value)
+        .or_else(|_| DateTime::parse_from_rfc2822(value))
+        .ok()
+}
+
+fn atom_feed(items: &[Item], page: &PageContext) -> Feed {
+    let entries: Vec<Entry> = items
+        .iter()
+        .map(|item| {
+            let mut entry = Entry::default();
+            entry.set_id(page.permalink(&item.id));
+            entry.set_title(Text::plain(
+                item.title.as_deref().or(item.summary.as_deref()).unwrap_or(&item.url),
+            ));
+            let published = parse_date(&item.published_at);
+            entry.set_published(published);
+            entry.set_updated(parse_date(&item.created_at).or(published).unwrap_or_default());
+
+            let mut link = Link::default();
+            link.set_href(item.url.clone());
+            entry.set_links(vec![link]);
+
+            let mut author = Person::default();
+            author.set_name(item.author.as_deref().unwrap_or(&item.source_id));
+            entry.set_authors(vec![author]);
+
+            entry.set_summary(item.summary.as_deref().map(Text::plain));
+            if let Some(html) = &item.content_html {
+                let mut content = Content::default();
+                content.set_content_type(Some("html".to_string()));
+                content.set_value(Some(html.clone()));
+                entry.set_content(Some(content));
+            }
+
+            entry.set_categories(
+                item.tags
+                    .iter()
+                    .map(|tag| {
+                        let mut category = Category::default();
+                        category.set_term(tag.clone());
+                        category
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            entry
+        })
+        .collect();
+
+    let mut alternate = Link::default();
+    alternate.set_href(page.url("/"));
+    let mut this = Link::default();
+    this.set_href(page.url("/atom.xml"));
+    this.set_rel("self");
+
+    let mut feed = Feed::default();
+    feed.set_id(page.url("/"));
+    feed.set_title(TITLE);
+    feed.set_links(vec![alternate, this]);
+    feed.set_updated(
+        items
+            .iter()
+            .filter_map(|item| parse_date(&item.created_at))
+            .max()
+            .unwrap_or_default(),
+    );
+    feed.set_entries(entries);
+    feed
+}
+
+/// JSON Feed 1.1 document
+fn json_feed(items: &[Item], page: &PageContext) -> Result<Vec<u8>, PaiError> {
+    let entries: Vec<serde_json::Value> = items
+        .iter()
+        .map(|item| {
+            let mut entry = json!({
+                "id": item.id,
+                "url": item.url,
+                "external_url": page.permalink(&item.id),
+                "date_published": item.published_at,
+                "authors": [{ "name": item.author.as_deref().unwrap_or(&item.source_id) }],
+                "tags": item.tags,
+            });
+            if let Some(title) = &item.title {
+                entry["title"] = json!(title);
+            }
+            if let Some(summary) = &item.summary {
+                entry["summary"] = json!(summary);
+            }
+            match (&item.content_html, &item.summary) {
+                (Some(html), _) => entry["content_html"] = json!(html),
+                (None, Some(summary)) => entry["content_text"] = json!(summary),
+                (None, None) => entry["content_text"] = json!(""),
+            }
+            entry
+        })
+        .collect();
+
+    let feed = json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": TITLE,
+        "home_page_url": page.url("/"),
+        "feed_url": page.url("/feed.json"),
+        "items": entries,
+    });
+    let mut body =
+        serde_json::to_vec_pretty(&feed).map_err(|e| PaiError::Parse(format!("Failed to serialize JSON Feed: {e}")))?;
+    body.push(b'\n');
+    Ok(body)
+}
+
+fn render_sitemap(urls: &[(String, Option<String>)]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for (url, modified) in urls {
+        xml.push_str(&format!("  <url><loc>{}</loc>", escape(url)));
+        if let Some(date) = modified.as_deref().and_then(parse_date) {
+            xml.push_str(&format!(
+                "<lastmod>{}</lastmod>",
+                date.with_timezone(&Utc).format("%Y-%m-%d")
+            ));
+        }
+        xml.push_str("</url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+/// Writes `files` under `output`, skipping files whose content is unchanged
+///
+/// Files listed in the previous build's manifest but no longer generated are removed, along
+/// with directories they leave empty.
+fn write_site(output: &Path, files: &BTreeMap<String, Vec<u8>>) -> Result<BuildReport, PaiError> {
+    fs::create_dir_all(output)?;
+    let manifest_path = output.join(MANIFEST);
+    let previous = match fs::read_to_string(&manifest_path) {
+        Ok(manifest) => manifest.lines().map(String::from).collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(PaiError::Io(e)),
+    };
+
+    let mut report = BuildReport::default();
+    for (path, content) in files {
+        if write_if_changed(&output.join(path), content)? {
+            report.written += 1;
+        } else {
+            report.unchanged += 1;
+        }
+    }
+
+    for path in previous.iter().filter(|path| !files.contains_key(*path)) {
+        // Only paths this command wrote are removed; anything outside the output directory is ignored.
+        if path.split('/').any(|part| part == ".." || part.is_empty()) {
+            continue;
+        }
+        let target = output.join(path);
+        if target.is_file() {
+            fs::remove_file(&target)?;
+            report.removed += 1;
+        }
+        let mut dir = target.parent();
+        while let Some(parent) = dir.filter(|dir| *dir != output) {
+            if fs::remove_dir(parent).is_err() {
+                break;
+            }
+            dir = parent.parent();
+        }
+    }
+
+    let manifest: Vec<&str> = files.keys().map(String::as_str).collect();
+    fs::write(&manifest_path, manifest.join("\n") + "\n")?;
+
+    Ok(report)
+}
+
+/// Writes `content` to `target` unless the file already holds it, creating parent directories
+///
+/// Returns whether the file was written.
+pub fn write_if_changed(target: &Path, content: &[u8]) -> io::Result<bool> {
+    if fs::read(target).is_ok_and(|existing| existing == content) {
+        return Ok(false);
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(target, content)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pai_core::{MemoryStorage, SourceKind};
+    use tempfile::tempdir;
+
+    fn item(id: &str, source_id: &str, tags: &[&str], published_at: &str) -> Item {
+        Item::test(id)
+            .with_source(SourceKind::Bluesky, source_id)
+            .with_summary(&format!("Post {id}"))
+            .with_url(&format!("https://bsky.app/profile/{source_id}/post/{id}"))
+            .with_tags(tags)
+            .with_published_at(published_at)
+    }
+
+    fn options(output: &Path) -> SiteOptions {
+        SiteOptions {
+            output: output.to_path_buf(),
+            base_url: "https://me.example.com/".to_string(),
+            per_page: 2,
+            templates: None,
+        }
+    }
+
+    #[test]
+    fn slugs_are_path_safe() {
+        assert_eq!(slug("me.bsky.social"), "me.bsky.social");
+        assert_eq!(slug("Rust Lang!"), "rust-lang");
+        assert!(!slug("日本").is_empty());
+        assert_eq!(page_path("/tag/rust/", 1), "/tag/rust/");
+        assert_eq!(page_path("/", 3), "/page/3/");
+    }
+
+    #[test]
+    fn builds_paginated_site_with_feeds() {
+        let storage = MemoryStorage::new();
+        storage
+            .insert_or_replace_item(&item("1", "me.bsky.social", &["Rust"], "2024-01-01T00:00:00Z"))
+            .unwrap();
+        storage
+            .insert_or_replace_item(&item("2", "me.bsky.social", &["rust"], "2024-01-02T00:00:00Z"))
+            .unwrap();
+        storage
+            .insert_or_replace_item(&item("3", "other.bsky.social", &[], "2024-01-03T00:00:00Z"))
+            .unwrap();
+
+        let dir = tempdir().unwrap();
+        let report = build_site(&storage, &options(dir.path())).unwrap();
+        assert_eq!(report.unchanged, 0);
+
+        let read = |path: &str| fs::read_to_string(dir.path().join(path)).unwrap();
+        let index = read("index.html");
+        assert!(index.contains(r#"<main class="h-feed">"#));
+        assert!(index.contains(r#"<a rel="next" href="https://me.example.com/page/2/">"#));
+        assert!(!index.contains("webmention"));
+        assert!(read("page/2/index.html").contains(r#"<a rel="prev" href="https://me.example.com/">"#));
+        assert_eq!(read("tag/rust/index.html").matches(r#"class="h-entry""#).count(), 2);
+        assert!(dir.path().join("source/bluesky/other.bsky.social/index.html").exists());
+        assert!(index.contains(&format!(r#"href="https://me.example.com/item/{}/""#, item_slug("3"))));
+        assert!(dir.path().join(format!("item/{}/index.html", item_slug("3"))).exists());
+
+        assert!(read("rss.xml").contains("<link>https://me.example.com/</link>"));
+        assert!(read("atom.xml").contains("<feed"));
+        let feed: serde_json::Value = serde_json::from_str(&read("feed.json")).unwrap();
+        assert_eq!(feed["items"].as_array().unwrap().len(), 2);
+        assert!(read("sitemap.xml").contains("<loc>https://me.example.com/tag/rust/</loc>"));
+
+        let report = build_site(&storage, &options(dir.path())).unwrap();
+        assert_eq!(
+            report,
+            BuildReport { written: 0, unchanged: report.unchanged, removed: 0 }
+        );
+    }
+
+    #[test]
+    fn rebuild_removes_stale_pages() {
+        let storage = MemoryStorage::new();
+        storage
+            .insert_or_replace_item(&item("1", "me.bsky.social", &["old"], "2024-01-01T00:00:00Z"))
+            .unwrap();
+        let dir = tempdir().unwrap();
+        build_site(&storage, &options(dir.path())).unwrap();
+        assert!(dir.path().join("tag/old/index.html").exists());
+
+        let storage = MemoryStorage::new();
+        storage
+            .insert_or_replace_item(&item("2", "me.bsky.social", &[], "2024-01-02T00:00:00Z"))
+            .unwrap();
+        let report = build_site(&storage, &options(dir.path())).unwrap();
+        assert!(report.removed >= 2);
+        assert!(!dir.path().join("tag").exists());
+        assert!(!dir.path().join(format!("item/{}", item_slug("1"))).exists());
+    }
+}
@@ -0,0 +1,596 @@
+use crate::html::{self, Token};
+use pai_core::{
+    BearBlogConfig, BearBlogFetcher, BlueskyConfig, BlueskyFetcher, ConfiguredSource, FeedConfig, FeedFetcher,
+    LeafletConfig, LeafletFetcher, PaiError, SourceFetcher, SourceKind, SourcesConfig, SubstackConfig, SubstackFetcher,
+};
+use pai_server::escape;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{value, ArrayOfTables, DocumentMut, Item, Table};
+
+/// Works out the source an added URL, handle or DID refers to
+///
+/// The kind is detected from well-known hosts (`*.substack.com`, `bsky.app/profile/...`,
+/// `*.leaflet.pub`, `*.bearblog.dev`); `kind` is required for anything else.
+/// `id` overrides the Leaflet or BearBlog ID, which defaults to the first label of the host, or the
+/// ID of a generic feed, which defaults to the host without `www.`.
+pub fn detect_source(input: &str, kind: Option<SourceKind>, id: Option<String>) -> Result<ConfiguredSource, PaiError> {
+    let input = input.trim();
+    let handle = input.strip_prefix('@').unwrap_or(input);
+    if handle.starts_with("did:") || (kind == Some(SourceKind::Bluesky) && !handle.contains('/')) {
+        return bluesky(handle, id);
+    }
+
+    let with_scheme = if input.contains("://") { input.to_string() } else { format!("https://{input}") };
+    let url = url::Url::parse(&with_scheme)
+        .map_err(|e| PaiError::InvalidArgument(format!("'{input}' is not a URL, Bluesky handle or DID: {e}")))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| PaiError::InvalidArgument(format!("'{input}' has no host")))?
+        .to_lowercase();
+    let base_url = format!("{}://{host}", url.scheme());
+
+    let detected = if host == "bsky.app" {
+        Some(SourceKind::Bluesky)
+    } else if host.ends_with(".substack.com") {
+        Some(SourceKind::Substack)
+    } else if host.ends_with(".leaflet.pub") {
+        Some(SourceKind::Leaflet)
+    } else if host.ends_with(".bearblog.dev") {
+        Some(SourceKind::BearBlog)
+    } else if !input.contains("://") && host.ends_with(".bsky.social") {
+        Some(SourceKind::Bluesky)
+    } else {
+        None
+    };
+
+    match kind.or(detected) {
+        Some(SourceKind::Bluesky) if host == "bsky.app" => {
+            let mut segments = url.path_segments().into_iter().flatten();
+            match (segments.next(), segments.next()) {
+                (Some("profile"), Some(handle)) if !handle.is_empty() => bluesky(handle, id),
+                _ => Err(PaiError::InvalidArgument(format!(
+                    "'{input}' is not a Bluesky profile URL (https://bsky.app/profile/<handle>)"
+                ))),
+            }
+        }
+        Some(SourceKind::Bluesky) => bluesky(&host, id),
+        Some(SourceKind::Substack) => {
+            if id.is_some() {
+                return Err(PaiError::InvalidArgument(
+                    "Substack source IDs come from the URL; --id only applies to Leaflet, BearBlog and feeds"
+                        .to_string(),
+                ));
+            }
+            let config = SubstackConfig { enabled: true, base_url };
+            Ok(ConfiguredSource {
+                kind: SourceKind::Substack,
+                id: config.source_id(),
+                url: config.base_url,
+                enabled: true,
+            })
+        }
+        Some(kind @ (SourceKind::Leaflet | SourceKind::BearBlog)) => {
+            let id = id
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty())
+                .unwrap_or_else(|| host.split('.').next().unwrap_or(&host).to_string());
+            Ok(ConfiguredSource { kind, id, url: base_url, enabled: true })
+        }
+        Some(SourceKind::Feed) => {
+            let id = id
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty())
+                .unwrap_or_else(|| host.trim_start_matches("www.").to_string());
+            Ok(ConfiguredSource { kind: SourceKind::Feed, id, url: with_scheme, enabled: true })
+        }
+        None => Err(PaiError::InvalidArgument(format!(
+            "Can't tell the source kind of '{input}'; pass it with -k"
+        ))),
+    }
+}
+
+fn bluesky(handle: &str, id: Option<String>) -> Result<ConfiguredSource, PaiError> {
+    if id.is_some() {
+        return Err(PaiError::InvalidArgument(
+            "Bluesky sources are identified by their handle; --id only applies to Leaflet, BearBlog and feeds"
+                .to_string(),
+        ));
+    }
+    Ok(ConfiguredSource {
+        kind: SourceKind::Bluesky,
+        id: handle.to_string(),
+        url: format!("https://bsky.app/profile/{handle}"),
+        enabled: true,
+    })
+}
+
+/// Fetches `source` once without storing anything, returning how many items it has
+pub fn verify_source(source: &ConfiguredSource) -> Result<usize, PaiError> {
+    let fetcher: Box<dyn SourceFetcher> = match source.kind {
+        SourceKind::Substack => Box::new(SubstackFetcher::new(SubstackConfig {
+            enabled: true,
+            base_url: source.url.clone(),
+        })),
+        SourceKind::Bluesky => Box::new(BlueskyFetcher::new(BlueskyConfig {
+            enabled: true,
+            handle: source.id.clone(),
+        })),
+        SourceKind::Leaflet => Box::new(LeafletFetcher::new(LeafletConfig {
+            enabled: true,
+            id: source.id.clone(),
+            base_url: source.url.clone(),
+        })),
+        SourceKind::BearBlog => Box::new(BearBlogFetcher::new(BearBlogConfig {
+            enabled: true,
+            id: source.id.clone(),
+            base_url: source.url.clone(),
+        })),
+        SourceKind::Feed => Box::new(FeedFetcher::new(FeedConfig {
+            enabled: true,
+            id: source.id.clone(),
+            url: source.url.clone(),
+        })),
+    };
+    Ok(fetcher.fetch()?.len())
+}
+
+/// Just the `[sources]` section, so other sections don't need to be valid
+#[derive(Deserialize)]
+struct SourcesSection {
+    #[serde(default)]
+    sources: SourcesConfig,
+}
+
+/// config.toml opened for editing, keeping its comments and formatting
+pub struct ConfigDocument {
+    path: PathBuf,
+    document: DocumentMut,
+}
+
+impl ConfigDocument {
+    /// Opens `path`, or starts an empty document if it doesn't exist yet
+    pub fn open(path: &Path) -> Result<Self, PaiError> {
+        let content = if path.exists() { fs::read_to_string(path)? } else { String::new() };
+        let document = content
+            .parse::<DocumentMut>()
+            .map_err(|e| PaiError::Config(format!("Failed to parse {}: {e}", path.display())))?;
+        Ok(Self { path: path.to_path_buf(), document })
+    }
+
+    pub fn save(&self) -> Result<(), PaiError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, self.document.to_string())?;
+        Ok(())
+    }
+
+    /// Sources as written in the document
+    pub fn sources(&self) -> Result<Vec<ConfiguredSource>, PaiError> {
+        let section: SourcesSection = toml::from_str(&self.document.to_string())
+            .map_err(|e| PaiError::Config(format!("Invalid [sources] in {}: {e}", self.path.display())))?;
+        Ok(section.sources.configured())
+    }
+
+    /// Appends `source`, failing if a source with the same kind and ID exists
+    ///
+    /// Substack and Bluesky allow a single source each.
+    pub fn add(&mut self, source: &ConfiguredSource) -> Result<(), PaiError> {
+        let existing = self.sources()?;
+        if let Some(found) = existing.iter().find(|s| s.kind == source.kind && s.id == source.id) {
+            return Err(PaiError::InvalidArgument(format!(
+                "{} source '{}' is already configured",
+                found.kind, found.id
+            )));
+        }
+        if matches!(source.kind, SourceKind::Substack | SourceKind::Bluesky) {
+            if let Some(found) = existing.iter().find(|s| s.kind == source.kind) {
+                return Err(PaiError::InvalidArgument(format!(
+                    "Only one {} source is supported and '{}' is already configured; remove it first",
+                    found.kind, found.id
+                )));
+            }
+        }
+
+        let mut table = Table::new();
+        table.insert("enabled", value(true));
+        match source.kind {
+            SourceKind::Substack => {
+                table.insert("base_url", value(&source.url));
+            }
+            SourceKind::Bluesky => {
+                table.insert("handle", value(&source.id));
+            }
+            SourceKind::Leaflet | SourceKind::BearBlog => {
+                table.insert("id", value(&source.id));
+                table.insert("base_url", value(&source.url));
+            }
+            SourceKind::Feed => {
+                table.insert("id", value(&source.id));
+                table.insert("url", value(&source.url));
+            }
+        }
+
+        let sources = self.sources_table()?;
+        let key = table_key(source.kind);
+        match source.kind {
+            SourceKind::Substack | SourceKind::Bluesky => {
+                sources.insert(key, Item::Table(table));
+            }
+            SourceKind::Leaflet | SourceKind::BearBlog | SourceKind::Feed => {
+                let entry = sources.entry(key).or_insert(Item::ArrayOfTables(ArrayOfTables::new()));
+                let array = entry
+                    .as_array_of_tables_mut()
+                    .ok_or_else(|| PaiError::Config(format!("sources.{key} must be an array of tables")))?;
+                array.push(table);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the source matching `id` (or its URL), returning it
+    pub fn remove(&mut self, id: &str, kind: Option<SourceKind>) -> Result<ConfiguredSource, PaiError> {
+        let (source, index) = self.find(id, kind)?;
+        let sources = self.sources_table()?;
+        let key = table_key(source.kind);
+        match index {
+            None => {
+                sources.remove(key);
+            }
+            Some(index) => {
+                let now_empty = match sources.get_mut(key).and_then(Item::as_array_of_tables_mut) {
+                    Some(array) => {
+                        array.remove(index);
+                        array.is_empty()
+                    }
+                    None => false,
+                };
+                if now_empty {
+                    sources.remove(key);
+                }
+            }
+        }
+        Ok(source)
+    }
+
+    /// Sets `enabled` on the source matching `id` (or its URL), returning it
+    pub fn set_enabled(
+        &mut self, id: &str, kind: Option<SourceKind>, enabled: bool,
+    ) -> Result<ConfiguredSource, PaiError> {
+        let (source, index) = self.find(id, kind)?;
+        let sources = self.sources_table()?;
+        let key = table_key(source.kind);
+        let table = match index {
+            None => sources.get_mut(key).and_then(Item::as_table_mut),
+            Some(index) => sources
+                .get_mut(key)
+                .and_then(Item::as_array_of_tables_mut)
+                .and_then(|array| array.get_mut(index)),
+        };
+        let table = table.ok_or_else(|| PaiError::Config(format!("sources.{key} is not a table")))?;
+        table.insert("enabled", value(enabled));
+        Ok(ConfiguredSource { enabled, ..source })
+    }
+
+    /// Finds a source by ID or URL, with its position in its array of tables for the kinds that allow several
+    fn find(&self, id: &str, kind: Option<SourceKind>) -> Result<(ConfiguredSource, Option<usize>), PaiError> {
+        let id = id.trim();
+        let wanted = id.trim_end_matches('/');
+        let sources = self.sources()?;
+        let matches: Vec<(usize, &ConfiguredSource)> = sources
+            .iter()
+            .enumerate()
+            .filter(|(_, source)| kind.is_none_or(|kind| source.kind == kind))
+            .filter(|(_, source)| source.id == id || source.url.trim_end_matches('/') == wanted)
+            .collect();
+
+        let (position, source) = match matches.as_slice() {
+            [] => {
+                return Err(PaiError::InvalidArgument(format!(
+                    "No configured source matches '{id}'"
+                )))
+            }
+            [found] => *found,
+            _ => {
+                return Err(PaiError::InvalidArgument(format!(
+                    "'{id}' matches several sources; pick one with -k"
+                )))
+            }
+        };
+
+        // Position among the sources of the same kind, which is the array index for the kinds that allow several
+        let index = match source.kind {
+            SourceKind::Substack | SourceKind::Bluesky => None,
+            kind => Some(sources[..position].iter().filter(|other| other.kind == kind).count()),
+        };
+        Ok((source.clone(), index))
+    }
+
+    /// Adds a source for every feed in an OPML subscription list
+    ///
+    /// Feeds already configured, and Substack or Bluesky feeds when one is configured, are skipped.
+    /// Generic feed IDs that are taken get a numeric suffix.
+    pub fn import_opml(&mut self, opml: &str) -> Result<Vec<Imported>, PaiError> {
+        let mut results = Vec::new();
+        for feed_url in opml_feed_urls(opml) {
+            let mut source = match detect_source(&feed_url, None, None)
+                .or_else(|_| detect_source(&feed_url, Some(SourceKind::Feed), None))
+            {
+                Ok(source) => source,
+                Err(e) => {
+                    results.push(Imported::Skipped(feed_url, e.to_string()));
+                    continue;
+                }
+            };
+
+            let existing = self.sources()?;
+            if existing
+                .iter()
+                .any(|s| s.kind == source.kind && s.feed_url() == source.feed_url())
+            {
+                results.push(Imported::Skipped(feed_url, "already configured".to_string()));
+                continue;
+            }
+            if source.kind == SourceKind::Feed {
+                let base = source.id.clone();
+                let mut suffix = 1;
+                while existing.iter().any(|s| s.kind == SourceKind::Feed && s.id == source.id) {
+                    suffix += 1;
+                    source.id = format!("{base}-{suffix}");
+                }
+            }
+
+            match self.add(&source) {
+                Ok(()) => results.push(Imported::Added(source)),
+                Err(e) => results.push(Imported::Skipped(feed_url, e.to_string())),
+            }
+        }
+        Ok(results)
+    }
+
+    fn sources_table(&mut self) -> Result<&mut Table, PaiError> {
+        let sources = self.document.entry("sources").or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        });
+        sources
+            .as_table_mut()
+            .ok_or_else(|| PaiError::Config("[sources] must be a table".to_string()))
+    }
+}
+
+/// Key of a source kind under `[sources]`
+fn table_key(kind: SourceKind) -> &'static str {
+    match kind {
+        SourceKind::Substack => "substack",
+        SourceKind::Bluesky => "bluesky",
+        SourceKind::Leaflet => "leaflet",
+        SourceKind::BearBlog => "bearblog",
+        SourceKind::Feed => "feed",
+    }
+}
+
+/// What happened to one outline of an imported OPML file
+#[derive(Debug)]
+pub enum Imported {
+    Added(ConfiguredSource),
+    /// The feed URL and why it was left out
+    Skipped(String, String),
+}
+
+/// `xmlUrl` of every outline in an OPML file, in document order
+///
+/// Folders are outlines without an `xmlUrl` and are flattened.
+fn opml_feed_urls(opml: &str) -> Vec<String> {
+    html::tokenize(opml)
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Tag { name, closing: false, source } if name == "outline" => html::attribute(source, "xmlurl"),
+            _ => None,
+        })
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect()
+}
+
+/// An OPML 2.0 subscription list with the feed of each source
+pub fn to_opml(sources: &[ConfiguredSource]) -> String {
+    let mut opml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml version=\"2.0\">\n  <head>\n    \
+         <title>Personal Activity Index sources</title>\n  </head>\n  <body>\n",
+    );
+    for source in sources {
+        let html_url = match source.kind {
+            SourceKind::Feed => String::new(),
+            _ => format!(" htmlUrl=\"{}\"", escape(&source.url)),
+        };
+        opml.push_str(&format!(
+            "    <outline type=\"rss\" text=\"{id}\" title=\"{id}\" category=\"{kind}\" xmlUrl=\"{feed}\"{html_url}/>\n",
+            id = escape(&source.id),
+            kind = source.kind,
+            feed = escape(&source.feed_url()),
+        ));
+    }
+    opml.push_str("  </body>\n</opml>\n");
+    opml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"# My sources
+[database]
+path = "/tmp/pai.db"
+
+[sources.bluesky]
+enabled = true # main account
+handle = "me.bsky.social"
+
+# Leaflet publications
+[[sources.leaflet]]
+enabled = true
+id = "first"
+base_url = "https://first.leaflet.pub"
+
+[[sources.leaflet]]
+enabled = true
+id = "second"
+base_url = "https://second.leaflet.pub"
+"#;
+
+    #[test]
+    fn detects_kinds_from_urls_and_handles() {
+        let detect = |input: &str| detect_source(input, None, None).unwrap();
+        assert_eq!(detect("https://me.substack.com/").kind, SourceKind::Substack);
+        assert_eq!(detect("me.substack.com").id, "me.substack.com");
+        assert_eq!(detect("https://bsky.app/profile/me.dev").id, "me.dev");
+        assert_eq!(detect("@me.bsky.social").kind, SourceKind::Bluesky);
+        assert_eq!(detect("did:plc:abc").id, "did:plc:abc");
+
+        let leaflet = detect("https://notes.leaflet.pub/some-post");
+        assert_eq!(
+            (leaflet.kind, leaflet.id.as_str(), leaflet.url.as_str()),
+            (SourceKind::Leaflet, "notes", "https://notes.leaflet.pub")
+        );
+        assert_eq!(detect("https://blog.bearblog.dev").kind, SourceKind::BearBlog);
+
+        assert!(detect_source("https://example.com", None, None).is_err());
+        let custom = detect_source(
+            "https://example.com",
+            Some(SourceKind::BearBlog),
+            Some("mine".to_string()),
+        )
+        .unwrap();
+        assert_eq!(
+            (custom.id.as_str(), custom.url.as_str()),
+            ("mine", "https://example.com")
+        );
+        assert_eq!(
+            detect_source("me.dev", Some(SourceKind::Bluesky), None).unwrap().id,
+            "me.dev"
+        );
+        assert!(detect_source("https://me.substack.com", None, Some("x".to_string())).is_err());
+
+        let feed = detect_source("https://www.example.com/feed.xml", Some(SourceKind::Feed), None).unwrap();
+        assert_eq!(
+            (feed.id.as_str(), feed.url.as_str(), feed.feed_url()),
+            ("example.com", "https://www.example.com/feed.xml", feed.url.clone())
+        );
+    }
+
+    #[test]
+    fn edits_sources_and_keeps_comments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, CONFIG).unwrap();
+
+        let mut document = ConfigDocument::open(&path).unwrap();
+        document
+            .add(&detect_source("https://blog.bearblog.dev", None, None).unwrap())
+            .unwrap();
+        assert!(document
+            .add(&detect_source("https://first.leaflet.pub", None, None).unwrap())
+            .is_err());
+        assert!(document
+            .add(&detect_source("@other.bsky.social", None, None).unwrap())
+            .is_err());
+
+        assert_eq!(
+            document.set_enabled("second", None, false).unwrap().kind,
+            SourceKind::Leaflet
+        );
+        assert_eq!(document.remove("https://first.leaflet.pub/", None).unwrap().id, "first");
+        assert!(document.remove("missing", None).is_err());
+        document.save().unwrap();
+
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.starts_with("# My sources\n[database]"));
+        assert!(saved.contains("enabled = true # main account"));
+        assert!(saved.contains(
+            "[[sources.bearblog]]\nenabled = true\nid = \"blog\"\nbase_url = \"https://blog.bearblog.dev\"\n"
+        ));
+        assert!(!saved.contains("first"));
+
+        let sources = ConfigDocument::open(&path).unwrap().sources().unwrap();
+        let summary: Vec<(&str, bool)> = sources.iter().map(|s| (s.id.as_str(), s.enabled)).collect();
+        assert_eq!(summary, [("me.bsky.social", true), ("second", false), ("blog", true)]);
+    }
+
+    #[test]
+    fn imports_and_exports_opml() {
+        const OPML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0">
+  <head><title>Subscriptions</title></head>
+  <body>
+    <outline text="Blogs">
+      <outline type="rss" text="Me" xmlUrl="https://me.substack.com/feed"/>
+      <outline type="rss" text="Notes" xmlUrl="https://notes.leaflet.pub/rss"/>
+      <outline type="rss" text="Bear" xmlUrl="https://blog.bearblog.dev/feed/?type=rss"/>
+      <outline type="rss" text="Example" xmlUrl="https://www.example.com/feed.xml?a=1&amp;b=2"/>
+      <outline type="rss" text="Comments" xmlUrl="https://example.com/comments.xml"/>
+    </outline>
+    <outline type="rss" text="Other" xmlUrl="https://other.substack.com/feed"/>
+  </body>
+</opml>"#;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, CONFIG).unwrap();
+        let mut document = ConfigDocument::open(&path).unwrap();
+
+        let added: Vec<(SourceKind, String)> = document
+            .import_opml(OPML)
+            .unwrap()
+            .into_iter()
+            .filter_map(|result| match result {
+                Imported::Added(source) => Some((source.kind, source.id)),
+                Imported::Skipped(..) => None,
+            })
+            .collect();
+        assert_eq!(
+            added,
+            [
+                (SourceKind::Substack, "me.substack.com".to_string()),
+                (SourceKind::Leaflet, "notes".to_string()),
+                (SourceKind::BearBlog, "blog".to_string()),
+                (SourceKind::Feed, "example.com".to_string()),
+                (SourceKind::Feed, "example.com-2".to_string()),
+            ]
+        );
+        assert!(document
+            .import_opml(OPML)
+            .unwrap()
+            .iter()
+            .all(|result| matches!(result, Imported::Skipped(..))));
+
+        let sources = document.sources().unwrap();
+        let opml = to_opml(&sources);
+        assert!(opml.contains(
+            r#"<outline type="rss" text="example.com" title="example.com" category="feed" xmlUrl="https://www.example.com/feed.xml?a=1&amp;b=2"/>"#
+        ));
+        let exported: Vec<String> = sources.iter().map(ConfiguredSource::feed_url).collect();
+        assert_eq!(opml_feed_urls(&opml), exported);
+        assert_eq!(exported[1], "https://bsky.app/profile/me.bsky.social/rss");
+    }
+
+    #[test]
+    fn adds_sources_to_a_new_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pai").join("config.toml");
+
+        let mut document = ConfigDocument::open(&path).unwrap();
+        document
+            .add(&detect_source("https://me.substack.com", None, None).unwrap())
+            .unwrap();
+        document.save().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[sources.substack]\nenabled = true\nbase_url = \"https://me.substack.com\"\n"
+        );
+    }
+}
@@ -0,0 +1,627 @@
+use crate::show;
+use base64::Engine;
+use pai_core::{Item, ListFilter, PaiError, SourceKind, Storage};
+use pai_server::SqliteStorage;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+/// How long to wait for a key before checking the database for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Most items loaded for one filter
+const ITEM_LIMIT: usize = 1000;
+
+/// Rows moved by PageUp/PageDown
+const PAGE: usize = 10;
+
+/// Browses the SQLite index at `db_path` until the user quits
+pub fn run(db_path: &Path) -> Result<(), PaiError> {
+    let storage = SqliteStorage::new(db_path)?;
+    let mut app = App::new(&storage);
+    let mut watcher = DbWatcher::new(db_path);
+
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, &mut app, &mut watcher);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App, watcher: &mut DbWatcher) -> Result<(), PaiError> {
+    while !app.quit {
+        terminal.draw(|frame| app.draw(frame))?;
+
+        if !event::poll(POLL_INTERVAL)? {
+            if watcher.changed() {
+                app.reload();
+            }
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match app.handle_key(key) {
+            Action::None => {}
+            Action::Open(url) => {
+                // Leave the alternate screen so terminal browsers can take over
+                disable_raw_mode()?;
+                execute!(io::stdout(), LeaveAlternateScreen)?;
+                let opened = show::open_in_browser(&url);
+                execute!(io::stdout(), EnterAlternateScreen)?;
+                enable_raw_mode()?;
+                terminal.clear()?;
+                app.status = Some(match opened {
+                    Ok(()) => format!("Opened {url}"),
+                    Err(e) => e.to_string(),
+                });
+            }
+            Action::Copy(url) => {
+                copy_to_clipboard(&url)?;
+                app.status = Some(format!("Copied {url}"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Copies `text` with an OSC 52 escape, which most terminals support, including over SSH
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{encoded}\x07")?;
+    stdout.flush()
+}
+
+/// Notices writes to the database from another process, such as `pai sync`
+///
+/// Compares the modification time and length of the database file and its WAL.
+struct DbWatcher {
+    paths: [PathBuf; 2],
+    stamps: [Option<(SystemTime, u64)>; 2],
+}
+
+impl DbWatcher {
+    fn new(db_path: &Path) -> Self {
+        let mut wal = db_path.as_os_str().to_owned();
+        wal.push("-wal");
+        let paths = [db_path.to_path_buf(), PathBuf::from(wal)];
+        let stamps = [stamp(&paths[0]), stamp(&paths[1])];
+        Self { paths, stamps }
+    }
+
+    fn changed(&mut self) -> bool {
+        let stamps = [stamp(&self.paths[0]), stamp(&self.paths[1])];
+        let changed = stamps != self.stamps;
+        self.stamps = stamps;
+        changed
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Side effects the event loop performs for a key
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    None,
+    Open(String),
+    Copy(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Kind,
+    Source,
+    Since,
+    Query,
+}
+
+impl Field {
+    const ALL: [Field; 4] = [Field::Kind, Field::Source, Field::Since, Field::Query];
+
+    fn label(self) -> &'static str {
+        match self {
+            Field::Kind => "kind",
+            Field::Source => "source",
+            Field::Since => "since",
+            Field::Query => "query",
+        }
+    }
+
+    fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|field| *field == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    fn previous(self) -> Self {
+        let index = Self::ALL.iter().position(|field| *field == self).unwrap_or(0);
+        Self::ALL[(index + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+/// Filter bar values as typed, parsed on every reload so relative `since` values stay current
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Filters {
+    kind: String,
+    source: String,
+    since: String,
+    query: String,
+}
+
+impl Filters {
+    fn get(&self, field: Field) -> &str {
+        match field {
+            Field::Kind => &self.kind,
+            Field::Source => &self.source,
+            Field::Since => &self.since,
+            Field::Query => &self.query,
+        }
+    }
+
+    fn get_mut(&mut self, field: Field) -> &mut String {
+        match field {
+            Field::Kind => &mut self.kind,
+            Field::Source => &mut self.source,
+            Field::Since => &mut self.since,
+            Field::Query => &mut self.query,
+        }
+    }
+
+    fn to_list_filter(&self) -> Result<ListFilter, PaiError> {
+        let kind = self.kind.trim();
+        let source_kind = if kind.is_empty() { None } else { Some(SourceKind::from_str(kind)?) };
+        Ok(ListFilter {
+            source_kind,
+            source_id: crate::normalize_optional_string(Some(self.source.clone())),
+            limit: Some(ITEM_LIMIT),
+            since: crate::normalize_since_input(Some(self.since.clone()))?,
+            query: crate::normalize_optional_string(Some(self.query.clone())),
+        })
+    }
+}
+
+enum Mode {
+    Browse,
+    /// Editing the filter bar; `draft` is applied with Enter and discarded with Esc
+    Filter {
+        field: Field,
+        draft: Filters,
+    },
+    /// Editing the selected item's comma-separated user tags
+    Tag(String),
+}
+
+struct App<'a> {
+    storage: &'a dyn Storage,
+    filters: Filters,
+    items: Vec<Item>,
+    list: ListState,
+    preview_scroll: u16,
+    mode: Mode,
+    status: Option<String>,
+    quit: bool,
+}
+
+impl<'a> App<'a> {
+    fn new(storage: &'a dyn Storage) -> Self {
+        let mut app = Self {
+            storage,
+            filters: Filters::default(),
+            items: Vec::new(),
+            list: ListState::default(),
+            preview_scroll: 0,
+            mode: Mode::Browse,
+            status: None,
+            quit: false,
+        };
+        app.reload();
+        app
+    }
+
+    fn selected(&self) -> Option<&Item> {
+        self.list.selected().and_then(|index| self.items.get(index))
+    }
+
+    /// Reruns the current filter, keeping the selection on the same item when it is still listed
+    fn reload(&mut self) {
+        let selected_id = self.selected().map(|item| item.id.clone());
+        let items = self
+            .filters
+            .to_list_filter()
+            .and_then(|filter| self.storage.list_items(&filter));
+        match items {
+            Ok(items) => self.items = items,
+            Err(e) => {
+                self.status = Some(e.to_string());
+                return;
+            }
+        }
+
+        let index = selected_id
+            .and_then(|id| self.items.iter().position(|item| item.id == id))
+            .or_else(|| {
+                self.list
+                    .selected()
+                    .map(|index| index.min(self.items.len().saturating_sub(1)))
+            })
+            .or(Some(0))
+            .filter(|_| !self.items.is_empty());
+        if index != self.list.selected() {
+            self.preview_scroll = 0;
+        }
+        self.list.select(index);
+    }
+
+    fn select(&mut self, index: usize) {
+        if self.items.is_empty() {
+            return;
+        }
+        self.list.select(Some(index.min(self.items.len() - 1)));
+        self.preview_scroll = 0;
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return Action::None;
+        }
+
+        match &mut self.mode {
+            Mode::Browse => return self.handle_browse_key(key),
+            Mode::Filter { field, draft } => match key.code {
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Tab => *field = field.next(),
+                KeyCode::BackTab => *field = field.previous(),
+                KeyCode::Backspace => {
+                    draft.get_mut(*field).pop();
+                }
+                KeyCode::Char(c) => draft.get_mut(*field).push(c),
+                KeyCode::Enter => match draft.to_list_filter() {
+                    Ok(_) => {
+                        self.filters = draft.clone();
+                        self.mode = Mode::Browse;
+                        self.status = None;
+                        self.reload();
+                    }
+                    Err(e) => self.status = Some(e.to_string()),
+                },
+                _ => {}
+            },
+            Mode::Tag(input) => match key.code {
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Enter => {
+                    let tags = parse_tags(input);
+                    self.mode = Mode::Browse;
+                    self.save_tags(tags);
+                }
+                _ => {}
+            },
+        }
+        Action::None
+    }
+
+    fn handle_browse_key(&mut self, key: KeyEvent) -> Action {
+        let selected = self.list.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('j') | KeyCode::Down => self.select(selected + 1),
+            KeyCode::Char('k') | KeyCode::Up => self.select(selected.saturating_sub(1)),
+            KeyCode::PageDown => self.select(selected + PAGE),
+            KeyCode::PageUp => self.select(selected.saturating_sub(PAGE)),
+            KeyCode::Char('g') | KeyCode::Home => self.select(0),
+            KeyCode::Char('G') | KeyCode::End => self.select(self.items.len().saturating_sub(1)),
+            KeyCode::Char('J') => self.preview_scroll = self.preview_scroll.saturating_add(1),
+            KeyCode::Char('K') => self.preview_scroll = self.preview_scroll.saturating_sub(1),
+            KeyCode::Char('r') => {
+                self.status = None;
+                self.reload();
+            }
+            KeyCode::Tab => self.mode = Mode::Filter { field: Field::Kind, draft: self.filters.clone() },
+            KeyCode::Char('/') => self.mode = Mode::Filter { field: Field::Query, draft: self.filters.clone() },
+            KeyCode::Char('t') => {
+                if let Some(id) = self.selected().map(|item| item.id.clone()) {
+                    // Only the user's own tags are edited; the source's stay as the source sets them
+                    match self.storage.get_user_tags(&id) {
+                        Ok(tags) => self.mode = Mode::Tag(tags.join(", ")),
+                        Err(e) => self.status = Some(e.to_string()),
+                    }
+                }
+            }
+            KeyCode::Char('o') | KeyCode::Enter => {
+                if let Some(item) = self.selected() {
+                    return Action::Open(item.url.clone());
+                }
+            }
+            KeyCode::Char('y') => {
+                if let Some(item) = self.selected() {
+                    return Action::Copy(item.url.clone());
+                }
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    /// Stores `tags` as the selected item's user tags; tags from the source can't be removed
+    fn save_tags(&mut self, tags: Vec<String>) {
+        let Some(id) = self.selected().map(|item| item.id.clone()) else { return };
+        self.status = Some(match self.storage.set_user_tags(&id, &tags) {
+            Ok(true) if tags.is_empty() => "Removed tags".to_string(),
+            Ok(true) => format!("Tagged {}", tags.join(", ")),
+            Ok(false) => format!("Item '{id}' no longer exists"),
+            Err(e) => e.to_string(),
+        });
+        self.reload();
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [filter_area, main_area, status_area] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [list_area, preview_area] =
+            Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)]).areas(main_area);
+
+        self.draw_filter_bar(frame, filter_area);
+
+        let rows: Vec<ListItem> = self.items.iter().map(list_row).collect();
+        let list = List::new(rows)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" {} item(s) ", self.items.len())),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+
+        let width = usize::from(preview_area.width.saturating_sub(2)).max(20);
+        let preview = self
+            .selected()
+            .map(|item| preview_text(item, width))
+            .unwrap_or_default();
+        let preview = Paragraph::new(preview)
+            .block(Block::default().borders(Borders::ALL).title(" Preview "))
+            .wrap(Wrap { trim: false })
+            .scroll((self.preview_scroll, 0));
+        frame.render_widget(preview, preview_area);
+
+        let status = match (&self.mode, &self.status) {
+            (Mode::Tag(input), _) => {
+                let prompt = "Tags (comma-separated): ";
+                let cursor = prompt.chars().count() + input.chars().count();
+                frame.set_cursor_position(Position::new(status_area.x + cursor as u16, status_area.y));
+                Line::from(vec![Span::raw(prompt).bold(), Span::raw(input.as_str())])
+            }
+            (_, Some(status)) => Line::from(status.as_str()).yellow(),
+            (Mode::Filter { .. }, None) => Line::from("tab next field  enter apply  esc cancel").dark_gray(),
+            (Mode::Browse, None) => {
+                Line::from("j/k move  J/K scroll  tab filter  / search  o open  y copy URL  t tag  r reload  q quit")
+                    .dark_gray()
+            }
+        };
+        frame.render_widget(Paragraph::new(status), status_area);
+    }
+
+    fn draw_filter_bar(&self, frame: &mut Frame, area: Rect) {
+        let (active, filters) = match &self.mode {
+            Mode::Filter { field, draft } => (Some(*field), draft),
+            _ => (None, &self.filters),
+        };
+
+        let mut spans = Vec::new();
+        let mut cursor = None;
+        let mut column = 0;
+        for field in Field::ALL {
+            let label = format!("{}: ", field.label());
+            let value = filters.get(field);
+            let shown = if value.is_empty() && active != Some(field) { "any" } else { value };
+            column += label.chars().count();
+            if active == Some(field) {
+                cursor = Some(column + value.chars().count());
+                spans.push(Span::raw(label).bold().cyan());
+                spans.push(Span::raw(shown).underlined());
+            } else {
+                spans.push(Span::raw(label).bold());
+                spans.push(Span::raw(shown).dark_gray());
+            }
+            spans.push(Span::raw("   "));
+            column += shown.chars().count() + 3;
+        }
+
+        frame.render_widget(
+            Paragraph::new(Line::from(spans)).block(Block::default().borders(Borders::ALL).title(" Filter ")),
+            area,
+        );
+        if let Some(cursor) = cursor {
+            frame.set_cursor_position(Position::new(area.x + 1 + cursor as u16, area.y + 1));
+        }
+    }
+}
+
+/// Splits comma-separated tags, dropping blanks and case-insensitive duplicates
+fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in input.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+        if !tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+fn list_row(item: &Item) -> ListItem<'static> {
+    let headline = item
+        .title
+        .as_deref()
+        .or(item.summary.as_deref())
+        .unwrap_or(&item.url)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let published = crate::format_published_display(&item.published_at);
+    let date = published.get(..10).unwrap_or(&published).to_string();
+    ListItem::new(Line::from(vec![
+        Span::raw(date).dark_gray(),
+        Span::raw(" "),
+        Span::raw(format!("{:<8}", item.source_kind.to_string())).fg(Color::Cyan),
+        Span::raw(" "),
+        Span::raw(headline),
+    ]))
+}
+
+fn preview_text(item: &Item, width: usize) -> Text<'static> {
+    let mut lines = Vec::new();
+    if let Some(title) = &item.title {
+        lines.push(Line::from(title.clone()).bold());
+        lines.push(Line::default());
+    }
+
+    let published = crate::format_published_display(&item.published_at);
+    let tags = item.tags.join(", ");
+    let fields = [
+        ("Source", Some(format!("{} / {}", item.source_kind, item.source_id))),
+        ("Author", item.author.clone()),
+        ("URL", Some(item.url.clone())),
+        ("Published", Some(published)),
+        ("Tags", Some(tags).filter(|tags| !tags.is_empty())),
+    ];
+    for (label, value) in fields {
+        if let Some(value) = value {
+            lines.push(Line::from(vec![
+                Span::raw(format!("{label:<10}")).dark_gray(),
+                Span::raw(value),
+            ]));
+        }
+    }
+
+    let body = match (&item.content_html, &item.summary) {
+        (Some(html), _) if !html.trim().is_empty() => show::render_html(html, width),
+        (_, Some(summary)) => show::wrap_text(summary, width),
+        _ => String::new(),
+    };
+    if !body.is_empty() {
+        lines.push(Line::default());
+        lines.extend(body.lines().map(|line| Line::from(line.to_string())));
+    }
+    Text::from(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pai_core::MemoryStorage;
+
+    fn item(id: &str, kind: SourceKind, title: &str, published_at: &str) -> Item {
+        Item::test(id)
+            .with_source(kind, "me")
+            .with_title(title)
+            .with_published_at(published_at)
+    }
+
+    fn press(app: &mut App, keys: &str) -> Action {
+        let mut action = Action::None;
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\t' => KeyCode::Tab,
+                c => KeyCode::Char(c),
+            };
+            action = app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+        }
+        action
+    }
+
+    fn storage() -> MemoryStorage {
+        let storage = MemoryStorage::new();
+        storage
+            .insert_or_replace_item(&item("a", SourceKind::Substack, "Rust notes", "2024-01-03T00:00:00Z"))
+            .unwrap();
+        storage
+            .insert_or_replace_item(&item("b", SourceKind::Bluesky, "Hello", "2024-01-02T00:00:00Z"))
+            .unwrap();
+        storage
+            .insert_or_replace_item(&item("c", SourceKind::Bluesky, "More rust", "2024-01-01T00:00:00Z"))
+            .unwrap();
+        storage
+    }
+
+    #[test]
+    fn filter_bar_narrows_the_list() {
+        let storage = storage();
+        let mut app = App::new(&storage);
+        assert_eq!(app.items.len(), 3);
+
+        press(&mut app, "/rust\n");
+        assert_eq!(
+            app.items.iter().map(|item| item.id.as_str()).collect::<Vec<_>>(),
+            ["a", "c"]
+        );
+
+        press(&mut app, "\tbluesky\n");
+        assert_eq!(app.items.iter().map(|item| item.id.as_str()).collect::<Vec<_>>(), ["c"]);
+
+        press(&mut app, "\tnope\n");
+        assert!(matches!(app.mode, Mode::Filter { .. }));
+        assert!(app.status.as_deref().unwrap().contains("nope"));
+        assert_eq!(app.filters.kind, "bluesky");
+    }
+
+    #[test]
+    fn keys_move_open_copy_and_tag() {
+        let storage = storage();
+        let mut app = App::new(&storage);
+
+        assert_eq!(press(&mut app, "jj"), Action::None);
+        assert_eq!(app.selected().unwrap().id, "c");
+        assert_eq!(press(&mut app, "jkk"), Action::None);
+        assert_eq!(press(&mut app, "o"), Action::Open("https://example.com/a".to_string()));
+        assert_eq!(press(&mut app, "y"), Action::Copy("https://example.com/a".to_string()));
+
+        press(&mut app, "trust, Notes,rust,\n");
+        assert_eq!(storage.get_item("a").unwrap().unwrap().tags, ["rust", "Notes"]);
+        assert_eq!(app.selected().unwrap().id, "a");
+
+        // A sync rewriting the item keeps the tags added here
+        let synced =
+            item("a", SourceKind::Substack, "Rust notes, edited", "2024-01-03T00:00:00Z").with_tags(&["Source"]);
+        storage.insert_or_replace_items(&[synced]).unwrap();
+        assert_eq!(
+            storage.get_item("a").unwrap().unwrap().tags,
+            ["Source", "rust", "Notes"]
+        );
+
+        // The editor starts from the user's tags, so saving them doesn't copy the source's
+        app.reload();
+        press(&mut app, "t");
+        assert!(matches!(&app.mode, Mode::Tag(input) if input == "rust, Notes"));
+        press(&mut app, ", later\n");
+        assert_eq!(storage.get_user_tags("a").unwrap(), ["rust", "Notes", "later"]);
+
+        // Once the source drops its tag, only the user's remain
+        storage
+            .insert_or_replace_items(&[item("a", SourceKind::Substack, "Rust notes", "2024-01-03T00:00:00Z")])
+            .unwrap();
+        assert_eq!(storage.get_item("a").unwrap().unwrap().tags, ["rust", "Notes", "later"]);
+
+        // An item removed elsewhere drops out on the next reload without losing the position
+        storage.delete_item("a").unwrap();
+        app.reload();
+        assert_eq!(app.selected().unwrap().id, "b");
+    }
+}
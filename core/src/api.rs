@@ -0,0 +1,519 @@
+use crate::{Item, ListFilter, Mention, PaiError, Result, SourceKind};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+/// Number of items returned by `/api/feed` when no limit is given
+pub const DEFAULT_FEED_LIMIT: usize = 20;
+
+/// Query parameters accepted by `/api/feed` and `/rss.xml`
+#[derive(Debug, Default, Clone, Serialize, Deserialize, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+pub struct FeedQuery {
+    /// Filter by source type
+    pub source_kind: Option<SourceKind>,
+    /// Filter by specific source identifier (domain or handle)
+    pub source_id: Option<String>,
+    /// Maximum number of items to return (defaults to 20)
+    #[param(minimum = 1)]
+    pub limit: Option<usize>,
+    /// ISO 8601 timestamp; only return items published at or after this time
+    pub since: Option<String>,
+    /// Search query matched against title and summary
+    pub q: Option<String>,
+}
+
+impl FeedQuery {
+    /// Validates the query and converts it into a storage filter
+    ///
+    /// Blank strings are treated as absent and a zero limit is rejected.
+    pub fn into_filter(self) -> Result<ListFilter> {
+        let limit = match self.limit {
+            Some(0) => return Err(PaiError::InvalidArgument("Limit must be greater than zero".to_string())),
+            Some(value) => value,
+            None => DEFAULT_FEED_LIMIT,
+        };
+
+        Ok(ListFilter {
+            source_kind: self.source_kind,
+            source_id: normalize_optional_string(self.source_id),
+            limit: Some(limit),
+            since: normalize_optional_string(self.since),
+            query: normalize_optional_string(self.q),
+        })
+    }
+}
+
+fn normalize_optional_string(value: Option<String>) -> Option<String> {
+    value.and_then(|input| {
+        let trimmed = input.trim();
+        if trimmed.is_empty() {
+            None
+        } else {
+            Some(trimmed.to_string())
+        }
+    })
+}
+
+/// Response body for `/api/feed`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FeedResponse {
+    /// Number of items in this response
+    pub count: usize,
+    /// Items ordered by publish date, newest first
+    pub items: Vec<Item>,
+}
+
+/// Response body for `/api/item/{id}/mentions`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MentionsResponse {
+    /// Number of mentions in this response
+    pub count: usize,
+    /// Verified Webmentions of the item, most recently verified first
+    pub mentions: Vec<Mention>,
+}
+
+/// Response body for `/status`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StatusResponse {
+    pub status: String,
+    pub version: String,
+    /// Seconds since the server started (HTTP server only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uptime_seconds: Option<u64>,
+    /// Description of the storage backend (HTTP server only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database_path: Option<String>,
+    pub total_items: usize,
+    pub sources: SourceCounts,
+}
+
+/// Item counts per source kind in a `/status` response
+///
+/// Each deployment keeps the shape it has always returned, so existing clients don't break.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum SourceCounts {
+    /// One entry per source kind (HTTP server)
+    List(Vec<SourceStat>),
+    /// Count keyed by source kind (Cloudflare Worker)
+    ByKind(BTreeMap<String, usize>),
+}
+
+/// Item count for a single source kind
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SourceStat {
+    pub kind: SourceKind,
+    pub count: usize,
+}
+
+/// Response body for `/api/sync`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SyncResponse {
+    pub status: String,
+    pub message: String,
+}
+
+/// Error body returned with 4xx/5xx responses
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+/// Documentation-only operation stubs
+///
+/// The real handlers live in `pai-server` and `pai-worker`; these describe the routes both serve.
+/// Operations tagged `server` or `worker` exist on only that deployment. A `pai-server` test
+/// checks its router against these paths, and `worker_api_docs_match_openapi` the Worker's docs.
+#[allow(dead_code)]
+mod paths {
+    use super::*;
+
+    #[utoipa::path(
+        get,
+        path = "/api/feed",
+        tag = "api",
+        params(FeedQuery),
+        responses(
+            (status = 200, description = "Newest items matching the filters", body = FeedResponse),
+            (status = 304, description = "Not modified since the cached ETag or date"),
+            (status = 400, description = "Invalid query parameters", body = ErrorResponse)
+        )
+    )]
+    fn feed() {}
+
+    #[utoipa::path(
+        get,
+        path = "/api/item/{id}",
+        tag = "api",
+        params(("id" = String, Path, description = "The unique identifier of the item")),
+        responses(
+            (status = 200, description = "The item", body = Item),
+            (status = 304, description = "Not modified since the cached ETag or date"),
+            (status = 404, description = "No item with this ID", body = ErrorResponse)
+        )
+    )]
+    fn item() {}
+
+    #[utoipa::path(
+        get,
+        path = "/api/item/{id}/mentions",
+        tag = "server",
+        params(("id" = String, Path, description = "The unique identifier of the item")),
+        responses(
+            (status = 200, description = "Verified Webmentions of the item", body = MentionsResponse),
+            (status = 404, description = "No item with this ID", body = ErrorResponse)
+        )
+    )]
+    fn mentions() {}
+
+    #[utoipa::path(
+        get,
+        path = "/status",
+        tag = "api",
+        responses((status = 200, description = "Health check, version and item counts", body = StatusResponse))
+    )]
+    fn status() {}
+
+    #[utoipa::path(
+        get,
+        path = "/openapi.json",
+        tag = "api",
+        responses((status = 200, description = "This OpenAPI document", content_type = "application/json"))
+    )]
+    fn openapi() {}
+
+    #[utoipa::path(
+        get,
+        path = "/rss.xml",
+        tag = "server",
+        params(FeedQuery),
+        responses(
+            (status = 200, description = "RSS 2.0 feed of matching items", content_type = "application/rss+xml"),
+            (status = 304, description = "Not modified since the cached ETag or date"),
+            (status = 400, description = "Invalid query parameters", body = ErrorResponse)
+        )
+    )]
+    fn rss() {}
+
+    #[utoipa::path(
+        get,
+        path = "/",
+        tag = "server",
+        params(FeedQuery),
+        responses(
+            (status = 200, description = "HTML timeline of matching items marked up as a microformats2 h-feed", content_type = "text/html"),
+            (status = 304, description = "Not modified since the cached ETag or date"),
+            (status = 400, description = "Invalid query parameters", body = ErrorResponse)
+        )
+    )]
+    fn index() {}
+
+    #[utoipa::path(
+        get,
+        path = "/item/{id}",
+        tag = "server",
+        params(("id" = String, Path, description = "Item ID, percent-encoded")),
+        responses(
+            (status = 200, description = "HTML permalink page of the item (h-entry) with its Webmentions", content_type = "text/html"),
+            (status = 404, description = "No item with this ID", body = ErrorResponse)
+        )
+    )]
+    fn item_page() {}
+
+    #[utoipa::path(
+        get,
+        path = "/api/stream",
+        tag = "server",
+        params(
+            FeedQuery,
+            ("Last-Event-ID" = Option<String>, Header, description = "Resume after this event ID; replays every change written since, oldest first")
+        ),
+        responses(
+            (status = 200, description = "Server-Sent Events stream of new and changed items. Each `item` event carries an Item as JSON and its change sequence number as the event ID", content_type = "text/event-stream"),
+            (status = 400, description = "Invalid query parameters", body = ErrorResponse)
+        )
+    )]
+    fn stream() {}
+
+    #[utoipa::path(
+        post,
+        path = "/websub",
+        tag = "server",
+        request_body(
+            content = String,
+            content_type = "application/x-www-form-urlencoded",
+            description = "WebSub subscription request: `hub.mode` (subscribe or unsubscribe), `hub.topic` (this server's `/rss.xml`, optionally with feed filters), `hub.callback`, and optional `hub.lease_seconds` and `hub.secret`"
+        ),
+        responses(
+            (status = 202, description = "Accepted; intent is verified with the callback before the subscription changes"),
+            (status = 400, description = "Invalid subscription request, a topic that is not this server's feed, a callback that is not a public address, or a callback or topic with too many subscriptions", body = ErrorResponse),
+            (status = 404, description = "Hub disabled because `[server] public_url` is not set", body = ErrorResponse),
+            (status = 503, description = "Too many subscription requests are already being verified; retry later", body = ErrorResponse)
+        )
+    )]
+    fn websub() {}
+
+    #[utoipa::path(
+        post,
+        path = "/webmention",
+        tag = "server",
+        request_body(
+            content = String,
+            content_type = "application/x-www-form-urlencoded",
+            description = "Webmention: `source` (the page linking to an item) and `target` (the item's URL or `/item/{id}` page)"
+        ),
+        responses(
+            (status = 202, description = "Accepted; the source is fetched to verify it links to the target"),
+            (status = 400, description = "Invalid URLs or a target that is not a stored item", body = ErrorResponse),
+            (status = 503, description = "Too many Webmentions are already being verified; retry later", body = ErrorResponse)
+        )
+    )]
+    fn webmention() {}
+
+    #[utoipa::path(
+        get,
+        path = "/metrics",
+        tag = "server",
+        responses((status = 200, description = "Prometheus text exposition format", content_type = "text/plain"))
+    )]
+    fn metrics() {}
+
+    #[utoipa::path(
+        post,
+        path = "/api/sync",
+        tag = "worker",
+        responses(
+            (status = 200, description = "Synchronization finished", body = SyncResponse),
+            (status = 500, description = "Synchronization failed")
+        )
+    )]
+    fn sync() {}
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Personal Activity Index API",
+        description = "Aggregate and query your personal activity across multiple platforms"
+    ),
+    paths(
+        paths::feed,
+        paths::item,
+        paths::mentions,
+        paths::status, paths::openapi, paths::rss, paths::index, paths::item_page, paths::stream, paths::websub, paths::webmention, paths::metrics, paths::sync),
+    components(schemas(
+        Item,
+        SourceKind,
+        FeedQuery,
+        FeedResponse,
+        Mention,
+        MentionsResponse,
+        StatusResponse,
+        SourceCounts,
+        SourceStat,
+        SyncResponse,
+        ErrorResponse
+    )),
+    tags(
+        (name = "api", description = "Served by both the HTTP server and the Cloudflare Worker"),
+        (name = "server", description = "HTTP server only"),
+        (name = "worker", description = "Cloudflare Worker only")
+    )
+)]
+struct ApiDoc;
+
+/// Builds the OpenAPI 3.1 document served at `/openapi.json`
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::collections::BTreeSet;
+
+    const WORKER_API_DOCS: &str = include_str!("../../worker/api-docs.json");
+
+    #[test]
+    fn feed_query_defaults() {
+        let filter = FeedQuery::default().into_filter().unwrap();
+        assert_eq!(filter.limit, Some(DEFAULT_FEED_LIMIT));
+        assert!(filter.source_kind.is_none());
+        assert!(filter.source_id.is_none());
+    }
+
+    #[test]
+    fn feed_query_respects_parameters() {
+        let query = FeedQuery {
+            source_kind: Some(SourceKind::Bluesky),
+            source_id: Some(" desertthunder.dev ".to_string()),
+            limit: Some(5),
+            since: Some("2024-01-01T00:00:00Z".to_string()),
+            q: Some(" rust ".to_string()),
+        };
+
+        let filter = query.into_filter().unwrap();
+        assert_eq!(filter.limit, Some(5));
+        assert_eq!(filter.source_kind, Some(SourceKind::Bluesky));
+        assert_eq!(filter.source_id.as_deref(), Some("desertthunder.dev"));
+        assert_eq!(filter.query.as_deref(), Some("rust"));
+        assert_eq!(filter.since.as_deref(), Some("2024-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn feed_query_rejects_zero_limit() {
+        let err = FeedQuery { limit: Some(0), ..Default::default() }
+            .into_filter()
+            .unwrap_err();
+        assert!(matches!(err, PaiError::InvalidArgument(_)));
+    }
+
+    #[test]
+    fn openapi_is_version_3_1() {
+        let spec = serde_json::to_value(openapi()).unwrap();
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3.1"));
+        assert!(spec["paths"]["/api/feed"]["get"].is_object());
+    }
+
+    /// Resolves a schema (or a nullable wrapper around one) to the component it references
+    fn resolve<'a>(schema: &'a Value, spec: &'a Value) -> &'a Value {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/components/schemas/");
+            return &spec["components"]["schemas"][name];
+        }
+
+        if let Some(variants) = schema["oneOf"].as_array() {
+            if let Some(inner) = variants.iter().find(|variant| variant["$ref"].is_string()) {
+                return resolve(inner, spec);
+            }
+        }
+
+        schema
+    }
+
+    /// Asserts that the keys of a documented example match the schema's properties, recursively
+    fn assert_example_matches(example: &Value, schema: &Value, spec: &Value, context: &str) {
+        let schema = resolve(schema, spec);
+
+        // Untagged enums: check the example against the variant of the same JSON type
+        if let Some(variants) = schema["oneOf"].as_array() {
+            let wanted = if example.is_array() { "array" } else { "object" };
+            let variant = variants
+                .iter()
+                .find(|variant| variant["type"] == wanted)
+                .unwrap_or_else(|| panic!("{context} has no {wanted} variant"));
+            return assert_example_matches(example, variant, spec, context);
+        }
+
+        match example {
+            Value::Object(fields) if schema["properties"].is_null() && schema["additionalProperties"].is_object() => {
+                for (key, value) in fields {
+                    assert_example_matches(
+                        value,
+                        &schema["additionalProperties"],
+                        spec,
+                        &format!("{context}.{key}"),
+                    );
+                }
+            }
+            Value::Object(fields) => {
+                let documented: BTreeSet<&str> = fields.keys().map(String::as_str).collect();
+                let actual: BTreeSet<&str> = schema["properties"]
+                    .as_object()
+                    .map(|props| props.keys().map(String::as_str).collect())
+                    .unwrap_or_default();
+                assert_eq!(documented, actual, "fields of {context} drifted from the API types");
+
+                for (key, value) in fields {
+                    assert_example_matches(value, &schema["properties"][key], spec, &format!("{context}.{key}"));
+                }
+            }
+            Value::Array(values) => {
+                if let Some(first) = values.first() {
+                    assert_example_matches(first, &schema["items"], spec, &format!("{context}[]"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn worker_api_docs_match_openapi() {
+        let docs: Value = serde_json::from_str(WORKER_API_DOCS).unwrap();
+        let spec = serde_json::to_value(openapi()).unwrap();
+        let mut documented_paths = BTreeSet::new();
+
+        for endpoint in docs["endpoints"].as_array().unwrap() {
+            let path = endpoint["path"].as_str().unwrap();
+            if path == "/" {
+                continue;
+            }
+
+            let method = endpoint["method"].as_str().unwrap().to_lowercase();
+            let openapi_path = path
+                .split('/')
+                .map(|segment| match segment.strip_prefix(':') {
+                    Some(name) => format!("{{{name}}}"),
+                    None => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            documented_paths.insert(openapi_path.clone());
+
+            let operation = &spec["paths"][&openapi_path][&method];
+            assert!(
+                operation.is_object(),
+                "{method} {path} is missing from the OpenAPI document"
+            );
+
+            let documented_params: BTreeSet<&str> = endpoint["parameters"]
+                .as_array()
+                .map(|params| params.iter().filter_map(|p| p["name"].as_str()).collect())
+                .unwrap_or_default();
+            let actual_params: BTreeSet<&str> = operation["parameters"]
+                .as_array()
+                .map(|params| params.iter().filter_map(|p| p["name"].as_str()).collect())
+                .unwrap_or_default();
+            assert_eq!(documented_params, actual_params, "parameters of {path} drifted");
+
+            let response = &operation["responses"]["200"]["content"]["application/json"]["schema"];
+            assert_example_matches(&endpoint["response"], response, &spec, path);
+        }
+
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            let server_only = item.as_object().unwrap().values().all(|operation| {
+                operation["tags"]
+                    .as_array()
+                    .is_some_and(|tags| tags.contains(&"server".into()))
+            });
+            if !server_only {
+                assert!(
+                    documented_paths.contains(path),
+                    "{path} is missing from worker/api-docs.json"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn worker_api_docs_list_every_source_kind() {
+        let docs: Value = serde_json::from_str(WORKER_API_DOCS).unwrap();
+        let spec = serde_json::to_value(openapi()).unwrap();
+
+        let documented: BTreeSet<&str> = docs["sources"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let actual: BTreeSet<&str> = spec["components"]["schemas"]["SourceKind"]["enum"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(Value::as_str)
+            .collect();
+        assert_eq!(documented, actual);
+    }
+}
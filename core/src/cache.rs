@@ -0,0 +1,134 @@
+use crate::Item;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+/// Default `Cache-Control: max-age` for API responses, in seconds
+pub const DEFAULT_CACHE_MAX_AGE: u64 = 60;
+
+/// HTTP cache validators for a list of items
+///
+/// The ETag is strong: it is a hash of the serialized items plus the representation name, so the
+/// JSON feed and RSS feed for the same result set get different tags. `Last-Modified` is the newest
+/// `created_at` in the set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheValidators {
+    pub etag: String,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl CacheValidators {
+    /// Computes validators for `items` rendered as `representation` (e.g. `"feed"` or `"rss"`)
+    pub fn for_items(representation: &str, items: &[Item]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(representation.as_bytes());
+        hasher.update([0]);
+        hasher.update(serde_json::to_vec(items).unwrap_or_default());
+
+        let digest = hasher.finalize();
+        let hex: String = digest.iter().take(16).map(|byte| format!("{byte:02x}")).collect();
+
+        let last_modified = items
+            .iter()
+            .filter_map(|item| DateTime::parse_from_rfc3339(&item.created_at).ok())
+            .map(|dt| dt.with_timezone(&Utc))
+            .max();
+
+        Self { etag: format!("\"{hex}\""), last_modified }
+    }
+
+    /// Formats `last_modified` as an HTTP date (`Sun, 06 Nov 1994 08:49:37 GMT`)
+    pub fn last_modified_header(&self) -> Option<String> {
+        self.last_modified
+            .map(|dt| dt.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+    }
+
+    /// Evaluates conditional request headers
+    ///
+    /// Returns true when the client's cached copy is current and a `304 Not Modified` should be sent.
+    /// `If-None-Match` takes precedence over `If-Modified-Since`, as required by RFC 9110.
+    pub fn is_not_modified(&self, if_none_match: Option<&str>, if_modified_since: Option<&str>) -> bool {
+        if let Some(header) = if_none_match {
+            return header
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == self.etag);
+        }
+
+        match (self.last_modified, if_modified_since) {
+            (Some(last_modified), Some(header)) => DateTime::parse_from_rfc2822(header.trim())
+                .map(|since| last_modified.timestamp() <= since.timestamp())
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+}
+
+/// Builds the `Cache-Control` header value for a max-age in seconds
+///
+/// A max-age of zero asks clients to revalidate on every request.
+pub fn cache_control(max_age: u64) -> String {
+    if max_age == 0 {
+        "no-cache".to_string()
+    } else {
+        format!("public, max-age={max_age}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn etag_is_stable_and_representation_specific() {
+        let items = vec![Item::test("a").with_created_at("2024-01-02T00:00:00Z")];
+
+        let feed = CacheValidators::for_items("feed", &items);
+        assert_eq!(feed, CacheValidators::for_items("feed", &items));
+        assert_ne!(feed.etag, CacheValidators::for_items("rss", &items).etag);
+        assert_ne!(feed.etag, CacheValidators::for_items("feed", &[]).etag);
+        assert!(feed.etag.starts_with('"') && feed.etag.ends_with('"'));
+    }
+
+    #[test]
+    fn last_modified_uses_newest_created_at() {
+        let items = vec![
+            Item::test("a").with_created_at("2024-01-02T00:00:00Z"),
+            Item::test("b").with_created_at("2024-03-04T05:06:07+00:00"),
+        ];
+        let validators = CacheValidators::for_items("feed", &items);
+
+        assert_eq!(
+            validators.last_modified_header().as_deref(),
+            Some("Mon, 04 Mar 2024 05:06:07 GMT")
+        );
+        assert!(CacheValidators::for_items("feed", &[]).last_modified.is_none());
+    }
+
+    #[test]
+    fn if_none_match_matches_etag_lists() {
+        let validators = CacheValidators::for_items("feed", &[Item::test("a").with_created_at("2024-01-02T00:00:00Z")]);
+        let listed = format!("\"other\", W/{}", validators.etag);
+
+        assert!(validators.is_not_modified(Some(&validators.etag), None));
+        assert!(validators.is_not_modified(Some(&listed), None));
+        assert!(validators.is_not_modified(Some("*"), None));
+        assert!(!validators.is_not_modified(Some("\"other\""), Some("Tue, 01 Jan 2030 00:00:00 GMT")));
+    }
+
+    #[test]
+    fn if_modified_since_compares_seconds() {
+        let validators =
+            CacheValidators::for_items("feed", &[Item::test("a").with_created_at("2024-01-02T00:00:00.500Z")]);
+
+        assert!(validators.is_not_modified(None, Some("Tue, 02 Jan 2024 00:00:00 GMT")));
+        assert!(!validators.is_not_modified(None, Some("Mon, 01 Jan 2024 23:59:59 GMT")));
+        assert!(!validators.is_not_modified(None, Some("not a date")));
+        assert!(!validators.is_not_modified(None, None));
+    }
+
+    #[test]
+    fn cache_control_formats_max_age() {
+        assert_eq!(cache_control(60), "public, max-age=60");
+        assert_eq!(cache_control(0), "no-cache");
+    }
+}
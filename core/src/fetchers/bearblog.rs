@@ -1,4 +1,4 @@
-use crate::{BearBlogConfig, Item, PaiError, Result, SourceFetcher, SourceKind, Storage};
+use crate::{BearBlogConfig, Item, PaiError, Result, SourceFetcher, SourceKind};
 use chrono::Utc;
 use feed_rs::parser;
 
@@ -37,50 +37,52 @@ impl BearBlogFetcher {
 }
 
 impl SourceFetcher for BearBlogFetcher {
-    fn sync(&self, storage: &dyn Storage) -> Result<()> {
+    fn fetch(&self) -> Result<Vec<Item>> {
         let runtime =
             tokio::runtime::Runtime::new().map_err(|e| PaiError::Fetch(format!("Failed to create runtime: {e}")))?;
 
-        runtime.block_on(async {
-            let feed = self.fetch_feed().await?;
-
-            for entry in feed.entries {
-                let id = entry.id.clone();
-                let url = entry
-                    .links
-                    .first()
-                    .map(|link| link.href.clone())
-                    .unwrap_or_else(|| id.clone());
-
-                let title = entry.title.as_ref().map(|t| t.content.clone());
-                let summary = entry.summary.as_ref().map(|s| s.content.clone());
-                let author = entry.authors.first().map(|a| a.name.clone());
-                let content_html = entry.content.and_then(|c| c.body);
-
-                let published_at = entry
-                    .published
-                    .or(entry.updated)
-                    .map(|dt| dt.to_rfc3339())
-                    .unwrap_or_else(|| Utc::now().to_rfc3339());
-
-                let item = Item {
-                    id,
-                    source_kind: SourceKind::BearBlog,
-                    source_id: self.config.id.clone(),
-                    author,
-                    title,
-                    summary,
-                    url,
-                    content_html,
-                    published_at,
-                    created_at: Utc::now().to_rfc3339(),
-                };
-
-                storage.insert_or_replace_item(&item)?;
-            }
-
-            Ok(())
-        })
+        let feed = runtime.block_on(self.fetch_feed())?;
+        let mut items = Vec::new();
+
+        for entry in feed.entries {
+            let id = entry.id.clone();
+            let url = entry
+                .links
+                .first()
+                .map(|link| link.href.clone())
+                .unwrap_or_else(|| id.clone());
+
+            let title = entry.title.as_ref().map(|t| t.content.clone());
+            let summary = entry.summary.as_ref().map(|s| s.content.clone());
+            let author = entry.authors.first().map(|a| a.name.clone());
+            let content_html = entry.content.and_then(|c| c.body);
+
+            let published_at = entry
+                .published
+                .or(entry.updated)
+                .map(|dt| dt.to_rfc3339())
+                .unwrap_or_else(|| Utc::now().to_rfc3339());
+
+            let tags = entry.categories.iter().map(|category| category.term.clone()).collect();
+
+            let item = Item {
+                id,
+                source_kind: SourceKind::BearBlog,
+                source_id: self.config.id.clone(),
+                author,
+                title,
+                summary,
+                url,
+                content_html,
+                tags,
+                published_at,
+                created_at: Utc::now().to_rfc3339(),
+            };
+
+            items.push(item);
+        }
+
+        Ok(items)
     }
 }
 
@@ -1,4 +1,4 @@
-use crate::{BlueskyConfig, Item, PaiError, Result, SourceFetcher, SourceKind, Storage};
+use crate::{BlueskyConfig, Item, PaiError, Result, SourceFetcher, SourceKind};
 use chrono::Utc;
 use serde::Deserialize;
 
@@ -105,6 +105,33 @@ impl BlueskyFetcher {
         record.get("text").and_then(|v| v.as_str()).map(String::from)
     }
 
+    /// Collects hashtags from the post's richtext facets and its `tags` field, without duplicates
+    fn extract_tags(record: &serde_json::Value) -> Vec<String> {
+        let facet_tags = record
+            .get("facets")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|facet| facet.get("features").and_then(|v| v.as_array()))
+            .flatten()
+            .filter(|feature| feature.get("$type").and_then(|v| v.as_str()) == Some("app.bsky.richtext.facet#tag"))
+            .filter_map(|feature| feature.get("tag").and_then(|v| v.as_str()));
+        let record_tags = record
+            .get("tags")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|v| v.as_str());
+
+        let mut tags: Vec<String> = Vec::new();
+        for tag in facet_tags.chain(record_tags) {
+            if !tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.to_string());
+            }
+        }
+        tags
+    }
+
     /// Creates a title from the post text (truncated to 100 chars)
     fn create_title(text: &str) -> String {
         if text.len() <= 100 {
@@ -113,52 +140,60 @@ impl BlueskyFetcher {
             format!("{}...", &text[..97])
         }
     }
+
+    /// Maps an `app.bsky.feed.post` record at `uri` to an item
+    ///
+    /// Shared with the repository importer, so imported posts match synced ones.
+    pub(crate) fn post_item(uri: &str, handle: &str, source_id: &str, record: &serde_json::Value) -> Result<Item> {
+        let text = Self::extract_text(record);
+        let title = text.as_ref().map(|t| Self::create_title(t));
+        let url = Self::at_uri_to_url(uri, handle)?;
+
+        let published_at = record
+            .get("createdAt")
+            .and_then(|v| v.as_str())
+            .map(String::from)
+            .unwrap_or_else(|| Utc::now().to_rfc3339());
+
+        Ok(Item {
+            id: uri.to_string(),
+            source_kind: SourceKind::Bluesky,
+            source_id: source_id.to_string(),
+            author: Some(handle.to_string()),
+            title,
+            summary: text,
+            url,
+            content_html: None,
+            tags: Self::extract_tags(record),
+            published_at,
+            created_at: Utc::now().to_rfc3339(),
+        })
+    }
 }
 
 impl SourceFetcher for BlueskyFetcher {
-    fn sync(&self, storage: &dyn Storage) -> Result<()> {
+    fn fetch(&self) -> Result<Vec<Item>> {
         let runtime =
             tokio::runtime::Runtime::new().map_err(|e| PaiError::Fetch(format!("Failed to create runtime: {e}")))?;
 
-        runtime.block_on(async {
-            let response = self.fetch_author_feed().await?;
-
-            for feed_post in response.feed {
-                if !Self::is_original_post(&feed_post) {
-                    continue;
-                }
-
-                let post = feed_post.post;
-                let text = Self::extract_text(&post.record);
-
-                let title = text.as_ref().map(|t| Self::create_title(t));
-                let url = Self::at_uri_to_url(&post.uri, &post.author.handle)?;
-
-                let published_at = post
-                    .record
-                    .get("createdAt")
-                    .and_then(|v| v.as_str())
-                    .map(String::from)
-                    .unwrap_or_else(|| Utc::now().to_rfc3339());
-
-                let item = Item {
-                    id: post.uri.clone(),
-                    source_kind: SourceKind::Bluesky,
-                    source_id: self.config.handle.clone(),
-                    author: Some(post.author.handle.clone()),
-                    title,
-                    summary: text,
-                    url,
-                    content_html: None,
-                    published_at,
-                    created_at: Utc::now().to_rfc3339(),
-                };
-
-                storage.insert_or_replace_item(&item)?;
+        let response = runtime.block_on(self.fetch_author_feed())?;
+        let mut items = Vec::new();
+
+        for feed_post in response.feed {
+            if !Self::is_original_post(&feed_post) {
+                continue;
             }
 
-            Ok(())
-        })
+            let post = feed_post.post;
+            items.push(Self::post_item(
+                &post.uri,
+                &post.author.handle,
+                &self.config.handle,
+                &post.record,
+            )?);
+        }
+
+        Ok(items)
     }
 }
 
@@ -248,4 +283,18 @@ mod tests {
         };
         assert!(!BlueskyFetcher::is_original_post(&feed_post));
     }
+
+    #[test]
+    fn extract_tags_from_facets_and_record() {
+        let record = serde_json::json!({
+            "text": "Shipping #rust today",
+            "facets": [
+                {"features": [{"$type": "app.bsky.richtext.facet#tag", "tag": "rust"}]},
+                {"features": [{"$type": "app.bsky.richtext.facet#link", "uri": "https://example.com"}]}
+            ],
+            "tags": ["Rust", "wasm"]
+        });
+        assert_eq!(BlueskyFetcher::extract_tags(&record), vec!["rust", "wasm"]);
+        assert!(BlueskyFetcher::extract_tags(&serde_json::json!({})).is_empty());
+    }
 }
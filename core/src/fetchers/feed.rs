@@ -0,0 +1,118 @@
+use crate::{FeedConfig, Item, PaiError, Result, SourceFetcher, SourceKind};
+use chrono::Utc;
+use feed_rs::parser;
+
+/// Fetcher for any RSS or Atom feed
+///
+/// Used for feeds that aren't one of the dedicated sources, such as blogs imported from an OPML file.
+pub struct FeedFetcher {
+    config: FeedConfig,
+    client: reqwest::Client,
+}
+
+impl FeedFetcher {
+    /// Creates a new feed fetcher with the given configuration
+    pub fn new(config: FeedConfig) -> Self {
+        Self { config, client: reqwest::Client::new() }
+    }
+
+    /// Fetches and parses the feed
+    async fn fetch_feed(&self) -> Result<feed_rs::model::Feed> {
+        let response = self
+            .client
+            .get(&self.config.url)
+            .send()
+            .await
+            .map_err(|e| PaiError::Fetch(format!("Failed to fetch feed {}: {e}", self.config.url)))?;
+
+        let body = response
+            .text()
+            .await
+            .map_err(|e| PaiError::Fetch(format!("Failed to read response body: {e}")))?;
+
+        parser::parse(body.as_bytes()).map_err(|e| PaiError::Parse(format!("Failed to parse feed: {e}")))
+    }
+
+    fn items(&self, feed: feed_rs::model::Feed) -> Vec<Item> {
+        feed.entries
+            .into_iter()
+            .map(|entry| {
+                let url = entry
+                    .links
+                    .first()
+                    .map(|link| link.href.clone())
+                    .unwrap_or_else(|| entry.id.clone());
+
+                let published_at = entry
+                    .published
+                    .or(entry.updated)
+                    .map(|dt| dt.to_rfc3339())
+                    .unwrap_or_else(|| Utc::now().to_rfc3339());
+
+                Item {
+                    id: entry.id,
+                    source_kind: SourceKind::Feed,
+                    source_id: self.config.id.clone(),
+                    author: entry.authors.first().map(|a| a.name.clone()),
+                    title: entry.title.map(|t| t.content),
+                    summary: entry.summary.map(|s| s.content),
+                    url,
+                    content_html: entry.content.and_then(|c| c.body),
+                    tags: entry.categories.into_iter().map(|category| category.term).collect(),
+                    published_at,
+                    created_at: Utc::now().to_rfc3339(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl SourceFetcher for FeedFetcher {
+    fn fetch(&self) -> Result<Vec<Item>> {
+        let runtime =
+            tokio::runtime::Runtime::new().map_err(|e| PaiError::Fetch(format!("Failed to create runtime: {e}")))?;
+
+        let feed = runtime.block_on(self.fetch_feed())?;
+        Ok(self.items(feed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_atom_entries() {
+        let atom = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+    <title>Example</title>
+    <id>urn:example</id>
+    <updated>2024-01-02T00:00:00Z</updated>
+    <entry>
+        <title>Hello</title>
+        <id>urn:example:hello</id>
+        <link href="https://example.com/hello"/>
+        <updated>2024-01-02T00:00:00Z</updated>
+        <author><name>Me</name></author>
+        <category term="notes"/>
+    </entry>
+</feed>"#;
+
+        let fetcher = FeedFetcher::new(FeedConfig {
+            enabled: true,
+            id: "example".to_string(),
+            url: "https://example.com/atom.xml".to_string(),
+        });
+        let items = fetcher.items(parser::parse(atom.as_bytes()).unwrap());
+        assert_eq!(items.len(), 1);
+        let item = &items[0];
+        assert_eq!(
+            (item.source_kind, item.source_id.as_str(), item.url.as_str()),
+            (SourceKind::Feed, "example", "https://example.com/hello")
+        );
+        assert_eq!(item.title.as_deref(), Some("Hello"));
+        assert_eq!(item.author.as_deref(), Some("Me"));
+        assert_eq!(item.tags, ["notes"]);
+        assert!(item.published_at.starts_with("2024-01-02"));
+    }
+}
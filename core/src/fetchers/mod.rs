@@ -1,9 +1,11 @@
 mod bearblog;
 mod bluesky;
+mod feed;
 mod leaflet;
 mod substack;
 
 pub use bearblog::BearBlogFetcher;
 pub use bluesky::BlueskyFetcher;
+pub use feed::FeedFetcher;
 pub use leaflet::LeafletFetcher;
 pub use substack::SubstackFetcher;
@@ -1,4 +1,4 @@
-use crate::{Item, PaiError, Result, SourceFetcher, SourceKind, Storage, SubstackConfig};
+use crate::{Item, PaiError, Result, SourceFetcher, SourceKind, SubstackConfig};
 use chrono::Utc;
 use feed_rs::parser;
 use tokio::runtime::Runtime;
@@ -38,114 +38,84 @@ impl SubstackFetcher {
 
     /// Extracts the source ID from the base URL (e.g., "patternmatched.substack.com")
     fn extract_source_id(&self) -> String {
-        Self::normalize_source_id(&self.config.base_url)
+        self.config.source_id()
     }
 
-    pub(crate) fn normalize_source_id(base_url: &str) -> String {
-        base_url
-            .trim_start_matches("https://")
-            .trim_start_matches("http://")
-            .trim_end_matches('/')
-            .to_string()
+    /// Maps the entries of a parsed feed to items
+    pub(crate) fn feed_items(&self, feed: feed_rs::model::Feed) -> Vec<Item> {
+        let mut items = Vec::new();
+        let source_id = self.extract_source_id();
+
+        for entry in feed.entries {
+            let id = entry.id.clone();
+            let url = entry
+                .links
+                .first()
+                .map(|link| link.href.clone())
+                .unwrap_or_else(|| id.clone());
+
+            let title = entry.title.as_ref().map(|t| t.content.clone());
+            let summary = entry.summary.as_ref().map(|s| s.content.clone());
+            let author = entry.authors.first().map(|a| a.name.clone());
+            let content_html = entry.content.and_then(|c| c.body);
+
+            let published_at = entry
+                .published
+                .or(entry.updated)
+                .map(|dt| dt.to_rfc3339())
+                .unwrap_or_else(|| Utc::now().to_rfc3339());
+
+            let tags = entry.categories.iter().map(|category| category.term.clone()).collect();
+
+            let item = Item {
+                id,
+                source_kind: SourceKind::Substack,
+                source_id: source_id.clone(),
+                author,
+                title,
+                summary,
+                url,
+                content_html,
+                tags,
+                published_at,
+                created_at: Utc::now().to_rfc3339(),
+            };
+
+            items.push(item);
+        }
+
+        items
     }
 }
 
 impl SourceFetcher for SubstackFetcher {
-    fn sync(&self, storage: &dyn Storage) -> Result<()> {
+    fn fetch(&self) -> Result<Vec<Item>> {
         let runtime = Runtime::new().map_err(|e| PaiError::Fetch(format!("Failed to create runtime: {e}")))?;
 
-        runtime.block_on(async {
-            let feed = self.fetch_feed().await?;
-            let source_id = self.extract_source_id();
-
-            for entry in feed.entries {
-                let id = entry.id.clone();
-                let url = entry
-                    .links
-                    .first()
-                    .map(|link| link.href.clone())
-                    .unwrap_or_else(|| id.clone());
-
-                let title = entry.title.as_ref().map(|t| t.content.clone());
-                let summary = entry.summary.as_ref().map(|s| s.content.clone());
-                let author = entry.authors.first().map(|a| a.name.clone());
-                let content_html = entry.content.and_then(|c| c.body);
-
-                let published_at = entry
-                    .published
-                    .or(entry.updated)
-                    .map(|dt| dt.to_rfc3339())
-                    .unwrap_or_else(|| Utc::now().to_rfc3339());
-
-                let item = Item {
-                    id,
-                    source_kind: SourceKind::Substack,
-                    source_id: source_id.clone(),
-                    author,
-                    title,
-                    summary,
-                    url,
-                    content_html,
-                    published_at,
-                    created_at: Utc::now().to_rfc3339(),
-                };
-
-                storage.insert_or_replace_item(&item)?;
-            }
-
-            Ok(())
-        })
+        let feed = runtime.block_on(self.fetch_feed())?;
+        Ok(self.feed_items(feed))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ListFilter;
-    use std::sync::{Arc, Mutex};
-
-    #[derive(Clone)]
-    #[allow(dead_code)]
-    struct MockStorage {
-        items: Arc<Mutex<Vec<Item>>>,
-    }
-
-    #[allow(dead_code)]
-    impl MockStorage {
-        fn new() -> Self {
-            Self { items: Arc::new(Mutex::new(Vec::new())) }
-        }
-
-        fn get_items(&self) -> Vec<Item> {
-            self.items.lock().unwrap().clone()
-        }
-    }
-
-    impl Storage for MockStorage {
-        fn insert_or_replace_item(&self, item: &Item) -> Result<()> {
-            self.items.lock().unwrap().push(item.clone());
-            Ok(())
-        }
 
-        fn list_items(&self, _filter: &ListFilter) -> Result<Vec<Item>> {
-            Ok(self.items.lock().unwrap().clone())
-        }
+    fn source_id(base_url: &str) -> String {
+        SubstackFetcher::new(SubstackConfig { enabled: true, base_url: base_url.to_string() }).extract_source_id()
     }
 
     #[test]
     fn extract_source_id_https() {
         assert_eq!(
-            SubstackFetcher::normalize_source_id("https://patternmatched.substack.com"),
+            source_id("https://patternmatched.substack.com"),
             "patternmatched.substack.com"
         );
     }
 
     #[test]
     fn extract_source_id_http() {
-        assert_eq!(
-            SubstackFetcher::normalize_source_id("http://test.substack.com/"),
-            "test.substack.com"
-        );
+        assert_eq!(source_id("http://test.substack.com/"), "test.substack.com");
     }
 
     #[test]
@@ -0,0 +1,198 @@
+use super::car::{Car, Cbor};
+use crate::{BlueskyFetcher, Item, PaiError, Result};
+use std::collections::HashSet;
+
+const POST_COLLECTION: &str = "app.bsky.feed.post";
+/// Repository trees are a few levels deep; anything deeper is malformed or hostile
+const MAX_TREE_DEPTH: usize = 64;
+
+/// Reads the posts in a Bluesky repository export as items
+///
+/// `car` is the CAR file from "Export my data" in the Bluesky app or `com.atproto.sync.getRepo`.
+/// Posts are mapped like [`BlueskyFetcher`] maps them, under `handle`, so they get the same IDs
+/// as synced posts.
+pub fn read_bluesky_repo(car: &[u8], handle: &str) -> Result<Vec<Item>> {
+    let car = Car::read(car)?;
+    let root = car
+        .roots
+        .first()
+        .ok_or_else(|| repo_error("the CAR file has no root"))?;
+    let commit = car
+        .get(root)?
+        .ok_or_else(|| repo_error("the commit block is missing"))?;
+    let did = match commit.get("did") {
+        Some(Cbor::Text(did)) => did.clone(),
+        _ => return Err(repo_error("the commit has no DID")),
+    };
+    let Some(Cbor::Link(data)) = commit.get("data") else {
+        return Err(repo_error("the commit has no data"));
+    };
+
+    let mut items = Vec::new();
+    for (key, cid) in records(&car, data)? {
+        if !key.starts_with(&format!("{POST_COLLECTION}/")) {
+            continue;
+        }
+        // Exports of a partial repository can leave records out
+        let Some(record) = car.get(&cid)? else { continue };
+        if record.get("$type") != Some(&Cbor::Text(POST_COLLECTION.to_string())) {
+            continue;
+        }
+        let uri = format!("at://{did}/{key}");
+        items.push(BlueskyFetcher::post_item(&uri, handle, handle, &record.to_json())?);
+    }
+    Ok(items)
+}
+
+/// Keys (`collection/rkey`) and record CIDs of the Merkle search tree rooted at `root`
+///
+/// Each node holds a left subtree `l` and entries `e`, whose keys share `p` bytes with the key
+/// before them and may each have a subtree `t` to their right. A node that is reached twice or
+/// lies deeper than [`MAX_TREE_DEPTH`] makes the tree invalid.
+fn records(car: &Car, root: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut records = Vec::new();
+    let mut visited = HashSet::new();
+    let mut pending = vec![(root.to_vec(), 0)];
+    while let Some((cid, depth)) = pending.pop() {
+        if depth > MAX_TREE_DEPTH {
+            return Err(repo_error("the tree is too deep"));
+        }
+        if !visited.insert(cid.clone()) {
+            return Err(repo_error("a tree node is linked more than once"));
+        }
+        let Some(node) = car.get(&cid)? else { continue };
+        if let Some(Cbor::Link(left)) = node.get("l") {
+            pending.push((left.clone(), depth + 1));
+        }
+
+        let Some(Cbor::Array(entries)) = node.get("e") else { continue };
+        let mut key: Vec<u8> = Vec::new();
+        for entry in entries {
+            let (Some(Cbor::Int(prefix)), Some(Cbor::Bytes(suffix)), Some(Cbor::Link(value))) =
+                (entry.get("p"), entry.get("k"), entry.get("v"))
+            else {
+                return Err(repo_error("malformed tree entry"));
+            };
+            key.truncate(usize::try_from(*prefix).unwrap_or(0));
+            key.extend(suffix);
+            let key = String::from_utf8(key.clone()).map_err(|_| repo_error("tree key is not UTF-8"))?;
+            records.push((key, value.clone()));
+            if let Some(Cbor::Link(right)) = entry.get("t") {
+                pending.push((right.clone(), depth + 1));
+            }
+        }
+    }
+    Ok(records)
+}
+
+fn repo_error(message: &str) -> PaiError {
+    PaiError::Parse(format!("Invalid Bluesky repository: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::car::tests::{car, cid};
+    use super::*;
+    use crate::SourceKind;
+
+    fn text(value: &str) -> Cbor {
+        Cbor::Text(value.to_string())
+    }
+
+    fn entry(prefix: i128, suffix: &str, value: Vec<u8>, right: Option<Vec<u8>>) -> Cbor {
+        Cbor::Map(vec![
+            ("k".into(), Cbor::Bytes(suffix.as_bytes().to_vec())),
+            ("p".into(), Cbor::Int(prefix)),
+            ("t".into(), right.map_or(Cbor::Null, Cbor::Link)),
+            ("v".into(), Cbor::Link(value)),
+        ])
+    }
+
+    #[test]
+    fn reads_posts_from_a_repository() {
+        let commit = Cbor::Map(vec![
+            ("did".into(), text("did:plc:abc")),
+            ("data".into(), Cbor::Link(cid(2))),
+            ("version".into(), Cbor::Int(3)),
+        ]);
+        // Root node: a like and the first post, with the second post in the subtree to the right
+        let root = Cbor::Map(vec![
+            ("l".into(), Cbor::Null),
+            (
+                "e".into(),
+                Cbor::Array(vec![
+                    entry(0, "app.bsky.feed.like/3aaa", cid(4), None),
+                    entry(14, "post/3kaa", cid(5), Some(cid(3))),
+                ]),
+            ),
+        ]);
+        let subtree = Cbor::Map(vec![
+            ("l".into(), Cbor::Null),
+            (
+                "e".into(),
+                Cbor::Array(vec![
+                    entry(0, "app.bsky.feed.post/3kbb", cid(6), None),
+                    entry(0, "app.bsky.feed.post/3kcc", cid(9), None),
+                ]),
+            ),
+        ]);
+        let like = Cbor::Map(vec![("$type".into(), text("app.bsky.feed.like"))]);
+        let post = |body: &str| {
+            Cbor::Map(vec![
+                ("$type".into(), text(POST_COLLECTION)),
+                ("text".into(), text(body)),
+                ("createdAt".into(), text("2023-04-01T12:00:00.000Z")),
+                ("tags".into(), Cbor::Array(vec![text("rust")])),
+            ])
+        };
+        let data = car(&[
+            (cid(1), commit),
+            (cid(2), root),
+            (cid(3), subtree),
+            (cid(4), like),
+            (cid(5), post("first")),
+            (cid(6), post("second")),
+        ]);
+
+        let items = read_bluesky_repo(&data, "me.bsky.social").unwrap();
+        let mut summary: Vec<(&str, &str)> = items
+            .iter()
+            .map(|item| (item.id.as_str(), item.summary.as_deref().unwrap()))
+            .collect();
+        summary.sort();
+        assert_eq!(
+            summary,
+            [
+                ("at://did:plc:abc/app.bsky.feed.post/3kaa", "first"),
+                ("at://did:plc:abc/app.bsky.feed.post/3kbb", "second"),
+            ]
+        );
+
+        let item = items.iter().find(|item| item.id.ends_with("3kaa")).unwrap();
+        assert_eq!(item.source_kind, SourceKind::Bluesky);
+        assert_eq!(item.source_id, "me.bsky.social");
+        assert_eq!(item.url, "https://bsky.app/profile/me.bsky.social/post/3kaa");
+        assert_eq!(item.published_at, "2023-04-01T12:00:00.000Z");
+        assert_eq!(item.tags, ["rust"]);
+    }
+
+    #[test]
+    fn rejects_cyclic_and_deep_trees() {
+        let commit = Cbor::Map(vec![
+            ("did".into(), text("did:plc:abc")),
+            ("data".into(), Cbor::Link(cid(2))),
+        ]);
+        let node = |left: Vec<u8>| Cbor::Map(vec![("l".into(), Cbor::Link(left)), ("e".into(), Cbor::Array(vec![]))]);
+
+        // Two nodes pointing at each other
+        let cyclic = car(&[(cid(1), commit.clone()), (cid(2), node(cid(3))), (cid(3), node(cid(2)))]);
+        assert!(read_bluesky_repo(&cyclic, "me.bsky.social").is_err());
+
+        // A chain of distinct nodes longer than any real tree
+        let mut blocks = vec![(cid(1), commit)];
+        for n in 2..(MAX_TREE_DEPTH as u8 + 4) {
+            blocks.push((cid(n), node(cid(n + 1))));
+        }
+        assert!(read_bluesky_repo(&car(&blocks), "me.bsky.social").is_err());
+    }
+}
@@ -0,0 +1,347 @@
+use crate::{PaiError, Result};
+use std::collections::HashMap;
+
+/// A DAG-CBOR value
+///
+/// Map keys are always strings in DAG-CBOR, and CID links (tag 42) are kept as their binary CID.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Cbor {
+    Null,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(String, Cbor)>),
+    Link(Vec<u8>),
+}
+
+impl Cbor {
+    /// Value of `key` in a map
+    pub(crate) fn get(&self, key: &str) -> Option<&Cbor> {
+        match self {
+            Cbor::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// JSON form of a record, as the XRPC API returns it
+    ///
+    /// Links become `{"$link": ...}` with the CID in base32; byte strings, which items don't use, become null.
+    pub(crate) fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value;
+        match self {
+            Cbor::Null | Cbor::Bytes(_) => Value::Null,
+            Cbor::Bool(b) => Value::Bool(*b),
+            Cbor::Int(i) => i64::try_from(*i)
+                .map(Value::from)
+                .or_else(|_| u64::try_from(*i).map(Value::from))
+                .unwrap_or(Value::Null),
+            Cbor::Float(f) => serde_json::Number::from_f64(*f).map_or(Value::Null, Value::Number),
+            Cbor::Text(s) => Value::String(s.clone()),
+            Cbor::Array(items) => Value::Array(items.iter().map(Cbor::to_json).collect()),
+            Cbor::Map(entries) => Value::Object(entries.iter().map(|(k, v)| (k.clone(), v.to_json())).collect()),
+            Cbor::Link(cid) => serde_json::json!({ "$link": cid_string(cid) }),
+        }
+    }
+}
+
+/// The blocks of a CAR v1 file, keyed by binary CID
+pub(crate) struct Car {
+    pub roots: Vec<Vec<u8>>,
+    blocks: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Car {
+    /// Reads a CARv1 file; see <https://ipld.io/specs/transport/car/carv1/>
+    ///
+    /// Block hashes are not verified.
+    pub(crate) fn read(mut data: &[u8]) -> Result<Self> {
+        let header_len = read_varint(&mut data)? as usize;
+        let header = decode(take(&mut data, header_len)?)?;
+        if header.get("version") != Some(&Cbor::Int(1)) {
+            return Err(PaiError::Parse("Only CAR version 1 files are supported".to_string()));
+        }
+        let roots = match header.get("roots") {
+            Some(Cbor::Array(roots)) => roots
+                .iter()
+                .filter_map(|root| match root {
+                    Cbor::Link(cid) => Some(cid.clone()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let mut blocks = HashMap::new();
+        while !data.is_empty() {
+            let len = read_varint(&mut data)? as usize;
+            let mut block = take(&mut data, len)?;
+            let cid_len = cid_length(block)?;
+            let cid = take(&mut block, cid_len)?.to_vec();
+            blocks.insert(cid, block.to_vec());
+        }
+
+        Ok(Self { roots, blocks })
+    }
+
+    /// Decodes the block with `cid`, if the file has it
+    pub(crate) fn get(&self, cid: &[u8]) -> Result<Option<Cbor>> {
+        self.blocks.get(cid).map(|block| decode(block)).transpose()
+    }
+}
+
+/// Decodes a single DAG-CBOR value that fills `data`
+pub(crate) fn decode(mut data: &[u8]) -> Result<Cbor> {
+    let value = decode_value(&mut data, 0)?;
+    if !data.is_empty() {
+        return Err(parse_error("trailing bytes after CBOR value"));
+    }
+    Ok(value)
+}
+
+fn decode_value(data: &mut &[u8], depth: usize) -> Result<Cbor> {
+    // Records are shallow; this only guards against malicious nesting
+    if depth > 128 {
+        return Err(parse_error("CBOR nested too deeply"));
+    }
+    let initial = take(data, 1)?[0];
+    let (major, info) = (initial >> 5, initial & 0x1f);
+    if major == 7 {
+        return match info {
+            20 => Ok(Cbor::Bool(false)),
+            21 => Ok(Cbor::Bool(true)),
+            22 => Ok(Cbor::Null),
+            26 => Ok(Cbor::Float(
+                f32::from_be_bytes(take(data, 4)?.try_into().unwrap()).into(),
+            )),
+            27 => Ok(Cbor::Float(f64::from_be_bytes(take(data, 8)?.try_into().unwrap()))),
+            _ => Err(parse_error(&format!("unsupported CBOR simple value {info}"))),
+        };
+    }
+
+    let arg = match info {
+        0..=23 => u64::from(info),
+        24 => u64::from(take(data, 1)?[0]),
+        25 => u64::from(u16::from_be_bytes(take(data, 2)?.try_into().unwrap())),
+        26 => u64::from(u32::from_be_bytes(take(data, 4)?.try_into().unwrap())),
+        27 => u64::from_be_bytes(take(data, 8)?.try_into().unwrap()),
+        _ => return Err(parse_error("indefinite-length CBOR is not valid DAG-CBOR")),
+    };
+
+    match major {
+        0 => Ok(Cbor::Int(i128::from(arg))),
+        1 => Ok(Cbor::Int(-1 - i128::from(arg))),
+        2 => Ok(Cbor::Bytes(take(data, arg as usize)?.to_vec())),
+        3 => String::from_utf8(take(data, arg as usize)?.to_vec())
+            .map(Cbor::Text)
+            .map_err(|_| parse_error("CBOR text is not UTF-8")),
+        4 => {
+            let mut items = Vec::new();
+            for _ in 0..arg {
+                items.push(decode_value(data, depth + 1)?);
+            }
+            Ok(Cbor::Array(items))
+        }
+        5 => {
+            let mut entries = Vec::new();
+            for _ in 0..arg {
+                let Cbor::Text(key) = decode_value(data, depth + 1)? else {
+                    return Err(parse_error("DAG-CBOR map keys must be strings"));
+                };
+                entries.push((key, decode_value(data, depth + 1)?));
+            }
+            Ok(Cbor::Map(entries))
+        }
+        6 if arg == 42 => match decode_value(data, depth + 1)? {
+            // Links carry a leading 0x00, the multibase prefix for raw binary
+            Cbor::Bytes(bytes) if bytes.first() == Some(&0) => Ok(Cbor::Link(bytes[1..].to_vec())),
+            _ => Err(parse_error("CID link is not a byte string")),
+        },
+        _ => Err(parse_error(&format!("unsupported CBOR major type {major}"))),
+    }
+}
+
+/// Length of the binary CID at the start of a block
+fn cid_length(block: &[u8]) -> Result<usize> {
+    // CIDv0 is a bare sha2-256 multihash
+    if block.starts_with(&[0x12, 0x20]) {
+        return Ok(34);
+    }
+    let mut rest = block;
+    let version = read_varint(&mut rest)?;
+    if version != 1 {
+        return Err(parse_error(&format!("unsupported CID version {version}")));
+    }
+    read_varint(&mut rest)?; // codec
+    read_varint(&mut rest)?; // hash function
+    let digest_len = read_varint(&mut rest)? as usize;
+    take(&mut rest, digest_len)?;
+    Ok(block.len() - rest.len())
+}
+
+/// Reads an unsigned LEB128 varint
+fn read_varint(data: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = take(data, 1)?[0];
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(parse_error("varint is too long"))
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if data.len() < len {
+        return Err(parse_error("unexpected end of file"));
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+/// A CID in its usual text form: `b` and lowercase, unpadded base32
+fn cid_string(cid: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+    let mut out = String::from("b");
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in cid {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+fn parse_error(message: &str) -> PaiError {
+    PaiError::Parse(format!("Invalid CAR file: {message}"))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Encodes `value` as DAG-CBOR, for building test repositories
+    pub(crate) fn encode(value: &Cbor) -> Vec<u8> {
+        fn head(out: &mut Vec<u8>, major: u8, arg: u64) {
+            match arg {
+                0..=23 => out.push(major << 5 | arg as u8),
+                24..=0xff => out.extend([major << 5 | 24, arg as u8]),
+                0x100..=0xffff => {
+                    out.push(major << 5 | 25);
+                    out.extend((arg as u16).to_be_bytes());
+                }
+                _ => {
+                    out.push(major << 5 | 27);
+                    out.extend(arg.to_be_bytes());
+                }
+            }
+        }
+
+        let mut out = Vec::new();
+        match value {
+            Cbor::Null => out.push(0xf6),
+            Cbor::Bool(b) => out.push(if *b { 0xf5 } else { 0xf4 }),
+            Cbor::Int(i) if *i >= 0 => head(&mut out, 0, *i as u64),
+            Cbor::Int(i) => head(&mut out, 1, (-1 - *i) as u64),
+            Cbor::Float(f) => {
+                out.push(0xfb);
+                out.extend(f.to_be_bytes());
+            }
+            Cbor::Bytes(bytes) => {
+                head(&mut out, 2, bytes.len() as u64);
+                out.extend(bytes);
+            }
+            Cbor::Text(text) => {
+                head(&mut out, 3, text.len() as u64);
+                out.extend(text.as_bytes());
+            }
+            Cbor::Array(items) => {
+                head(&mut out, 4, items.len() as u64);
+                items.iter().for_each(|item| out.extend(encode(item)));
+            }
+            Cbor::Map(entries) => {
+                head(&mut out, 5, entries.len() as u64);
+                for (key, value) in entries {
+                    out.extend(encode(&Cbor::Text(key.clone())));
+                    out.extend(encode(value));
+                }
+            }
+            Cbor::Link(cid) => {
+                head(&mut out, 6, 42);
+                let mut bytes = vec![0];
+                bytes.extend(cid);
+                out.extend(encode(&Cbor::Bytes(bytes)));
+            }
+        }
+        out
+    }
+
+    /// A CAR file with `blocks` and the first block's CID as its root
+    pub(crate) fn car(blocks: &[(Vec<u8>, Cbor)]) -> Vec<u8> {
+        fn varint(out: &mut Vec<u8>, mut value: usize) {
+            while value >= 0x80 {
+                out.push(value as u8 | 0x80);
+                value >>= 7;
+            }
+            out.push(value as u8);
+        }
+
+        let header = encode(&Cbor::Map(vec![
+            ("roots".into(), Cbor::Array(vec![Cbor::Link(blocks[0].0.clone())])),
+            ("version".into(), Cbor::Int(1)),
+        ]));
+        let mut out = Vec::new();
+        varint(&mut out, header.len());
+        out.extend(header);
+        for (cid, value) in blocks {
+            let data = encode(value);
+            varint(&mut out, cid.len() + data.len());
+            out.extend(cid);
+            out.extend(data);
+        }
+        out
+    }
+
+    /// A dag-cbor, sha2-256 CIDv1 with a made-up digest
+    pub(crate) fn cid(n: u8) -> Vec<u8> {
+        let mut cid = vec![0x01, 0x71, 0x12, 0x20];
+        cid.extend([n; 32]);
+        cid
+    }
+
+    #[test]
+    fn decodes_values_and_blocks() {
+        let record = Cbor::Map(vec![
+            ("text".into(), Cbor::Text("héllo".repeat(10))),
+            ("count".into(), Cbor::Int(-300)),
+            ("big".into(), Cbor::Int(70_000)),
+            ("ok".into(), Cbor::Bool(true)),
+            ("ref".into(), Cbor::Link(cid(2))),
+            ("list".into(), Cbor::Array(vec![Cbor::Null, Cbor::Bytes(vec![1, 2])])),
+        ]);
+        let data = car(&[(cid(1), record.clone())]);
+
+        let car = Car::read(&data).unwrap();
+        assert_eq!(car.roots, [cid(1)]);
+        assert_eq!(car.get(&cid(1)).unwrap(), Some(record.clone()));
+        assert_eq!(car.get(&cid(9)).unwrap(), None);
+
+        let json = record.to_json();
+        assert_eq!(json["count"], -300);
+        assert_eq!(json["list"], serde_json::json!([null, null]));
+        assert!(json["ref"]["$link"].as_str().unwrap().starts_with("bafyrei"));
+
+        assert!(Car::read(&data[..data.len() - 1]).is_err());
+        assert!(decode(&[0x9f]).is_err());
+    }
+}
@@ -0,0 +1,6 @@
+mod bluesky;
+mod car;
+mod substack;
+
+pub use bluesky::read_bluesky_repo;
+pub use substack::read_substack_export;
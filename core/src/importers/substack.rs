@@ -0,0 +1,165 @@
+use crate::{Item, PaiError, Result, SourceKind, SubstackConfig};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+/// A row of `posts.csv`
+#[derive(Debug, Deserialize)]
+struct PostRow {
+    /// `<numeric id>.<slug>`, also the name of the post's HTML file
+    post_id: String,
+    post_date: Option<String>,
+    is_published: Option<String>,
+    title: Option<String>,
+    subtitle: Option<String>,
+}
+
+/// Reads the published posts in a Substack data export as items
+///
+/// `zip` is the archive from the publication's Settings → Exports: `posts.csv` lists the posts
+/// and `posts/<post_id>.html` holds each body. Items get the IDs and URLs the RSS feed uses,
+/// `{base_url}/p/{slug}`, so they match synced posts. Drafts are skipped.
+pub fn read_substack_export(zip: &[u8], config: &SubstackConfig) -> Result<Vec<Item>> {
+    let mut archive = ZipArchive::new(Cursor::new(zip)).map_err(|e| export_error(&e.to_string()))?;
+    let csv_path = archive
+        .file_names()
+        .find(|name| *name == "posts.csv" || name.ends_with("/posts.csv"))
+        .map(String::from)
+        .ok_or_else(|| export_error("posts.csv is missing"))?;
+    let dir = csv_path.trim_end_matches("posts.csv").to_string();
+
+    let rows: Vec<PostRow> = {
+        let file = archive.by_name(&csv_path).map_err(|e| export_error(&e.to_string()))?;
+        csv::Reader::from_reader(file)
+            .deserialize()
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|e| export_error(&format!("posts.csv: {e}")))?
+    };
+
+    let base_url = config.base_url.trim_end_matches('/');
+    let source_id = config.source_id();
+    let mut items = Vec::new();
+    for row in rows {
+        if row.is_published.as_deref() != Some("true") {
+            continue;
+        }
+        let slug = row
+            .post_id
+            .split_once('.')
+            .map_or(row.post_id.as_str(), |(_, slug)| slug);
+        let url = format!("{base_url}/p/{slug}");
+
+        let content_html = match archive.by_name(&format!("{dir}posts/{}.html", row.post_id)) {
+            Ok(mut file) => {
+                let mut html = String::new();
+                file.read_to_string(&mut html)?;
+                Some(html)
+            }
+            Err(_) => None,
+        };
+
+        let published_at = row
+            .post_date
+            .as_deref()
+            .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+            .map(|date| date.to_rfc3339())
+            .unwrap_or_else(|| Utc::now().to_rfc3339());
+
+        items.push(Item {
+            id: url.clone(),
+            source_kind: SourceKind::Substack,
+            source_id: source_id.clone(),
+            author: None,
+            title: row.title,
+            summary: row.subtitle,
+            url,
+            content_html,
+            tags: Vec::new(),
+            published_at,
+            created_at: Utc::now().to_rfc3339(),
+        });
+    }
+    Ok(items)
+}
+
+fn export_error(message: &str) -> PaiError {
+    PaiError::Parse(format!("Invalid Substack export: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn export(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn reads_published_posts() {
+        let csv = "post_id,post_date,is_published,email_sent_at,inbox_sent_at,type,audience,title,subtitle,podcast_url
+101.first-post,2021-03-04T05:06:07.000Z,true,,,newsletter,everyone,\"First, post\",A subtitle,
+102.draft,,false,,,newsletter,everyone,Draft,,
+";
+        let data = export(&[
+            ("posts.csv", csv),
+            ("posts/101.first-post.html", "<p>Hello</p>"),
+            ("posts/102.draft.html", "<p>Draft</p>"),
+        ]);
+        let config = SubstackConfig { enabled: true, base_url: "https://me.substack.com/".to_string() };
+
+        let items = read_substack_export(&data, &config).unwrap();
+        assert_eq!(items.len(), 1);
+        let item = &items[0];
+        assert_eq!(item.id, "https://me.substack.com/p/first-post");
+        assert_eq!(item.url, item.id);
+        assert_eq!(item.source_id, "me.substack.com");
+        assert_eq!(item.title.as_deref(), Some("First, post"));
+        assert_eq!(item.summary.as_deref(), Some("A subtitle"));
+        assert_eq!(item.content_html.as_deref(), Some("<p>Hello</p>"));
+        assert_eq!(item.published_at, "2021-03-04T05:06:07+00:00");
+
+        assert!(read_substack_export(&export(&[("other.csv", "")]), &config).is_err());
+    }
+
+    #[test]
+    fn export_ids_match_synced_ids() {
+        // An item as Substack's /feed serves it: the guid is the post URL
+        let rss = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0">
+<channel>
+    <title>Me</title>
+    <link>https://me.substack.com</link>
+    <description>Posts</description>
+    <item>
+        <title><![CDATA[First, post]]></title>
+        <link>https://me.substack.com/p/first-post</link>
+        <guid isPermaLink="false">https://me.substack.com/p/first-post</guid>
+        <pubDate>Thu, 04 Mar 2021 05:06:07 GMT</pubDate>
+    </item>
+</channel>
+</rss>"#;
+        let config = SubstackConfig { enabled: true, base_url: "https://me.substack.com/".to_string() };
+        let synced =
+            crate::SubstackFetcher::new(config.clone()).feed_items(feed_rs::parser::parse(rss.as_bytes()).unwrap());
+
+        let csv =
+            "post_id,post_date,is_published,title\n101.first-post,2021-03-04T05:06:07.000Z,true,\"First, post\"\n";
+        let imported = read_substack_export(&export(&[("posts.csv", csv)]), &config).unwrap();
+
+        let ids = |items: &[Item]| {
+            items
+                .iter()
+                .map(|item| (item.id.clone(), item.url.clone(), item.source_id.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&imported), ids(&synced));
+    }
+}
@@ -0,0 +1,355 @@
+use crate::validate::{self, Shape, SCHEMA};
+use crate::{Config, ConfigIssue, ConfigReport};
+use std::path::PathBuf;
+
+/// Prefix of the environment variables that override config keys, e.g. `PAI_SERVER__CACHE_MAX_AGE`
+pub const ENV_PREFIX: &str = "PAI_";
+
+/// Config file applied before the user's, for settings shared by every account on a machine
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/pai/config.toml";
+
+/// Where [`Config::load_layered`] looks for config files
+#[derive(Debug, Clone, Default)]
+pub struct ConfigPaths {
+    /// System-wide config file, usually [`SYSTEM_CONFIG_PATH`]
+    pub system: Option<PathBuf>,
+    /// Directory holding the user's `config.toml` and its `config.d/` drop-ins
+    pub user_dir: Option<PathBuf>,
+}
+
+impl ConfigPaths {
+    /// Candidate files in the order they are applied; later files override earlier ones.
+    /// A file reachable through both the system path and the user dir is listed once.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self.system.iter().cloned().collect();
+        if let Some(dir) = &self.user_dir {
+            files.push(dir.join("config.toml"));
+            if let Ok(entries) = std::fs::read_dir(dir.join("config.d")) {
+                let mut drop_ins: Vec<PathBuf> = entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+                    .collect();
+                drop_ins.sort();
+                files.extend(drop_ins);
+            }
+        }
+        let mut seen = std::collections::HashSet::new();
+        files.retain(|path| seen.insert(std::fs::canonicalize(path).unwrap_or_else(|_| path.clone())));
+        files
+    }
+}
+
+pub(crate) fn check(paths: &ConfigPaths, env: &dyn Fn(&str) -> Option<String>) -> ConfigReport {
+    let mut merged = toml::Table::new();
+    let mut layers = Vec::new();
+    let mut issues = Vec::new();
+
+    for path in paths.files() {
+        if !path.exists() {
+            continue;
+        }
+        let origin = path.display().to_string();
+        let mut file_issues = Vec::new();
+        match std::fs::read_to_string(&path) {
+            Ok(content) => match toml::from_str::<toml::Table>(&content) {
+                Ok(table) => {
+                    validate::check_keys(&table, SCHEMA, "", &mut file_issues);
+                    merge(&mut merged, table);
+                }
+                Err(e) => file_issues.push(validate::error("", e.to_string().trim_end().to_string())),
+            },
+            Err(e) => file_issues.push(validate::error("", format!("failed to read: {e}"))),
+        }
+        issues.extend(with_origin(file_issues, &origin));
+        layers.push(origin);
+    }
+
+    let mut names = Vec::new();
+    let mut env_issues = Vec::new();
+    let overrides = env_table(SCHEMA, ENV_PREFIX, env, &mut names, &mut env_issues);
+    patch(&mut merged, overrides);
+    issues.extend(with_origin(env_issues, "environment"));
+    if !names.is_empty() {
+        layers.push(format!("environment ({})", names.join(", ")));
+    }
+
+    validate::check_enabled_flags(&merged, &mut issues);
+    let config = match toml::Value::Table(merged).try_into::<Config>() {
+        Ok(config) => config,
+        Err(e) => {
+            issues.push(validate::error("", e.to_string().trim_end().to_string()));
+            return ConfigReport { config: None, layers, issues };
+        }
+    };
+
+    validate::check_values(&config, &mut issues);
+    ConfigReport { config: Some(config), layers, issues }
+}
+
+fn with_origin(issues: Vec<ConfigIssue>, origin: &str) -> impl Iterator<Item = ConfigIssue> + '_ {
+    issues
+        .into_iter()
+        .map(move |issue| ConfigIssue { origin: Some(origin.to_string()), ..issue })
+}
+
+/// Builds a table from the variables named after `schema`, e.g. `PAI_SOURCES__BLUESKY__HANDLE`
+///
+/// Arrays of tables take an index, `PAI_SOURCES__LEAFLET__0__ID`, counting up from 0 until an
+/// index has no variables. Names of the variables read are collected into `names`.
+fn env_table(
+    schema: &[(&str, Shape)], prefix: &str, env: &dyn Fn(&str) -> Option<String>, names: &mut Vec<String>,
+    issues: &mut Vec<ConfigIssue>,
+) -> toml::Table {
+    let mut table = toml::Table::new();
+    for (key, shape) in schema {
+        let name = format!("{prefix}{}", key.to_uppercase());
+        match shape {
+            Shape::Table(fields) => {
+                let nested = env_table(fields, &format!("{name}__"), env, names, issues);
+                if !nested.is_empty() {
+                    table.insert(key.to_string(), toml::Value::Table(nested));
+                }
+            }
+            Shape::ArrayOfTables(fields) => {
+                let mut array = Vec::new();
+                loop {
+                    let entry = env_table(fields, &format!("{name}__{}__", array.len()), env, names, issues);
+                    if entry.is_empty() {
+                        break;
+                    }
+                    array.push(toml::Value::Table(entry));
+                }
+                if !array.is_empty() {
+                    table.insert(key.to_string(), toml::Value::Array(array));
+                }
+            }
+            shape => {
+                let Some(raw) = read_var(&name, env, names, issues) else { continue };
+                match parse_value(*shape, &raw) {
+                    Ok(value) => {
+                        table.insert(key.to_string(), value);
+                    }
+                    Err(message) => issues.push(validate::error(&name, message)),
+                }
+            }
+        }
+    }
+    table
+}
+
+/// Reads `name`, or failing that the file named by `name_FILE` so secrets can live outside the environment
+fn read_var(
+    name: &str, env: &dyn Fn(&str) -> Option<String>, names: &mut Vec<String>, issues: &mut Vec<ConfigIssue>,
+) -> Option<String> {
+    if let Some(value) = env(name) {
+        names.push(name.to_string());
+        return Some(value);
+    }
+
+    let file_var = format!("{name}_FILE");
+    let path = env(&file_var)?;
+    names.push(file_var.clone());
+    match std::fs::read_to_string(&path) {
+        Ok(content) => Some(content.trim_end_matches(['\r', '\n']).to_string()),
+        Err(e) => {
+            issues.push(validate::error(&file_var, format!("failed to read {path}: {e}")));
+            None
+        }
+    }
+}
+
+fn parse_value(shape: Shape, raw: &str) -> Result<toml::Value, String> {
+    match shape {
+        Shape::Bool => match raw.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Ok(toml::Value::Boolean(true)),
+            "false" | "0" | "no" | "off" => Ok(toml::Value::Boolean(false)),
+            _ => Err(format!("expected true or false, got '{raw}'")),
+        },
+        Shape::Int => raw
+            .trim()
+            .parse()
+            .map(toml::Value::Integer)
+            .map_err(|_| format!("expected a whole number, got '{raw}'")),
+        Shape::List => Ok(toml::Value::Array(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(|value| toml::Value::String(value.to_string()))
+                .collect(),
+        )),
+        _ => Ok(toml::Value::String(raw.to_string())),
+    }
+}
+
+fn is_array_of_tables(array: &[toml::Value]) -> bool {
+    !array.is_empty() && array.iter().all(toml::Value::is_table)
+}
+
+/// Applies a later config file: tables merge key by key, arrays of tables such as
+/// `[[sources.leaflet]]` gain the new entries, and any other value is replaced
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(table)) => merge(existing, table),
+            (Some(toml::Value::Array(existing)), toml::Value::Array(array)) if is_array_of_tables(&array) => {
+                existing.extend(array)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Applies environment overrides like [`merge`], except that indexed entries patch the entry
+/// already at that index rather than adding another
+fn patch(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(table)) => patch(existing, table),
+            (Some(toml::Value::Array(existing)), toml::Value::Array(array)) if is_array_of_tables(&array) => {
+                for (index, value) in array.into_iter().enumerate() {
+                    match (existing.get_mut(index), value) {
+                        (Some(toml::Value::Table(entry)), toml::Value::Table(table)) => patch(entry, table),
+                        (_, value) => existing.push(value),
+                    }
+                }
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn layers_apply_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = dir.path().join("system.toml");
+        fs::write(
+            &system,
+            "[server]\ncache_max_age = 60\npublic_url = \"https://system.example\"\n\n\
+             [[sources.leaflet]]\nenabled = true\nid = \"a\"\nbase_url = \"https://a.leaflet.pub\"\n",
+        )
+        .unwrap();
+        let user = dir.path().join("user");
+        fs::create_dir_all(user.join("config.d")).unwrap();
+        fs::write(user.join("config.toml"), "[server]\ncache_max_age = 120\n").unwrap();
+        fs::write(
+            user.join("config.d/10-leaflet.toml"),
+            "[[sources.leaflet]]\nenabled = true\nid = \"b\"\nbase_url = \"https://b.leaflet.pub\"\n",
+        )
+        .unwrap();
+        fs::write(user.join("config.d/notes.txt"), "ignored").unwrap();
+
+        let paths = ConfigPaths { system: Some(system), user_dir: Some(user) };
+        let env = lookup(&[
+            ("PAI_SERVER__CACHE_MAX_AGE", "30"),
+            ("PAI_SOURCES__LEAFLET__0__ENABLED", "false"),
+            ("PAI_CORS__ALLOWED_ORIGINS", "https://a.example, https://b.example"),
+        ]);
+        let report = check(&paths, &env);
+        assert_eq!(report.issues, Vec::new());
+        assert_eq!(report.layers.len(), 4);
+
+        let config = report.config.unwrap();
+        assert_eq!(config.server.cache_max_age, 30);
+        assert_eq!(config.server.public_url.as_deref(), Some("https://system.example"));
+        let leaflet: Vec<_> = config
+            .sources
+            .leaflet
+            .iter()
+            .map(|l| (l.id.as_str(), l.enabled))
+            .collect();
+        assert_eq!(leaflet, vec![("a", false), ("b", true)]);
+        assert_eq!(
+            config.cors.allowed_origins,
+            vec!["https://a.example", "https://b.example"]
+        );
+    }
+
+    #[test]
+    fn system_file_in_user_dir_is_merged_once() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("config.d")).unwrap();
+        fs::write(
+            dir.path().join("config.toml"),
+            "[[sources.leaflet]]\nenabled = true\nid = \"a\"\nbase_url = \"https://a.leaflet.pub\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("config.d/10-extra.toml"),
+            "[server]\ncache_max_age = 30\n",
+        )
+        .unwrap();
+
+        let paths = ConfigPaths { system: Some(dir.path().join("config.toml")), user_dir: Some(dir.path().join(".")) };
+        assert_eq!(paths.files().len(), 2);
+
+        let report = check(&paths, &lookup(&[]));
+        assert_eq!(report.issues, Vec::new());
+        assert_eq!(report.layers.len(), 2);
+        assert_eq!(report.config.unwrap().sources.leaflet.len(), 1);
+    }
+
+    #[test]
+    fn environment_alone_builds_a_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let secret = dir.path().join("dev_key");
+        fs::write(&secret, "s3cret\n").unwrap();
+
+        let env = lookup(&[
+            ("PAI_SOURCES__BLUESKY__ENABLED", "yes"),
+            ("PAI_SOURCES__BLUESKY__HANDLE", "me.bsky.social"),
+            ("PAI_SOURCES__BEARBLOG__0__ENABLED", "true"),
+            ("PAI_SOURCES__BEARBLOG__0__ID", "blog"),
+            ("PAI_SOURCES__BEARBLOG__0__BASE_URL", "https://blog.bearblog.dev"),
+            ("PAI_CORS__DEV_KEY_FILE", secret.to_str().unwrap()),
+        ]);
+        let report = check(&ConfigPaths::default(), &env);
+        assert_eq!(report.issues, Vec::new());
+
+        let config = report.config.unwrap();
+        assert!(config
+            .sources
+            .bluesky
+            .is_some_and(|b| b.enabled && b.handle == "me.bsky.social"));
+        assert_eq!(config.sources.bearblog.len(), 1);
+        assert_eq!(config.cors.dev_key.as_deref(), Some("s3cret"));
+    }
+
+    #[test]
+    fn reports_bad_values_with_their_origin() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("config.toml"), "[server]\ncache_max_ag = 1\n").unwrap();
+        let paths = ConfigPaths { system: None, user_dir: Some(dir.path().to_path_buf()) };
+        let env = lookup(&[
+            ("PAI_WEBMENTIONS__SEND", "maybe"),
+            ("PAI_CORS__DEV_KEY_FILE", "/nonexistent/dev_key"),
+        ]);
+
+        let messages: Vec<String> = check(&paths, &env).issues.iter().map(ToString::to_string).collect();
+        let config_file = dir.path().join("config.toml").display().to_string();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(
+            messages[0],
+            format!("{config_file}: server.cache_max_ag: unknown key; did you mean `cache_max_age`?")
+        );
+        assert!(messages[1].starts_with("environment: PAI_CORS__DEV_KEY_FILE: failed to read /nonexistent/dev_key"));
+        assert_eq!(
+            messages[2],
+            "environment: PAI_WEBMENTIONS__SEND: expected true or false, got 'maybe'"
+        );
+    }
+}
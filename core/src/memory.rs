@@ -0,0 +1,502 @@
+use crate::{
+    Item, ItemChange, ItemStats, ListFilter, Mention, PaiError, Result, SentWebmention, SourceSyncStatus, StatsGroup,
+    Storage, SyncRun, WebSubSubscription, WebhookDelivery,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard};
+
+/// In-memory implementation of the Storage trait
+///
+/// Keeps items in a map guarded by a mutex. Intended for tests and for embedding the
+/// indexer without a database; nothing is persisted.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    items: Mutex<BTreeMap<String, Item>>,
+    user_tags: Mutex<BTreeMap<String, Vec<String>>>,
+    changes: Mutex<ChangeLog>,
+    sync_status: Mutex<BTreeMap<(String, String), SourceSyncStatus>>,
+    webhook_deliveries: Mutex<Vec<WebhookDelivery>>,
+    websub_subscriptions: Mutex<BTreeMap<(String, String), WebSubSubscription>>,
+    sent_webmentions: Mutex<Vec<SentWebmention>>,
+    mentions: Mutex<BTreeMap<(String, String), Mention>>,
+}
+
+/// Change sequence numbers of the stored items
+#[derive(Debug, Default, Clone)]
+struct ChangeLog {
+    last: u64,
+    seqs: BTreeMap<String, u64>,
+}
+
+impl MemoryStorage {
+    /// Creates an empty in-memory store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, BTreeMap<String, Item>>> {
+        self.items
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))
+    }
+
+    fn changes(&self) -> Result<MutexGuard<'_, ChangeLog>> {
+        self.changes
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))
+    }
+
+    fn user_tags(&self) -> Result<MutexGuard<'_, BTreeMap<String, Vec<String>>>> {
+        self.user_tags
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))
+    }
+
+    /// Stored items as readers see them, with their user tags
+    fn tagged(&self, items: impl IntoIterator<Item = Item>) -> Result<Vec<Item>> {
+        let user_tags = self.user_tags()?;
+        Ok(items
+            .into_iter()
+            .map(|item| match user_tags.get(&item.id) {
+                Some(tags) => item.with_user_tags(tags),
+                None => item,
+            })
+            .collect())
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn insert_or_replace_item(&self, item: &Item) -> Result<()> {
+        let mut items = self.lock()?;
+        if items.get(&item.id).is_some_and(|stored| stored.same_content(item)) {
+            return Ok(());
+        }
+
+        let mut changes = self.changes()?;
+        changes.last += 1;
+        let seq = changes.last;
+        changes.seqs.insert(item.id.clone(), seq);
+        items.insert(item.id.clone(), item.clone());
+        Ok(())
+    }
+
+    fn list_items(&self, filter: &ListFilter) -> Result<Vec<Item>> {
+        let matching: Vec<Item> = self.lock()?.values().cloned().collect();
+        let mut items: Vec<Item> = self
+            .tagged(matching)?
+            .into_iter()
+            .filter(|item| filter.matches(item))
+            .collect();
+
+        items.sort_by(|a, b| b.published_at.cmp(&a.published_at));
+
+        if let Some(limit) = filter.limit {
+            items.truncate(limit);
+        }
+
+        Ok(items)
+    }
+
+    fn latest_change(&self) -> Result<u64> {
+        Ok(self.changes()?.last)
+    }
+
+    fn list_changes(&self, after: u64, limit: usize) -> Result<Vec<ItemChange>> {
+        let items = self.lock()?;
+        let changes = self.changes()?;
+        let mut seqs: Vec<(u64, &String)> = changes
+            .seqs
+            .iter()
+            .filter(|(_, seq)| **seq > after)
+            .map(|(id, seq)| (*seq, id))
+            .collect();
+        seqs.sort_unstable();
+
+        let user_tags = self.user_tags()?;
+        Ok(seqs
+            .into_iter()
+            .filter_map(|(seq, id)| {
+                let item = items.get(id)?.clone();
+                let item = match user_tags.get(id) {
+                    Some(tags) => item.with_user_tags(tags),
+                    None => item,
+                };
+                Some(ItemChange { seq, item })
+            })
+            .take(limit)
+            .collect())
+    }
+
+    fn get_item(&self, id: &str) -> Result<Option<Item>> {
+        let item = self.lock()?.get(id).cloned();
+        Ok(self.tagged(item)?.pop())
+    }
+
+    fn delete_item(&self, id: &str) -> Result<bool> {
+        self.changes()?.seqs.remove(id);
+        self.user_tags()?.remove(id);
+        Ok(self.lock()?.remove(id).is_some())
+    }
+
+    fn set_user_tags(&self, id: &str, tags: &[String]) -> Result<bool> {
+        let items = self.lock()?;
+        if !items.contains_key(id) {
+            return Ok(false);
+        }
+
+        let mut changes = self.changes()?;
+        changes.last += 1;
+        let seq = changes.last;
+        changes.seqs.insert(id.to_string(), seq);
+        self.user_tags()?.insert(id.to_string(), tags.to_vec());
+        Ok(true)
+    }
+
+    fn get_user_tags(&self, id: &str) -> Result<Vec<String>> {
+        Ok(self.user_tags()?.get(id).cloned().unwrap_or_default())
+    }
+
+    fn count_items(&self) -> Result<usize> {
+        Ok(self.lock()?.len())
+    }
+
+    fn get_stats(&self, group: StatsGroup) -> Result<Vec<ItemStats>> {
+        let mut counts: HashMap<(String, Option<String>), ItemStats> = HashMap::new();
+
+        for item in self.lock()?.values() {
+            let source_id = match group {
+                StatsGroup::Kind => None,
+                StatsGroup::Source => Some(item.source_id.clone()),
+            };
+            counts
+                .entry((item.source_kind.to_string(), source_id.clone()))
+                .or_insert(ItemStats { source_kind: item.source_kind, source_id, count: 0 })
+                .count += 1;
+        }
+
+        let mut stats: Vec<(_, ItemStats)> = counts.into_iter().collect();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(stats.into_iter().map(|(_, stat)| stat).collect())
+    }
+
+    fn record_sync_run(&self, run: &SyncRun) -> Result<()> {
+        let mut statuses = self
+            .sync_status
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))?;
+
+        let key = (run.source_kind.to_string(), run.source_id.clone());
+        let status = SourceSyncStatus::apply(statuses.get(&key), run);
+        statuses.insert(key, status);
+        Ok(())
+    }
+
+    fn get_sync_status(&self) -> Result<Vec<SourceSyncStatus>> {
+        let statuses = self
+            .sync_status
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))?;
+        Ok(statuses.values().cloned().collect())
+    }
+
+    fn record_webhook_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
+        self.webhook_deliveries
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))?
+            .push(delivery.clone());
+        Ok(())
+    }
+
+    fn list_webhook_deliveries(&self, limit: usize) -> Result<Vec<WebhookDelivery>> {
+        let deliveries = self
+            .webhook_deliveries
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))?;
+        Ok(deliveries.iter().rev().take(limit).cloned().collect())
+    }
+
+    fn upsert_websub_subscription(&self, subscription: &WebSubSubscription) -> Result<()> {
+        self.websub_subscriptions
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))?
+            .insert(
+                (subscription.topic.clone(), subscription.callback.clone()),
+                subscription.clone(),
+            );
+        Ok(())
+    }
+
+    fn remove_websub_subscription(&self, topic: &str, callback: &str) -> Result<bool> {
+        let mut subscriptions = self
+            .websub_subscriptions
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))?;
+        Ok(subscriptions
+            .remove(&(topic.to_string(), callback.to_string()))
+            .is_some())
+    }
+
+    fn list_websub_subscriptions(&self) -> Result<Vec<WebSubSubscription>> {
+        let subscriptions = self
+            .websub_subscriptions
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))?;
+        Ok(subscriptions.values().cloned().collect())
+    }
+
+    fn get_item_by_url(&self, url: &str) -> Result<Option<Item>> {
+        let item = self.lock()?.values().find(|item| item.url == url).cloned();
+        Ok(self.tagged(item)?.pop())
+    }
+
+    fn find_items_by_id_prefix(&self, prefix: &str, limit: usize) -> Result<Vec<Item>> {
+        let mut items: Vec<Item> = self
+            .lock()?
+            .values()
+            .filter(|item| item.id.starts_with(prefix))
+            .cloned()
+            .collect();
+        items.sort_by(|a, b| a.id.cmp(&b.id));
+        items.truncate(limit);
+        self.tagged(items)
+    }
+
+    fn record_sent_webmention(&self, sent: &SentWebmention) -> Result<()> {
+        self.sent_webmentions
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))?
+            .push(sent.clone());
+        Ok(())
+    }
+
+    fn list_sent_webmentions(&self, limit: usize) -> Result<Vec<SentWebmention>> {
+        let sent = self
+            .sent_webmentions
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))?;
+        Ok(sent.iter().rev().take(limit).cloned().collect())
+    }
+
+    fn upsert_mention(&self, mention: &Mention) -> Result<()> {
+        self.mentions
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))?
+            .insert((mention.source.clone(), mention.target.clone()), mention.clone());
+        Ok(())
+    }
+
+    fn remove_mention(&self, source: &str, target: &str) -> Result<bool> {
+        let mut mentions = self
+            .mentions
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))?;
+        Ok(mentions.remove(&(source.to_string(), target.to_string())).is_some())
+    }
+
+    fn list_mentions(&self, item_id: &str) -> Result<Vec<Mention>> {
+        let mentions = self
+            .mentions
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Memory storage lock poisoned: {e}")))?;
+        let mut found: Vec<Mention> = mentions
+            .values()
+            .filter(|mention| mention.item_id == item_id)
+            .cloned()
+            .collect();
+        found.sort_by(|a, b| b.verified_at.cmp(&a.verified_at));
+        Ok(found)
+    }
+
+    fn verify_schema(&self) -> Result<()> {
+        self.lock().map(|_| ())
+    }
+
+    /// Runs `f` against this store and restores the previous contents if it fails
+    ///
+    /// Writes are not isolated from concurrent readers while `f` runs.
+    fn transaction(&self, f: &mut dyn FnMut(&dyn Storage) -> Result<()>) -> Result<()> {
+        let snapshot = (self.lock()?.clone(), self.changes()?.clone(), self.user_tags()?.clone());
+
+        let result = f(self);
+        if result.is_err() {
+            *self.lock()? = snapshot.0;
+            *self.changes()? = snapshot.1;
+            *self.user_tags()? = snapshot.2;
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SourceKind;
+
+    fn create_test_item(id: &str, source_kind: SourceKind, source_id: &str, published_at: &str) -> Item {
+        Item::test(id)
+            .with_source(source_kind, source_id)
+            .with_title(&format!("Title {id}"))
+            .with_published_at(published_at)
+    }
+
+    #[test]
+    fn list_orders_newest_first_and_limits() {
+        let storage = MemoryStorage::new();
+        storage
+            .insert_or_replace_items(&[
+                create_test_item("a", SourceKind::Substack, "s", "2024-01-01T00:00:00Z"),
+                create_test_item("b", SourceKind::Substack, "s", "2024-03-01T00:00:00Z"),
+                create_test_item("c", SourceKind::Substack, "s", "2024-02-01T00:00:00Z"),
+            ])
+            .unwrap();
+
+        let items = storage
+            .list_items(&ListFilter { limit: Some(2), ..Default::default() })
+            .unwrap();
+        let ids: Vec<&str> = items.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn filter_by_kind_since_and_query() {
+        let storage = MemoryStorage::new();
+        storage
+            .insert_or_replace_items(&[
+                create_test_item("old", SourceKind::Bluesky, "me", "2023-01-01T00:00:00Z"),
+                create_test_item("new", SourceKind::Bluesky, "me", "2024-06-01T00:00:00Z"),
+                create_test_item("other", SourceKind::Leaflet, "pub", "2024-06-01T00:00:00Z"),
+            ])
+            .unwrap();
+
+        let filter = ListFilter {
+            source_kind: Some(SourceKind::Bluesky),
+            since: Some("2024-01-01T00:00:00Z".to_string()),
+            query: Some("title NEW".to_string()),
+            ..Default::default()
+        };
+        let items = storage.list_items(&filter).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, "new");
+    }
+
+    #[test]
+    fn get_delete_and_count() {
+        let storage = MemoryStorage::new();
+        let item = create_test_item("a", SourceKind::Substack, "s", "2024-01-01T00:00:00Z");
+        storage.insert_or_replace_item(&item).unwrap();
+
+        assert!(storage.get_item("a").unwrap().is_some());
+        assert_eq!(storage.count_items().unwrap(), 1);
+        assert!(storage.delete_item("a").unwrap());
+        assert!(!storage.delete_item("a").unwrap());
+        assert_eq!(storage.count_items().unwrap(), 0);
+    }
+
+    #[test]
+    fn stats_group_by_kind_and_source() {
+        let storage = MemoryStorage::new();
+        let items = vec![
+            create_test_item("a", SourceKind::Leaflet, "one", "2024-01-01T00:00:00Z"),
+            create_test_item("b", SourceKind::Leaflet, "two", "2024-01-01T00:00:00Z"),
+            create_test_item("c", SourceKind::Bluesky, "me", "2024-01-01T00:00:00Z"),
+        ];
+        assert_eq!(storage.insert_or_replace_items(&items).unwrap(), 3);
+
+        let by_kind = storage.get_stats(StatsGroup::Kind).unwrap();
+        assert_eq!(by_kind.len(), 2);
+        assert_eq!(by_kind[0].source_kind, SourceKind::Bluesky);
+        assert_eq!(by_kind[1].count, 2);
+
+        let by_source = storage.get_stats(StatsGroup::Source).unwrap();
+        assert_eq!(by_source.len(), 3);
+        assert_eq!(by_source[1].source_id.as_deref(), Some("one"));
+    }
+
+    #[test]
+    fn transaction_rolls_back_on_error() {
+        let storage = MemoryStorage::new();
+        let item = create_test_item("a", SourceKind::Substack, "s", "2024-01-01T00:00:00Z");
+
+        let result = storage.transaction(&mut |tx| {
+            tx.insert_or_replace_item(&item)?;
+            Err(PaiError::Storage("boom".to_string()))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(storage.count_items().unwrap(), 0);
+    }
+
+    #[test]
+    fn changes_list_each_rewritten_item_once_in_write_order() {
+        let storage = MemoryStorage::new();
+        let a = create_test_item("a", SourceKind::Substack, "s", "2024-01-01T00:00:00Z");
+        let b = create_test_item("b", SourceKind::Substack, "s", "2023-01-01T00:00:00Z");
+        storage.insert_or_replace_items(&[a.clone(), b.clone()]).unwrap();
+        let before = storage.latest_change().unwrap();
+
+        storage.insert_or_replace_item(&b).unwrap();
+        assert_eq!(storage.latest_change().unwrap(), before, "unchanged rewrite");
+
+        let a_edited = Item { title: Some("Edited".to_string()), ..a };
+        storage.insert_or_replace_item(&a_edited).unwrap();
+        let changes = storage.list_changes(0, 10).unwrap();
+        let ids: Vec<&str> = changes.iter().map(|c| c.item.id.as_str()).collect();
+        assert_eq!(ids, ["b", "a"]);
+        assert_eq!(changes[1].item.title.as_deref(), Some("Edited"));
+
+        let after = storage.list_changes(before, 10).unwrap();
+        assert_eq!(after.len(), 1);
+        assert_eq!(after[0].seq, storage.latest_change().unwrap());
+    }
+
+    #[test]
+    fn sync_runs_track_latest_outcome_and_errors() {
+        let storage = MemoryStorage::new();
+        let run = |started_at: &str, item_count, error: Option<&str>| SyncRun {
+            source_kind: SourceKind::Bluesky,
+            source_id: "test.bsky.social".to_string(),
+            started_at: started_at.to_string(),
+            duration_ms: 120,
+            item_count,
+            error: error.map(String::from),
+        };
+
+        storage.record_sync_run(&run("2024-01-01T00:00:00Z", 5, None)).unwrap();
+        storage
+            .record_sync_run(&run("2024-01-02T00:00:00Z", 0, Some("timeout")))
+            .unwrap();
+
+        let status = storage.get_sync_status().unwrap();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].last_run_at, "2024-01-02T00:00:00Z");
+        assert_eq!(status[0].last_success_at.as_deref(), Some("2024-01-01T00:00:00Z"));
+        assert_eq!(status[0].last_item_count, 5);
+        assert_eq!(status[0].last_error.as_deref(), Some("timeout"));
+        assert_eq!(status[0].error_count, 1);
+    }
+
+    #[test]
+    fn user_tags_survive_syncs() {
+        let storage = MemoryStorage::new();
+        let mut item = create_test_item("a", SourceKind::Bluesky, "me", "2024-01-01T00:00:00Z");
+        item.tags = vec!["rust".to_string()];
+        storage.insert_or_replace_item(&item).unwrap();
+
+        let tags = vec!["Rust".to_string(), "later".to_string()];
+        assert!(storage.set_user_tags("a", &tags).unwrap());
+        assert!(!storage.set_user_tags("missing", &tags).unwrap());
+        assert_eq!(storage.get_item("a").unwrap().unwrap().tags, ["rust", "later"]);
+        assert_eq!(storage.get_user_tags("a").unwrap(), tags);
+        assert!(storage.get_user_tags("missing").unwrap().is_empty());
+        assert_eq!(storage.list_changes(1, 10).unwrap()[0].item.tags, ["rust", "later"]);
+
+        item.tags = vec!["rust".to_string(), "wasm".to_string()];
+        storage.insert_or_replace_item(&item).unwrap();
+        assert_eq!(storage.get_item("a").unwrap().unwrap().tags, ["rust", "wasm", "later"]);
+
+        storage.delete_item("a").unwrap();
+        storage.insert_or_replace_item(&item).unwrap();
+        assert_eq!(storage.get_item("a").unwrap().unwrap().tags, ["rust", "wasm"]);
+    }
+}
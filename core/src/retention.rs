@@ -0,0 +1,201 @@
+use crate::{Item, ListFilter, Result, RetentionConfig, RetentionRule, SourceKind, StatsGroup, Storage};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::cmp::Reverse;
+
+impl RetentionConfig {
+    /// The most specific rule covering a source, if any
+    pub fn rule_for(&self, kind: SourceKind, source_id: &str) -> Option<&RetentionRule> {
+        self.rules
+            .iter()
+            .filter(|rule| {
+                rule.kind.is_none_or(|k| k == kind) && rule.source_id.as_deref().is_none_or(|id| id == source_id)
+            })
+            .max_by_key(|rule| (rule.source_id.is_some(), rule.kind.is_some()))
+    }
+
+    /// Whether an item is exempt from `rule`, by being pinned or tagged
+    ///
+    /// Any tag exempts an item unless the rule sets `prune_tagged`; then only its `keep_tags` do.
+    pub fn keeps(&self, rule: &RetentionRule, item: &Item) -> bool {
+        if self
+            .pinned
+            .iter()
+            .any(|pinned| *pinned == item.id || *pinned == item.url)
+        {
+            return true;
+        }
+        if !rule.prune_tagged {
+            return !item.tags.is_empty();
+        }
+        rule.keep_tags.iter().any(|wanted| {
+            (wanted == "*" && !item.tags.is_empty()) || item.tags.iter().any(|tag| tag.eq_ignore_ascii_case(wanted))
+        })
+    }
+
+    /// The items of a single source that `rule` no longer keeps as of `now`, newest first
+    ///
+    /// Exempt items are neither returned nor counted toward `max_items`. Items whose publish date
+    /// can't be parsed are treated as new, so they are never the ones dropped.
+    pub fn expired<'a>(&self, rule: &RetentionRule, items: &'a [Item], now: DateTime<Utc>) -> Vec<&'a Item> {
+        let cutoff = rule
+            .max_age_days
+            .and_then(|days| TimeDelta::try_days(i64::try_from(days).ok()?))
+            .and_then(|age| now.checked_sub_signed(age));
+
+        let mut candidates: Vec<(Option<DateTime<Utc>>, &Item)> = items
+            .iter()
+            .filter(|item| !self.keeps(rule, item))
+            .map(|item| (published(item), item))
+            .collect();
+        candidates.sort_by_key(|(published, _)| Reverse(published.unwrap_or(DateTime::<Utc>::MAX_UTC)));
+
+        candidates
+            .into_iter()
+            .enumerate()
+            .filter(|(index, (published, _))| {
+                rule.max_items.is_some_and(|max| *index >= max)
+                    || cutoff.is_some_and(|cutoff| published.is_some_and(|published| published < cutoff))
+            })
+            .map(|(_, (_, item))| item)
+            .collect()
+    }
+
+    /// Delete every item the rules no longer keep, returning what was deleted
+    ///
+    /// With `dry_run` nothing is deleted and the returned items are those that would be.
+    pub fn prune(&self, storage: &dyn Storage, dry_run: bool) -> Result<Vec<Item>> {
+        let now = Utc::now();
+        let mut pruned = Vec::new();
+        for stats in storage.get_stats(StatsGroup::Source)? {
+            let source_id = stats.source_id.unwrap_or_default();
+            let Some(rule) = self.rule_for(stats.source_kind, &source_id) else { continue };
+
+            let items = storage.list_items(&ListFilter {
+                source_kind: Some(stats.source_kind),
+                source_id: Some(source_id.clone()),
+                ..Default::default()
+            })?;
+            pruned.extend(self.expired(rule, &items, now).into_iter().cloned());
+        }
+
+        if !dry_run && !pruned.is_empty() {
+            storage.transaction(&mut |tx| {
+                for item in &pruned {
+                    tx.delete_item(&item.id)?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(pruned)
+    }
+}
+
+fn published(item: &Item) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&item.published_at)
+        .ok()
+        .map(|published| published.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryStorage;
+
+    fn item(id: &str, kind: SourceKind, source_id: &str, published_at: &str, tags: &[&str]) -> Item {
+        Item::test(id)
+            .with_source(kind, source_id)
+            .with_published_at(published_at)
+            .with_tags(tags)
+    }
+
+    #[test]
+    fn picks_the_most_specific_rule() {
+        let config = RetentionConfig {
+            rules: vec![
+                RetentionRule { max_items: Some(1), ..Default::default() },
+                RetentionRule { kind: Some(SourceKind::Bluesky), max_items: Some(2), ..Default::default() },
+                RetentionRule { source_id: Some("me".into()), max_items: Some(3), ..Default::default() },
+            ],
+            ..Default::default()
+        };
+        let limit = |kind, source_id| config.rule_for(kind, source_id).and_then(|rule| rule.max_items);
+        assert_eq!(limit(SourceKind::Bluesky, "me"), Some(3));
+        assert_eq!(limit(SourceKind::Bluesky, "other"), Some(2));
+        assert_eq!(limit(SourceKind::Substack, "blog"), Some(1));
+        assert!(RetentionConfig::default()
+            .rule_for(SourceKind::Substack, "blog")
+            .is_none());
+    }
+
+    #[test]
+    fn expires_by_age_and_count_but_keeps_exempt_items() {
+        let config = RetentionConfig { pinned: vec!["https://example.com/pinned".into()], ..Default::default() };
+        let rule = RetentionRule {
+            max_age_days: Some(365),
+            max_items: Some(2),
+            prune_tagged: true,
+            keep_tags: vec!["Keep".into()],
+            ..Default::default()
+        };
+        let items = [
+            item("new", SourceKind::Bluesky, "me", "2024-06-01T00:00:00Z", &[]),
+            item("pinned", SourceKind::Bluesky, "me", "2020-01-01T00:00:00Z", &[]),
+            item("undated", SourceKind::Bluesky, "me", "yesterday", &[]),
+            item("tagged", SourceKind::Bluesky, "me", "2020-01-01T00:00:00Z", &["keep"]),
+            item("recent", SourceKind::Bluesky, "me", "2024-05-01T00:00:00+02:00", &[]),
+            item("old", SourceKind::Bluesky, "me", "2023-01-01T00:00:00Z", &["other"]),
+        ];
+        let now = "2024-07-01T00:00:00Z".parse().unwrap();
+
+        let expired: Vec<&str> = config
+            .expired(&rule, &items, now)
+            .iter()
+            .map(|i| i.id.as_str())
+            .collect();
+        assert_eq!(expired, ["recent", "old"]);
+
+        let ids = |rule: &RetentionRule| -> Vec<&str> {
+            config
+                .expired(rule, &items, now)
+                .iter()
+                .map(|i| i.id.as_str())
+                .collect()
+        };
+        // Tagged items are kept by default, as with `keep_tags = ["*"]`
+        let untagged = ["undated", "new", "recent"];
+        assert_eq!(
+            ids(&RetentionRule { max_items: Some(0), ..Default::default() }),
+            untagged
+        );
+        let any_tag =
+            RetentionRule { max_items: Some(0), prune_tagged: true, keep_tags: vec!["*".into()], ..Default::default() };
+        assert_eq!(ids(&any_tag), untagged);
+        let all = RetentionRule { max_items: Some(0), prune_tagged: true, ..Default::default() };
+        assert_eq!(ids(&all), ["undated", "new", "recent", "old", "tagged"]);
+    }
+
+    #[test]
+    fn prunes_only_sources_with_a_rule() {
+        let storage = MemoryStorage::new();
+        storage
+            .insert_or_replace_items(&[
+                item("a", SourceKind::Bluesky, "me", "2024-01-01T00:00:00Z", &[]),
+                item("b", SourceKind::Bluesky, "me", "2024-02-01T00:00:00Z", &[]),
+                item("c", SourceKind::Substack, "blog", "2000-01-01T00:00:00Z", &[]),
+            ])
+            .unwrap();
+        let config = RetentionConfig {
+            rules: vec![RetentionRule { kind: Some(SourceKind::Bluesky), max_items: Some(1), ..Default::default() }],
+            ..Default::default()
+        };
+
+        let would_prune = config.prune(&storage, true).unwrap();
+        assert_eq!(would_prune.len(), 1);
+        assert_eq!(storage.count_items().unwrap(), 3);
+
+        let pruned = config.prune(&storage, false).unwrap();
+        assert_eq!(pruned[0].id, "a");
+        assert!(storage.get_item("a").unwrap().is_none());
+        assert_eq!(storage.count_items().unwrap(), 2);
+    }
+}
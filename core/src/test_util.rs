@@ -0,0 +1,71 @@
+//! Builders for tests in this and the other pai crates, behind the `test-util` feature
+
+use crate::{Item, SourceKind};
+
+impl Item {
+    /// A bare Bluesky post from `test.bsky.social` at `https://example.com/<id>`, published and
+    /// stored at 2024-01-01T00:00:00Z, with no optional fields or tags
+    pub fn test(id: &str) -> Item {
+        Item {
+            id: id.to_string(),
+            source_kind: SourceKind::Bluesky,
+            source_id: "test.bsky.social".to_string(),
+            author: None,
+            title: None,
+            summary: None,
+            url: format!("https://example.com/{id}"),
+            content_html: None,
+            tags: Vec::new(),
+            published_at: "2024-01-01T00:00:00Z".to_string(),
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    pub fn with_source(mut self, source_kind: SourceKind, source_id: &str) -> Item {
+        self.source_kind = source_kind;
+        self.source_id = source_id.to_string();
+        self
+    }
+
+    pub fn with_author(mut self, author: &str) -> Item {
+        self.author = Some(author.to_string());
+        self
+    }
+
+    pub fn with_title(mut self, title: &str) -> Item {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub fn with_summary(mut self, summary: &str) -> Item {
+        self.summary = Some(summary.to_string());
+        self
+    }
+
+    pub fn with_url(mut self, url: &str) -> Item {
+        self.url = url.to_string();
+        self
+    }
+
+    pub fn with_content_html(mut self, content_html: &str) -> Item {
+        self.content_html = Some(content_html.to_string());
+        self
+    }
+
+    pub fn with_tags(mut self, tags: &[&str]) -> Item {
+        self.tags = tags.iter().map(ToString::to_string).collect();
+        self
+    }
+
+    /// Sets when the item was published, and stored, which tests rarely need to tell apart
+    pub fn with_published_at(mut self, published_at: &str) -> Item {
+        self.published_at = published_at.to_string();
+        self.created_at = published_at.to_string();
+        self
+    }
+
+    pub fn with_created_at(mut self, created_at: &str) -> Item {
+        self.created_at = created_at.to_string();
+        self
+    }
+}
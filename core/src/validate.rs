@@ -0,0 +1,617 @@
+use crate::{Config, PaiError, Result, RetentionConfig, SourcesConfig};
+use reqwest::Url;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Keys a config table accepts, and the shape of each value
+///
+/// Value shapes carry their type so the environment layer knows how to parse `PAI_*` variables.
+#[derive(Clone, Copy)]
+pub(crate) enum Shape {
+    Str,
+    Bool,
+    Int,
+    /// A list of strings, written comma-separated in the environment
+    List,
+    Table(&'static [(&'static str, Shape)]),
+    ArrayOfTables(&'static [(&'static str, Shape)]),
+}
+
+const CLOUDFLARE: &[(&str, Shape)] = &[
+    ("worker_name", Shape::Str),
+    ("d1_binding", Shape::Str),
+    ("database_name", Shape::Str),
+];
+
+const PUBLICATION: &[(&str, Shape)] = &[("enabled", Shape::Bool), ("id", Shape::Str), ("base_url", Shape::Str)];
+
+const SOURCES: &[(&str, Shape)] = &[
+    (
+        "substack",
+        Shape::Table(&[("enabled", Shape::Bool), ("base_url", Shape::Str)]),
+    ),
+    (
+        "bluesky",
+        Shape::Table(&[("enabled", Shape::Bool), ("handle", Shape::Str)]),
+    ),
+    ("leaflet", Shape::ArrayOfTables(PUBLICATION)),
+    ("bearblog", Shape::ArrayOfTables(PUBLICATION)),
+    (
+        "feed",
+        Shape::ArrayOfTables(&[("enabled", Shape::Bool), ("id", Shape::Str), ("url", Shape::Str)]),
+    ),
+];
+
+const WEBHOOK: &[(&str, Shape)] = &[
+    ("name", Shape::Str),
+    ("url", Shape::Str),
+    ("format", Shape::Str),
+    ("secret", Shape::Str),
+    ("kind", Shape::Str),
+    ("source_id", Shape::Str),
+    ("tags", Shape::List),
+];
+
+const RETENTION_RULE: &[(&str, Shape)] = &[
+    ("kind", Shape::Str),
+    ("source_id", Shape::Str),
+    ("max_age_days", Shape::Int),
+    ("max_items", Shape::Int),
+    ("prune_tagged", Shape::Bool),
+    ("keep_tags", Shape::List),
+];
+
+/// Every key [`Config`] reads; anything else is reported as unknown
+pub(crate) const SCHEMA: &[(&str, Shape)] = &[
+    ("database", Shape::Table(&[("path", Shape::Str), ("url", Shape::Str)])),
+    (
+        "deployment",
+        Shape::Table(&[("mode", Shape::Str), ("cloudflare", Shape::Table(CLOUDFLARE))]),
+    ),
+    ("sources", Shape::Table(SOURCES)),
+    (
+        "cors",
+        Shape::Table(&[("allowed_origins", Shape::List), ("dev_key", Shape::Str)]),
+    ),
+    (
+        "server",
+        Shape::Table(&[
+            ("cache_max_age", Shape::Int),
+            ("public_url", Shape::Str),
+            ("templates", Shape::Str),
+            ("listen", Shape::Str),
+        ]),
+    ),
+    ("webhooks", Shape::ArrayOfTables(WEBHOOK)),
+    ("webmentions", Shape::Table(&[("send", Shape::Bool)])),
+    (
+        "retention",
+        Shape::Table(&[
+            ("after_sync", Shape::Bool),
+            ("pinned", Shape::List),
+            ("rules", Shape::ArrayOfTables(RETENTION_RULE)),
+        ]),
+    ),
+];
+
+/// How serious a [`ConfigIssue`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IssueLevel {
+    /// Probably not what was meant, but the config still loads
+    Warning,
+    /// The config is rejected
+    Error,
+}
+
+/// A problem found in a config file, located by its dotted key path (e.g. `sources.leaflet[1].id`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub level: IssueLevel,
+    /// The file or `environment` the issue came from, when checking layered config
+    pub origin: Option<String>,
+    /// Empty for problems with the file as a whole, such as a syntax error
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(origin) = &self.origin {
+            write!(f, "{origin}: ")?;
+        }
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Result of [`Config::check`] and [`Config::check_layered`]
+#[derive(Debug)]
+pub struct ConfigReport {
+    /// The parsed config, unless the file could not be deserialized at all
+    pub config: Option<Config>,
+    /// Files and environment variables the config was merged from, lowest priority first;
+    /// empty for [`Config::check`]
+    pub layers: Vec<String>,
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl ConfigReport {
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|issue| issue.level == IssueLevel::Error)
+    }
+
+    /// The config, after logging warnings; fails listing every error if there are any
+    pub fn into_config(self) -> Result<Config> {
+        let (errors, warnings): (Vec<_>, Vec<_>) =
+            self.issues.iter().partition(|issue| issue.level == IssueLevel::Error);
+        for issue in warnings {
+            tracing::warn!("Config: {issue}");
+        }
+
+        match self.config {
+            Some(config) if errors.is_empty() => Ok(config),
+            _ => {
+                let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+                Err(PaiError::Config(format!("Invalid config: {}", errors.join("; "))))
+            }
+        }
+    }
+}
+
+pub(crate) fn check(content: &str) -> ConfigReport {
+    let mut issues = Vec::new();
+    let table: toml::Table = match toml::from_str(content) {
+        Ok(table) => table,
+        Err(e) => {
+            issues.push(error("", e.to_string().trim_end().to_string()));
+            return ConfigReport { config: None, layers: Vec::new(), issues };
+        }
+    };
+
+    check_keys(&table, SCHEMA, "", &mut issues);
+    check_enabled_flags(&table, &mut issues);
+
+    let config = match toml::from_str::<Config>(content) {
+        Ok(config) => config,
+        Err(e) => {
+            let message = match e.span() {
+                Some(span) => format!("line {}: {}", line_number(content, span.start), e.message()),
+                None => e.message().to_string(),
+            };
+            issues.push(error("", message));
+            return ConfigReport { config: None, layers: Vec::new(), issues };
+        }
+    };
+
+    check_values(&config, &mut issues);
+    ConfigReport { config: Some(config), layers: Vec::new(), issues }
+}
+
+/// Checks what deserialization cannot: source handles, duplicate IDs, URLs and retention rules
+pub(crate) fn check_values(config: &Config, issues: &mut Vec<ConfigIssue>) {
+    check_sources(&config.sources, issues);
+    check_retention(&config.retention, issues);
+    if let Some(url) = &config.database.url {
+        check_url("database.url", url, &["postgres", "postgresql"], issues);
+    }
+    if let Some(url) = &config.server.public_url {
+        check_url("server.public_url", url, &["http", "https"], issues);
+    }
+    if let Err(PaiError::Config(message)) = config.server.listen_addr() {
+        issues.push(error("server.listen", message));
+    }
+    for (index, origin) in config.cors.allowed_origins.iter().enumerate() {
+        check_url(
+            &format!("cors.allowed_origins[{index}]"),
+            origin,
+            &["http", "https"],
+            issues,
+        );
+    }
+    for (index, webhook) in config.webhooks.iter().enumerate() {
+        check_url(
+            &format!("webhooks[{index}].url"),
+            &webhook.url,
+            &["http", "https"],
+            issues,
+        );
+    }
+}
+
+fn line_number(content: &str, offset: usize) -> usize {
+    content[..offset.min(content.len())].matches('\n').count() + 1
+}
+
+pub(crate) fn error(path: &str, message: String) -> ConfigIssue {
+    ConfigIssue { level: IssueLevel::Error, origin: None, path: path.to_string(), message }
+}
+
+fn warning(path: &str, message: String) -> ConfigIssue {
+    ConfigIssue { level: IssueLevel::Warning, origin: None, path: path.to_string(), message }
+}
+
+pub(crate) fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+/// Reports keys missing from `schema`, suggesting the closest known key
+pub(crate) fn check_keys(table: &toml::Table, schema: &[(&str, Shape)], prefix: &str, issues: &mut Vec<ConfigIssue>) {
+    for (key, value) in table {
+        let path = join(prefix, key);
+        let Some((_, shape)) = schema.iter().find(|(known, _)| known == key) else {
+            let message = match suggest(key, schema.iter().map(|(known, _)| *known)) {
+                Some(known) => format!("unknown key; did you mean `{known}`?"),
+                None => "unknown key".to_string(),
+            };
+            issues.push(error(&path, message));
+            continue;
+        };
+
+        // Type mismatches are left to deserialization, which names the expected type
+        match (shape, value) {
+            (Shape::Table(fields), toml::Value::Table(table)) => check_keys(table, fields, &path, issues),
+            (Shape::ArrayOfTables(fields), toml::Value::Array(array)) => {
+                for (index, value) in array.iter().enumerate() {
+                    if let toml::Value::Table(table) = value {
+                        check_keys(table, fields, &format!("{path}[{index}]"), issues);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The closest of `candidates` to a misspelt `key`, if any is close enough to be a likely typo
+fn suggest<'a>(key: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let key = key.to_lowercase();
+    let threshold = (key.chars().count() / 3).max(1);
+    candidates
+        .map(|candidate| (edit_distance(&key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Warns about sources without `enabled`, which defaults to false and so silently skips them
+pub(crate) fn check_enabled_flags(table: &toml::Table, issues: &mut Vec<ConfigIssue>) {
+    let Some(toml::Value::Table(sources)) = table.get("sources") else { return };
+    let mut entries = Vec::new();
+    for key in ["substack", "bluesky"] {
+        if let Some(toml::Value::Table(source)) = sources.get(key) {
+            entries.push((format!("sources.{key}"), source));
+        }
+    }
+    for key in ["leaflet", "bearblog", "feed"] {
+        if let Some(toml::Value::Array(array)) = sources.get(key) {
+            for (index, value) in array.iter().enumerate() {
+                if let toml::Value::Table(source) = value {
+                    entries.push((format!("sources.{key}[{index}]"), source));
+                }
+            }
+        }
+    }
+
+    for (path, source) in entries {
+        if !source.contains_key("enabled") {
+            issues.push(warning(
+                &path,
+                "`enabled` is not set, so this source is never synced; add `enabled = true`".to_string(),
+            ));
+        }
+    }
+}
+
+fn check_sources(sources: &SourcesConfig, issues: &mut Vec<ConfigIssue>) {
+    if let Some(substack) = &sources.substack {
+        check_url(
+            "sources.substack.base_url",
+            &substack.base_url,
+            &["http", "https"],
+            issues,
+        );
+    }
+
+    if let Some(bluesky) = &sources.bluesky {
+        let handle = bluesky.handle.trim();
+        if handle.is_empty() {
+            issues.push(error("sources.bluesky.handle", "must not be empty".to_string()));
+        } else if handle.contains("://") || handle.contains('/') || handle.contains(char::is_whitespace) {
+            issues.push(error(
+                "sources.bluesky.handle",
+                format!("'{handle}' is not a handle or DID (e.g. name.bsky.social or did:plc:...)"),
+            ));
+        }
+    }
+
+    let publications = [
+        (
+            "leaflet",
+            "base_url",
+            sources
+                .leaflet
+                .iter()
+                .map(|p| (p.id.as_str(), p.base_url.as_str()))
+                .collect::<Vec<_>>(),
+        ),
+        (
+            "bearblog",
+            "base_url",
+            sources
+                .bearblog
+                .iter()
+                .map(|p| (p.id.as_str(), p.base_url.as_str()))
+                .collect(),
+        ),
+        (
+            "feed",
+            "url",
+            sources.feed.iter().map(|f| (f.id.as_str(), f.url.as_str())).collect(),
+        ),
+    ];
+    for (key, url_key, entries) in publications {
+        let mut seen = HashSet::new();
+        for (index, (id, url)) in entries.into_iter().enumerate() {
+            let path = format!("sources.{key}[{index}]");
+            if id.trim().is_empty() {
+                issues.push(error(&format!("{path}.id"), "must not be empty".to_string()));
+            } else if !seen.insert(id) {
+                issues.push(error(
+                    &format!("{path}.id"),
+                    format!("duplicate id '{id}'; each {key} source needs its own id"),
+                ));
+            }
+            check_url(&format!("{path}.{url_key}"), url, &["http", "https"], issues);
+        }
+    }
+}
+
+fn check_retention(retention: &RetentionConfig, issues: &mut Vec<ConfigIssue>) {
+    let mut seen = HashSet::new();
+    for (index, rule) in retention.rules.iter().enumerate() {
+        let path = format!("retention.rules[{index}]");
+        if rule.max_age_days.is_none() && rule.max_items.is_none() {
+            issues.push(error(
+                &path,
+                "keeps everything; set max_age_days or max_items".to_string(),
+            ));
+        }
+        if !seen.insert((rule.kind, rule.source_id.as_deref())) {
+            let scope = match (rule.kind, &rule.source_id) {
+                (Some(kind), Some(id)) => format!("{kind} source '{id}'"),
+                (Some(kind), None) => format!("{kind} sources"),
+                (None, Some(id)) => format!("source '{id}'"),
+                (None, None) => "every source".to_string(),
+            };
+            issues.push(error(&path, format!("another rule already applies to {scope}")));
+        }
+    }
+}
+
+fn check_url(path: &str, value: &str, schemes: &[&str], issues: &mut Vec<ConfigIssue>) {
+    let message = match Url::parse(value.trim()) {
+        Err(e) => format!("'{value}' is not a valid URL: {e}"),
+        Ok(url) if !schemes.contains(&url.scheme()) => {
+            format!("'{value}' must be a {} URL", schemes.join(" or "))
+        }
+        Ok(url) if url.host_str().is_none_or(str::is_empty) => format!("'{value}' has no host"),
+        Ok(_) => return,
+    };
+    issues.push(error(path, message));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        BearBlogConfig, BlueskyConfig, CloudflareConfig, CorsConfig, DatabaseConfig, DeploymentConfig, FeedConfig,
+        LeafletConfig, RetentionRule, ServerConfig, SubstackConfig, WebhookConfig, WebmentionConfig,
+    };
+
+    fn messages(content: &str) -> Vec<String> {
+        check(content).issues.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn schema_covers_every_config_field() {
+        let publication = || LeafletConfig { enabled: true, id: "a".into(), base_url: "https://a.example".into() };
+        let config = Config {
+            database: DatabaseConfig { path: Some("/tmp/pai.db".into()), url: Some("postgres://localhost/pai".into()) },
+            deployment: DeploymentConfig {
+                mode: "sqlite".into(),
+                cloudflare: Some(CloudflareConfig {
+                    worker_name: "w".into(),
+                    d1_binding: "DB".into(),
+                    database_name: "d".into(),
+                }),
+            },
+            sources: SourcesConfig {
+                substack: Some(SubstackConfig { enabled: true, base_url: "https://a.substack.com".into() }),
+                bluesky: Some(BlueskyConfig { enabled: true, handle: "a.bsky.social".into() }),
+                leaflet: vec![publication()],
+                bearblog: vec![BearBlogConfig { enabled: true, id: "a".into(), base_url: "https://a.example".into() }],
+                feed: vec![FeedConfig { enabled: true, id: "a".into(), url: "https://a.example/feed.xml".into() }],
+            },
+            cors: CorsConfig { allowed_origins: vec!["https://a.example".into()], dev_key: Some("k".into()) },
+            server: ServerConfig {
+                cache_max_age: 60,
+                public_url: Some("https://a.example".into()),
+                templates: Some("/tmp".into()),
+                listen: Some("unix:/run/pai/pai.sock".into()),
+            },
+            webhooks: vec![WebhookConfig {
+                name: Some("n".into()),
+                url: "https://hooks.example".into(),
+                format: Default::default(),
+                secret: Some("s".into()),
+                kind: Some(crate::SourceKind::Bluesky),
+                source_id: Some("a".into()),
+                tags: vec!["t".into()],
+            }],
+            webmentions: WebmentionConfig { send: true },
+            retention: RetentionConfig {
+                after_sync: true,
+                pinned: vec!["https://a.example/post".into()],
+                rules: vec![RetentionRule {
+                    kind: Some(crate::SourceKind::Bluesky),
+                    source_id: Some("a".into()),
+                    max_age_days: Some(365),
+                    max_items: Some(1000),
+                    prune_tagged: true,
+                    keep_tags: vec!["t".into()],
+                }],
+            },
+        };
+
+        // Every field is set, so the file holds every key Config writes
+        let content = toml::to_string(&config).unwrap();
+        assert_eq!(messages(&content), Vec::<String>::new());
+
+        let table: toml::Table = toml::from_str(&content).unwrap();
+        let mut mismatches = Vec::new();
+        schema_matches(&table, SCHEMA, "", &mut mismatches);
+        assert_eq!(mismatches, Vec::<String>::new());
+
+        let reloaded: Config = toml::from_str(&content).unwrap();
+        assert_eq!(toml::to_string(&reloaded).unwrap(), content);
+    }
+
+    /// Records every [`SCHEMA`] key missing from `table` or holding a value of another shape
+    fn schema_matches(table: &toml::Table, schema: &[(&str, Shape)], prefix: &str, mismatches: &mut Vec<String>) {
+        for (key, shape) in schema {
+            let path = join(prefix, key);
+            match (shape, table.get(*key)) {
+                (Shape::Str, Some(toml::Value::String(_)))
+                | (Shape::Bool, Some(toml::Value::Boolean(_)))
+                | (Shape::Int, Some(toml::Value::Integer(_))) => {}
+                (Shape::List, Some(toml::Value::Array(values))) if values.iter().all(toml::Value::is_str) => {}
+                (Shape::Table(fields), Some(toml::Value::Table(table))) => {
+                    schema_matches(table, fields, &path, mismatches)
+                }
+                (Shape::ArrayOfTables(fields), Some(toml::Value::Array(values))) if !values.is_empty() => {
+                    for value in values {
+                        match value {
+                            toml::Value::Table(table) => schema_matches(table, fields, &path, mismatches),
+                            _ => mismatches.push(format!("{path}: not a table")),
+                        }
+                    }
+                }
+                (_, None) => mismatches.push(format!("{path}: not written by Config")),
+                (_, Some(value)) => mismatches.push(format!("{path}: unexpected {}", value.type_str())),
+            }
+        }
+    }
+
+    #[test]
+    fn example_config_is_valid() {
+        assert_eq!(
+            messages(include_str!("../../config.example.toml")),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn reports_unknown_keys_with_suggestions() {
+        let content = r#"
+[sources.bluesky]
+enabled = true
+handel = "me.bsky.social"
+
+[servr]
+public_url = "https://a.example"
+
+[[webhooks]]
+url = "https://hooks.example"
+colour = "red"
+"#;
+        assert_eq!(
+            messages(content),
+            [
+                "servr: unknown key; did you mean `server`?",
+                "sources.bluesky.handel: unknown key; did you mean `handle`?",
+                "webhooks[0].colour: unknown key",
+                "line 2: missing field `handle`",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_missing_flags_duplicate_ids_and_bad_urls() {
+        let content = r#"
+[database]
+url = "mysql://localhost/pai"
+
+[[sources.leaflet]]
+id = "notes"
+base_url = "https://notes.leaflet.pub"
+
+[[sources.leaflet]]
+enabled = true
+id = "notes"
+base_url = "notes.leaflet.pub"
+
+[sources.bluesky]
+enabled = true
+handle = "https://bsky.app/profile/me"
+
+[[sources.feed]]
+enabled = true
+id = "blog"
+url = "ftp://example.com/feed.xml"
+
+[[retention.rules]]
+kind = "bluesky"
+max_age_days = 365
+
+[[retention.rules]]
+kind = "bluesky"
+keep_tags = ["keep"]
+"#;
+        let report = check(content);
+        assert!(report.has_errors());
+        assert_eq!(
+            report.issues.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "sources.leaflet[0]: `enabled` is not set, so this source is never synced; add `enabled = true`",
+                "sources.bluesky.handle: 'https://bsky.app/profile/me' is not a handle or DID (e.g. name.bsky.social or did:plc:...)",
+                "sources.leaflet[1].id: duplicate id 'notes'; each leaflet source needs its own id",
+                "sources.leaflet[1].base_url: 'notes.leaflet.pub' is not a valid URL: relative URL without a base",
+                "sources.feed[0].url: 'ftp://example.com/feed.xml' must be a http or https URL",
+                "retention.rules[1]: keeps everything; set max_age_days or max_items",
+                "retention.rules[1]: another rule already applies to bluesky sources",
+                "database.url: 'mysql://localhost/pai' must be a postgres or postgresql URL",
+            ]
+        );
+        assert_eq!(report.issues[0].level, IssueLevel::Warning);
+    }
+
+    #[test]
+    fn edit_distance_counts_single_edits() {
+        assert_eq!(edit_distance("handel", "handle"), 2);
+        assert_eq!(edit_distance("enable", "enabled"), 1);
+        assert_eq!(suggest("enable", ["enabled", "id"].into_iter()), Some("enabled"));
+        assert_eq!(suggest("zzz", ["enabled", "id"].into_iter()), None);
+    }
+}
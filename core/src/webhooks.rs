@@ -0,0 +1,399 @@
+use crate::{Item, PaiError, Result, Storage, WebhookConfig, WebhookDelivery, WebhookFormat};
+use hmac::{Hmac, KeyInit, Mac};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::time::Duration;
+
+/// Header carrying `sha256=<hex>`, the HMAC-SHA256 of the request body keyed with the webhook secret
+pub const SIGNATURE_HEADER: &str = "X-Pai-Signature-256";
+
+/// Header naming the event that triggered the delivery
+const EVENT_HEADER: &str = "X-Pai-Event";
+
+const ITEM_CREATED: &str = "item.created";
+
+/// Attempts per item and webhook before the delivery is recorded as failed
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubled after each further failure
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends new items to the configured webhooks
+///
+/// Network errors, `429` and `5xx` responses are retried with exponential backoff; other `4xx`
+/// responses are not. Every delivery is recorded with [`Storage::record_webhook_delivery`].
+pub struct WebhookDispatcher<'a> {
+    webhooks: &'a [WebhookConfig],
+    client: reqwest::Client,
+    retry_delay: Duration,
+}
+
+impl<'a> WebhookDispatcher<'a> {
+    pub fn new(webhooks: &'a [WebhookConfig]) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("pai/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .unwrap_or_default();
+
+        Self { webhooks, client, retry_delay: RETRY_DELAY }
+    }
+
+    #[cfg(test)]
+    fn with_retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Delivers each item to every webhook whose filters it matches and records the outcomes
+    ///
+    /// Failed deliveries are logged and recorded, not returned; only storage errors are.
+    pub fn dispatch(&self, storage: &dyn Storage, items: &[Item]) -> Result<()> {
+        if self.webhooks.is_empty() || items.is_empty() {
+            return Ok(());
+        }
+
+        let runtime =
+            tokio::runtime::Runtime::new().map_err(|e| PaiError::Fetch(format!("Failed to create runtime: {e}")))?;
+        let deliveries = runtime.block_on(async {
+            let mut deliveries = Vec::new();
+            for webhook in self.webhooks {
+                for item in items.iter().filter(|item| webhook.matches(item)) {
+                    deliveries.push(self.deliver(webhook, item).await);
+                }
+            }
+            deliveries
+        });
+
+        for delivery in &deliveries {
+            storage.record_webhook_delivery(delivery)?;
+        }
+
+        Ok(())
+    }
+
+    async fn deliver(&self, webhook: &WebhookConfig, item: &Item) -> WebhookDelivery {
+        let label = webhook.label();
+        let body = payload(webhook.format, item).to_string();
+        let signature = webhook
+            .secret
+            .as_deref()
+            .map(|secret| sign_payload(secret, body.as_bytes()));
+
+        let mut delay = self.retry_delay;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+
+            let mut request = self
+                .client
+                .post(&webhook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, ITEM_CREATED)
+                .body(body.clone());
+            if let Some(signature) = &signature {
+                request = request.header(SIGNATURE_HEADER, signature);
+            }
+
+            let (status_code, error, retryable) = match request.send().await {
+                Ok(response) if response.status().is_success() => (Some(response.status()), None, false),
+                Ok(response) => {
+                    let status = response.status();
+                    let retryable = status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+                    (
+                        Some(status),
+                        Some(format!("Receiver responded with {status}")),
+                        retryable,
+                    )
+                }
+                Err(e) => (None, Some(format!("Request failed: {e}")), true),
+            };
+
+            if error.is_none() || !retryable || attempts >= MAX_ATTEMPTS {
+                match &error {
+                    None => tracing::info!(webhook = %label, item_id = %item.id, attempts, "Delivered webhook"),
+                    Some(e) => {
+                        tracing::warn!(webhook = %label, item_id = %item.id, attempts, error = %e, "Webhook delivery failed")
+                    }
+                }
+
+                return WebhookDelivery {
+                    webhook: label,
+                    item_id: item.id.clone(),
+                    attempted_at: chrono::Utc::now().to_rfc3339(),
+                    attempts,
+                    status_code: status_code.map(|status| status.as_u16()),
+                    error,
+                };
+            }
+
+            tracing::debug!(webhook = %label, item_id = %item.id, attempts, "Retrying webhook delivery");
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+}
+
+/// Builds the request body for an item in the webhook's format
+fn payload(format: WebhookFormat, item: &Item) -> Value {
+    let title = item.title.as_deref().or(item.summary.as_deref()).unwrap_or(&item.url);
+
+    match format {
+        WebhookFormat::Json => json!({ "event": ITEM_CREATED, "item": item }),
+        WebhookFormat::Slack => json!({
+            "text": format!("New {} post: <{}|{}>", item.source_kind, item.url, slack_escape(title)),
+        }),
+        WebhookFormat::Discord => {
+            let mut embed = json!({
+                "title": truncate(title, 256),
+                "url": item.url,
+                "timestamp": item.published_at,
+                "footer": { "text": format!("{} · {}", item.source_kind, item.source_id) },
+            });
+            if let Some(summary) = item.summary.as_deref().filter(|summary| *summary != title) {
+                embed["description"] = json!(truncate(summary, 2048));
+            }
+            if let Some(author) = &item.author {
+                embed["author"] = json!({ "name": truncate(author, 256) });
+            }
+            json!({ "embeds": [embed] })
+        }
+    }
+}
+
+/// Computes `sha256=<hex>`, the HMAC-SHA256 of `body` keyed with `secret`
+///
+/// This is the value of [`SIGNATURE_HEADER`] and of WebSub's `X-Hub-Signature`.
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("sha256={hex}")
+}
+
+/// Escapes the characters Slack treats as markup in message text
+fn slack_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Shortens `text` to at most `max` characters, ending with an ellipsis when cut
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(max - 1).collect();
+    cut.push('…');
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryStorage, SourceFetcher, SourceKind};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{mpsc, Mutex};
+    use std::thread;
+
+    /// A request captured by [`spawn_receiver`]: lower-cased header lines and the body
+    struct Received {
+        headers: Vec<String>,
+        body: String,
+    }
+
+    impl Received {
+        fn header(&self, name: &str) -> Option<&str> {
+            let prefix = format!("{}: ", name.to_ascii_lowercase());
+            self.headers.iter().find_map(|line| {
+                line.to_ascii_lowercase()
+                    .starts_with(&prefix)
+                    .then(|| &line[prefix.len()..])
+            })
+        }
+    }
+
+    /// Starts a local HTTP receiver that answers successive requests with `statuses`
+    fn spawn_receiver(statuses: Vec<u16>) -> (String, mpsc::Receiver<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut headers = Vec::new();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end().to_string();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length: ") {
+                        content_length = value.parse().unwrap();
+                    }
+                    headers.push(line);
+                }
+
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                sender
+                    .send(Received { headers, body: String::from_utf8(body).unwrap() })
+                    .unwrap();
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )
+                .unwrap();
+            }
+        });
+
+        (url, receiver)
+    }
+
+    fn webhook(url: &str) -> WebhookConfig {
+        WebhookConfig {
+            name: None,
+            url: url.to_string(),
+            format: WebhookFormat::Json,
+            secret: None,
+            kind: None,
+            source_id: None,
+            tags: Vec::new(),
+        }
+    }
+
+    fn item(id: &str, tags: &[&str]) -> Item {
+        Item::test(id)
+            .with_author("test.bsky.social")
+            .with_title(&format!("Post <{id}> & more"))
+            .with_summary("Summary")
+            .with_tags(tags)
+    }
+
+    #[test]
+    fn retries_server_errors_and_signs_requests() {
+        let (url, requests) = spawn_receiver(vec![500, 200]);
+        let webhooks = [WebhookConfig { secret: Some("s3cret".to_string()), ..webhook(&url) }];
+        let storage = MemoryStorage::new();
+
+        WebhookDispatcher::new(&webhooks)
+            .with_retry_delay(Duration::from_millis(10))
+            .dispatch(&storage, &[item("a", &[])])
+            .unwrap();
+
+        let first = requests.recv().unwrap();
+        let second = requests.recv().unwrap();
+        assert_eq!(first.body, second.body);
+        assert_eq!(
+            second.header(SIGNATURE_HEADER),
+            Some(sign_payload("s3cret", second.body.as_bytes()).as_str())
+        );
+        assert_eq!(second.header(EVENT_HEADER), Some("item.created"));
+
+        let body: Value = serde_json::from_str(&second.body).unwrap();
+        assert_eq!(body["event"], "item.created");
+        assert_eq!(body["item"]["id"], "a");
+
+        let deliveries = storage.list_webhook_deliveries(10).unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].webhook, "127.0.0.1");
+        assert_eq!(deliveries[0].attempts, 2);
+        assert_eq!(deliveries[0].status_code, Some(200));
+        assert_eq!(deliveries[0].error, None);
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        let (url, requests) = spawn_receiver(vec![404]);
+        let webhooks = [WebhookConfig { name: Some("broken".to_string()), ..webhook(&url) }];
+        let storage = MemoryStorage::new();
+
+        WebhookDispatcher::new(&webhooks)
+            .with_retry_delay(Duration::from_millis(10))
+            .dispatch(&storage, &[item("a", &[])])
+            .unwrap();
+
+        requests.recv().unwrap();
+        let deliveries = storage.list_webhook_deliveries(10).unwrap();
+        assert_eq!(deliveries[0].webhook, "broken");
+        assert_eq!(deliveries[0].attempts, 1);
+        assert_eq!(deliveries[0].status_code, Some(404));
+        assert!(deliveries[0].error.is_some());
+    }
+
+    #[test]
+    fn filters_by_kind_source_and_tags() {
+        let mut config = webhook("https://example.com");
+        assert!(config.matches(&item("a", &[])));
+
+        config.tags = vec!["Rust".to_string()];
+        assert!(config.matches(&item("a", &["rust", "wasm"])));
+        assert!(!config.matches(&item("a", &["go"])));
+
+        config.kind = Some(SourceKind::Leaflet);
+        assert!(!config.matches(&item("a", &["rust"])));
+    }
+
+    #[test]
+    fn formats_slack_and_discord_payloads() {
+        let item = item("a", &[]);
+
+        let slack = payload(WebhookFormat::Slack, &item);
+        assert_eq!(
+            slack["text"],
+            "New bluesky post: <https://example.com/a|Post &lt;a&gt; &amp; more>"
+        );
+
+        let discord = payload(WebhookFormat::Discord, &item);
+        let embed = &discord["embeds"][0];
+        assert_eq!(embed["title"], "Post <a> & more");
+        assert_eq!(embed["url"], "https://example.com/a");
+        assert_eq!(embed["description"], "Summary");
+        assert_eq!(embed["author"]["name"], "test.bsky.social");
+        assert_eq!(truncate("abcdef", 4), "abc…");
+    }
+
+    struct StaticFetcher(Mutex<Vec<Item>>);
+
+    impl SourceFetcher for StaticFetcher {
+        fn fetch(&self) -> Result<Vec<Item>> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+    }
+
+    #[test]
+    fn sync_notifies_only_for_newly_inserted_items() {
+        let (url, requests) = spawn_receiver(vec![200]);
+        let webhooks = [webhook(&url)];
+        let dispatcher = WebhookDispatcher::new(&webhooks);
+        let storage = MemoryStorage::new();
+        let fetcher = StaticFetcher(Mutex::new(vec![item("old", &[])]));
+
+        let first = crate::run_fetcher(&storage, &fetcher, SourceKind::Bluesky, "test.bsky.social").unwrap();
+        assert!(first.is_empty(), "first sync must not notify");
+
+        fetcher.0.lock().unwrap().push(item("new", &[]));
+        let second = crate::run_fetcher(&storage, &fetcher, SourceKind::Bluesky, "test.bsky.social").unwrap();
+        assert_eq!(second.len(), 1);
+        dispatcher.dispatch(&storage, &second).unwrap();
+
+        let body: Value = serde_json::from_str(&requests.recv().unwrap().body).unwrap();
+        assert_eq!(body["item"]["id"], "new");
+        let deliveries = storage.list_webhook_deliveries(10).unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].item_id, "new");
+    }
+}
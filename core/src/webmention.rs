@@ -0,0 +1,393 @@
+use crate::{Item, PaiError, Result, SentWebmention, Storage, WebmentionConfig};
+use regex::Regex;
+use reqwest::{header, Url};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest page read when looking for links or endpoints (1 MiB)
+pub const MAX_PAGE_BYTES: usize = 1024 * 1024;
+
+/// Opening `<a>` and `<link>` tags; the second group holds the attributes
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<(a|link)\b([^>]*)>").unwrap());
+
+/// `rel` and `href` attributes, double-, single- or unquoted
+static ATTRIBUTE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?is)\b(rel|href)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap());
+
+/// One `<url>; params` entry of a `Link` header
+static LINK_VALUE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<([^>]*)>([^,<]*)").unwrap());
+
+static REL_PARAM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\brel\s*=\s*(?:"([^"]*)"|([^\s;,]+))"#).unwrap());
+
+/// Sends Webmentions for the links in newly synced items
+///
+/// Each link's page is fetched to discover its Webmention endpoint. Links without one are skipped;
+/// every attempted send is recorded with [`Storage::record_sent_webmention`].
+pub struct WebmentionSender {
+    enabled: bool,
+    client: reqwest::Client,
+}
+
+impl WebmentionSender {
+    pub fn new(config: &WebmentionConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("pai/", env!("CARGO_PKG_VERSION"), " (Webmention)"))
+            .build()
+            .unwrap_or_default();
+
+        Self { enabled: config.send, client }
+    }
+
+    /// Sends a Webmention from each item to every page it links to and records the outcomes
+    ///
+    /// Failed sends are logged and recorded, not returned; only storage errors are.
+    pub fn send(&self, storage: &dyn Storage, items: &[Item]) -> Result<()> {
+        if !self.enabled || items.is_empty() {
+            return Ok(());
+        }
+
+        let runtime =
+            tokio::runtime::Runtime::new().map_err(|e| PaiError::Fetch(format!("Failed to create runtime: {e}")))?;
+        let sent = runtime.block_on(async {
+            let mut sent = Vec::new();
+            for item in items {
+                for target in outbound_links(item) {
+                    sent.extend(self.send_one(&item.url, &target).await);
+                }
+            }
+            sent
+        });
+
+        for sent in &sent {
+            storage.record_sent_webmention(sent)?;
+        }
+
+        Ok(())
+    }
+
+    async fn send_one(&self, source: &str, target: &str) -> Option<SentWebmention> {
+        let endpoint = match self.discover(target).await {
+            Ok(Some(endpoint)) => endpoint,
+            Ok(None) => {
+                tracing::debug!(target, "No Webmention endpoint");
+                return None;
+            }
+            Err(e) => {
+                tracing::debug!(target, error = %e, "Webmention endpoint discovery failed");
+                return None;
+            }
+        };
+
+        let (status_code, error) = match self
+            .client
+            .post(endpoint.clone())
+            .form(&[("source", source), ("target", target)])
+            .send()
+            .await
+        {
+            Ok(response) if response.status().is_success() => (Some(response.status()), None),
+            Ok(response) => (
+                Some(response.status()),
+                Some(format!("Endpoint responded with {}", response.status())),
+            ),
+            Err(e) => (None, Some(format!("Request failed: {e}"))),
+        };
+
+        match &error {
+            None => tracing::info!(source, target, "Sent Webmention"),
+            Some(e) => tracing::warn!(source, target, error = %e, "Webmention failed"),
+        }
+
+        Some(SentWebmention {
+            source: source.to_string(),
+            target: target.to_string(),
+            endpoint: endpoint.to_string(),
+            sent_at: chrono::Utc::now().to_rfc3339(),
+            status_code: status_code.map(|status| status.as_u16()),
+            error,
+        })
+    }
+
+    /// Fetches `target` and returns its Webmention endpoint, if it advertises one
+    async fn discover(&self, target: &str) -> Result<Option<Url>> {
+        let response = self
+            .client
+            .get(target)
+            .send()
+            .await
+            .map_err(|e| PaiError::Fetch(format!("Failed to fetch {target}: {e}")))?;
+
+        // Relative endpoints resolve against the final URL after redirects.
+        let page = response.url().clone();
+        let link_headers: Vec<String> = response
+            .headers()
+            .get_all(header::LINK)
+            .iter()
+            .filter_map(|value| value.to_str().ok().map(String::from))
+            .collect();
+        let is_html = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("html"));
+        let html = if is_html { read_page(response, MAX_PAGE_BYTES).await? } else { String::new() };
+
+        Ok(discover_endpoint(&page, &link_headers, &html))
+    }
+}
+
+/// Reads a response body as text, failing once it grows past `limit` bytes
+pub async fn read_page(mut response: reqwest::Response, limit: usize) -> Result<String> {
+    let url = response.url().to_string();
+    if response.content_length().is_some_and(|length| length > limit as u64) {
+        return Err(PaiError::Fetch(format!("{url} is larger than {limit} bytes")));
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| PaiError::Fetch(format!("Failed to read {url}: {e}")))?
+    {
+        if body.len() + chunk.len() > limit {
+            return Err(PaiError::Fetch(format!("{url} is larger than {limit} bytes")));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Absolute `http(s)` URLs of the `<a href>` links in `html`, resolved against `base`
+///
+/// Links are returned once each, in document order.
+pub fn html_links(html: &str, base: &str) -> Vec<String> {
+    let Ok(base) = Url::parse(base) else {
+        return Vec::new();
+    };
+
+    let mut links: Vec<String> = Vec::new();
+    for (tag, _, href) in tags(html) {
+        let Some(url) = href.filter(|_| tag == "a").and_then(|href| base.join(&href).ok()) else {
+            continue;
+        };
+        if matches!(url.scheme(), "http" | "https") && !links.contains(&url.to_string()) {
+            links.push(url.to_string());
+        }
+    }
+    links
+}
+
+/// Pages an item links to, excluding the item itself
+fn outbound_links(item: &Item) -> Vec<String> {
+    let Some(html) = item.content_html.as_deref() else {
+        return Vec::new();
+    };
+
+    html_links(html, &item.url)
+        .into_iter()
+        .filter(|link| link.split('#').next() != Some(item.url.as_str()))
+        .collect()
+}
+
+/// Finds the Webmention endpoint advertised by the page at `page`
+///
+/// A `Link` header takes precedence over the first `<link>` or `<a>` element with
+/// `rel="webmention"`. An empty `href` refers to the page itself.
+fn discover_endpoint(page: &Url, link_headers: &[String], html: &str) -> Option<Url> {
+    let from_header = link_headers.iter().find_map(|value| {
+        LINK_VALUE.captures_iter(value).find_map(|link| {
+            let rel = REL_PARAM.captures(&link[2])?;
+            let rel = rel.get(1).or(rel.get(2))?.as_str();
+            is_webmention_rel(rel).then(|| link[1].to_string())
+        })
+    });
+    let href = from_header
+        .or_else(|| tags(html).find_map(|(_, rel, href)| rel.filter(|rel| is_webmention_rel(rel)).and(href)))?;
+
+    page.join(&href).ok()
+}
+
+fn is_webmention_rel(rel: &str) -> bool {
+    rel.split_whitespace()
+        .any(|value| value.eq_ignore_ascii_case("webmention"))
+}
+
+/// `<a>` and `<link>` tags in document order, as `(tag, rel, href)` with entity-decoded `&amp;`
+fn tags(html: &str) -> impl Iterator<Item = (String, Option<String>, Option<String>)> + '_ {
+    TAG.captures_iter(html).map(|tag| {
+        let mut rel = None;
+        let mut href = None;
+        for attribute in ATTRIBUTE.captures_iter(&tag[2]) {
+            let value = attribute
+                .get(2)
+                .or(attribute.get(3))
+                .or(attribute.get(4))
+                .map_or("", |value| value.as_str())
+                .replace("&amp;", "&");
+            match attribute[1].to_ascii_lowercase().as_str() {
+                "rel" => rel = rel.or(Some(value)),
+                _ => href = href.or(Some(value)),
+            }
+        }
+        (tag[1].to_ascii_lowercase(), rel, href)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryStorage;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Serves a page advertising a relative Webmention endpoint, then records the Webmention sent to it
+    fn spawn_target(endpoint_status: u16) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/post", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim_end().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length: ") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+
+                if request_line.starts_with("GET") {
+                    let html =
+                        r#"<html><head><link rel="webmention" href="/webmention?via=pai&amp;x=1"></head></html>"#;
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{html}",
+                        html.len()
+                    )
+                    .unwrap();
+                } else {
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+                    sender
+                        .send(format!(
+                            "{}{}",
+                            request_line.trim_end(),
+                            String::from_utf8(body).unwrap()
+                        ))
+                        .unwrap();
+                    write!(
+                        stream,
+                        "HTTP/1.1 {endpoint_status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    )
+                    .unwrap();
+                }
+            }
+        });
+
+        (url, receiver)
+    }
+
+    fn item(content_html: &str) -> Item {
+        Item::test("post")
+            .with_url("https://blog.example.com/post/")
+            .with_content_html(content_html)
+    }
+
+    #[test]
+    fn extracts_absolute_outbound_links() {
+        let item = item(
+            r##"<p><a href="https://a.example/x?y=1&amp;z=2">a</a> <a href='/about'>about</a>
+            <a href="#top">top</a> <a href="mailto:me@example.com">mail</a> <A HREF=https://a.example/x?y=1&z=2>again</A></p>"##,
+        );
+
+        assert_eq!(
+            outbound_links(&item),
+            ["https://a.example/x?y=1&z=2", "https://blog.example.com/about"]
+        );
+    }
+
+    #[test]
+    fn discovers_endpoints_from_headers_before_html() {
+        let page = Url::parse("https://target.example/post/1").unwrap();
+        let html = r#"<a href="/a-link">x</a><link rel="stylesheet" href="/s.css"><a rel="nofollow webmention" href="/from-html">wm</a>"#;
+
+        let headers = vec![r#"<https://hub.example/>; rel="hub", </from-header>; rel="webmention""#.to_string()];
+        assert_eq!(
+            discover_endpoint(&page, &headers, html).unwrap().as_str(),
+            "https://target.example/from-header"
+        );
+        assert_eq!(
+            discover_endpoint(&page, &[], html).unwrap().as_str(),
+            "https://target.example/from-html"
+        );
+        assert_eq!(
+            discover_endpoint(&page, &[], r#"<link href="" rel=webmention>"#).unwrap(),
+            page
+        );
+        assert!(discover_endpoint(&page, &[], "<p>nothing</p>").is_none());
+    }
+
+    #[test]
+    fn sends_and_records_webmentions() {
+        let (target, requests) = spawn_target(202);
+        let sender = WebmentionSender::new(&WebmentionConfig { send: true });
+        let storage = MemoryStorage::new();
+        let item = item(&format!(r#"<p>Replying to <a href="{target}">this</a></p>"#));
+
+        sender.send(&storage, std::slice::from_ref(&item)).unwrap();
+
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("POST /webmention?via=pai&x=1 "));
+        assert!(request.contains("source=https%3A%2F%2Fblog.example.com%2Fpost%2F"));
+
+        let sent = storage.list_sent_webmentions(10).unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].target, target);
+        assert_eq!(sent[0].status_code, Some(202));
+        assert_eq!(sent[0].error, None);
+    }
+
+    #[tokio::test]
+    async fn discovery_stops_reading_oversized_pages() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/huge", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request_line = String::new();
+            BufReader::new(stream.try_clone().unwrap())
+                .read_line(&mut request_line)
+                .unwrap();
+            // No Content-Length, so only the streamed size can stop the read
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nConnection: close\r\n\r\n"
+            )
+            .unwrap();
+            let chunk = vec![b'x'; 64 * 1024];
+            for _ in 0..=(MAX_PAGE_BYTES / chunk.len()) {
+                if stream.write_all(&chunk).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let sender = WebmentionSender::new(&WebmentionConfig { send: true });
+        let error = sender.discover(&url).await.unwrap_err();
+        assert!(error.to_string().contains("larger than"), "{error}");
+    }
+}
@@ -0,0 +1,437 @@
+use chrono::DateTime;
+use pai_core::{Item, Mention, PaiError, Result};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// Title of the timeline page
+const TITLE: &str = "Personal Activity Index";
+
+const LAYOUT: &str = include_str!("../templates/layout.html");
+const FEED: &str = include_str!("../templates/feed.html");
+const ENTRY: &str = include_str!("../templates/entry.html");
+const ITEM: &str = include_str!("../templates/item.html");
+
+/// HTML templates for the timeline (`/`) and permalink (`/item/{id}`) pages
+///
+/// Each template is plain HTML with `{{ name }}` placeholders. Values are escaped before they are
+/// substituted, except those holding rendered markup (`head`, `content`, `entries`, `entry`,
+/// `categories` and `mentions`). An item's `content` is sanitized first, since it is markup written
+/// by whoever controls the source.
+pub struct Templates {
+    layout: Template,
+    feed: Template,
+    entry: Template,
+    item: Template,
+}
+
+/// Where a rendered page lives, for building links
+pub struct PageContext {
+    /// Base URL without a trailing slash; empty for root-relative links
+    pub base_url: String,
+    /// Raw feed filter query of the timeline, kept on its RSS link
+    pub query: Option<String>,
+    /// Whether pages are written by `pai build-site` rather than served by `pai serve`
+    static_site: bool,
+}
+
+impl PageContext {
+    pub fn new(base_url: &str, query: Option<&str>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            query: query.filter(|query| !query.is_empty()).map(String::from),
+            static_site: false,
+        }
+    }
+
+    /// Context for a static site, where permalinks are directories and there is no Webmention receiver
+    pub fn static_site(base_url: &str) -> Self {
+        Self { static_site: true, ..Self::new(base_url, None) }
+    }
+
+    /// Permalink page of an item
+    pub fn permalink(&self, id: &str) -> String {
+        if self.static_site {
+            format!("{}/item/{}/", self.base_url, item_slug(id))
+        } else {
+            format!("{}/item/{}", self.base_url, utf8_percent_encode(id, NON_ALPHANUMERIC))
+        }
+    }
+
+    /// Absolute URL of a site path such as `/` or `/tag/rust/`
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+
+    fn rss(&self) -> String {
+        match &self.query {
+            Some(query) => format!("{}/rss.xml?{query}", self.base_url),
+            None => format!("{}/rss.xml", self.base_url),
+        }
+    }
+
+    fn head(&self) -> String {
+        let mut head = format!(
+            "<link rel=\"alternate\" type=\"application/rss+xml\" title=\"{TITLE}\" href=\"{}\">",
+            escape(&self.rss())
+        );
+        if self.static_site {
+            head.push_str(&format!(
+                "\n  <link rel=\"alternate\" type=\"application/atom+xml\" title=\"{TITLE}\" href=\"{0}/atom.xml\">\
+                 \n  <link rel=\"alternate\" type=\"application/feed+json\" title=\"{TITLE}\" href=\"{0}/feed.json\">",
+                escape(&self.base_url)
+            ));
+        } else {
+            head.push_str(&format!(
+                "\n  <link rel=\"webmention\" href=\"{}/webmention\">",
+                escape(&self.base_url)
+            ));
+        }
+        head
+    }
+}
+
+/// Filesystem- and URL-safe name for an item's permalink directory
+///
+/// Item IDs are often URLs or `at://` URIs, so the readable part is reduced to `[a-z0-9.-]` and a
+/// digest of the full ID keeps slugs unique.
+pub fn item_slug(id: &str) -> String {
+    let readable: String = id
+        .rsplit('/')
+        .find(|part| !part.is_empty())
+        .unwrap_or(id)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' { c.to_ascii_lowercase() } else { '-' })
+        .take(48)
+        .collect();
+    let digest = Sha256::digest(id.as_bytes());
+    let hex: String = digest.iter().take(4).map(|byte| format!("{byte:02x}")).collect();
+
+    match readable.trim_matches(|c| c == '-' || c == '.') {
+        "" => hex,
+        readable => format!("{readable}-{hex}"),
+    }
+}
+
+impl Templates {
+    /// Loads templates from `dir`, falling back to the built-in template for each missing file
+    ///
+    /// Files are named `layout.html`, `feed.html`, `entry.html` and `item.html`.
+    pub fn load(dir: Option<&Path>) -> Result<Self> {
+        let source = |name: &str, default: &str| -> Result<String> {
+            match dir.map(|dir| dir.join(name)).filter(|path| path.exists()) {
+                Some(path) => std::fs::read_to_string(&path)
+                    .map_err(|e| PaiError::Config(format!("Failed to read template {}: {e}", path.display()))),
+                None => Ok(default.to_string()),
+            }
+        };
+
+        Ok(Self {
+            layout: Template::parse(
+                "layout.html",
+                &source("layout.html", LAYOUT)?,
+                &["title", "head", "content"],
+            )?,
+            feed: Template::parse(
+                "feed.html",
+                &source("feed.html", FEED)?,
+                &["title", "home", "rss", "count", "entries", "pagination"],
+            )?,
+            entry: Template::parse(
+                "entry.html",
+                &source("entry.html", ENTRY)?,
+                &[
+                    "id",
+                    "permalink",
+                    "url",
+                    "title",
+                    "summary",
+                    "content",
+                    "author",
+                    "published",
+                    "published_display",
+                    "source_kind",
+                    "source_id",
+                    "categories",
+                ],
+            )?,
+            item: Template::parse(
+                "item.html",
+                &source("item.html", ITEM)?,
+                &["home", "entry", "mention_count", "mentions"],
+            )?,
+        })
+    }
+
+    /// Renders the h-feed timeline page
+    pub fn render_feed(&self, items: &[Item], page: &PageContext) -> String {
+        self.render_listing(TITLE, items, &Pagination::default(), page)
+    }
+
+    /// Renders one page of an h-feed listing, such as a tag or source page of a static site
+    pub fn render_listing(&self, heading: &str, items: &[Item], pagination: &Pagination, page: &PageContext) -> String {
+        let entries: Vec<String> = items.iter().map(|item| self.render_entry(item, page)).collect();
+        let content = self.feed.render(&[
+            ("title", escape(heading)),
+            ("home", escape(&page.url("/"))),
+            ("rss", escape(&page.rss())),
+            ("count", items.len().to_string()),
+            ("entries", entries.join("\n")),
+            ("pagination", pagination.render()),
+        ]);
+        let title = if heading == TITLE { TITLE.to_string() } else { format!("{heading} · {TITLE}") };
+
+        self.layout
+            .render(&[("title", escape(&title)), ("head", page.head()), ("content", content)])
+    }
+
+    /// Renders the permalink page of an item with its mentions
+    pub fn render_item(&self, item: &Item, mentions: &[Mention], page: &PageContext) -> String {
+        let mentions_html = if mentions.is_empty() {
+            "<p>No mentions yet.</p>".to_string()
+        } else {
+            let list: Vec<String> = mentions
+                .iter()
+                .map(|mention| {
+                    format!(
+                        "<li class=\"h-cite\"><a class=\"u-url\" href=\"{0}\">{0}</a> \
+                         <time class=\"dt-published\" datetime=\"{1}\">{2}</time></li>",
+                        escape(&mention.source),
+                        escape(&mention.verified_at),
+                        escape(&display_date(&mention.verified_at))
+                    )
+                })
+                .collect();
+            format!("<ul>\n{}\n</ul>", list.join("\n"))
+        };
+
+        let content = self.item.render(&[
+            ("home", escape(&page.url("/"))),
+            ("entry", self.render_entry(item, page)),
+            ("mention_count", mentions.len().to_string()),
+            ("mentions", mentions_html),
+        ]);
+
+        self.layout.render(&[
+            ("title", escape(&format!("{} · {TITLE}", entry_title(item)))),
+            ("head", page.head()),
+            ("content", content),
+        ])
+    }
+
+    fn render_entry(&self, item: &Item, page: &PageContext) -> String {
+        let permalink = page.permalink(&item.id);
+        let content = match (&item.content_html, &item.summary) {
+            (Some(html), _) => ammonia::clean(html),
+            (None, Some(summary)) => escape(summary),
+            (None, None) => String::new(),
+        };
+        let categories: Vec<String> = item
+            .tags
+            .iter()
+            .map(|tag| format!("<span class=\"p-category\">#{}</span>", escape(tag)))
+            .collect();
+
+        self.entry.render(&[
+            ("id", escape(&item.id)),
+            ("url", escape(web_url(&item.url).unwrap_or(&permalink))),
+            ("permalink", escape(&permalink)),
+            ("title", escape(entry_title(item))),
+            ("summary", escape(item.summary.as_deref().unwrap_or(""))),
+            ("content", content),
+            ("author", escape(item.author.as_deref().unwrap_or(&item.source_id))),
+            ("published", escape(&item.published_at)),
+            ("published_display", escape(&display_date(&item.published_at))),
+            ("source_kind", escape(&item.source_kind.to_string())),
+            ("source_id", escape(&item.source_id)),
+            ("categories", categories.join(" ")),
+        ])
+    }
+}
+
+/// Links to the neighbouring pages of a paginated listing
+#[derive(Debug, Default)]
+pub struct Pagination {
+    /// URL of the page with newer items
+    pub newer: Option<String>,
+    /// URL of the page with older items
+    pub older: Option<String>,
+}
+
+impl Pagination {
+    fn render(&self) -> String {
+        if self.newer.is_none() && self.older.is_none() {
+            return String::new();
+        }
+
+        let mut links = Vec::new();
+        if let Some(url) = &self.newer {
+            links.push(format!("<a rel=\"prev\" href=\"{}\">← Newer</a>", escape(url)));
+        }
+        if let Some(url) = &self.older {
+            links.push(format!("<a rel=\"next\" href=\"{}\">Older →</a>", escape(url)));
+        }
+        format!("<nav class=\"pagination\">{}</nav>", links.join(" "))
+    }
+}
+
+/// `url` if it is an `http(s)` URL, which is safe to put in an `href`
+fn web_url(url: &str) -> Option<&str> {
+    let scheme = url.split_once(':')?.0;
+    (scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")).then_some(url)
+}
+
+fn entry_title(item: &Item) -> &str {
+    item.title.as_deref().or(item.summary.as_deref()).unwrap_or(&item.url)
+}
+
+/// `2024-01-02` for RFC 3339 dates, the raw value otherwise
+fn display_date(value: &str) -> String {
+    DateTime::parse_from_rfc3339(value)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|_| value.to_string())
+}
+
+/// Escapes text for use in HTML content and quoted attributes
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A template split into literal text and `{{ name }}` placeholders
+struct Template {
+    parts: Vec<Part>,
+}
+
+enum Part {
+    Text(String),
+    Var(String),
+}
+
+impl Template {
+    /// Parses `source`, rejecting placeholders not in `variables`
+    fn parse(name: &str, source: &str, variables: &[&str]) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+
+        while let Some(start) = rest.find("{{") {
+            parts.push(Part::Text(rest[..start].to_string()));
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| PaiError::Config(format!("Unclosed '{{{{' in template {name}")))?;
+            let variable = rest[start + 2..start + end].trim();
+            if !variables.contains(&variable) {
+                return Err(PaiError::Config(format!(
+                    "Unknown variable '{variable}' in template {name}. Expected one of: {}",
+                    variables.join(", ")
+                )));
+            }
+            parts.push(Part::Var(variable.to_string()));
+            rest = &rest[start + end + 2..];
+        }
+        parts.push(Part::Text(rest.to_string()));
+
+        Ok(Self { parts })
+    }
+
+    fn render(&self, values: &[(&str, String)]) -> String {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => output.push_str(text),
+                Part::Var(name) => {
+                    if let Some((_, value)) = values.iter().find(|(key, _)| key == name) {
+                        output.push_str(value);
+                    }
+                }
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pai_core::SourceKind;
+    use tempfile::tempdir;
+
+    fn item() -> Item {
+        Item::test("at://did:plc:abc/app.bsky.feed.post/1")
+            .with_source(SourceKind::Bluesky, "me.bsky.social")
+            .with_author("Me")
+            .with_summary("Hello <world> & friends")
+            .with_url("https://bsky.app/profile/me/post/1")
+            .with_tags(&["rust"])
+            .with_published_at("2024-01-02T03:04:05Z")
+    }
+
+    #[test]
+    fn renders_escaped_h_entries() {
+        let templates = Templates::load(None).unwrap();
+        let page = PageContext::new("https://pai.example.com/", Some("source_kind=bluesky"));
+        let html = templates.render_feed(&[item()], &page);
+
+        assert!(html.contains(r#"<main class="h-feed">"#));
+        assert!(html.contains(r#"<article class="h-entry">"#));
+        assert!(html.contains("Hello &lt;world&gt; &amp; friends"));
+        assert!(html.contains(r#"datetime="2024-01-02T03:04:05Z">2024-01-02</time>"#));
+        assert!(html.contains(
+            r#"href="https://pai.example.com/item/at%3A%2F%2Fdid%3Aplc%3Aabc%2Fapp%2Ebsky%2Efeed%2Epost%2F1""#
+        ));
+        assert!(html.contains(r#"href="https://pai.example.com/rss.xml?source_kind=bluesky""#));
+        assert!(html.contains(r#"<span class="p-category">#rust</span>"#));
+    }
+
+    #[test]
+    fn sanitizes_item_content_and_urls() {
+        let templates = Templates::load(None).unwrap();
+        let mut item = item();
+        item.url = "javascript:alert(1)".to_string();
+        item.content_html = Some(
+            r#"<p onclick="steal()">Hi <script>alert(1)</script><img src="x.png" onerror="alert(2)"><a href="javascript:alert(3)">x</a></p>"#
+                .to_string(),
+        );
+        let html = templates.render_item(&item, &[], &PageContext::new("", None));
+
+        assert!(html.contains("<p>Hi <img src=\"x.png\">"));
+        for payload in ["<script", "onerror", "onclick", "javascript:"] {
+            assert!(!html.contains(payload), "{payload} survived");
+        }
+        assert!(html.contains(r#"href="/item/at%3A%2F%2Fdid%3Aplc%3Aabc%2Fapp%2Ebsky%2Efeed%2Epost%2F1""#));
+    }
+
+    #[test]
+    fn custom_templates_override_defaults_and_are_checked() {
+        let dir = tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("entry.html"),
+            r#"<li class="h-entry"><a class="u-url p-name" href="{{url}}">{{ title }}</a></li>"#,
+        )
+        .unwrap();
+
+        let templates = Templates::load(Some(dir.path())).unwrap();
+        let page = PageContext::new("http://localhost:8080", None);
+        let html = templates.render_item(&item(), &[], &page);
+        assert!(
+            html.contains(r#"<li class="h-entry"><a class="u-url p-name" href="https://bsky.app/profile/me/post/1">"#)
+        );
+        assert!(html.contains("No mentions yet."));
+
+        std::fs::write(dir.path().join("feed.html"), "{{ entries }} {{ nope }}").unwrap();
+        let err = Templates::load(Some(dir.path())).err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("Unknown variable 'nope' in template feed.html"));
+    }
+}
@@ -1,5 +1,15 @@
+mod html;
+mod listen;
+mod metrics;
+mod outbound;
 mod server;
 mod storage;
+mod stream;
+mod webmention;
+mod websub;
 
+pub use html::{escape, item_slug, PageContext, Pagination, Templates};
 pub use server::serve;
-pub use storage::SqliteStorage;
+#[cfg(feature = "postgres")]
+pub use storage::PostgresStorage;
+pub use storage::{redact_url, DatabaseSize, SqliteStorage};
@@ -0,0 +1,147 @@
+use pai_core::{ListenAddr, PaiError};
+use std::fmt;
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use {
+    std::io,
+    std::os::fd::{FromRawFd, OwnedFd, RawFd},
+    std::os::unix::fs::FileTypeExt,
+    std::path::PathBuf,
+    tokio::net::UnixListener,
+};
+
+/// A bound socket, ready to hand to `axum::serve`
+pub(crate) enum Bound {
+    Tcp(TcpListener),
+    /// `path` is set when this process created the socket file and should remove it on shutdown
+    #[cfg(unix)]
+    Unix {
+        listener: UnixListener,
+        path: Option<PathBuf>,
+    },
+}
+
+impl fmt::Display for Bound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bound::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => write!(f, "http://{addr}"),
+                Err(_) => write!(f, "tcp socket"),
+            },
+            #[cfg(unix)]
+            Bound::Unix { listener, .. } => match listener
+                .local_addr()
+                .ok()
+                .and_then(|addr| addr.as_pathname().map(|path| path.display().to_string()))
+            {
+                Some(path) => write!(f, "unix:{path}"),
+                None => write!(f, "unix socket"),
+            },
+        }
+    }
+}
+
+/// Binds `addr`, or takes over the socket systemd passed in with socket activation
+pub(crate) async fn bind(addr: &ListenAddr) -> Result<Bound, PaiError> {
+    #[cfg(unix)]
+    if let Some(bound) = systemd_socket()? {
+        tracing::info!("Using the socket passed by systemd; ignoring listen address {addr}");
+        return Ok(bound);
+    }
+
+    match addr {
+        ListenAddr::Tcp(addr) => Ok(Bound::Tcp(TcpListener::bind(addr).await?)),
+        #[cfg(unix)]
+        ListenAddr::Unix(path) => {
+            // A socket file left by an earlier run that did not shut down cleanly blocks the bind.
+            // It is only removed when nothing answers on it, so a running server keeps its socket.
+            if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+                match std::os::unix::net::UnixStream::connect(path) {
+                    Ok(_) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::AddrInUse,
+                            format!("{} is already in use", path.display()),
+                        )
+                        .into())
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => std::fs::remove_file(path)?,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            let listener = UnixListener::bind(path)?;
+            Ok(Bound::Unix { listener, path: Some(path.clone()) })
+        }
+        #[cfg(not(unix))]
+        ListenAddr::Unix(_) => Err(PaiError::Config(
+            "Unix domain sockets are not supported on this platform".to_string(),
+        )),
+    }
+}
+
+/// The first socket from systemd socket activation, if `LISTEN_PID` names this process
+///
+/// See sd_listen_fds(3). The socket may be TCP or a Unix stream socket, depending on the
+/// `ListenStream=` line in the `.socket` unit.
+#[cfg(unix)]
+fn systemd_socket() -> io::Result<Option<Bound>> {
+    const SD_LISTEN_FDS_START: RawFd = 3;
+
+    let for_us = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .is_some_and(|pid| pid == std::process::id());
+    let count: usize = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(0);
+    if !for_us || count == 0 {
+        return Ok(None);
+    }
+    if count > 1 {
+        tracing::warn!(count, "systemd passed {count} sockets; only the first is used");
+    }
+
+    // SAFETY: with LISTEN_PID naming this process, systemd guarantees descriptors from
+    // SD_LISTEN_FDS_START are open listening sockets owned by us, and nothing else claims them.
+    let fd = unsafe { OwnedFd::from_raw_fd(SD_LISTEN_FDS_START) };
+
+    // local_addr only succeeds for the matching address family
+    let tcp = std::net::TcpListener::from(fd);
+    if tcp.local_addr().is_ok() {
+        tcp.set_nonblocking(true)?;
+        return Ok(Some(Bound::Tcp(TcpListener::from_std(tcp)?)));
+    }
+    let unix = std::os::unix::net::UnixListener::from(OwnedFd::from(tcp));
+    if unix.local_addr().is_err() {
+        return Err(io::Error::other(
+            "the socket passed by systemd is neither TCP nor a Unix stream socket",
+        ));
+    }
+    unix.set_nonblocking(true)?;
+    Ok(Some(Bound::Unix {
+        listener: UnixListener::from_std(unix)?,
+        path: None,
+    }))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn binds_unix_socket_replacing_a_stale_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pai.sock");
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let bound = bind(&ListenAddr::Unix(path.clone())).await.unwrap();
+        assert_eq!(bound.to_string(), format!("unix:{}", path.display()));
+        assert!(matches!(bound, Bound::Unix { path: Some(_), .. }));
+
+        let error = bind(&ListenAddr::Unix(path.clone())).await.err().unwrap();
+        assert!(error.to_string().contains("already in use"), "{error}");
+        assert!(path.exists());
+        drop(bound);
+    }
+}
@@ -0,0 +1,301 @@
+use chrono::DateTime;
+use pai_core::{ItemStats, SourceSyncStatus};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds (in seconds) of the request latency histogram buckets
+const LATENCY_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Content type of the Prometheus text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Default)]
+struct RequestStats {
+    count: u64,
+    sum_seconds: f64,
+    buckets: [u64; LATENCY_BUCKETS.len()],
+}
+
+/// Request counters and latency histograms, keyed by method, route and status
+///
+/// Storage-backed gauges (items, sync status, database size) are not tracked here; they are read
+/// at scrape time and passed to [`Metrics::render`] as a [`StorageSnapshot`].
+#[derive(Default)]
+pub struct Metrics {
+    requests: Mutex<BTreeMap<(String, String, u16), RequestStats>>,
+}
+
+/// Storage-derived values exported alongside the request metrics
+pub struct StorageSnapshot {
+    /// Item counts grouped by source
+    pub items: Vec<ItemStats>,
+    pub sync_status: Vec<SourceSyncStatus>,
+    pub database_size: Option<u64>,
+}
+
+impl Metrics {
+    /// Records one completed request
+    pub fn observe(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let Ok(mut requests) = self.requests.lock() else { return };
+        let stats = requests
+            .entry((method.to_string(), route.to_string(), status))
+            .or_default();
+
+        let seconds = elapsed.as_secs_f64();
+        stats.count += 1;
+        stats.sum_seconds += seconds;
+        for (bucket, bound) in stats.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format
+    pub fn render(&self, snapshot: &StorageSnapshot) -> String {
+        let mut out = String::new();
+        self.render_requests(&mut out);
+        render_storage(&mut out, snapshot);
+        out
+    }
+
+    fn render_requests(&self, out: &mut String) {
+        let Ok(requests) = self.requests.lock() else { return };
+
+        header(
+            out,
+            "pai_http_requests_total",
+            "counter",
+            "HTTP requests by method, route and status.",
+        );
+        for ((method, route, status), stats) in requests.iter() {
+            let labels = labels(&[("method", method), ("route", route), ("status", &status.to_string())]);
+            let _ = writeln!(out, "pai_http_requests_total{{{labels}}} {}", stats.count);
+        }
+
+        header(
+            out,
+            "pai_http_request_duration_seconds",
+            "histogram",
+            "HTTP request latency by method, route and status.",
+        );
+        for ((method, route, status), stats) in requests.iter() {
+            let labels = labels(&[("method", method), ("route", route), ("status", &status.to_string())]);
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(stats.buckets) {
+                let _ = writeln!(
+                    out,
+                    "pai_http_request_duration_seconds_bucket{{{labels},le=\"{bound}\"}} {count}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "pai_http_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {}",
+                stats.count
+            );
+            let _ = writeln!(
+                out,
+                "pai_http_request_duration_seconds_sum{{{labels}}} {}",
+                stats.sum_seconds
+            );
+            let _ = writeln!(
+                out,
+                "pai_http_request_duration_seconds_count{{{labels}}} {}",
+                stats.count
+            );
+        }
+    }
+}
+
+fn render_storage(out: &mut String, snapshot: &StorageSnapshot) {
+    header(out, "pai_items", "gauge", "Stored items by source.");
+    for stat in &snapshot.items {
+        let kind = stat.source_kind.to_string();
+        let labels = labels(&[
+            ("source_kind", &kind),
+            ("source_id", stat.source_id.as_deref().unwrap_or("")),
+        ]);
+        let _ = writeln!(out, "pai_items{{{labels}}} {}", stat.count);
+    }
+
+    let source_labels: Vec<(String, &SourceSyncStatus)> = snapshot
+        .sync_status
+        .iter()
+        .map(|status| {
+            let kind = status.source_kind.to_string();
+            (
+                labels(&[("source_kind", &kind), ("source_id", &status.source_id)]),
+                status,
+            )
+        })
+        .collect();
+
+    header(
+        out,
+        "pai_source_last_sync_timestamp_seconds",
+        "gauge",
+        "Unix time the most recent sync of the source started.",
+    );
+    for (labels, status) in &source_labels {
+        if let Some(ts) = unix_seconds(&status.last_run_at) {
+            let _ = writeln!(out, "pai_source_last_sync_timestamp_seconds{{{labels}}} {ts}");
+        }
+    }
+
+    header(
+        out,
+        "pai_source_last_success_timestamp_seconds",
+        "gauge",
+        "Unix time the most recent successful sync of the source started.",
+    );
+    for (labels, status) in &source_labels {
+        if let Some(ts) = status.last_success_at.as_deref().and_then(unix_seconds) {
+            let _ = writeln!(out, "pai_source_last_success_timestamp_seconds{{{labels}}} {ts}");
+        }
+    }
+
+    header(
+        out,
+        "pai_source_last_sync_duration_seconds",
+        "gauge",
+        "Duration of the most recent sync of the source.",
+    );
+    for (labels, status) in &source_labels {
+        let seconds = status.last_duration_ms as f64 / 1000.0;
+        let _ = writeln!(out, "pai_source_last_sync_duration_seconds{{{labels}}} {seconds}");
+    }
+
+    header(
+        out,
+        "pai_source_last_sync_items",
+        "gauge",
+        "Items fetched by the most recent successful sync of the source.",
+    );
+    for (labels, status) in &source_labels {
+        let _ = writeln!(out, "pai_source_last_sync_items{{{labels}}} {}", status.last_item_count);
+    }
+
+    header(
+        out,
+        "pai_source_last_sync_failed",
+        "gauge",
+        "1 if the most recent sync of the source failed, 0 otherwise.",
+    );
+    for (labels, status) in &source_labels {
+        let failed = u8::from(status.last_error.is_some());
+        let _ = writeln!(out, "pai_source_last_sync_failed{{{labels}}} {failed}");
+    }
+
+    header(
+        out,
+        "pai_source_fetch_errors_total",
+        "counter",
+        "Failed syncs of the source.",
+    );
+    for (labels, status) in &source_labels {
+        let _ = writeln!(out, "pai_source_fetch_errors_total{{{labels}}} {}", status.error_count);
+    }
+
+    if let Some(size) = snapshot.database_size {
+        header(
+            out,
+            "pai_database_size_bytes",
+            "gauge",
+            "Size of the database in bytes.",
+        );
+        let _ = writeln!(out, "pai_database_size_bytes {size}");
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Formats label pairs, escaping values as required by the exposition format
+fn labels(pairs: &[(&str, &str)]) -> String {
+    pairs
+        .iter()
+        .map(|(name, value)| {
+            let escaped = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{name}=\"{escaped}\"")
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn unix_seconds(timestamp: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(timestamp).ok().map(|dt| dt.timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pai_core::SourceKind;
+
+    fn empty_snapshot() -> StorageSnapshot {
+        StorageSnapshot { items: Vec::new(), sync_status: Vec::new(), database_size: None }
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let metrics = Metrics::default();
+        metrics.observe("GET", "/api/feed", 200, Duration::from_millis(3));
+        metrics.observe("GET", "/api/feed", 200, Duration::from_millis(300));
+
+        let text = metrics.render(&empty_snapshot());
+        let labels = r#"method="GET",route="/api/feed",status="200""#;
+        assert!(text.contains(&format!("pai_http_requests_total{{{labels}}} 2")));
+        assert!(text.contains(&format!(
+            "pai_http_request_duration_seconds_bucket{{{labels},le=\"0.005\"}} 1"
+        )));
+        assert!(text.contains(&format!(
+            "pai_http_request_duration_seconds_bucket{{{labels},le=\"0.5\"}} 2"
+        )));
+        assert!(text.contains(&format!(
+            "pai_http_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} 2"
+        )));
+    }
+
+    #[test]
+    fn renders_storage_gauges() {
+        let snapshot = StorageSnapshot {
+            items: vec![ItemStats {
+                source_kind: SourceKind::Bluesky,
+                source_id: Some("test.bsky.social".to_string()),
+                count: 7,
+            }],
+            sync_status: vec![SourceSyncStatus {
+                source_kind: SourceKind::Bluesky,
+                source_id: "test.bsky.social".to_string(),
+                last_run_at: "2024-01-02T00:00:00Z".to_string(),
+                last_duration_ms: 1500,
+                last_item_count: 7,
+                last_success_at: Some("2024-01-01T00:00:00Z".to_string()),
+                last_error: Some("timeout".to_string()),
+                error_count: 2,
+            }],
+            database_size: Some(4096),
+        };
+
+        let text = Metrics::default().render(&snapshot);
+        let labels = r#"source_kind="bluesky",source_id="test.bsky.social""#;
+        assert!(text.contains(&format!("pai_items{{{labels}}} 7")));
+        assert!(text.contains(&format!(
+            "pai_source_last_sync_timestamp_seconds{{{labels}}} 1704153600"
+        )));
+        assert!(text.contains(&format!(
+            "pai_source_last_success_timestamp_seconds{{{labels}}} 1704067200"
+        )));
+        assert!(text.contains(&format!("pai_source_last_sync_duration_seconds{{{labels}}} 1.5")));
+        assert!(text.contains(&format!("pai_source_last_sync_failed{{{labels}}} 1")));
+        assert!(text.contains(&format!("pai_source_fetch_errors_total{{{labels}}} 2")));
+        assert!(text.contains("pai_database_size_bytes 4096"));
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(labels(&[("route", "a\"b\\c")]), r#"route="a\"b\\c""#);
+    }
+}
@@ -0,0 +1,174 @@
+use pai_core::{PaiError, Result};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::{Attempt, Policy};
+use reqwest::Url;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REDIRECTS: usize = 5;
+
+/// HTTP client for URLs that anyone can submit, such as Webmention sources and WebSub callbacks
+///
+/// With `public_only`, hosts are checked after DNS resolution and again on every redirect, so
+/// requests never reach loopback, private or link-local addresses.
+pub fn client(user_agent: &str, public_only: bool) -> reqwest::Client {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(user_agent)
+        .timeout(REQUEST_TIMEOUT)
+        .connect_timeout(CONNECT_TIMEOUT);
+    if public_only {
+        builder = builder
+            .dns_resolver(Arc::new(PublicResolver))
+            .redirect(Policy::custom(follow_public));
+    }
+    builder.build().unwrap_or_default()
+}
+
+/// Checks that `url` names a public host, resolving host names
+///
+/// The client checks again when it connects, so a host that changes its address in between is
+/// still refused.
+pub async fn ensure_public(url: &Url) -> Result<()> {
+    let refused = || PaiError::InvalidArgument(format!("{url} is not a public address"));
+    if !has_public_host(url) {
+        return Err(refused());
+    }
+    let host = url.host_str().ok_or_else(refused)?;
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+        .await
+        .map_err(|e| PaiError::InvalidArgument(format!("Failed to resolve {host}: {e}")))?
+        .collect();
+    if addrs.is_empty() || !addrs.iter().all(|addr| is_public(addr.ip())) {
+        return Err(refused());
+    }
+    Ok(())
+}
+
+/// An error with its causes, which reqwest leaves out of its own message
+pub fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+    message
+}
+
+/// Resolves hosts to their public addresses only, failing when none are left
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{host} does not resolve to a public address").into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Follows a redirect unless it points at a private address literal or the chain is too long
+///
+/// Redirects to host names are checked by [`PublicResolver`] when they are connected to.
+fn follow_public(attempt: Attempt) -> reqwest::redirect::Action {
+    if attempt.previous().len() >= MAX_REDIRECTS {
+        attempt.error("too many redirects")
+    } else if !has_public_host(attempt.url()) {
+        attempt.error("redirected to a non-public address")
+    } else {
+        attempt.follow()
+    }
+}
+
+/// Whether `url` names a host that isn't a loopback, private or otherwise internal IP address
+///
+/// Host names pass here and are checked once resolved.
+pub fn has_public_host(url: &Url) -> bool {
+    match url.host_str() {
+        Some(host) => host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse()
+            .map_or(true, is_public),
+        None => false,
+    }
+}
+
+/// Whether `ip` is reachable on the public internet, as opposed to loopback, private, link-local,
+/// unique-local, shared (CGNAT), multicast or unspecified addresses
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_public_addresses_are_fetched() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1", "::"] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+        assert!(is_public("93.184.216.34".parse().unwrap()));
+        assert!(is_public("2606:4700::1111".parse().unwrap()));
+
+        assert!(!has_public_host(&Url::parse("http://[::1]:8080/reply").unwrap()));
+        assert!(has_public_host(&Url::parse("https://a.example/reply").unwrap()));
+    }
+
+    #[tokio::test]
+    async fn refuses_private_hosts_before_connecting() {
+        for url in [
+            "http://127.0.0.1:9/push",
+            "http://localhost:9/push",
+            "http://169.254.169.254/latest",
+        ] {
+            assert!(ensure_public(&Url::parse(url).unwrap()).await.is_err(), "{url}");
+        }
+    }
+}
@@ -1,69 +1,169 @@
-use crate::storage::SqliteStorage;
-
+use crate::html::{PageContext, Templates};
+use crate::listen::{self, Bound};
+use crate::metrics::{self, Metrics, StorageSnapshot};
+use crate::stream::{self, ChangeFeed, Subscription};
+use crate::webmention::{self, SourceCheck, WebmentionRequest};
+use crate::websub::{self, FeedLinks, HubRequest, Intent, Mode};
 use axum::{
-    extract::{Path, Query, Request, State},
-    http::{header, HeaderValue, Method, StatusCode},
+    extract::{MatchedPath, Path, Query, RawQuery, Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
     middleware::{self, Next},
-    response::{IntoResponse, Response},
-    routing::get,
-    Json, Router,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Response,
+    },
+    routing::{get, post, MethodRouter},
+    Form, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use futures_util::stream::Stream;
+use pai_core::{
+    cache_control, CacheValidators, Config, CorsConfig, ErrorResponse, FeedQuery, FeedResponse, Item, ItemChange,
+    ListFilter, ListenAddr, Mention, MentionsResponse, PaiError, SourceCounts, SourceStat, StatsGroup, StatusResponse,
+    Storage, WebSubSubscription,
 };
-use chrono::DateTime;
-use owo_colors::OwoColorize;
-use pai_core::{Config, CorsConfig, Item, ListFilter, PaiError, SourceKind};
 use rss::{Channel, ChannelBuilder, ItemBuilder};
-use serde::{Deserialize, Serialize};
-use std::{io, net::SocketAddr, path::PathBuf, sync::Arc, time::Instant};
-use tokio::net::TcpListener;
+use std::{collections::VecDeque, convert::Infallible, sync::Arc, time::Instant};
+use tracing::Instrument;
 
-const DEFAULT_LIMIT: usize = 20;
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-/// Launches the HTTP server using the provided config and address.
-pub fn serve(config: Config, db_path: PathBuf, address: &str) -> Result<(), PaiError> {
-    let addr: SocketAddr = address
-        .parse()
-        .map_err(|e| PaiError::Config(format!("Invalid listen address '{address}': {e}")))?;
+/// Launches the HTTP server using the provided config and storage backend.
+///
+/// Listens on `[server] listen`, a TCP address or `unix:` socket path, unless started through systemd
+/// socket activation. `database` is a human-readable description of the backend (e.g. the SQLite path)
+/// reported by `/status`. Storage calls run on Tokio's blocking pool, so synchronous backends never
+/// block the async workers.
+pub fn serve<S>(config: Config, storage: S, database: String) -> Result<(), PaiError>
+where
+    S: Storage + Send + Sync + 'static,
+{
+    let addr = config.server.listen_addr()?;
+
+    storage.verify_schema()?;
 
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .map_err(PaiError::Io)?;
 
-    runtime.block_on(async move { run_server(config, db_path, addr).await })
+    // Keep a handle outside the runtime so the backend is dropped after it shuts down.
+    let storage = Arc::new(storage);
+    runtime.block_on(run_server(config, Arc::clone(&storage), database, addr))
 }
 
-async fn run_server(config: Config, db_path: PathBuf, addr: SocketAddr) -> Result<(), PaiError> {
-    let storage = SqliteStorage::new(&db_path)?;
-    storage.verify_schema()?;
-    drop(storage);
+async fn run_server<S>(config: Config, storage: Arc<S>, database: String, addr: ListenAddr) -> Result<(), PaiError>
+where
+    S: Storage + Send + Sync + 'static,
+{
+    let templates = Templates::load(config.server.templates.as_deref().map(std::path::Path::new))?;
+    let state = AppState {
+        storage,
+        database: Arc::new(database),
+        start_time: Instant::now(),
+        cors_config: Arc::new(config.cors.clone()),
+        cache_max_age: config.server.cache_max_age,
+        metrics: Arc::new(Metrics::default()),
+        changes: Arc::new(ChangeFeed::default()),
+        public_url: config.server.public_url.clone().map(Arc::new),
+        hub: Arc::new(websub::Hub::default()),
+        webmentions: Arc::new(webmention::Verifier::default()),
+        templates: Arc::new(templates),
+    };
+    let changes = Arc::clone(&state.changes);
+    tokio::spawn(watch_changes(state.clone()));
+    let app = build_router(state, &config.cors);
 
-    let state =
-        AppState { db_path: Arc::new(db_path), start_time: Instant::now(), cors_config: Arc::new(config.cors.clone()) };
+    let bound = listen::bind(&addr).await?;
+    tracing::info!(address = %bound, "Listening on {bound}");
 
-    let mut app = Router::new()
-        .route("/api/feed", get(feed_handler))
-        .route("/api/item/:id", get(item_handler))
-        .route("/status", get(status_handler))
-        .route("/rss.xml", get(rss_handler))
+    let shutdown = async move {
+        shutdown_signal().await;
+        changes.close();
+    };
+    let result = match bound {
+        Bound::Tcp(listener) => axum::serve(listener, app).with_graceful_shutdown(shutdown).await,
+        #[cfg(unix)]
+        Bound::Unix { listener, path } => {
+            let result = axum::serve(listener, app).with_graceful_shutdown(shutdown).await;
+            if let Some(path) = path {
+                let _ = std::fs::remove_file(path);
+            }
+            result
+        }
+    };
+    result.map_err(PaiError::Io)
+}
+
+/// Every route the server handles
+///
+/// Tests check this table against the OpenAPI document, so a route can't be added or removed
+/// without updating `pai_core::openapi`.
+fn routes<S>() -> Vec<(&'static str, MethodRouter<AppState<S>>)>
+where
+    S: Storage + Send + Sync + 'static,
+{
+    vec![
+        ("/", get(index_handler::<S>)),
+        ("/item/{id}", get(item_page_handler::<S>)),
+        ("/api/feed", get(feed_handler::<S>)),
+        ("/api/item/{id}", get(item_handler::<S>)),
+        ("/api/item/{id}/mentions", get(mentions_handler::<S>)),
+        ("/api/stream", get(stream_handler::<S>)),
+        ("/status", get(status_handler::<S>)),
+        (websub::TOPIC_PATH, get(rss_handler::<S>)),
+        (websub::HUB_PATH, post(websub_handler::<S>)),
+        (webmention::ENDPOINT_PATH, post(webmention_handler::<S>)),
+        ("/openapi.json", get(openapi_handler)),
+        ("/metrics", get(metrics_handler::<S>)),
+    ]
+}
+
+/// Builds the application router with all routes and middleware
+fn build_router<S>(state: AppState<S>, cors: &CorsConfig) -> Router
+where
+    S: Storage + Send + Sync + 'static,
+{
+    let mut app = routes::<S>()
+        .into_iter()
+        .fold(Router::new(), |router, (path, handlers)| router.route(path, handlers))
+        .route_layer(middleware::from_fn_with_state(state.clone(), track_request::<S>))
         .with_state(state.clone());
 
-    if !config.cors.allowed_origins.is_empty() || config.cors.dev_key.is_some() {
-        app = app.layer(middleware::from_fn_with_state(state.clone(), cors_middleware));
+    if !cors.allowed_origins.is_empty() || cors.dev_key.is_some() {
+        app = app.layer(middleware::from_fn_with_state(state, cors_middleware::<S>));
     }
 
-    let listener = TcpListener::bind(addr).await.map_err(PaiError::Io)?;
-    let local_addr = listener.local_addr().map_err(PaiError::Io)?;
-    println!("{} Listening on http://{}", "Info:".cyan(), local_addr);
+    app
+}
+
+/// Logs and records metrics for every matched route
+///
+/// Each request runs inside a `request` span carrying its method and route, so events emitted by
+/// handlers and storage are attributed to it.
+async fn track_request<S>(State(state): State<AppState<S>>, request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let span = tracing::info_span!("request", method = %method, route = %route);
+    let start = Instant::now();
 
-    axum::serve(listener, app.into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .map_err(|e| io::Error::other(e).into())
+    let response = next.run(request).instrument(span.clone()).await;
+    let status = response.status().as_u16();
+    let elapsed = start.elapsed();
+    span.in_scope(|| tracing::info!(status, latency_ms = elapsed.as_secs_f64() * 1000.0, "Request completed"));
+    state.metrics.observe(&method, &route, status, elapsed);
+
+    response
 }
 
 /// CORS middleware that validates origins and dev keys
-async fn cors_middleware(State(state): State<AppState>, request: Request, next: Next) -> Result<Response, StatusCode> {
+async fn cors_middleware<S>(
+    State(state): State<AppState<S>>, request: Request, next: Next,
+) -> Result<Response, StatusCode> {
     let origin = request
         .headers()
         .get(header::ORIGIN)
@@ -132,120 +232,571 @@ async fn cors_middleware(State(state): State<AppState>, request: Request, next:
     Ok(response)
 }
 
-#[derive(Clone)]
-struct AppState {
-    db_path: Arc<PathBuf>,
+struct AppState<S> {
+    storage: Arc<S>,
+    database: Arc<String>,
     start_time: Instant,
     cors_config: Arc<CorsConfig>,
+    cache_max_age: u64,
+    metrics: Arc<Metrics>,
+    changes: Arc<ChangeFeed>,
+    /// Base URL for feed links, from `[server] public_url`
+    public_url: Option<Arc<String>>,
+    hub: Arc<websub::Hub>,
+    webmentions: Arc<webmention::Verifier>,
+    templates: Arc<Templates>,
 }
 
-impl AppState {
-    fn open_storage(&self) -> Result<SqliteStorage, PaiError> {
-        SqliteStorage::new(self.db_path.as_ref())
+impl<S> Clone for AppState<S> {
+    fn clone(&self) -> Self {
+        Self {
+            storage: Arc::clone(&self.storage),
+            database: Arc::clone(&self.database),
+            start_time: self.start_time,
+            cors_config: Arc::clone(&self.cors_config),
+            cache_max_age: self.cache_max_age,
+            metrics: Arc::clone(&self.metrics),
+            changes: Arc::clone(&self.changes),
+            public_url: self.public_url.clone(),
+            hub: Arc::clone(&self.hub),
+            webmentions: Arc::clone(&self.webmentions),
+            templates: Arc::clone(&self.templates),
+        }
     }
+}
 
-    fn status_snapshot(&self) -> Result<StatusResponse, PaiError> {
-        let storage = self.open_storage()?;
-        let total_items = storage.count_items()?;
-        let sources = storage
-            .get_stats()?
-            .into_iter()
-            .map(|(kind, count)| SourceStat { kind, count })
-            .collect();
+impl<S: Storage + Send + Sync + 'static> AppState<S> {
+    /// Runs a storage operation on the blocking thread pool
+    async fn with_storage<T, F>(&self, f: F) -> Result<T, PaiError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&S) -> Result<T, PaiError> + Send + 'static,
+    {
+        let storage = Arc::clone(&self.storage);
+        tokio::task::spawn_blocking(move || f(&storage))
+            .await
+            .map_err(|e| PaiError::Storage(format!("Storage task failed: {e}")))?
+    }
+
+    /// Publishes the items written since `cursor` and advances it past them
+    ///
+    /// The first call only establishes the starting point. Returns the published items.
+    async fn poll_changes(&self, cursor: &mut Option<u64>) -> Result<Vec<Item>, PaiError> {
+        let Some(mut after) = *cursor else {
+            *cursor = Some(self.with_storage(|storage| storage.latest_change()).await?);
+            return Ok(Vec::new());
+        };
+
+        let mut changed = Vec::new();
+        loop {
+            let page = self
+                .with_storage(move |storage| storage.list_changes(after, stream::PAGE_SIZE))
+                .await?;
+            let caught_up = page.len() < stream::PAGE_SIZE;
+            if let Some(last) = page.last() {
+                after = last.seq;
+                *cursor = Some(after);
+            }
+
+            changed.extend(page.iter().map(|change| change.item.clone()));
+            self.changes.publish(page);
+            if caught_up {
+                return Ok(changed);
+            }
+        }
+    }
+
+    /// Verifies a hub request with the subscriber and applies it if confirmed
+    ///
+    /// Returns whether the subscriber confirmed.
+    async fn confirm_intent(&self, intent: Intent) -> Result<bool, PaiError> {
+        if !self.hub.verify(&intent).await? {
+            return Ok(false);
+        }
+
+        match intent.mode {
+            Mode::Subscribe => {
+                // Requests verified side by side may have filled the caps since this one was accepted
+                let subscriptions = self.with_storage(|storage| storage.list_websub_subscriptions()).await?;
+                intent.check_limits(&subscriptions)?;
+                let subscription = intent.into_subscription();
+                self.with_storage(move |storage| storage.upsert_websub_subscription(&subscription))
+                    .await?;
+            }
+            Mode::Unsubscribe => {
+                self.with_storage(move |storage| storage.remove_websub_subscription(&intent.topic, &intent.callback))
+                    .await?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Sends the current feed to every subscriber whose topic matches one of `changed`
+    ///
+    /// Expired subscriptions are removed. Returns the number of successful deliveries; none
+    /// without `[server] public_url`, since the hub is disabled then.
+    async fn notify_subscribers(&self, changed: &[Item]) -> Result<usize, PaiError> {
+        let Some(public_url) = self.public_url.clone() else {
+            return Ok(0);
+        };
+        let subscriptions = self.with_storage(|storage| storage.list_websub_subscriptions()).await?;
+        let now = Utc::now();
+        let mut delivered = 0;
+
+        for subscription in subscriptions {
+            if !websub::is_active(&subscription, now) {
+                let (topic, callback) = (subscription.topic.clone(), subscription.callback.clone());
+                self.with_storage(move |storage| storage.remove_websub_subscription(&topic, &callback))
+                    .await?;
+                tracing::info!(callback = %subscription.callback, "WebSub subscription expired");
+                continue;
+            }
+
+            match self.notify_subscriber(&subscription, changed, &public_url).await {
+                Ok(true) => delivered += 1,
+                Ok(false) => {}
+                Err(e) => tracing::warn!(callback = %subscription.callback, error = %e, "WebSub delivery failed"),
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    /// Delivers the subscription's topic if any of `changed` belongs to it, returning whether it did
+    async fn notify_subscriber(
+        &self, subscription: &WebSubSubscription, changed: &[Item], public_url: &str,
+    ) -> Result<bool, PaiError> {
+        let filter = websub::topic_filter(&subscription.topic, public_url)?;
+        if !changed.iter().any(|item| filter.matches(item)) {
+            return Ok(false);
+        }
+
+        let items = self.with_storage(move |storage| storage.list_items(&filter)).await?;
+        let body = build_rss_channel(&items, Some(&FeedLinks::for_topic(&subscription.topic)))?.to_string();
+        self.hub.deliver(subscription, body).await?;
+        Ok(true)
+    }
+
+    /// Fetches a Webmention's source and stores the mention if it links to `target`, which points at `item`
+    ///
+    /// A mention whose source is gone or no longer links to the item is removed. Returns whether
+    /// the mention is stored.
+    async fn verify_mention(&self, source: String, target: String, item: Item) -> Result<bool, PaiError> {
+        match self.webmentions.check(&source, &target).await? {
+            SourceCheck::Links => {
+                let mention = Mention { source, target, item_id: item.id, verified_at: Utc::now().to_rfc3339() };
+                self.with_storage(move |storage| storage.upsert_mention(&mention))
+                    .await?;
+                Ok(true)
+            }
+            SourceCheck::Missing => {
+                self.with_storage(move |storage| storage.remove_mention(&source, &target))
+                    .await?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Base URL for links in HTML pages: `[server] public_url`, or empty for root-relative links
+    fn base_url(&self) -> &str {
+        self.public_url.as_deref().map_or("", |url| url.as_str())
+    }
+
+    async fn status_snapshot(&self) -> Result<StatusResponse, PaiError> {
+        let (total_items, stats) = self
+            .with_storage(|storage| Ok((storage.count_items()?, storage.get_stats(StatsGroup::Kind)?)))
+            .await?;
+        let sources = SourceCounts::List(
+            stats
+                .into_iter()
+                .map(|stat| SourceStat { kind: stat.source_kind, count: stat.count })
+                .collect(),
+        );
 
         Ok(StatusResponse {
-            status: "ok",
-            version: VERSION,
-            uptime_seconds: self.start_time.elapsed().as_secs(),
-            database_path: self.db_path.display().to_string(),
+            status: "ok".to_string(),
+            version: VERSION.to_string(),
+            uptime_seconds: Some(self.start_time.elapsed().as_secs()),
+            database_path: Some(self.database.to_string()),
             total_items,
             sources,
         })
     }
 }
 
-#[derive(Debug, Default, Deserialize)]
-struct FeedQuery {
-    source_kind: Option<SourceKind>,
-    source_id: Option<String>,
-    limit: Option<usize>,
-    since: Option<String>,
-    q: Option<String>,
+async fn feed_handler<S: Storage + Send + Sync + 'static>(
+    State(state): State<AppState<S>>, headers: HeaderMap, Query(query): Query<FeedQuery>,
+) -> Result<Response, ApiError> {
+    let filter = query.into_filter()?;
+    let items = state.with_storage(move |storage| storage.list_items(&filter)).await?;
+
+    let validators = CacheValidators::for_items("feed", &items);
+    Ok(cached_response(&headers, &validators, state.cache_max_age, || {
+        Json(FeedResponse { count: items.len(), items })
+    }))
 }
 
-impl FeedQuery {
-    fn into_filter(self) -> Result<ListFilter, PaiError> {
-        let limit = match self.limit {
-            Some(value) => ensure_positive_limit(value)?,
-            None => DEFAULT_LIMIT,
-        };
+async fn item_handler<S: Storage + Send + Sync + 'static>(
+    State(state): State<AppState<S>>, headers: HeaderMap, Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    let lookup = id.clone();
+    let item = state
+        .with_storage(move |storage| storage.get_item(&lookup))
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Item '{id}' not found")))?;
 
-        Ok(ListFilter {
-            source_kind: self.source_kind,
-            source_id: normalize_optional_string(self.source_id),
-            limit: Some(limit),
-            since: normalize_optional_string(self.since),
-            query: normalize_optional_string(self.q),
+    let validators = CacheValidators::for_items("item", std::slice::from_ref(&item));
+    Ok(cached_response(&headers, &validators, state.cache_max_age, || {
+        Json(item)
+    }))
+}
+
+async fn mentions_handler<S: Storage + Send + Sync + 'static>(
+    State(state): State<AppState<S>>, Path(id): Path<String>,
+) -> Result<Json<MentionsResponse>, ApiError> {
+    let lookup = id.clone();
+    let mentions = state
+        .with_storage(move |storage| {
+            Ok(match storage.get_item(&lookup)? {
+                Some(_) => Some(storage.list_mentions(&lookup)?),
+                None => None,
+            })
         })
-    }
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Item '{id}' not found")))?;
+
+    Ok(Json(MentionsResponse { count: mentions.len(), mentions }))
 }
 
-#[derive(Serialize)]
-struct FeedResponse {
-    count: usize,
-    items: Vec<Item>,
+/// Webmention receiver
+///
+/// Accepts mentions whose target is the URL of a stored item, or its permalink page under
+/// `[server] public_url`, answers
+/// `202 Accepted` and verifies the source in the background. At most [`webmention::MAX_PENDING`]
+/// sources are fetched at once; mentions beyond that get `503 Service Unavailable`.
+async fn webmention_handler<S: Storage + Send + Sync + 'static>(
+    State(state): State<AppState<S>>, Form(request): Form<WebmentionRequest>,
+) -> Result<StatusCode, ApiError> {
+    request.validate()?;
+    let target = request.target.clone();
+    let public_url = state.public_url.clone();
+    let item = state
+        .with_storage(move |storage| match storage.get_item_by_url(&target)? {
+            Some(item) => Ok(Some(item)),
+            None => match public_url.and_then(|public_url| webmention::permalink_id(&target, &public_url)) {
+                Some(id) => storage.get_item(&id),
+                None => Ok(None),
+            },
+        })
+        .await?
+        .ok_or_else(|| ApiError::bad_request(format!("Target '{}' is not an item on this server", request.target)))?;
+    let permit = state
+        .webmentions
+        .reserve()
+        .ok_or_else(|| ApiError::unavailable("Too many Webmentions are being verified. Try again later."))?;
+
+    tokio::spawn(async move {
+        let _permit = permit;
+        let WebmentionRequest { source, target } = request;
+        let item_id = item.id.clone();
+        match state.verify_mention(source.clone(), target, item).await {
+            Ok(true) => tracing::info!(%source, %item_id, "Webmention verified"),
+            Ok(false) => tracing::info!(%source, %item_id, "Webmention source does not link to the item"),
+            Err(e) => tracing::warn!(%source, %item_id, error = %e, "Webmention verification failed"),
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED)
 }
 
-#[derive(Serialize)]
-struct StatusResponse {
-    status: &'static str,
-    version: &'static str,
-    uptime_seconds: u64,
-    database_path: String,
-    total_items: usize,
-    sources: Vec<SourceStat>,
+/// HTML timeline marked up as an h-feed, with the same filters as `/api/feed`
+async fn index_handler<S: Storage + Send + Sync + 'static>(
+    State(state): State<AppState<S>>, headers: HeaderMap, RawQuery(raw_query): RawQuery, Query(query): Query<FeedQuery>,
+) -> Result<Response, ApiError> {
+    let filter = query.into_filter()?;
+    let items = state.with_storage(move |storage| storage.list_items(&filter)).await?;
+
+    let page = PageContext::new(state.base_url(), raw_query.as_deref());
+    let validators = CacheValidators::for_items("html", &items);
+    Ok(cached_response(&headers, &validators, state.cache_max_age, || {
+        Html(state.templates.render_feed(&items, &page))
+    }))
 }
 
-#[derive(Serialize)]
-struct SourceStat {
-    kind: String,
-    count: usize,
+/// HTML permalink page of an item with its verified mentions
+async fn item_page_handler<S: Storage + Send + Sync + 'static>(
+    State(state): State<AppState<S>>, Path(id): Path<String>,
+) -> Result<Html<String>, ApiError> {
+    let lookup = id.clone();
+    let (item, mentions) = state
+        .with_storage(move |storage| {
+            Ok(match storage.get_item(&lookup)? {
+                Some(item) => Some((item, storage.list_mentions(&lookup)?)),
+                None => None,
+            })
+        })
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Item '{id}' not found")))?;
+
+    let page = PageContext::new(state.base_url(), None);
+    Ok(Html(state.templates.render_item(&item, &mentions, &page)))
 }
 
-async fn feed_handler(
-    State(state): State<AppState>, Query(query): Query<FeedQuery>,
-) -> Result<Json<FeedResponse>, ApiError> {
+/// Streams new and changed items as Server-Sent Events
+///
+/// Accepts the same filters as `/api/feed`, except `limit`. Each event ID is the change's sequence
+/// number; with a `Last-Event-ID` header, every matching change after it is replayed in write
+/// order first, then live changes follow.
+async fn stream_handler<S: Storage + Send + Sync + 'static>(
+    State(state): State<AppState<S>>, headers: HeaderMap, Query(query): Query<FeedQuery>,
+) -> Result<Response, ApiError> {
     let filter = query.into_filter()?;
-    let storage = state.open_storage()?;
-    let items = pai_core::Storage::list_items(&storage, &filter)?;
+    // Subscribe before reading the backlog so nothing written in between is lost.
+    let subscription = state.changes.subscribe();
+    let position = match header_str(&headers, header::HeaderName::from_static("last-event-id"))
+        .and_then(stream::parse_last_event_id)
+    {
+        Some(after) => Position::Replaying(after),
+        None => Position::Live(0),
+    };
 
-    Ok(Json(FeedResponse { count: items.len(), items }))
+    let replay = Replay { state, subscription, filter, position, pending: VecDeque::new() };
+    let events = futures_util::stream::unfold(replay, |mut replay| async move {
+        let change = replay.next().await?;
+        Some((change_event(&change), replay))
+    });
+
+    Ok(sse_response(events))
 }
 
-async fn item_handler(State(state): State<AppState>, Path(id): Path<String>) -> Result<Json<Item>, ApiError> {
-    let storage = state.open_storage()?;
-    let item = storage
-        .get_item(&id)?
-        .ok_or_else(|| ApiError::not_found(format!("Item '{id}' not found")))?;
+/// Where a `/api/stream` response is reading changes from
+enum Position {
+    /// Paging through storage after this sequence number
+    Replaying(u64),
+    /// Following the change feed, skipping changes up to this sequence number that were replayed
+    Live(u64),
+}
 
-    Ok(Json(item))
+/// Catches a stream subscriber up from storage, then hands over to the live change feed
+struct Replay<S> {
+    state: AppState<S>,
+    subscription: Subscription,
+    filter: ListFilter,
+    position: Position,
+    pending: VecDeque<Arc<ItemChange>>,
+}
+
+impl<S: Storage + Send + Sync + 'static> Replay<S> {
+    /// The next change matching the filter, or `None` once the feed closes
+    async fn next(&mut self) -> Option<Arc<ItemChange>> {
+        loop {
+            if let Some(change) = self.pending.pop_front() {
+                if self.filter.matches(&change.item) {
+                    return Some(change);
+                }
+                continue;
+            }
+
+            match self.position {
+                Position::Replaying(after) => {
+                    let page = match self
+                        .state
+                        .with_storage(move |storage| storage.list_changes(after, stream::PAGE_SIZE))
+                        .await
+                    {
+                        Ok(page) => page,
+                        Err(e) => {
+                            tracing::warn!(error = %e, "Failed to replay stream backlog");
+                            return None;
+                        }
+                    };
+                    let last = page.last().map_or(after, |change| change.seq);
+                    self.position =
+                        if page.len() < stream::PAGE_SIZE { Position::Live(last) } else { Position::Replaying(last) };
+                    self.pending.extend(page.into_iter().map(Arc::new));
+                }
+                Position::Live(replayed) => {
+                    let change = self.subscription.next().await?;
+                    if change.seq > replayed {
+                        self.pending.push_back(change);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn sse_response(events: impl Stream<Item = Result<Event, Infallible>> + Send + 'static) -> Response {
+    let mut response = Sse::new(events).keep_alive(KeepAlive::default()).into_response();
+    // Ask nginx not to buffer the stream.
+    response
+        .headers_mut()
+        .insert("x-accel-buffering", HeaderValue::from_static("no"));
+    response
+}
+
+fn change_event(change: &ItemChange) -> Result<Event, Infallible> {
+    let event = Event::default().event("item").id(change.seq.to_string());
+    Ok(event
+        .json_data(&change.item)
+        .unwrap_or_else(|_| Event::default().comment("unserializable item")))
+}
+
+/// Publishes new and changed items to `/api/stream` subscribers
+///
+/// Syncs usually run in a separate `pai sync` process, so the watcher polls storage for items
+/// written after the last change sequence number it saw.
+async fn watch_changes<S: Storage + Send + Sync + 'static>(state: AppState<S>) {
+    let mut cursor = None;
+    let mut interval = tokio::time::interval(stream::POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        match state.poll_changes(&mut cursor).await {
+            Ok(changed) if changed.is_empty() => {}
+            Ok(changed) => {
+                tracing::debug!(items = changed.len(), "Published changed items");
+                let state = state.clone();
+                tokio::spawn(async move {
+                    match state.notify_subscribers(&changed).await {
+                        Ok(0) => {}
+                        Ok(count) => tracing::info!(subscribers = count, "Notified WebSub subscribers"),
+                        Err(e) => tracing::warn!(error = %e, "Failed to notify WebSub subscribers"),
+                    }
+                });
+            }
+            Err(e) => tracing::warn!(error = %e, "Failed to check for changed items"),
+        }
+    }
 }
 
-async fn status_handler(State(state): State<AppState>) -> Result<Json<StatusResponse>, ApiError> {
-    let snapshot = state.status_snapshot()?;
+async fn status_handler<S: Storage + Send + Sync + 'static>(
+    State(state): State<AppState<S>>,
+) -> Result<Json<StatusResponse>, ApiError> {
+    let snapshot = state.status_snapshot().await?;
     Ok(Json(snapshot))
 }
 
-async fn rss_handler(State(state): State<AppState>, Query(query): Query<FeedQuery>) -> Result<RssResponse, ApiError> {
+async fn metrics_handler<S: Storage + Send + Sync + 'static>(
+    State(state): State<AppState<S>>,
+) -> Result<Response, ApiError> {
+    let snapshot = state
+        .with_storage(|storage| {
+            Ok(StorageSnapshot {
+                items: storage.get_stats(StatsGroup::Source)?,
+                sync_status: storage.get_sync_status()?,
+                database_size: storage.database_size()?,
+            })
+        })
+        .await?;
+
+    let body = state.metrics.render(&snapshot);
+    Ok(([(header::CONTENT_TYPE, metrics::CONTENT_TYPE)], body).into_response())
+}
+
+async fn openapi_handler() -> impl IntoResponse {
+    Json(pai_core::openapi())
+}
+
+/// Serves the RSS feed
+///
+/// With `[server] public_url` set, the WebSub hub is advertised in the channel and a `Link` header.
+async fn rss_handler<S: Storage + Send + Sync + 'static>(
+    State(state): State<AppState<S>>, headers: HeaderMap, RawQuery(raw_query): RawQuery, Query(query): Query<FeedQuery>,
+) -> Result<Response, ApiError> {
     let filter = query.into_filter()?;
-    let storage = state.open_storage()?;
-    let items = pai_core::Storage::list_items(&storage, &filter)?;
+    let items = state.with_storage(move |storage| storage.list_items(&filter)).await?;
+
+    let links = state
+        .public_url
+        .as_deref()
+        .map(|public_url| FeedLinks::new(public_url, raw_query.as_deref()));
+
+    let validators = CacheValidators::for_items("rss", &items);
+    let channel = build_rss_channel(&items, links.as_ref())?;
+    let mut response = cached_response(&headers, &validators, state.cache_max_age, || RssResponse(channel));
+    if let Some(value) = links.as_ref().and_then(FeedLinks::header_value) {
+        response.headers_mut().insert(header::LINK, value);
+    }
+    Ok(response)
+}
+
+/// WebSub hub endpoint
+///
+/// Validates a subscribe or unsubscribe request, answers `202 Accepted` and verifies the intent
+/// with the subscriber's callback in the background. The hub needs `[server] public_url` to know
+/// which topics are its own feeds. Callbacks on loopback, private or link-local addresses, and
+/// subscriptions beyond the per-callback and per-topic caps, are refused with `400 Bad Request`.
+/// At most [`websub::MAX_PENDING`] intents are verified at once; requests beyond that get
+/// `503 Service Unavailable`.
+async fn websub_handler<S: Storage + Send + Sync + 'static>(
+    State(state): State<AppState<S>>, Form(request): Form<HubRequest>,
+) -> Result<StatusCode, ApiError> {
+    let public_url = state
+        .public_url
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("WebSub hub is disabled. Set [server] public_url to enable it."))?;
+    let intent = request.validate(public_url)?;
+    state.hub.check_callback(&intent.callback).await?;
+    let subscriptions = state
+        .with_storage(|storage| storage.list_websub_subscriptions())
+        .await?;
+    intent.check_limits(&subscriptions)?;
+    let permit = state
+        .hub
+        .reserve()
+        .ok_or_else(|| ApiError::unavailable("Too many WebSub requests are being verified. Try again later."))?;
+
+    tokio::spawn(async move {
+        let _permit = permit;
+        let (mode, callback) = (intent.mode, intent.callback.clone());
+        match state.confirm_intent(intent).await {
+            Ok(true) => tracing::info!(?mode, %callback, "WebSub intent verified"),
+            Ok(false) => tracing::info!(?mode, %callback, "WebSub subscriber did not confirm"),
+            Err(e) => tracing::warn!(?mode, %callback, error = %e, "WebSub verification failed"),
+        }
+    });
 
-    let channel = build_rss_channel(&items)?;
-    Ok(RssResponse(channel))
+    Ok(StatusCode::ACCEPTED)
 }
 
-fn build_rss_channel(items: &[Item]) -> Result<Channel, PaiError> {
+/// Renders a cacheable response
+///
+/// Answers with `304 Not Modified` when the request's `If-None-Match`/`If-Modified-Since` headers
+/// match `validators`, otherwise calls `render`. Both paths carry `ETag`, `Last-Modified` and
+/// `Cache-Control`.
+fn cached_response<R: IntoResponse>(
+    headers: &HeaderMap, validators: &CacheValidators, max_age: u64, render: impl FnOnce() -> R,
+) -> Response {
+    let not_modified = validators.is_not_modified(
+        header_str(headers, header::IF_NONE_MATCH),
+        header_str(headers, header::IF_MODIFIED_SINCE),
+    );
+
+    let mut response = if not_modified { StatusCode::NOT_MODIFIED.into_response() } else { render().into_response() };
+
+    let response_headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&validators.etag) {
+        response_headers.insert(header::ETAG, value);
+    }
+    if let Some(value) = validators
+        .last_modified_header()
+        .and_then(|v| HeaderValue::from_str(&v).ok())
+    {
+        response_headers.insert(header::LAST_MODIFIED, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&cache_control(max_age)) {
+        response_headers.insert(header::CACHE_CONTROL, value);
+    }
+
+    response
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn build_rss_channel(items: &[Item], links: Option<&FeedLinks>) -> Result<Channel, PaiError> {
     const TITLE: &str = "Personal Activity Index";
     const LINK: &str = "https://personal-activity-index.local/";
     const DESCRIPTION: &str = "Aggregated feed exported by the Personal Activity Index.";
@@ -289,6 +840,7 @@ fn build_rss_channel(items: &[Item]) -> Result<Channel, PaiError> {
         .link(LINK)
         .description(DESCRIPTION)
         .items(rss_items)
+        .atom_ext(links.map(FeedLinks::atom_extension))
         .build();
 
     Ok(channel)
@@ -331,6 +883,10 @@ impl ApiError {
         Self { status: StatusCode::NOT_FOUND, message: msg.into() }
     }
 
+    fn unavailable(msg: impl Into<String>) -> Self {
+        Self { status: StatusCode::SERVICE_UNAVAILABLE, message: msg.into() }
+    }
+
     fn internal(msg: impl Into<String>) -> Self {
         Self { status: StatusCode::INTERNAL_SERVER_ERROR, message: msg.into() }
     }
@@ -345,118 +901,694 @@ impl From<PaiError> for ApiError {
     }
 }
 
-#[derive(Serialize)]
-struct ErrorBody {
-    error: String,
-}
-
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        (self.status, Json(ErrorBody { error: self.message })).into_response()
+        (self.status, Json(ErrorResponse { error: self.message })).into_response()
     }
 }
 
+/// Resolves on Ctrl-C, or on SIGTERM, which is how systemd and container runtimes stop the server
 async fn shutdown_signal() {
-    let _ = tokio::signal::ctrl_c().await;
-}
-
-fn ensure_positive_limit(limit: usize) -> Result<usize, PaiError> {
-    if limit == 0 {
-        return Err(PaiError::InvalidArgument("Limit must be greater than zero".to_string()));
-    }
-    Ok(limit)
-}
-
-fn normalize_optional_string(value: Option<String>) -> Option<String> {
-    value.and_then(|input| {
-        let trimmed = input.trim();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed.to_string())
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => tracing::warn!(error = %e, "Failed to listen for SIGTERM"),
         }
-    })
+    }
+    let _ = tokio::signal::ctrl_c().await;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::SqliteStorage;
+    use axum::body::Body;
     use chrono::Utc;
-    use pai_core::Storage;
+    use futures_util::StreamExt;
+    use pai_core::{MemoryStorage, SourceKind};
+    use std::collections::HashMap;
     use tempfile::tempdir;
+    use tokio::net::TcpListener;
+    use tower::ServiceExt;
 
     #[test]
-    fn feed_query_defaults() {
-        let filter = FeedQuery::default().into_filter().unwrap();
-        assert_eq!(filter.limit, Some(DEFAULT_LIMIT));
-        assert!(filter.source_kind.is_none());
-        assert!(filter.source_id.is_none());
+    fn api_error_into_response_sets_status() {
+        let resp = ApiError::bad_request("oops").into_response();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
     }
 
-    #[test]
-    fn feed_query_respects_parameters() {
-        let query = FeedQuery {
-            source_kind: Some(SourceKind::Bluesky),
-            source_id: Some(" desertthunder.dev ".to_string()),
-            limit: Some(5),
-            since: Some("2024-01-01T00:00:00Z".to_string()),
-            q: Some(" rust ".to_string()),
-        };
+    fn memory_state() -> AppState<MemoryStorage> {
+        AppState {
+            storage: Arc::new(MemoryStorage::new()),
+            database: Arc::new("memory".to_string()),
+            start_time: Instant::now(),
+            cors_config: Arc::new(pai_core::CorsConfig::default()),
+            cache_max_age: pai_core::DEFAULT_CACHE_MAX_AGE,
+            metrics: Arc::new(Metrics::default()),
+            changes: Arc::new(ChangeFeed::default()),
+            public_url: None,
+            hub: Arc::new(websub::Hub::default()),
+            webmentions: Arc::new(webmention::Verifier::default()),
+            templates: Arc::new(Templates::load(None).unwrap()),
+        }
+    }
 
-        let filter = query.into_filter().unwrap();
-        assert_eq!(filter.limit, Some(5));
-        assert_eq!(filter.source_kind, Some(SourceKind::Bluesky));
-        assert_eq!(filter.source_id.as_deref(), Some("desertthunder.dev"));
-        assert_eq!(filter.query.as_deref(), Some("rust"));
-        assert_eq!(filter.since.as_deref(), Some("2024-01-01T00:00:00Z"));
+    /// A memory-backed state served at `http://pai.test`
+    fn public_state() -> AppState<MemoryStorage> {
+        AppState { public_url: Some(Arc::new("http://pai.test".to_string())), ..memory_state() }
     }
 
-    #[test]
-    fn feed_query_rejects_zero_limit() {
-        let err = FeedQuery { limit: Some(0), ..Default::default() }
-            .into_filter()
-            .unwrap_err();
-        assert!(matches!(err, PaiError::InvalidArgument(_)));
+    fn sample_item(id: &str) -> Item {
+        Item::test(id)
+            .with_source(SourceKind::Substack, "status.substack.com")
+            .with_title("Status")
+            .with_published_at(&Utc::now().to_rfc3339())
     }
 
-    #[test]
-    fn api_error_into_response_sets_status() {
-        let resp = ApiError::bad_request("oops").into_response();
-        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    #[tokio::test]
+    async fn routes_match_the_openapi_document() {
+        let spec = serde_json::to_value(pai_core::openapi()).unwrap();
+        let documented: HashMap<&str, Vec<&str>> = spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .filter_map(|(path, operations)| {
+                let methods: Vec<&str> = operations
+                    .as_object()
+                    .unwrap()
+                    .iter()
+                    .filter(|(_, operation)| operation["tags"] != serde_json::json!(["worker"]))
+                    .map(|(method, _)| method.as_str())
+                    .collect();
+                (!methods.is_empty()).then_some((path.as_str(), methods))
+            })
+            .collect();
+
+        let mut served: Vec<&str> = routes::<MemoryStorage>().into_iter().map(|(path, _)| path).collect();
+        let mut expected: Vec<&str> = documented.keys().copied().collect();
+        served.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(served, expected, "routes and OpenAPI paths differ");
+
+        let app = build_router(memory_state(), &CorsConfig::default());
+        for (path, methods) in documented {
+            for method in methods {
+                let request = Request::builder()
+                    .method(method.to_uppercase().as_str())
+                    .uri(path.replace("{id}", "missing"))
+                    .body(Body::empty())
+                    .unwrap();
+                let response = app.clone().oneshot(request).await.unwrap();
+                assert_ne!(response.status(), StatusCode::METHOD_NOT_ALLOWED, "{method} {path}");
+            }
+        }
     }
 
-    #[test]
-    fn status_snapshot_reports_counts() {
+    #[tokio::test]
+    async fn status_snapshot_reports_counts() {
         let dir = tempdir().unwrap();
         let db_path = dir.path().join("status.db");
         let state = AppState {
-            db_path: Arc::new(db_path),
+            storage: Arc::new(SqliteStorage::new(&db_path).unwrap()),
+            database: Arc::new(db_path.display().to_string()),
             start_time: Instant::now(),
             cors_config: Arc::new(pai_core::CorsConfig::default()),
+            cache_max_age: pai_core::DEFAULT_CACHE_MAX_AGE,
+            metrics: Arc::new(Metrics::default()),
+            changes: Arc::new(ChangeFeed::default()),
+            public_url: None,
+            hub: Arc::new(websub::Hub::default()),
+            webmentions: Arc::new(webmention::Verifier::default()),
+            templates: Arc::new(Templates::load(None).unwrap()),
         };
 
-        let storage = state.open_storage().unwrap();
-        let now = Utc::now().to_rfc3339();
-        let item = Item {
-            id: "status-test".to_string(),
-            source_kind: SourceKind::Substack,
-            source_id: "status.substack.com".to_string(),
-            author: None,
-            title: Some("Status".to_string()),
-            summary: None,
-            url: "https://example.com/status".to_string(),
-            content_html: None,
-            published_at: now.clone(),
-            created_at: now,
-        };
-        storage.insert_or_replace_item(&item).unwrap();
+        state
+            .storage
+            .insert_or_replace_item(&sample_item("status-test"))
+            .unwrap();
 
-        let snapshot = state.status_snapshot().unwrap();
+        let snapshot = state.status_snapshot().await.unwrap();
         assert_eq!(snapshot.status, "ok");
         assert_eq!(snapshot.version, VERSION);
-        assert!(snapshot.uptime_seconds < 5);
+        assert!(snapshot.uptime_seconds.unwrap() < 5);
         assert_eq!(snapshot.total_items, 1);
-        assert_eq!(snapshot.sources.len(), 1);
-        assert_eq!(snapshot.sources[0].kind, "substack");
+        let SourceCounts::List(sources) = snapshot.sources else { panic!("server lists sources") };
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].kind, SourceKind::Substack);
+        assert_eq!(snapshot.database_path, Some(db_path.display().to_string()));
+    }
+
+    #[tokio::test]
+    async fn router_serves_items_from_any_storage() {
+        let state = memory_state();
+        state.storage.insert_or_replace_item(&sample_item("memory-1")).unwrap();
+        let app = build_router(state, &CorsConfig::default());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/item/memory-1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(Request::builder().uri("/api/item/missing").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn feed_supports_conditional_requests() {
+        let state = memory_state();
+        state.storage.insert_or_replace_item(&sample_item("memory-1")).unwrap();
+        let app = build_router(state, &CorsConfig::default());
+
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/api/feed").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "public, max-age=60");
+        assert!(response.headers().contains_key(header::LAST_MODIFIED));
+        let etag = response.headers()[header::ETAG].clone();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/feed")
+                    .header(header::IF_NONE_MATCH, etag.clone())
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/rss.xml")
+                    .header(header::IF_NONE_MATCH, etag)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "RSS and JSON must not share an ETag");
+    }
+
+    #[tokio::test]
+    async fn serves_openapi_document() {
+        let app = build_router(memory_state(), &CorsConfig::default());
+
+        let response = app
+            .oneshot(Request::builder().uri("/openapi.json").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let spec: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(spec["paths"]["/rss.xml"].is_object());
+    }
+
+    #[tokio::test]
+    async fn metrics_report_requests_and_items() {
+        let state = memory_state();
+        state.storage.insert_or_replace_item(&sample_item("memory-1")).unwrap();
+        let app = build_router(state, &CorsConfig::default());
+
+        app.clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/item/memory-1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let response = app
+            .oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains(r#"pai_http_requests_total{method="GET",route="/api/item/{id}",status="200"} 1"#));
+        assert!(text.contains(r#"pai_items{source_kind="substack",source_id="status.substack.com"} 1"#));
+    }
+
+    async fn first_frame(response: Response) -> String {
+        let frame = response.into_body().into_data_stream().next().await.unwrap().unwrap();
+        String::from_utf8(frame.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn stream_replays_every_missed_change_oldest_first() {
+        let state = memory_state();
+        state.storage.insert_or_replace_item(&sample_item("seen")).unwrap();
+        let missed: Vec<Item> = (0..stream::PAGE_SIZE + 50)
+            .map(|i| {
+                let mut item = sample_item(&format!("missed-{i}"));
+                item.published_at = format!("2020-01-01T00:00:{:02}Z", i % 60);
+                item
+            })
+            .collect();
+        state.storage.insert_or_replace_items(&missed).unwrap();
+        let app = build_router(state, &CorsConfig::default());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/stream")
+                    .header("last-event-id", "1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/event-stream");
+
+        let frames: Vec<String> = response
+            .into_body()
+            .into_data_stream()
+            .take(missed.len())
+            .map(|frame| String::from_utf8(frame.unwrap().to_vec()).unwrap())
+            .collect()
+            .await;
+        assert!(frames[0].contains("event: item"));
+        for (i, frame) in frames.iter().enumerate() {
+            assert!(frame.contains(&format!("id: {}\n", i + 2)), "frame {i}: {frame}");
+            assert!(frame.contains(&format!(r#""id":"missed-{i}""#)), "frame {i}: {frame}");
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_publishes_items_written_by_a_sync() {
+        let state = memory_state();
+        let mut cursor = None;
+        let existing = sample_item("existing");
+        state.storage.insert_or_replace_item(&existing).unwrap();
+        state.poll_changes(&mut cursor).await.unwrap();
+        assert_eq!(cursor, Some(1));
+
+        let app = build_router(state.clone(), &CorsConfig::default());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/stream?source_kind=substack")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        state.storage.insert_or_replace_item(&existing).unwrap();
+        assert!(state.poll_changes(&mut cursor).await.unwrap().is_empty());
+
+        let fresh: Vec<Item> = (0..stream::PAGE_SIZE + 1)
+            .map(|i| sample_item(&format!("fresh-{i}")))
+            .collect();
+        state.storage.insert_or_replace_items(&fresh).unwrap();
+        assert_eq!(state.poll_changes(&mut cursor).await.unwrap().len(), fresh.len());
+        assert_eq!(cursor, Some(1 + fresh.len() as u64));
+
+        let frame = first_frame(response).await;
+        assert!(frame.contains(r#""id":"fresh-0""#));
+    }
+
+    /// Runs a WebSub subscriber that echoes challenges if `confirm` is set and records deliveries
+    async fn spawn_subscriber(confirm: bool) -> (String, Arc<std::sync::Mutex<Vec<(HeaderMap, String)>>>) {
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&received);
+        let app = Router::new().route(
+            "/push",
+            get(move |Query(params): Query<HashMap<String, String>>| async move {
+                match params.get("hub.challenge") {
+                    Some(challenge) if confirm => challenge.clone(),
+                    _ => "no thanks".to_string(),
+                }
+            })
+            .post(move |headers: HeaderMap, body: String| async move {
+                recorder.lock().unwrap().push((headers, body));
+                StatusCode::NO_CONTENT
+            }),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{addr}/push"), received)
+    }
+
+    fn hub_request(mode: &str, topic: &str, callback: &str) -> HubRequest {
+        HubRequest {
+            mode: mode.to_string(),
+            topic: topic.to_string(),
+            callback: callback.to_string(),
+            lease_seconds: None,
+            secret: Some("s3cret".to_string()),
+        }
+    }
+
+    /// A public state whose hub may call back the local test subscribers
+    fn local_hub_state() -> AppState<MemoryStorage> {
+        AppState { hub: Arc::new(websub::Hub::allowing_private_addresses()), ..public_state() }
+    }
+
+    #[tokio::test]
+    async fn websub_hub_verifies_subscribers_and_pushes_updates() {
+        let (callback, received) = spawn_subscriber(true).await;
+        let state = local_hub_state();
+        let topic = "http://pai.test/rss.xml?source_kind=substack";
+
+        let intent = hub_request("subscribe", topic, &callback)
+            .validate("http://pai.test")
+            .unwrap();
+        assert!(state.confirm_intent(intent).await.unwrap());
+        assert_eq!(state.storage.list_websub_subscriptions().unwrap().len(), 1);
+
+        let mut unrelated = sample_item("unrelated");
+        unrelated.source_kind = SourceKind::Bluesky;
+        assert_eq!(state.notify_subscribers(&[unrelated]).await.unwrap(), 0);
+
+        let item = sample_item("fresh");
+        state.storage.insert_or_replace_item(&item).unwrap();
+        assert_eq!(state.notify_subscribers(&[item]).await.unwrap(), 1);
+
+        let (headers, body) = received.lock().unwrap().pop().unwrap();
+        assert!(body.contains(">fresh</guid>"));
+        assert_eq!(
+            headers["x-hub-signature"],
+            pai_core::sign_payload("s3cret", body.as_bytes())
+        );
+        assert_eq!(
+            headers[header::LINK],
+            format!(r#"<http://pai.test/websub>; rel="hub", <{topic}>; rel="self""#)
+        );
+
+        let intent = hub_request("unsubscribe", topic, &callback)
+            .validate("http://pai.test")
+            .unwrap();
+        assert!(state.confirm_intent(intent).await.unwrap());
+        assert!(state.storage.list_websub_subscriptions().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn websub_hub_rejects_invalid_and_unconfirmed_requests() {
+        let (callback, _) = spawn_subscriber(false).await;
+        let state = local_hub_state();
+        let app = build_router(state.clone(), &CorsConfig::default());
+
+        let post_form = |body: String| {
+            Request::builder()
+                .method(Method::POST)
+                .uri("/websub")
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let response = app
+            .clone()
+            .oneshot(post_form(format!(
+                "hub.mode=subscribe&hub.topic=http%3A%2F%2Fpai.test%2Fapi%2Ffeed&hub.callback={callback}"
+            )))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .clone()
+            .oneshot(post_form(format!(
+                "hub.mode=subscribe&hub.topic=http%3A%2F%2Fother.test%2Frss.xml&hub.callback={callback}"
+            )))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let disabled = build_router(memory_state(), &CorsConfig::default())
+            .oneshot(post_form(format!(
+                "hub.mode=subscribe&hub.topic=http%3A%2F%2Fpai.test%2Frss.xml&hub.callback={callback}"
+            )))
+            .await
+            .unwrap();
+        assert_eq!(disabled.status(), StatusCode::NOT_FOUND);
+
+        // A hub that only calls public addresses turns the local callback away up front
+        let private = build_router(public_state(), &CorsConfig::default())
+            .oneshot(post_form(format!(
+                "hub.mode=subscribe&hub.topic=http%3A%2F%2Fpai.test%2Frss.xml&hub.callback={callback}"
+            )))
+            .await
+            .unwrap();
+        assert_eq!(private.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .oneshot(post_form(format!(
+                "hub.mode=subscribe&hub.topic=http%3A%2F%2Fpai.test%2Frss.xml&hub.callback={callback}"
+            )))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let intent = hub_request("subscribe", "http://pai.test/rss.xml", &callback)
+            .validate("http://pai.test")
+            .unwrap();
+        assert!(!state.confirm_intent(intent).await.unwrap());
+        assert!(state.storage.list_websub_subscriptions().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rss_advertises_websub_hub() {
+        let app = build_router(public_state(), &CorsConfig::default());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/rss.xml?source_kind=substack")
+                    .header(header::HOST, "pai.test")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers()[header::LINK],
+            r#"<http://pai.test/websub>; rel="hub", <http://pai.test/rss.xml?source_kind=substack>; rel="self""#
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let channel = Channel::read_from(&body[..]).unwrap();
+        let links = channel.atom_ext().unwrap().links();
+        assert_eq!(links[0].rel(), "hub");
+        assert_eq!(links[0].href(), "http://pai.test/websub");
+        assert_eq!(links[1].href(), "http://pai.test/rss.xml?source_kind=substack");
+
+        let response = build_router(memory_state(), &CorsConfig::default())
+            .oneshot(
+                Request::builder()
+                    .uri("/rss.xml")
+                    .header(header::HOST, "pai.test")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(!response.headers().contains_key(header::LINK));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(Channel::read_from(&body[..]).unwrap().atom_ext().is_none());
+    }
+
+    /// Serves `html` at `/reply` (or `410 Gone` without it) for Webmention verification
+    async fn spawn_source(html: Option<&'static str>) -> String {
+        let app = Router::new().route(
+            "/reply",
+            get(move || async move {
+                match html {
+                    Some(html) => (StatusCode::OK, [(header::CONTENT_TYPE, "text/html")], html).into_response(),
+                    None => StatusCode::GONE.into_response(),
+                }
+            }),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}/reply")
+    }
+
+    #[tokio::test]
+    async fn webmentions_are_verified_and_listed_per_item() {
+        let state =
+            AppState { webmentions: Arc::new(webmention::Verifier::allowing_private_addresses()), ..public_state() };
+        let item = sample_item("mentioned");
+        state.storage.insert_or_replace_item(&item).unwrap();
+        let app = build_router(state.clone(), &CorsConfig::default());
+
+        let post_form = |source: &str, target: &str| {
+            Request::builder()
+                .method(Method::POST)
+                .uri("/webmention")
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(format!("source={source}&target={target}")))
+                .unwrap()
+        };
+        let response = app
+            .clone()
+            .oneshot(post_form("https://a.example/reply", "https://example.com/unknown"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = app
+            .clone()
+            .oneshot(post_form("https://a.example/reply", &item.url))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let response = app
+            .clone()
+            .oneshot(post_form("https://a.example/reply", "http://pai.test/item/mentioned"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let response = app
+            .clone()
+            .oneshot(post_form(
+                "https://a.example/reply",
+                "https://other.example/item/mentioned",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let linking = spawn_source(Some(
+            r#"<p>Nice post: <a href="https://example.com/mentioned">link</a></p>"#,
+        ))
+        .await;
+        assert!(state
+            .verify_mention(linking.clone(), item.url.clone(), item.clone())
+            .await
+            .unwrap());
+        let unrelated = spawn_source(Some("<p>No links here</p>")).await;
+        assert!(!state
+            .verify_mention(unrelated, item.url.clone(), item.clone())
+            .await
+            .unwrap());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/item/mentioned/mentions")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mentions: MentionsResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(mentions.count, 1);
+        assert_eq!(mentions.mentions[0].source, linking);
+
+        let gone = spawn_source(None).await;
+        state
+            .storage
+            .upsert_mention(&Mention {
+                source: gone.clone(),
+                target: item.url.clone(),
+                item_id: item.id.clone(),
+                verified_at: Utc::now().to_rfc3339(),
+            })
+            .unwrap();
+        assert!(!state.verify_mention(gone, item.url.clone(), item).await.unwrap());
+        assert_eq!(state.storage.list_mentions("mentioned").unwrap().len(), 1);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/item/missing/mentions")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn html_pages_render_h_feed_and_permalinks() {
+        let state = memory_state();
+        let post = Item {
+            id: "at://did:plc:abc/app.bsky.feed.post/1".to_string(),
+            source_kind: SourceKind::Bluesky,
+            summary: Some("Hello from <Bluesky>".to_string()),
+            ..sample_item("post")
+        };
+        state.storage.insert_or_replace_item(&post).unwrap();
+        state.storage.insert_or_replace_item(&sample_item("article")).unwrap();
+        state
+            .storage
+            .upsert_mention(&Mention {
+                source: "https://a.example/reply".to_string(),
+                target: post.url.clone(),
+                item_id: post.id.clone(),
+                verified_at: "2024-01-02T03:04:05Z".to_string(),
+            })
+            .unwrap();
+        let app = build_router(state, &CorsConfig::default());
+
+        let get_page = |uri: &str| {
+            Request::builder()
+                .uri(uri)
+                .header(header::HOST, "pai.test")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = app.clone().oneshot(get_page("/?source_kind=bluesky")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+        assert!(response.headers().contains_key(header::ETAG));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains(r#"<main class="h-feed">"#));
+        assert_eq!(html.matches(r#"class="h-entry""#).count(), 1);
+        assert!(html.contains("Hello from &lt;Bluesky&gt;"));
+        // Without public_url links stay root-relative rather than trusting the Host header
+        assert!(html.contains(r#"href="/rss.xml?source_kind=bluesky""#));
+        assert!(html.contains(r#"<link rel="webmention" href="/webmention">"#));
+
+        let permalink = "/item/at%3A%2F%2Fdid%3Aplc%3Aabc%2Fapp%2Ebsky%2Efeed%2Epost%2F1";
+        assert!(html.contains(&format!(r#"href="{permalink}""#)));
+        let response = app.clone().oneshot(get_page(permalink)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains(r#"class="h-entry""#));
+        assert!(html.contains(r#"<li class="h-cite"><a class="u-url" href="https://a.example/reply">"#));
+
+        let response = app.oneshot(get_page("/item/missing")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 }
@@ -1,8 +1,29 @@
-use pai_core::{Item, ListFilter, PaiError, Result, SourceKind, Storage};
-use rusqlite::{params, Connection, OptionalExtension};
-use std::path::Path;
+use pai_core::{
+    Item, ItemChange, ItemStats, ListFilter, Mention, PaiError, Result, SentWebmention, SourceKind, SourceSyncStatus,
+    StatsGroup, Storage, SyncRun, WebSubSubscription, WebhookDelivery,
+};
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Row};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard};
+use std::time::Duration;
 
-const SCHEMA_VERSION: i32 = 1;
+const SCHEMA_VERSION: i32 = 4;
+
+/// How long a connection waits on a locked database before giving up
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum number of pooled read-only connections
+const READ_POOL_SIZE: u32 = 8;
+
+const ITEM_COLUMNS: &str =
+    "id, source_kind, source_id, author, title, summary, url, content_html, published_at, created_at, tags";
+
+/// [`ITEM_COLUMNS`] plus the tags the user added, which item writes leave alone
+const SELECT_COLUMNS: &str =
+    "id, source_kind, source_id, author, title, summary, url, content_html, published_at, created_at, tags, user_tags";
 
 const INIT_SQL: &str = r#"
 CREATE TABLE IF NOT EXISTS schema_version (
@@ -19,19 +40,143 @@ CREATE TABLE IF NOT EXISTS items (
     url           TEXT NOT NULL,
     content_html  TEXT,
     published_at  TEXT NOT NULL,
-    created_at    TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    created_at    TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    tags          TEXT NOT NULL DEFAULT '[]',
+    changed       INTEGER NOT NULL DEFAULT 0,
+    user_tags     TEXT NOT NULL DEFAULT '[]'
 );
 
 CREATE INDEX IF NOT EXISTS idx_items_source_date
     ON items (source_kind, source_id, published_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_items_url ON items (url);
+
+CREATE TABLE IF NOT EXISTS item_change_seq (
+    seq INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS sync_status (
+    source_kind      TEXT NOT NULL,
+    source_id        TEXT NOT NULL,
+    last_run_at      TEXT NOT NULL,
+    last_duration_ms INTEGER NOT NULL,
+    last_item_count  INTEGER NOT NULL,
+    last_success_at  TEXT,
+    last_error       TEXT,
+    error_count      INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (source_kind, source_id)
+);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    webhook       TEXT NOT NULL,
+    item_id       TEXT NOT NULL,
+    attempted_at  TEXT NOT NULL,
+    attempts      INTEGER NOT NULL,
+    status_code   INTEGER,
+    error         TEXT
+);
+
+CREATE TABLE IF NOT EXISTS websub_subscriptions (
+    topic        TEXT NOT NULL,
+    callback     TEXT NOT NULL,
+    secret       TEXT,
+    expires_at   TEXT NOT NULL,
+    verified_at  TEXT NOT NULL,
+    PRIMARY KEY (topic, callback)
+);
+
+CREATE TABLE IF NOT EXISTS sent_webmentions (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    source       TEXT NOT NULL,
+    target       TEXT NOT NULL,
+    endpoint     TEXT NOT NULL,
+    sent_at      TEXT NOT NULL,
+    status_code  INTEGER,
+    error        TEXT
+);
+
+CREATE TABLE IF NOT EXISTS mentions (
+    source       TEXT NOT NULL,
+    target       TEXT NOT NULL,
+    item_id      TEXT NOT NULL,
+    verified_at  TEXT NOT NULL,
+    PRIMARY KEY (source, target)
+);
+
+CREATE INDEX IF NOT EXISTS idx_mentions_item ON mentions (item_id);
 "#;
 
+/// Schema changes for databases created by older releases, indexed by the version they produce
+///
+/// Tables added with `CREATE TABLE IF NOT EXISTS` in [`INIT_SQL`] need no entry here.
+const MIGRATIONS: &[(i32, &str)] = &[
+    (2, "ALTER TABLE items ADD COLUMN tags TEXT NOT NULL DEFAULT '[]'"),
+    (
+        3,
+        "ALTER TABLE items ADD COLUMN changed INTEGER NOT NULL DEFAULT 0;
+         UPDATE items SET changed = rowid;",
+    ),
+    (4, "ALTER TABLE items ADD COLUMN user_tags TEXT NOT NULL DEFAULT '[]'"),
+];
+
+/// Change tracking for `items.changed`, set up once the column exists
+///
+/// `item_change_seq` holds the last sequence number handed out. The triggers advance it in the
+/// same statement that writes an item, so numbers are never reused, even after the newest item
+/// is deleted.
+const CHANGE_TRACKING_SQL: &str = r#"
+CREATE INDEX IF NOT EXISTS idx_items_changed ON items (changed);
+
+INSERT INTO item_change_seq (seq)
+    SELECT COALESCE(MAX(changed), 0) FROM items
+    WHERE NOT EXISTS (SELECT 1 FROM item_change_seq);
+
+CREATE TRIGGER IF NOT EXISTS items_insert_change AFTER INSERT ON items
+BEGIN
+    UPDATE item_change_seq SET seq = NEW.changed;
+END;
+
+CREATE TRIGGER IF NOT EXISTS items_update_change AFTER UPDATE OF changed ON items
+BEGIN
+    UPDATE item_change_seq SET seq = NEW.changed;
+END;
+"#;
+
+const SYNC_STATUS_COLUMNS: &str = "source_kind, source_id, last_run_at, last_duration_ms, last_item_count, \
+                                   last_success_at, last_error, error_count";
+
+const WEBHOOK_DELIVERY_COLUMNS: &str = "webhook, item_id, attempted_at, attempts, status_code, error";
+
+const WEBSUB_COLUMNS: &str = "topic, callback, secret, expires_at, verified_at";
+
+const SENT_WEBMENTION_COLUMNS: &str = "source, target, endpoint, sent_at, status_code, error";
+
+const MENTION_COLUMNS: &str = "source, target, item_id, verified_at";
+
 /// SQLite implementation of the Storage trait
 ///
 /// Manages persistent storage of items in a local SQLite database.
 /// Handles schema initialization and migrations automatically on first connection.
+///
+/// File databases run in WAL mode with a single writer connection guarded by a mutex and a pool
+/// of read-only connections, so reads proceed concurrently with each other and with a sync.
+/// In-memory databases use the writer connection for everything.
 pub struct SqliteStorage {
-    conn: Connection,
+    writer: Mutex<Connection>,
+    /// Held for reading while a pooled connection is in use, so a restore can wait for every read
+    /// to finish before it swaps the database file
+    readers: RwLock<Option<Pool<SqliteConnectionManager>>>,
+    /// The database file, or `None` for an in-memory database
+    path: Option<PathBuf>,
+}
+
+/// Space used by a database, from its page counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DatabaseSize {
+    pub bytes: u64,
+    /// Free pages that `VACUUM` would give back
+    pub free_bytes: u64,
 }
 
 impl SqliteStorage {
@@ -40,225 +185,1098 @@ impl SqliteStorage {
     /// Initializes the schema if the database is new or runs migrations if needed.
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path_ref = path.as_ref();
+        let in_memory = path_ref.as_os_str() == ":memory:";
 
-        if let Some(parent) = path_ref.parent() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| PaiError::Storage(format!("Failed to create database directory: {e}")))?;
+        if !in_memory {
+            if let Some(parent) = path_ref.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| PaiError::Storage(format!("Failed to create database directory: {e}")))?;
+            }
         }
 
-        let conn = Connection::open(path).map_err(|e| PaiError::Storage(format!("Failed to open database: {e}")))?;
+        let conn =
+            Connection::open(path_ref).map_err(|e| PaiError::Storage(format!("Failed to open database: {e}")))?;
+        configure_writer(&conn, in_memory)?;
+        init_schema(&conn)?;
+
+        let readers = if in_memory { None } else { Some(open_read_pool(path_ref)?) };
+        let path = (!in_memory).then(|| path_ref.to_path_buf());
 
-        let mut storage = Self { conn };
-        storage.init_schema()?;
-        Ok(storage)
+        Ok(Self { writer: Mutex::new(conn), readers: RwLock::new(readers), path })
     }
 
-    /// Initializes the database schema
-    ///
-    /// Creates tables and indexes if they don't exist, and sets up version tracking.
-    fn init_schema(&mut self) -> Result<()> {
-        self.conn
-            .execute_batch(INIT_SQL)
-            .map_err(|e| PaiError::Storage(format!("Failed to initialize schema: {e}")))?;
-
-        let version: Option<i32> = self
-            .conn
-            .query_row("SELECT version FROM schema_version LIMIT 1", [], |row| row.get(0))
-            .optional()
-            .map_err(|e| PaiError::Storage(format!("Failed to check schema version: {e}")))?;
+    fn writer(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.writer
+            .lock()
+            .map_err(|e| PaiError::Storage(format!("Database connection lock poisoned: {e}")))
+    }
 
-        match version {
-            None => {
-                self.conn
-                    .execute(
-                        "INSERT INTO schema_version (version) VALUES (?1)",
-                        params![SCHEMA_VERSION],
-                    )
-                    .map_err(|e| PaiError::Storage(format!("Failed to set schema version: {e}")))?;
+    fn readers(&self) -> Result<RwLockReadGuard<'_, Option<Pool<SqliteConnectionManager>>>> {
+        self.readers
+            .read()
+            .map_err(|e| PaiError::Storage(format!("Read connection pool lock poisoned: {e}")))
+    }
+
+    /// Runs `f` on a pooled read-only connection, or on the writer for in-memory databases
+    fn read<T>(&self, f: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+        let readers = self.readers()?;
+        match &*readers {
+            Some(pool) => {
+                let conn: PooledConnection<SqliteConnectionManager> = pool
+                    .get()
+                    .map_err(|e| PaiError::Storage(format!("Failed to acquire read connection: {e}")))?;
+                f(&conn)
             }
-            Some(v) if v < SCHEMA_VERSION => {
-                return Err(PaiError::Storage(format!(
-                    "Database migration needed: current={v}, required={SCHEMA_VERSION}"
-                )));
+            None => {
+                drop(readers);
+                f(&*self.writer()?)
             }
-            _ => {}
         }
+    }
 
-        Ok(())
+    /// Copies the database to a new file at `dest` with SQLite's online backup API
+    ///
+    /// The copy is taken in one step from a read connection, so it is a consistent snapshot and,
+    /// in WAL mode, doesn't block a running sync or server. It is written to a temporary file next
+    /// to `dest` and linked into place once complete, so a failed backup leaves no partial file and
+    /// a file created at `dest` in the meantime is never replaced.
+    pub fn backup(&self, dest: &Path) -> Result<()> {
+        let exists = || PaiError::Storage(format!("{} already exists", dest.display()));
+        if dest.exists() {
+            return Err(exists());
+        }
+        let name = dest
+            .file_name()
+            .ok_or_else(|| PaiError::Storage(format!("{} is not a file path", dest.display())))?;
+        let temp = dest.with_file_name(format!(".{}.tmp-{}", name.to_string_lossy(), std::process::id()));
+
+        let copied = Connection::open(&temp)
+            .map_err(|e| PaiError::Storage(format!("Failed to create backup file: {e}")))
+            .and_then(|mut target| {
+                self.read(|conn| copy_database(conn, &mut target))
+                    .map_err(|e| PaiError::Storage(format!("Failed to back up database: {e}")))
+            })
+            .and_then(|()| {
+                std::fs::hard_link(&temp, dest).map_err(|e| match e.kind() {
+                    std::io::ErrorKind::AlreadyExists => exists(),
+                    _ => PaiError::Storage(format!("Failed to move backup into place: {e}")),
+                })
+            });
+        let _ = std::fs::remove_file(&temp);
+        copied
     }
 
-    /// Gets basic statistics about stored items
-    pub fn get_stats(&self) -> Result<Vec<(String, usize)>> {
-        let mut stmt = self
-            .conn
-            .prepare("SELECT source_kind, COUNT(*) FROM items GROUP BY source_kind ORDER BY source_kind")
-            .map_err(|e| PaiError::Storage(format!("Failed to prepare stats query: {e}")))?;
+    /// Replaces the contents of the database with the backup at `source`, returning its schema version
+    ///
+    /// Backups from newer releases are refused; older ones are migrated once restored. The backup
+    /// is copied and migrated into a new database first, which then replaces the current one while
+    /// writes are locked out and no read is in progress, so readers never see a half-restored
+    /// database and backups with a different page size restore too.
+    pub fn restore(&self, source: &Path) -> Result<i32> {
+        let backup = Connection::open_with_flags(source, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| PaiError::Storage(format!("Failed to open {}: {e}", source.display())))?;
+        let problems = integrity_check(&backup, "quick_check")?;
+        if !problems.is_empty() {
+            return Err(PaiError::Storage(format!(
+                "{} is damaged: {}",
+                source.display(),
+                problems.join("; ")
+            )));
+        }
+        let version: i32 = backup
+            .query_row("SELECT version FROM schema_version LIMIT 1", [], |row| row.get(0))
+            .map_err(|_| PaiError::Storage(format!("{} is not a pai database", source.display())))?;
+        if version > SCHEMA_VERSION {
+            return Err(PaiError::Storage(format!(
+                "{} has schema version {version}, but this pai supports up to {SCHEMA_VERSION}; upgrade pai to restore it",
+                source.display()
+            )));
+        }
+
+        let mut writer = self.writer()?;
+        let Some(path) = &self.path else {
+            let mut conn =
+                Connection::open_in_memory().map_err(|e| PaiError::Storage(format!("Failed to open database: {e}")))?;
+            copy_database(&backup, &mut conn)?;
+            configure_writer(&conn, true)?;
+            init_schema(&conn)?;
+            *writer = conn;
+            return Ok(version);
+        };
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temp = path.with_file_name(format!(".{name}.restore-{}", std::process::id()));
+        let restored = Connection::open(&temp)
+            .map_err(|e| PaiError::Storage(format!("Failed to create {}: {e}", temp.display())))
+            .and_then(|mut conn| {
+                copy_database(&backup, &mut conn)?;
+                init_schema(&conn)
+            });
+        if let Err(e) = restored {
+            let _ = std::fs::remove_file(&temp);
+            return Err(e);
+        }
 
-        let stats = stmt
-            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, usize>(1)?)))
-            .map_err(|e| PaiError::Storage(format!("Failed to query stats: {e}")))?
-            .collect::<std::result::Result<Vec<_>, _>>()
-            .map_err(|e| PaiError::Storage(format!("Failed to collect stats: {e}")))?;
+        let mut readers = self
+            .readers
+            .write()
+            .map_err(|e| PaiError::Storage(format!("Read connection pool lock poisoned: {e}")))?;
+        // Close every connection so the WAL is checkpointed and removed before the file is replaced
+        *readers = None;
+        let closed = std::mem::replace(
+            &mut *writer,
+            Connection::open_in_memory().map_err(|e| PaiError::Storage(format!("Failed to open database: {e}")))?,
+        );
+        let swapped = closed
+            .close()
+            .map_err(|(_, e)| PaiError::Storage(format!("Failed to close database: {e}")))
+            .and_then(|()| {
+                for suffix in ["-wal", "-shm"] {
+                    let mut sidecar = path.clone().into_os_string();
+                    sidecar.push(suffix);
+                    match std::fs::remove_file(&sidecar) {
+                        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                            return Err(PaiError::Storage(format!(
+                                "Failed to remove {}: {e}",
+                                sidecar.to_string_lossy()
+                            )))
+                        }
+                        _ => {}
+                    }
+                }
+                std::fs::rename(&temp, path)
+                    .map_err(|e| PaiError::Storage(format!("Failed to replace the database: {e}")))
+            });
+        if swapped.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
 
-        Ok(stats)
+        // Reopen whichever file is in place, so the storage stays usable when the swap failed
+        let conn = Connection::open(path).map_err(|e| PaiError::Storage(format!("Failed to open database: {e}")))?;
+        configure_writer(&conn, false)?;
+        *writer = conn;
+        *readers = Some(open_read_pool(path)?);
+        swapped.map(|()| version)
     }
 
-    /// Gets total item count
-    pub fn count_items(&self) -> Result<usize> {
-        self.conn
-            .query_row("SELECT COUNT(*) FROM items", [], |row| row.get(0))
-            .map_err(|e| PaiError::Storage(format!("Failed to count items: {e}")))
+    /// Problems found by `PRAGMA integrity_check`; empty when the database is sound
+    pub fn integrity_check(&self) -> Result<Vec<String>> {
+        self.read(|conn| integrity_check(conn, "integrity_check"))
     }
 
-    /// Verifies schema integrity
-    ///
-    /// Checks that required tables and indexes exist.
-    pub fn verify_schema(&self) -> Result<()> {
-        let tables = vec!["schema_version", "items"];
-        for table in tables {
-            let exists: bool = self
-                .conn
-                .query_row(
-                    "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name=?1",
-                    params![table],
-                    |row| {
-                        let count: i32 = row.get(0)?;
-                        Ok(count > 0)
-                    },
-                )
-                .map_err(|e| PaiError::Storage(format!("Failed to verify table {table}: {e}")))?;
-
-            if !exists {
-                return Err(PaiError::Storage(format!("Missing table: {table}")));
+    pub fn size(&self) -> Result<DatabaseSize> {
+        self.read(|conn| {
+            let pragma = |name: &str| -> Result<u64> {
+                conn.query_row(&format!("PRAGMA {name}"), [], |row| row.get(0))
+                    .map_err(|e| PaiError::Storage(format!("Failed to read {name}: {e}")))
+            };
+            let page_size = pragma("page_size")?;
+            Ok(DatabaseSize {
+                bytes: pragma("page_count")? * page_size,
+                free_bytes: pragma("freelist_count")? * page_size,
+            })
+        })
+    }
+
+    /// Rebuilds the database file without its free pages
+    pub fn vacuum(&self) -> Result<()> {
+        self.writer()?
+            .execute_batch("VACUUM")
+            .map_err(|e| PaiError::Storage(format!("Failed to vacuum database: {e}")))
+    }
+
+    /// Refreshes query planner statistics and merges the segments of any FTS5 index,
+    /// returning the FTS tables it optimized
+    pub fn optimize(&self) -> Result<Vec<String>> {
+        let conn = self.writer()?;
+        let fts_tables = conn
+            .prepare(
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND sql LIKE 'CREATE VIRTUAL TABLE%USING fts5%'",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()
+            })
+            .map_err(|e| PaiError::Storage(format!("Failed to list FTS tables: {e}")))?;
+        for table in &fts_tables {
+            conn.execute(&format!("INSERT INTO \"{table}\"(\"{table}\") VALUES ('optimize')"), [])
+                .map_err(|e| PaiError::Storage(format!("Failed to optimize {table}: {e}")))?;
+        }
+        conn.execute_batch("ANALYZE; PRAGMA optimize;")
+            .map_err(|e| PaiError::Storage(format!("Failed to analyze database: {e}")))?;
+        Ok(fts_tables)
+    }
+}
+
+/// Copies every page of `source` over `target` in a single backup step
+///
+/// A step that finds either database locked is retried until [`BUSY_TIMEOUT`] runs out.
+fn copy_database(source: &Connection, target: &mut Connection) -> Result<()> {
+    let backup_error = |e: rusqlite::Error| PaiError::Storage(format!("Backup failed: {e}"));
+    let backup = Backup::new(source, target).map_err(backup_error)?;
+    let started = std::time::Instant::now();
+    loop {
+        match backup.step(-1).map_err(backup_error)? {
+            StepResult::Done => return Ok(()),
+            _ if started.elapsed() > BUSY_TIMEOUT => {
+                return Err(PaiError::Storage(
+                    "Backup failed: the database stayed locked".to_string(),
+                ))
             }
+            _ => std::thread::sleep(Duration::from_millis(50)),
         }
+    }
+}
+
+/// Rows of `PRAGMA integrity_check` or `quick_check` other than the lone "ok" of a sound database
+fn integrity_check(conn: &Connection, pragma: &str) -> Result<Vec<String>> {
+    let rows = conn
+        .prepare(&format!("PRAGMA {pragma}"))
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        })
+        .map_err(|e| PaiError::Storage(format!("Failed to check database integrity: {e}")))?;
+    Ok(rows.into_iter().filter(|row| row != "ok").collect())
+}
+
+/// Applies connection settings for the writer: WAL journaling and a busy timeout
+fn configure_writer(conn: &Connection, in_memory: bool) -> Result<()> {
+    conn.busy_timeout(BUSY_TIMEOUT)
+        .map_err(|e| PaiError::Storage(format!("Failed to set busy timeout: {e}")))?;
 
-        Ok(())
+    if !in_memory {
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")
+            .map_err(|e| PaiError::Storage(format!("Failed to enable WAL mode: {e}")))?;
     }
 
-    /// Fetches a single item by ID, if it exists
-    pub fn get_item(&self, id: &str) -> Result<Option<Item>> {
-        let mut stmt = self
-            .conn
-            .prepare(
-                "SELECT id, source_kind, source_id, author, title, summary, url, content_html, published_at, created_at
-             FROM items WHERE id = ?1 LIMIT 1",
+    Ok(())
+}
+
+/// Builds the pool of read-only connections used for queries
+fn open_read_pool(path: &Path) -> Result<Pool<SqliteConnectionManager>> {
+    let manager = SqliteConnectionManager::file(path)
+        .with_flags(OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI)
+        .with_init(|conn| conn.busy_timeout(BUSY_TIMEOUT));
+
+    Pool::builder()
+        .max_size(READ_POOL_SIZE)
+        .min_idle(Some(1))
+        .build(manager)
+        .map_err(|e| PaiError::Storage(format!("Failed to create read connection pool: {e}")))
+}
+
+/// Initializes the database schema
+///
+/// Creates tables and indexes if they don't exist, and sets up version tracking.
+fn init_schema(conn: &Connection) -> Result<()> {
+    conn.execute_batch(INIT_SQL)
+        .map_err(|e| PaiError::Storage(format!("Failed to initialize schema: {e}")))?;
+
+    let version: Option<i32> = conn
+        .query_row("SELECT version FROM schema_version LIMIT 1", [], |row| row.get(0))
+        .optional()
+        .map_err(|e| PaiError::Storage(format!("Failed to check schema version: {e}")))?;
+
+    match version {
+        None => {
+            conn.execute(
+                "INSERT INTO schema_version (version) VALUES (?1)",
+                params![SCHEMA_VERSION],
             )
-            .map_err(|e| PaiError::Storage(format!("Failed to prepare get_item query: {e}")))?;
+            .map_err(|e| PaiError::Storage(format!("Failed to set schema version: {e}")))?;
+        }
+        Some(v) if v < SCHEMA_VERSION => migrate(conn, v)?,
+        _ => {}
+    }
+
+    conn.execute_batch(CHANGE_TRACKING_SQL)
+        .map_err(|e| PaiError::Storage(format!("Failed to set up change tracking: {e}")))
+}
+
+/// Applies each migration newer than `current` in its own transaction
+fn migrate(conn: &Connection, current: i32) -> Result<()> {
+    for &(version, sql) in MIGRATIONS.iter().filter(|(version, _)| *version > current) {
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(|e| PaiError::Storage(format!("Failed to begin migration: {e}")))?;
+        tx.execute_batch(sql)
+            .map_err(|e| PaiError::Storage(format!("Failed to apply migration {version}: {e}")))?;
+        tx.execute("UPDATE schema_version SET version = ?1", params![version])
+            .map_err(|e| PaiError::Storage(format!("Failed to set schema version: {e}")))?;
+        tx.commit()
+            .map_err(|e| PaiError::Storage(format!("Failed to commit migration {version}: {e}")))?;
+    }
+
+    Ok(())
+}
+
+/// Maps a row selected with [`SELECT_COLUMNS`] to an [`Item`]
+fn item_from_row(row: &Row<'_>) -> rusqlite::Result<Item> {
+    let source_kind_str: String = row.get(1)?;
+    let source_kind = source_kind_str
+        .parse::<SourceKind>()
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e)))?;
+
+    let tags = tags_from_row(row, 10)?;
+    let user_tags = tags_from_row(row, 11)?;
+
+    Ok(Item {
+        id: row.get(0)?,
+        source_kind,
+        source_id: row.get(2)?,
+        author: row.get(3)?,
+        title: row.get(4)?,
+        summary: row.get(5)?,
+        url: row.get(6)?,
+        content_html: row.get(7)?,
+        tags,
+        published_at: row.get(8)?,
+        created_at: row.get(9)?,
+    }
+    .with_user_tags(&user_tags))
+}
+
+fn tags_from_row(row: &Row<'_>, index: usize) -> rusqlite::Result<Vec<String>> {
+    let json: String = row.get(index)?;
+    serde_json::from_str(&json)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+/// Writes an item, taking the next change sequence number unless its content is unchanged
+fn insert_or_replace_item(conn: &Connection, item: &Item) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO items ({ITEM_COLUMNS}, changed)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, (SELECT seq + 1 FROM item_change_seq))
+             ON CONFLICT (id) DO UPDATE SET
+                 source_kind = excluded.source_kind, source_id = excluded.source_id, author = excluded.author,
+                 title = excluded.title, summary = excluded.summary, url = excluded.url,
+                 content_html = excluded.content_html, published_at = excluded.published_at,
+                 created_at = excluded.created_at, tags = excluded.tags, changed = excluded.changed
+             WHERE (source_kind, source_id, author, title, summary, url, content_html, published_at, tags)
+                 IS NOT (excluded.source_kind, excluded.source_id, excluded.author, excluded.title,
+                         excluded.summary, excluded.url, excluded.content_html, excluded.published_at,
+                         excluded.tags)"
+        ),
+        params![
+            item.id,
+            item.source_kind.to_string(),
+            item.source_id,
+            item.author,
+            item.title,
+            item.summary,
+            item.url,
+            item.content_html,
+            item.published_at,
+            item.created_at,
+            serde_json::to_string(&item.tags).unwrap_or_else(|_| "[]".to_string()),
+        ],
+    )
+    .map_err(|e| PaiError::Storage(format!("Failed to insert item: {e}")))?;
+
+    Ok(())
+}
+
+fn list_items(conn: &Connection, filter: &ListFilter) -> Result<Vec<Item>> {
+    let mut sql = format!("SELECT {SELECT_COLUMNS} FROM items WHERE 1=1");
+    let mut conditions = Vec::new();
+
+    if let Some(source_kind) = filter.source_kind {
+        sql.push_str(" AND source_kind = ?");
+        conditions.push(source_kind.to_string());
+    }
+
+    if let Some(ref source_id) = filter.source_id {
+        sql.push_str(" AND source_id = ?");
+        conditions.push(source_id.clone());
+    }
+
+    if let Some(ref since) = filter.since {
+        sql.push_str(" AND published_at >= ?");
+        conditions.push(since.clone());
+    }
+
+    if let Some(ref query) = filter.query {
+        sql.push_str(" AND (title LIKE ? OR summary LIKE ?)");
+        let pattern = format!("%{query}%");
+        conditions.push(pattern.clone());
+        conditions.push(pattern);
+    }
 
-        stmt.query_row([id], |row| {
-            let source_kind_str: String = row.get(1)?;
+    sql.push_str(" ORDER BY published_at DESC");
+
+    if let Some(limit) = filter.limit {
+        sql.push_str(&format!(" LIMIT {limit}"));
+    }
+
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| PaiError::Storage(format!("Failed to prepare query: {e}")))?;
+
+    let params_refs: Vec<&dyn rusqlite::ToSql> = conditions.iter().map(|s| s as &dyn rusqlite::ToSql).collect();
+
+    let items = stmt
+        .query_map(params_refs.as_slice(), item_from_row)
+        .map_err(|e| PaiError::Storage(format!("Failed to query items: {e}")))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| PaiError::Storage(format!("Failed to collect items: {e}")))?;
+
+    Ok(items)
+}
+
+fn latest_change(conn: &Connection) -> Result<u64> {
+    conn.query_row("SELECT COALESCE(MAX(seq), 0) FROM item_change_seq", [], |row| {
+        row.get(0)
+    })
+    .map_err(|e| PaiError::Storage(format!("Failed to read latest change: {e}")))
+}
+
+fn list_changes(conn: &Connection, after: u64, limit: usize) -> Result<Vec<ItemChange>> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {SELECT_COLUMNS}, changed FROM items WHERE changed > ?1 ORDER BY changed LIMIT {limit}"
+        ))
+        .map_err(|e| PaiError::Storage(format!("Failed to prepare query: {e}")))?;
+
+    let changes = stmt
+        .query_map(params![after], |row| {
+            Ok(ItemChange { seq: row.get(12)?, item: item_from_row(row)? })
+        })
+        .map_err(|e| PaiError::Storage(format!("Failed to query changes: {e}")))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| PaiError::Storage(format!("Failed to collect changes: {e}")))?;
+
+    Ok(changes)
+}
+
+fn get_item(conn: &Connection, id: &str) -> Result<Option<Item>> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT {SELECT_COLUMNS} FROM items WHERE id = ?1 LIMIT 1"))
+        .map_err(|e| PaiError::Storage(format!("Failed to prepare get_item query: {e}")))?;
+
+    stmt.query_row([id], item_from_row)
+        .optional()
+        .map_err(|e| PaiError::Storage(format!("Failed to fetch item by id: {e}")))
+}
+
+fn get_item_by_url(conn: &Connection, url: &str) -> Result<Option<Item>> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT {SELECT_COLUMNS} FROM items WHERE url = ?1 LIMIT 1"))
+        .map_err(|e| PaiError::Storage(format!("Failed to prepare get_item_by_url query: {e}")))?;
+
+    stmt.query_row([url], item_from_row)
+        .optional()
+        .map_err(|e| PaiError::Storage(format!("Failed to fetch item by URL: {e}")))
+}
+
+// `substr` rather than LIKE, so `%` and `_` in the prefix match literally.
+fn find_items_by_id_prefix(conn: &Connection, prefix: &str, limit: usize) -> Result<Vec<Item>> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {SELECT_COLUMNS} FROM items WHERE substr(id, 1, length(?1)) = ?1 ORDER BY id LIMIT {limit}"
+        ))
+        .map_err(|e| PaiError::Storage(format!("Failed to prepare find_items_by_id_prefix query: {e}")))?;
+
+    let items = stmt
+        .query_map([prefix], item_from_row)
+        .map_err(|e| PaiError::Storage(format!("Failed to find items by ID prefix: {e}")))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| PaiError::Storage(format!("Failed to collect items: {e}")))?;
+
+    Ok(items)
+}
+
+fn delete_item(conn: &Connection, id: &str) -> Result<bool> {
+    let removed = conn
+        .execute("DELETE FROM items WHERE id = ?1", [id])
+        .map_err(|e| PaiError::Storage(format!("Failed to delete item: {e}")))?;
+
+    Ok(removed > 0)
+}
+
+/// Stores the user's tags for an item under the next change sequence number
+fn set_user_tags(conn: &Connection, id: &str, tags: &[String]) -> Result<bool> {
+    let updated = conn
+        .execute(
+            "UPDATE items SET user_tags = ?2, changed = (SELECT seq + 1 FROM item_change_seq) WHERE id = ?1",
+            params![id, serde_json::to_string(tags).unwrap_or_else(|_| "[]".to_string())],
+        )
+        .map_err(|e| PaiError::Storage(format!("Failed to set user tags: {e}")))?;
+
+    Ok(updated > 0)
+}
+
+fn get_user_tags(conn: &Connection, id: &str) -> Result<Vec<String>> {
+    conn.query_row("SELECT user_tags FROM items WHERE id = ?1", [id], |row| {
+        tags_from_row(row, 0)
+    })
+    .optional()
+    .map(Option::unwrap_or_default)
+    .map_err(|e| PaiError::Storage(format!("Failed to read user tags: {e}")))
+}
+
+fn count_items(conn: &Connection) -> Result<usize> {
+    conn.query_row("SELECT COUNT(*) FROM items", [], |row| row.get(0))
+        .map_err(|e| PaiError::Storage(format!("Failed to count items: {e}")))
+}
+
+fn get_stats(conn: &Connection, group: StatsGroup) -> Result<Vec<ItemStats>> {
+    let sql = match group {
+        StatsGroup::Kind => "SELECT source_kind, NULL, COUNT(*) FROM items GROUP BY source_kind ORDER BY source_kind",
+        StatsGroup::Source => {
+            "SELECT source_kind, source_id, COUNT(*) FROM items GROUP BY source_kind, source_id
+             ORDER BY source_kind, source_id"
+        }
+    };
+
+    let mut stmt = conn
+        .prepare(sql)
+        .map_err(|e| PaiError::Storage(format!("Failed to prepare stats query: {e}")))?;
+
+    let stats = stmt
+        .query_map([], |row| {
+            let source_kind_str: String = row.get(0)?;
             let source_kind = source_kind_str
                 .parse::<SourceKind>()
-                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e)))?;
-
-            Ok(Item {
-                id: row.get(0)?,
-                source_kind,
-                source_id: row.get(2)?,
-                author: row.get(3)?,
-                title: row.get(4)?,
-                summary: row.get(5)?,
-                url: row.get(6)?,
-                content_html: row.get(7)?,
-                published_at: row.get(8)?,
-                created_at: row.get(9)?,
-            })
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?;
+            Ok(ItemStats { source_kind, source_id: row.get(1)?, count: row.get(2)? })
         })
+        .map_err(|e| PaiError::Storage(format!("Failed to query stats: {e}")))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| PaiError::Storage(format!("Failed to collect stats: {e}")))?;
+
+    Ok(stats)
+}
+
+/// Maps a row selected with [`SYNC_STATUS_COLUMNS`] to a [`SourceSyncStatus`]
+fn sync_status_from_row(row: &Row<'_>) -> rusqlite::Result<SourceSyncStatus> {
+    let source_kind_str: String = row.get(0)?;
+    let source_kind = source_kind_str
+        .parse::<SourceKind>()
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?;
+
+    Ok(SourceSyncStatus {
+        source_kind,
+        source_id: row.get(1)?,
+        last_run_at: row.get(2)?,
+        last_duration_ms: row.get(3)?,
+        last_item_count: row.get(4)?,
+        last_success_at: row.get(5)?,
+        last_error: row.get(6)?,
+        error_count: row.get(7)?,
+    })
+}
+
+fn record_sync_run(conn: &Connection, run: &SyncRun) -> Result<()> {
+    let previous = conn
+        .query_row(
+            &format!("SELECT {SYNC_STATUS_COLUMNS} FROM sync_status WHERE source_kind = ?1 AND source_id = ?2"),
+            params![run.source_kind.to_string(), run.source_id],
+            sync_status_from_row,
+        )
         .optional()
-        .map_err(|e| PaiError::Storage(format!("Failed to fetch item by id: {e}")))
+        .map_err(|e| PaiError::Storage(format!("Failed to read sync status: {e}")))?;
+
+    let status = SourceSyncStatus::apply(previous.as_ref(), run);
+    conn.execute(
+        &format!("INSERT OR REPLACE INTO sync_status ({SYNC_STATUS_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"),
+        params![
+            status.source_kind.to_string(),
+            status.source_id,
+            status.last_run_at,
+            status.last_duration_ms,
+            status.last_item_count,
+            status.last_success_at,
+            status.last_error,
+            status.error_count,
+        ],
+    )
+    .map_err(|e| PaiError::Storage(format!("Failed to record sync run: {e}")))?;
+
+    Ok(())
+}
+
+fn get_sync_status(conn: &Connection) -> Result<Vec<SourceSyncStatus>> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {SYNC_STATUS_COLUMNS} FROM sync_status ORDER BY source_kind, source_id"
+        ))
+        .map_err(|e| PaiError::Storage(format!("Failed to prepare sync status query: {e}")))?;
+
+    let statuses = stmt
+        .query_map([], sync_status_from_row)
+        .map_err(|e| PaiError::Storage(format!("Failed to query sync status: {e}")))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| PaiError::Storage(format!("Failed to collect sync status: {e}")))?;
+
+    Ok(statuses)
+}
+
+fn record_webhook_delivery(conn: &Connection, delivery: &WebhookDelivery) -> Result<()> {
+    conn.execute(
+        &format!("INSERT INTO webhook_deliveries ({WEBHOOK_DELIVERY_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6)"),
+        params![
+            delivery.webhook,
+            delivery.item_id,
+            delivery.attempted_at,
+            delivery.attempts,
+            delivery.status_code,
+            delivery.error,
+        ],
+    )
+    .map_err(|e| PaiError::Storage(format!("Failed to record webhook delivery: {e}")))?;
+
+    Ok(())
+}
+
+fn list_webhook_deliveries(conn: &Connection, limit: usize) -> Result<Vec<WebhookDelivery>> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {WEBHOOK_DELIVERY_COLUMNS} FROM webhook_deliveries ORDER BY id DESC LIMIT {limit}"
+        ))
+        .map_err(|e| PaiError::Storage(format!("Failed to prepare webhook delivery query: {e}")))?;
+
+    let deliveries = stmt
+        .query_map([], |row| {
+            Ok(WebhookDelivery {
+                webhook: row.get(0)?,
+                item_id: row.get(1)?,
+                attempted_at: row.get(2)?,
+                attempts: row.get(3)?,
+                status_code: row.get(4)?,
+                error: row.get(5)?,
+            })
+        })
+        .map_err(|e| PaiError::Storage(format!("Failed to query webhook deliveries: {e}")))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| PaiError::Storage(format!("Failed to collect webhook deliveries: {e}")))?;
+
+    Ok(deliveries)
+}
+
+fn upsert_websub_subscription(conn: &Connection, subscription: &WebSubSubscription) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO websub_subscriptions ({WEBSUB_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(topic, callback) DO UPDATE SET
+                secret = excluded.secret,
+                expires_at = excluded.expires_at,
+                verified_at = excluded.verified_at"
+        ),
+        params![
+            subscription.topic,
+            subscription.callback,
+            subscription.secret,
+            subscription.expires_at,
+            subscription.verified_at,
+        ],
+    )
+    .map_err(|e| PaiError::Storage(format!("Failed to save WebSub subscription: {e}")))?;
+
+    Ok(())
+}
+
+fn remove_websub_subscription(conn: &Connection, topic: &str, callback: &str) -> Result<bool> {
+    let deleted = conn
+        .execute(
+            "DELETE FROM websub_subscriptions WHERE topic = ?1 AND callback = ?2",
+            params![topic, callback],
+        )
+        .map_err(|e| PaiError::Storage(format!("Failed to remove WebSub subscription: {e}")))?;
+
+    Ok(deleted > 0)
+}
+
+fn list_websub_subscriptions(conn: &Connection) -> Result<Vec<WebSubSubscription>> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {WEBSUB_COLUMNS} FROM websub_subscriptions ORDER BY topic, callback"
+        ))
+        .map_err(|e| PaiError::Storage(format!("Failed to prepare WebSub subscription query: {e}")))?;
+
+    let subscriptions = stmt
+        .query_map([], |row| {
+            Ok(WebSubSubscription {
+                topic: row.get(0)?,
+                callback: row.get(1)?,
+                secret: row.get(2)?,
+                expires_at: row.get(3)?,
+                verified_at: row.get(4)?,
+            })
+        })
+        .map_err(|e| PaiError::Storage(format!("Failed to query WebSub subscriptions: {e}")))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| PaiError::Storage(format!("Failed to collect WebSub subscriptions: {e}")))?;
+
+    Ok(subscriptions)
+}
+
+fn record_sent_webmention(conn: &Connection, sent: &SentWebmention) -> Result<()> {
+    conn.execute(
+        &format!("INSERT INTO sent_webmentions ({SENT_WEBMENTION_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6)"),
+        params![
+            sent.source,
+            sent.target,
+            sent.endpoint,
+            sent.sent_at,
+            sent.status_code,
+            sent.error
+        ],
+    )
+    .map_err(|e| PaiError::Storage(format!("Failed to record sent Webmention: {e}")))?;
+
+    Ok(())
+}
+
+fn list_sent_webmentions(conn: &Connection, limit: usize) -> Result<Vec<SentWebmention>> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {SENT_WEBMENTION_COLUMNS} FROM sent_webmentions ORDER BY id DESC LIMIT {limit}"
+        ))
+        .map_err(|e| PaiError::Storage(format!("Failed to prepare sent Webmention query: {e}")))?;
+
+    let sent = stmt
+        .query_map([], |row| {
+            Ok(SentWebmention {
+                source: row.get(0)?,
+                target: row.get(1)?,
+                endpoint: row.get(2)?,
+                sent_at: row.get(3)?,
+                status_code: row.get(4)?,
+                error: row.get(5)?,
+            })
+        })
+        .map_err(|e| PaiError::Storage(format!("Failed to query sent Webmentions: {e}")))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| PaiError::Storage(format!("Failed to collect sent Webmentions: {e}")))?;
+
+    Ok(sent)
+}
+
+fn upsert_mention(conn: &Connection, mention: &Mention) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO mentions ({MENTION_COLUMNS}) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(source, target) DO UPDATE SET
+                item_id = excluded.item_id,
+                verified_at = excluded.verified_at"
+        ),
+        params![mention.source, mention.target, mention.item_id, mention.verified_at],
+    )
+    .map_err(|e| PaiError::Storage(format!("Failed to save mention: {e}")))?;
+
+    Ok(())
+}
+
+fn remove_mention(conn: &Connection, source: &str, target: &str) -> Result<bool> {
+    let deleted = conn
+        .execute(
+            "DELETE FROM mentions WHERE source = ?1 AND target = ?2",
+            params![source, target],
+        )
+        .map_err(|e| PaiError::Storage(format!("Failed to remove mention: {e}")))?;
+
+    Ok(deleted > 0)
+}
+
+fn list_mentions(conn: &Connection, item_id: &str) -> Result<Vec<Mention>> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {MENTION_COLUMNS} FROM mentions WHERE item_id = ?1 ORDER BY verified_at DESC"
+        ))
+        .map_err(|e| PaiError::Storage(format!("Failed to prepare mention query: {e}")))?;
+
+    let mentions = stmt
+        .query_map([item_id], |row| {
+            Ok(Mention { source: row.get(0)?, target: row.get(1)?, item_id: row.get(2)?, verified_at: row.get(3)? })
+        })
+        .map_err(|e| PaiError::Storage(format!("Failed to query mentions: {e}")))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| PaiError::Storage(format!("Failed to collect mentions: {e}")))?;
+
+    Ok(mentions)
+}
+
+fn database_size(conn: &Connection) -> Result<Option<u64>> {
+    let page_count: u64 = conn
+        .query_row("PRAGMA page_count", [], |row| row.get(0))
+        .map_err(|e| PaiError::Storage(format!("Failed to read page count: {e}")))?;
+    let page_size: u64 = conn
+        .query_row("PRAGMA page_size", [], |row| row.get(0))
+        .map_err(|e| PaiError::Storage(format!("Failed to read page size: {e}")))?;
+
+    Ok(Some(page_count * page_size))
+}
+
+/// Verifies schema integrity
+///
+/// Checks that required tables and indexes exist.
+fn verify_schema(conn: &Connection) -> Result<()> {
+    let tables = vec![
+        "schema_version",
+        "items",
+        "item_change_seq",
+        "sync_status",
+        "webhook_deliveries",
+        "websub_subscriptions",
+        "sent_webmentions",
+        "mentions",
+    ];
+    for table in tables {
+        let exists: bool = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name=?1",
+                params![table],
+                |row| {
+                    let count: i32 = row.get(0)?;
+                    Ok(count > 0)
+                },
+            )
+            .map_err(|e| PaiError::Storage(format!("Failed to verify table {table}: {e}")))?;
+
+        if !exists {
+            return Err(PaiError::Storage(format!("Missing table: {table}")));
+        }
     }
+
+    Ok(())
 }
 
 impl Storage for SqliteStorage {
     fn insert_or_replace_item(&self, item: &Item) -> Result<()> {
-        self.conn
-            .execute(
-                "INSERT OR REPLACE INTO items
-             (id, source_kind, source_id, author, title, summary, url, content_html, published_at, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-                params![
-                    item.id,
-                    item.source_kind.to_string(),
-                    item.source_id,
-                    item.author,
-                    item.title,
-                    item.summary,
-                    item.url,
-                    item.content_html,
-                    item.published_at,
-                    item.created_at,
-                ],
-            )
-            .map_err(|e| PaiError::Storage(format!("Failed to insert item: {e}")))?;
+        insert_or_replace_item(&*self.writer()?, item)
+    }
+
+    fn list_items(&self, filter: &ListFilter) -> Result<Vec<Item>> {
+        self.read(|conn| list_items(conn, filter))
+    }
+
+    fn latest_change(&self) -> Result<u64> {
+        self.read(latest_change)
+    }
+
+    fn list_changes(&self, after: u64, limit: usize) -> Result<Vec<ItemChange>> {
+        self.read(|conn| list_changes(conn, after, limit))
+    }
+
+    fn get_item(&self, id: &str) -> Result<Option<Item>> {
+        self.read(|conn| get_item(conn, id))
+    }
+
+    fn delete_item(&self, id: &str) -> Result<bool> {
+        delete_item(&*self.writer()?, id)
+    }
 
-        Ok(())
+    fn set_user_tags(&self, id: &str, tags: &[String]) -> Result<bool> {
+        set_user_tags(&*self.writer()?, id, tags)
+    }
+
+    fn get_user_tags(&self, id: &str) -> Result<Vec<String>> {
+        self.read(|conn| get_user_tags(conn, id))
+    }
+
+    fn count_items(&self) -> Result<usize> {
+        self.read(count_items)
+    }
+
+    fn get_stats(&self, group: StatsGroup) -> Result<Vec<ItemStats>> {
+        self.read(|conn| get_stats(conn, group))
+    }
+
+    fn record_sync_run(&self, run: &SyncRun) -> Result<()> {
+        record_sync_run(&*self.writer()?, run)
+    }
+
+    fn get_sync_status(&self) -> Result<Vec<SourceSyncStatus>> {
+        self.read(get_sync_status)
+    }
+
+    fn record_webhook_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
+        record_webhook_delivery(&*self.writer()?, delivery)
+    }
+
+    fn list_webhook_deliveries(&self, limit: usize) -> Result<Vec<WebhookDelivery>> {
+        self.read(|conn| list_webhook_deliveries(conn, limit))
+    }
+
+    fn upsert_websub_subscription(&self, subscription: &WebSubSubscription) -> Result<()> {
+        upsert_websub_subscription(&*self.writer()?, subscription)
+    }
+
+    fn remove_websub_subscription(&self, topic: &str, callback: &str) -> Result<bool> {
+        remove_websub_subscription(&*self.writer()?, topic, callback)
+    }
+
+    fn list_websub_subscriptions(&self) -> Result<Vec<WebSubSubscription>> {
+        self.read(list_websub_subscriptions)
+    }
+
+    fn get_item_by_url(&self, url: &str) -> Result<Option<Item>> {
+        self.read(|conn| get_item_by_url(conn, url))
+    }
+
+    fn find_items_by_id_prefix(&self, prefix: &str, limit: usize) -> Result<Vec<Item>> {
+        self.read(|conn| find_items_by_id_prefix(conn, prefix, limit))
+    }
+
+    fn record_sent_webmention(&self, sent: &SentWebmention) -> Result<()> {
+        record_sent_webmention(&*self.writer()?, sent)
+    }
+
+    fn list_sent_webmentions(&self, limit: usize) -> Result<Vec<SentWebmention>> {
+        self.read(|conn| list_sent_webmentions(conn, limit))
+    }
+
+    fn upsert_mention(&self, mention: &Mention) -> Result<()> {
+        upsert_mention(&*self.writer()?, mention)
+    }
+
+    fn remove_mention(&self, source: &str, target: &str) -> Result<bool> {
+        remove_mention(&*self.writer()?, source, target)
+    }
+
+    fn list_mentions(&self, item_id: &str) -> Result<Vec<Mention>> {
+        self.read(|conn| list_mentions(conn, item_id))
+    }
+
+    fn database_size(&self) -> Result<Option<u64>> {
+        self.read(database_size)
+    }
+
+    fn verify_schema(&self) -> Result<()> {
+        self.read(verify_schema)
+    }
+
+    fn transaction(&self, f: &mut dyn FnMut(&dyn Storage) -> Result<()>) -> Result<()> {
+        let mut conn = self.writer()?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| PaiError::Storage(format!("Failed to begin transaction: {e}")))?;
+
+        f(&SqliteTransaction { conn: &tx })?;
+
+        tx.commit()
+            .map_err(|e| PaiError::Storage(format!("Failed to commit transaction: {e}")))
+    }
+}
+
+/// Storage view bound to an open SQLite transaction
+///
+/// Handed to the closure passed to [`Storage::transaction`]; the transaction is rolled back
+/// when it is dropped without being committed.
+struct SqliteTransaction<'a> {
+    conn: &'a Connection,
+}
+
+impl Storage for SqliteTransaction<'_> {
+    fn insert_or_replace_item(&self, item: &Item) -> Result<()> {
+        insert_or_replace_item(self.conn, item)
     }
 
     fn list_items(&self, filter: &ListFilter) -> Result<Vec<Item>> {
-        let mut sql = String::from("SELECT id, source_kind, source_id, author, title, summary, url, content_html, published_at, created_at FROM items WHERE 1=1");
-        let mut conditions = Vec::new();
+        list_items(self.conn, filter)
+    }
 
-        if filter.source_kind.is_some() {
-            sql.push_str(" AND source_kind = ?");
-            conditions.push(filter.source_kind.unwrap().to_string());
-        }
+    fn latest_change(&self) -> Result<u64> {
+        latest_change(self.conn)
+    }
 
-        if let Some(ref source_id) = filter.source_id {
-            sql.push_str(" AND source_id = ?");
-            conditions.push(source_id.clone());
-        }
+    fn list_changes(&self, after: u64, limit: usize) -> Result<Vec<ItemChange>> {
+        list_changes(self.conn, after, limit)
+    }
 
-        if let Some(ref since) = filter.since {
-            sql.push_str(" AND published_at >= ?");
-            conditions.push(since.clone());
-        }
+    fn get_item(&self, id: &str) -> Result<Option<Item>> {
+        get_item(self.conn, id)
+    }
 
-        if let Some(ref query) = filter.query {
-            sql.push_str(" AND (title LIKE ? OR summary LIKE ?)");
-            let pattern = format!("%{query}%");
-            conditions.push(pattern.clone());
-            conditions.push(pattern);
-        }
+    fn delete_item(&self, id: &str) -> Result<bool> {
+        delete_item(self.conn, id)
+    }
 
-        sql.push_str(" ORDER BY published_at DESC");
+    fn set_user_tags(&self, id: &str, tags: &[String]) -> Result<bool> {
+        set_user_tags(self.conn, id, tags)
+    }
 
-        if let Some(limit) = filter.limit {
-            sql.push_str(&format!(" LIMIT {limit}"));
-        }
+    fn get_user_tags(&self, id: &str) -> Result<Vec<String>> {
+        get_user_tags(self.conn, id)
+    }
 
-        let mut stmt = self
-            .conn
-            .prepare(&sql)
-            .map_err(|e| PaiError::Storage(format!("Failed to prepare query: {e}")))?;
-
-        let params_refs: Vec<&dyn rusqlite::ToSql> = conditions.iter().map(|s| s as &dyn rusqlite::ToSql).collect();
-
-        let items = stmt
-            .query_map(params_refs.as_slice(), |row| {
-                let source_kind_str: String = row.get(1)?;
-                let source_kind = source_kind_str.parse::<SourceKind>().map_err(|e| {
-                    rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e))
-                })?;
-
-                Ok(Item {
-                    id: row.get(0)?,
-                    source_kind,
-                    source_id: row.get(2)?,
-                    author: row.get(3)?,
-                    title: row.get(4)?,
-                    summary: row.get(5)?,
-                    url: row.get(6)?,
-                    content_html: row.get(7)?,
-                    published_at: row.get(8)?,
-                    created_at: row.get(9)?,
-                })
-            })
-            .map_err(|e| PaiError::Storage(format!("Failed to query items: {e}")))?
-            .collect::<std::result::Result<Vec<_>, _>>()
-            .map_err(|e| PaiError::Storage(format!("Failed to collect items: {e}")))?;
+    fn count_items(&self) -> Result<usize> {
+        count_items(self.conn)
+    }
+
+    fn get_stats(&self, group: StatsGroup) -> Result<Vec<ItemStats>> {
+        get_stats(self.conn, group)
+    }
+
+    fn record_sync_run(&self, run: &SyncRun) -> Result<()> {
+        record_sync_run(self.conn, run)
+    }
 
-        Ok(items)
+    fn get_sync_status(&self) -> Result<Vec<SourceSyncStatus>> {
+        get_sync_status(self.conn)
+    }
+
+    fn record_webhook_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
+        record_webhook_delivery(self.conn, delivery)
+    }
+
+    fn list_webhook_deliveries(&self, limit: usize) -> Result<Vec<WebhookDelivery>> {
+        list_webhook_deliveries(self.conn, limit)
+    }
+
+    fn upsert_websub_subscription(&self, subscription: &WebSubSubscription) -> Result<()> {
+        upsert_websub_subscription(self.conn, subscription)
+    }
+
+    fn remove_websub_subscription(&self, topic: &str, callback: &str) -> Result<bool> {
+        remove_websub_subscription(self.conn, topic, callback)
+    }
+
+    fn list_websub_subscriptions(&self) -> Result<Vec<WebSubSubscription>> {
+        list_websub_subscriptions(self.conn)
+    }
+
+    fn get_item_by_url(&self, url: &str) -> Result<Option<Item>> {
+        get_item_by_url(self.conn, url)
+    }
+
+    fn find_items_by_id_prefix(&self, prefix: &str, limit: usize) -> Result<Vec<Item>> {
+        find_items_by_id_prefix(self.conn, prefix, limit)
+    }
+
+    fn record_sent_webmention(&self, sent: &SentWebmention) -> Result<()> {
+        record_sent_webmention(self.conn, sent)
+    }
+
+    fn list_sent_webmentions(&self, limit: usize) -> Result<Vec<SentWebmention>> {
+        list_sent_webmentions(self.conn, limit)
+    }
+
+    fn upsert_mention(&self, mention: &Mention) -> Result<()> {
+        upsert_mention(self.conn, mention)
+    }
+
+    fn remove_mention(&self, source: &str, target: &str) -> Result<bool> {
+        remove_mention(self.conn, source, target)
+    }
+
+    fn list_mentions(&self, item_id: &str) -> Result<Vec<Mention>> {
+        list_mentions(self.conn, item_id)
+    }
+
+    fn database_size(&self) -> Result<Option<u64>> {
+        database_size(self.conn)
+    }
+
+    fn verify_schema(&self) -> Result<()> {
+        verify_schema(self.conn)
+    }
+
+    /// Nested transactions join the enclosing one
+    fn transaction(&self, f: &mut dyn FnMut(&dyn Storage) -> Result<()>) -> Result<()> {
+        f(self)
     }
 }
 
@@ -266,24 +1284,61 @@ impl Storage for SqliteStorage {
 mod tests {
     use super::*;
     use chrono::Utc;
+    use pai_core::SourceFetcher;
 
     fn create_test_storage() -> SqliteStorage {
         SqliteStorage::new(":memory:").expect("Failed to create in-memory database")
     }
 
     fn create_test_item(id: &str, source_kind: SourceKind, source_id: &str) -> Item {
-        Item {
-            id: id.to_string(),
-            source_kind,
-            source_id: source_id.to_string(),
-            author: Some("Test Author".to_string()),
-            title: Some("Test Title".to_string()),
-            summary: Some("Test summary".to_string()),
-            url: format!("https://example.com/{id}"),
-            content_html: Some("<p>Test content</p>".to_string()),
-            published_at: Utc::now().to_rfc3339(),
-            created_at: Utc::now().to_rfc3339(),
-        }
+        Item::test(id)
+            .with_source(source_kind, source_id)
+            .with_author("Test Author")
+            .with_title("Test Title")
+            .with_summary("Test summary")
+            .with_content_html("<p>Test content</p>")
+            .with_published_at(&Utc::now().to_rfc3339())
+    }
+
+    #[test]
+    fn file_database_uses_wal_and_read_pool() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SqliteStorage::new(dir.path().join("pai.db")).unwrap();
+
+        let mode: String = storage
+            .writer()
+            .unwrap()
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+        assert!(storage.readers().unwrap().is_some());
+
+        let readonly = storage
+            .read(|conn| Ok(conn.is_readonly(rusqlite::MAIN_DB).unwrap()))
+            .unwrap();
+        assert!(readonly);
+    }
+
+    #[test]
+    fn readers_are_not_blocked_by_open_write_transaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = std::sync::Arc::new(SqliteStorage::new(dir.path().join("pai.db")).unwrap());
+        storage
+            .insert_or_replace_item(&create_test_item("test-0", SourceKind::Substack, "test"))
+            .unwrap();
+
+        let writer = std::sync::Arc::clone(&storage);
+        let result = storage.transaction(&mut |tx| {
+            tx.insert_or_replace_item(&create_test_item("test-1", SourceKind::Substack, "test"))?;
+
+            let reader = std::sync::Arc::clone(&writer);
+            let count = std::thread::spawn(move || reader.count_items()).join().unwrap()?;
+            assert_eq!(count, 1, "uncommitted writes must not be visible to readers");
+            Ok(())
+        });
+
+        assert!(result.is_ok());
+        assert_eq!(storage.count_items().unwrap(), 2);
     }
 
     #[test]
@@ -413,11 +1468,15 @@ mod tests {
             .insert_or_replace_item(&create_test_item("test-3", SourceKind::Bluesky, "test.bsky.social"))
             .expect("Failed to insert");
 
-        let stats = storage.get_stats().expect("Failed to get stats");
+        let stats = storage.get_stats(StatsGroup::Kind).expect("Failed to get stats");
 
         assert_eq!(stats.len(), 2);
-        assert!(stats.iter().any(|(k, v)| k == "bluesky" && *v == 1));
-        assert!(stats.iter().any(|(k, v)| k == "substack" && *v == 2));
+        assert!(stats
+            .iter()
+            .any(|s| s.source_kind == SourceKind::Bluesky && s.count == 1));
+        assert!(stats
+            .iter()
+            .any(|s| s.source_kind == SourceKind::Substack && s.count == 2));
     }
 
     #[test]
@@ -455,4 +1514,384 @@ mod tests {
         let result = storage.get_item("nope").expect("query failed");
         assert!(result.is_none());
     }
+
+    #[test]
+    fn get_stats_groups_by_source() {
+        let storage = create_test_storage();
+
+        storage
+            .insert_or_replace_item(&create_test_item("test-1", SourceKind::Leaflet, "one"))
+            .expect("Failed to insert");
+        storage
+            .insert_or_replace_item(&create_test_item("test-2", SourceKind::Leaflet, "two"))
+            .expect("Failed to insert");
+
+        let stats = storage.get_stats(StatsGroup::Source).expect("Failed to get stats");
+
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].source_id.as_deref(), Some("one"));
+        assert_eq!(stats[1].count, 1);
+    }
+
+    #[test]
+    fn delete_item_removes_record() {
+        let storage = create_test_storage();
+        let item = create_test_item("test-1", SourceKind::Substack, "test.substack.com");
+        storage.insert_or_replace_item(&item).expect("Failed to insert");
+
+        assert!(storage.delete_item("test-1").expect("delete failed"));
+        assert!(!storage.delete_item("test-1").expect("delete failed"));
+        assert_eq!(storage.count_items().unwrap(), 0);
+    }
+
+    #[test]
+    fn batch_insert_writes_all_items() {
+        let storage = create_test_storage();
+        let items: Vec<Item> = (0..3)
+            .map(|i| create_test_item(&format!("test-{i}"), SourceKind::Bluesky, "test.bsky.social"))
+            .collect();
+
+        let written = storage.insert_or_replace_items(&items).expect("Failed to insert batch");
+
+        assert_eq!(written, 3);
+        assert_eq!(storage.count_items().unwrap(), 3);
+    }
+
+    #[test]
+    fn changes_skip_unchanged_rewrites_and_never_reuse_numbers() {
+        let storage = create_test_storage();
+        let item = create_test_item("test-1", SourceKind::Bluesky, "me");
+        storage.insert_or_replace_item(&item).unwrap();
+        storage.insert_or_replace_item(&item).unwrap();
+        assert_eq!(storage.latest_change().unwrap(), 1);
+
+        let edited = item.with_title("Edited");
+        storage.insert_or_replace_item(&edited).unwrap();
+        let changes = storage.list_changes(0, 10).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].seq, 2);
+        assert_eq!(changes[0].item.title.as_deref(), Some("Edited"));
+
+        storage.delete_item("test-1").unwrap();
+        storage
+            .insert_or_replace_item(&create_test_item("test-2", SourceKind::Bluesky, "me"))
+            .unwrap();
+        assert_eq!(storage.list_changes(2, 10).unwrap()[0].seq, 3);
+    }
+
+    #[test]
+    fn record_sync_run_accumulates_errors() {
+        let storage = create_test_storage();
+        let mut run = SyncRun {
+            source_kind: SourceKind::Leaflet,
+            source_id: "test".to_string(),
+            started_at: "2024-01-01T00:00:00Z".to_string(),
+            duration_ms: 250,
+            item_count: 3,
+            error: None,
+        };
+        storage.record_sync_run(&run).unwrap();
+
+        run.started_at = "2024-01-02T00:00:00Z".to_string();
+        run.item_count = 0;
+        run.error = Some("HTTP 500".to_string());
+        storage.record_sync_run(&run).unwrap();
+
+        let status = storage.get_sync_status().unwrap();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].last_item_count, 3);
+        assert_eq!(status[0].last_success_at.as_deref(), Some("2024-01-01T00:00:00Z"));
+        assert_eq!(status[0].last_error.as_deref(), Some("HTTP 500"));
+        assert_eq!(status[0].error_count, 1);
+        assert!(storage.database_size().unwrap().unwrap() > 0);
+    }
+
+    #[test]
+    fn migrates_version_1_databases() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("v1.db");
+        {
+            let conn = Connection::open(&db_path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE schema_version (version INTEGER PRIMARY KEY);
+                 INSERT INTO schema_version (version) VALUES (1);
+                 CREATE TABLE items (
+                     id TEXT PRIMARY KEY, source_kind TEXT NOT NULL, source_id TEXT NOT NULL, author TEXT,
+                     title TEXT, summary TEXT, url TEXT NOT NULL, content_html TEXT, published_at TEXT NOT NULL,
+                     created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+                 );
+                 INSERT INTO items (id, source_kind, source_id, url, published_at)
+                     VALUES ('old', 'bluesky', 'me', 'https://example.com', '2024-01-01T00:00:00Z');",
+            )
+            .unwrap();
+        }
+
+        let storage = SqliteStorage::new(&db_path).unwrap();
+        storage.verify_schema().unwrap();
+        assert!(storage.get_item("old").unwrap().unwrap().tags.is_empty());
+        assert_eq!(storage.latest_change().unwrap(), 1);
+        assert!(storage.set_user_tags("old", &["kept".to_string()]).unwrap());
+        assert_eq!(storage.get_item("old").unwrap().unwrap().tags, ["kept"]);
+        storage
+            .insert_or_replace_item(&create_test_item("new", SourceKind::Bluesky, "me"))
+            .unwrap();
+        assert_eq!(storage.list_changes(2, 10).unwrap()[0].item.id, "new");
+
+        let version: i32 = storage
+            .writer()
+            .unwrap()
+            .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn backs_up_and_restores() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SqliteStorage::new(dir.path().join("pai.db")).unwrap();
+        storage
+            .insert_or_replace_item(&create_test_item("kept", SourceKind::Bluesky, "me"))
+            .unwrap();
+
+        let backup = dir.path().join("backup.db");
+        storage.backup(&backup).unwrap();
+        assert!(storage.backup(&backup).is_err());
+        let mut files: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| !name.starts_with("pai.db"))
+            .collect();
+        files.sort();
+        assert_eq!(files, ["backup.db"]);
+        assert!(storage.backup(&dir.path().join("missing/backup.db")).is_err());
+
+        storage
+            .insert_or_replace_item(&create_test_item("later", SourceKind::Bluesky, "me"))
+            .unwrap();
+        assert_eq!(storage.restore(&backup).unwrap(), SCHEMA_VERSION);
+        assert_eq!(storage.count_items().unwrap(), 1);
+        assert!(storage.get_item("kept").unwrap().is_some());
+
+        let newer = dir.path().join("newer.db");
+        storage.backup(&newer).unwrap();
+        Connection::open(&newer)
+            .unwrap()
+            .execute("UPDATE schema_version SET version = ?1", params![SCHEMA_VERSION + 1])
+            .unwrap();
+        let error = storage.restore(&newer).unwrap_err().to_string();
+        assert!(error.contains("upgrade pai"), "{error}");
+
+        let other = dir.path().join("other.db");
+        Connection::open(&other)
+            .unwrap()
+            .execute_batch("CREATE TABLE t (x)")
+            .unwrap();
+        assert!(storage
+            .restore(&other)
+            .unwrap_err()
+            .to_string()
+            .contains("not a pai database"));
+        assert_eq!(storage.count_items().unwrap(), 1);
+    }
+
+    #[test]
+    fn restores_backup_with_other_page_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SqliteStorage::new(dir.path().join("pai.db")).unwrap();
+        storage
+            .insert_or_replace_item(&create_test_item("kept", SourceKind::Bluesky, "me"))
+            .unwrap();
+        let backup = dir.path().join("backup.db");
+        storage.backup(&backup).unwrap();
+        let conn = Connection::open(&backup).unwrap();
+        conn.execute_batch("PRAGMA journal_mode = DELETE; PRAGMA page_size = 8192; VACUUM;")
+            .unwrap();
+        let page_size: u32 = conn.query_row("PRAGMA page_size", [], |row| row.get(0)).unwrap();
+        assert_eq!(page_size, 8192);
+        drop(conn);
+
+        storage
+            .insert_or_replace_item(&create_test_item("later", SourceKind::Bluesky, "me"))
+            .unwrap();
+        assert_eq!(storage.restore(&backup).unwrap(), SCHEMA_VERSION);
+        assert_eq!(storage.count_items().unwrap(), 1);
+        assert!(storage.get_item("kept").unwrap().is_some());
+        storage
+            .insert_or_replace_item(&create_test_item("after", SourceKind::Bluesky, "me"))
+            .unwrap();
+        assert_eq!(storage.count_items().unwrap(), 2);
+        assert_eq!(storage.size().unwrap().bytes % 8192, 0);
+    }
+
+    #[test]
+    fn checks_vacuums_and_optimizes() {
+        let storage = create_test_storage();
+        storage
+            .writer()
+            .unwrap()
+            .execute_batch("CREATE VIRTUAL TABLE items_fts USING fts5(title)")
+            .unwrap();
+        for i in 0..50 {
+            storage
+                .insert_or_replace_item(&create_test_item(&format!("item-{i}"), SourceKind::Bluesky, "me"))
+                .unwrap();
+        }
+
+        assert!(storage.integrity_check().unwrap().is_empty());
+        assert_eq!(storage.optimize().unwrap(), ["items_fts"]);
+
+        storage.writer().unwrap().execute("DELETE FROM items", []).unwrap();
+        let before = storage.size().unwrap();
+        assert!(before.free_bytes > 0);
+        storage.vacuum().unwrap();
+        let after = storage.size().unwrap();
+        assert_eq!(after.free_bytes, 0);
+        assert!(after.bytes < before.bytes);
+    }
+
+    #[test]
+    fn user_tags_survive_a_sync() {
+        struct Source(Item);
+        impl SourceFetcher for Source {
+            fn fetch(&self) -> Result<Vec<Item>> {
+                Ok(vec![self.0.clone()])
+            }
+        }
+
+        let storage = create_test_storage();
+        let mut item = create_test_item("tagged", SourceKind::Bluesky, "me");
+        item.tags = vec!["rust".to_string()];
+        Source(item.clone()).sync(&storage).unwrap();
+
+        let before = storage.latest_change().unwrap();
+        assert!(storage.set_user_tags("tagged", &["later".to_string()]).unwrap());
+        assert!(!storage.set_user_tags("missing", &["later".to_string()]).unwrap());
+        let changes = storage.list_changes(before, 10).unwrap();
+        assert_eq!(changes[0].item.tags, ["rust", "later"]);
+        assert_eq!(storage.get_user_tags("tagged").unwrap(), ["later"]);
+        assert!(storage.get_user_tags("missing").unwrap().is_empty());
+
+        item.title = Some("Edited at the source".to_string());
+        Source(item).sync(&storage).unwrap();
+        let stored = storage.get_item("tagged").unwrap().unwrap();
+        assert_eq!(stored.title.as_deref(), Some("Edited at the source"));
+        assert_eq!(stored.tags, ["rust", "later"]);
+    }
+
+    #[test]
+    fn stores_tags_and_webhook_deliveries() {
+        let storage = create_test_storage();
+        let mut item = create_test_item("tagged", SourceKind::Bluesky, "me");
+        item.tags = vec!["rust".to_string(), "wasm".to_string()];
+        storage.insert_or_replace_item(&item).unwrap();
+        assert_eq!(storage.get_item("tagged").unwrap().unwrap().tags, item.tags);
+
+        for (item_id, error) in [("a", Some("HTTP 500")), ("b", None)] {
+            storage
+                .record_webhook_delivery(&WebhookDelivery {
+                    webhook: "hooks.slack.com".to_string(),
+                    item_id: item_id.to_string(),
+                    attempted_at: "2024-01-01T00:00:00Z".to_string(),
+                    attempts: 3,
+                    status_code: Some(500),
+                    error: error.map(String::from),
+                })
+                .unwrap();
+        }
+
+        let deliveries = storage.list_webhook_deliveries(1).unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].item_id, "b");
+    }
+
+    #[test]
+    fn upserts_and_removes_websub_subscriptions() {
+        let storage = create_test_storage();
+        let mut subscription = WebSubSubscription {
+            topic: "https://pai.example.com/rss.xml".to_string(),
+            callback: "https://reader.example.com/push".to_string(),
+            secret: None,
+            expires_at: "2024-01-10T00:00:00Z".to_string(),
+            verified_at: "2024-01-01T00:00:00Z".to_string(),
+        };
+        storage.upsert_websub_subscription(&subscription).unwrap();
+
+        subscription.secret = Some("s3cret".to_string());
+        subscription.expires_at = "2024-01-20T00:00:00Z".to_string();
+        storage.upsert_websub_subscription(&subscription).unwrap();
+        assert_eq!(storage.list_websub_subscriptions().unwrap(), vec![subscription.clone()]);
+
+        assert!(storage
+            .remove_websub_subscription(&subscription.topic, &subscription.callback)
+            .unwrap());
+        assert!(!storage
+            .remove_websub_subscription(&subscription.topic, &subscription.callback)
+            .unwrap());
+        assert!(storage.list_websub_subscriptions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn stores_mentions_against_items() {
+        let storage = create_test_storage();
+        let item = create_test_item("target", SourceKind::BearBlog, "blog");
+        storage.insert_or_replace_item(&item).unwrap();
+        assert_eq!(storage.get_item_by_url(&item.url).unwrap().unwrap().id, "target");
+        assert!(storage.get_item_by_url("https://elsewhere.example/").unwrap().is_none());
+
+        for (source, verified_at) in [
+            ("https://a.example/reply", "2024-01-01T00:00:00Z"),
+            ("https://b.example/like", "2024-01-02T00:00:00Z"),
+        ] {
+            storage
+                .upsert_mention(&Mention {
+                    source: source.to_string(),
+                    target: item.url.clone(),
+                    item_id: item.id.clone(),
+                    verified_at: verified_at.to_string(),
+                })
+                .unwrap();
+        }
+
+        let mentions = storage.list_mentions("target").unwrap();
+        assert_eq!(mentions.len(), 2);
+        assert_eq!(mentions[0].source, "https://b.example/like");
+
+        assert!(storage.remove_mention("https://a.example/reply", &item.url).unwrap());
+        assert_eq!(storage.list_mentions("target").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn finds_items_by_id_prefix() {
+        let storage = create_test_storage();
+        for id in [
+            "at://did:plc:a/post/1",
+            "at://did:plc:a/post/2",
+            "at://did:plc:b/post/1",
+            "100%_literal",
+        ] {
+            storage
+                .insert_or_replace_item(&create_test_item(id, SourceKind::Bluesky, "me.bsky.social"))
+                .unwrap();
+        }
+
+        let found = storage.find_items_by_id_prefix("at://did:plc:a/", 10).unwrap();
+        let ids: Vec<&str> = found.iter().map(|item| item.id.as_str()).collect();
+        assert_eq!(ids, ["at://did:plc:a/post/1", "at://did:plc:a/post/2"]);
+        assert_eq!(storage.find_items_by_id_prefix("at://", 1).unwrap().len(), 1);
+        assert_eq!(storage.find_items_by_id_prefix("100%_", 10).unwrap().len(), 1);
+        assert!(storage.find_items_by_id_prefix("10_", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn transaction_rolls_back_on_error() {
+        let storage = create_test_storage();
+
+        let result = storage.transaction(&mut |tx| {
+            tx.insert_or_replace_item(&create_test_item("test-1", SourceKind::Substack, "test.substack.com"))?;
+            Err(PaiError::Storage("abort".to_string()))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(storage.count_items().unwrap(), 0);
+    }
 }
@@ -0,0 +1,104 @@
+use pai_core::ItemChange;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+
+/// How often the change watcher checks for newly written items
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Changes read from storage per query when catching up
+pub const PAGE_SIZE: usize = 200;
+
+/// Items buffered per subscriber before it starts missing events
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// Fans out new and changed items to `/api/stream` subscribers
+pub struct ChangeFeed {
+    items: broadcast::Sender<Arc<ItemChange>>,
+    closed: watch::Sender<bool>,
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        let (items, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let (closed, _) = watch::channel(false);
+        Self { items, closed }
+    }
+}
+
+impl ChangeFeed {
+    pub fn subscribe(&self) -> Subscription {
+        Subscription { items: self.items.subscribe(), closed: self.closed.subscribe() }
+    }
+
+    /// Sends changes to every current subscriber
+    pub fn publish(&self, changes: Vec<ItemChange>) {
+        for change in changes {
+            // An error only means nobody is subscribed right now.
+            let _ = self.items.send(Arc::new(change));
+        }
+    }
+
+    /// Ends all subscriptions so open streams don't hold up graceful shutdown
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+    }
+}
+
+/// One subscriber's view of the [`ChangeFeed`]
+pub struct Subscription {
+    items: broadcast::Receiver<Arc<ItemChange>>,
+    closed: watch::Receiver<bool>,
+}
+
+impl Subscription {
+    /// Waits for the next published change, or returns `None` once the feed is closed
+    pub async fn next(&mut self) -> Option<Arc<ItemChange>> {
+        loop {
+            tokio::select! {
+                _ = self.closed.wait_for(|closed| *closed) => return None,
+                result = self.items.recv() => match result {
+                    Ok(change) => return Some(change),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "Stream subscriber fell behind, dropping items");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                },
+            }
+        }
+    }
+}
+
+/// Resume position parsed from a `Last-Event-ID` header: the last change sequence number seen
+pub fn parse_last_event_id(value: &str) -> Option<u64> {
+    value.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pai_core::Item;
+
+    fn item(id: &str) -> Item {
+        Item::test(id).with_created_at(&chrono::Utc::now().to_rfc3339())
+    }
+
+    #[test]
+    fn parses_last_event_ids() {
+        assert_eq!(parse_last_event_id(" 42 "), Some(42));
+        assert_eq!(parse_last_event_id("2024-01-02T00:00:00Z a"), None);
+        assert_eq!(parse_last_event_id("-1"), None);
+    }
+
+    #[tokio::test]
+    async fn subscriptions_end_when_closed() {
+        let feed = ChangeFeed::default();
+        let mut subscription = feed.subscribe();
+
+        feed.publish(vec![ItemChange { seq: 1, item: item("a") }]);
+        assert_eq!(subscription.next().await.unwrap().item.id, "a");
+
+        feed.close();
+        assert!(subscription.next().await.is_none());
+    }
+}
@@ -0,0 +1,204 @@
+use crate::outbound::{self, error_chain, has_public_host};
+use pai_core::{html_links, read_page, PaiError, Result, MAX_PAGE_BYTES};
+use percent_encoding::percent_decode_str;
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Receiver endpoint, relative to the server's base URL
+pub const ENDPOINT_PATH: &str = "/webmention";
+
+/// Sources verified at once; further mentions are turned away until one finishes
+pub const MAX_PENDING: usize = 16;
+
+/// Form body of an incoming Webmention
+#[derive(Debug, Deserialize)]
+pub struct WebmentionRequest {
+    pub source: String,
+    pub target: String,
+}
+
+impl WebmentionRequest {
+    /// Checks both URLs before the mention is accepted for verification
+    pub fn validate(&self) -> Result<()> {
+        for (name, value) in [("source", &self.source), ("target", &self.target)] {
+            let url = Url::parse(value)
+                .map_err(|e| PaiError::InvalidArgument(format!("Invalid {name} URL '{value}': {e}")))?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(PaiError::InvalidArgument(format!(
+                    "Invalid {name} URL '{value}': must be an http or https URL"
+                )));
+            }
+        }
+
+        if self.source == self.target {
+            return Err(PaiError::InvalidArgument("Source and target must differ".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Item ID of a `/item/{id}` permalink page URL under `public_url`
+pub fn permalink_id(target: &str, public_url: &str) -> Option<String> {
+    let url = Url::parse(target).ok()?;
+    let base = Url::parse(public_url).ok()?;
+    let same_site = url.scheme() == base.scheme()
+        && url.host_str() == base.host_str()
+        && url.port_or_known_default() == base.port_or_known_default();
+    if !same_site {
+        return None;
+    }
+
+    let prefix = format!("{}/item/", base.path().trim_end_matches('/'));
+    let id = url.path().strip_prefix(&prefix).filter(|id| !id.is_empty())?;
+    percent_decode_str(id).decode_utf8().ok().map(String::from)
+}
+
+/// Result of fetching a mention's source
+#[derive(Debug, PartialEq, Eq)]
+pub enum SourceCheck {
+    /// The source links to the target
+    Links,
+    /// The source is gone or no longer links to the target
+    Missing,
+}
+
+/// Fetches mention sources to check they really link to their target
+///
+/// Anyone can name a source, so the verifier only connects to public addresses: hosts are checked
+/// after DNS resolution and again on every redirect, and pages are read up to [`MAX_PAGE_BYTES`].
+pub struct Verifier {
+    client: reqwest::Client,
+    public_only: bool,
+    pending: Arc<Semaphore>,
+}
+
+impl Default for Verifier {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+impl Verifier {
+    fn new(public_only: bool) -> Self {
+        let client = outbound::client(concat!("pai/", env!("CARGO_PKG_VERSION"), " (Webmention)"), public_only);
+        Self { client, public_only, pending: Arc::new(Semaphore::new(MAX_PENDING)) }
+    }
+
+    /// A verifier that also fetches loopback and private addresses, for tests against local servers
+    #[cfg(test)]
+    pub fn allowing_private_addresses() -> Self {
+        Self::new(false)
+    }
+
+    /// Reserves a verification slot, or `None` when [`MAX_PENDING`] sources are already being fetched
+    pub fn reserve(&self) -> Option<OwnedSemaphorePermit> {
+        Arc::clone(&self.pending).try_acquire_owned().ok()
+    }
+
+    /// Fetches `source` and looks for a link to `target`
+    ///
+    /// Network errors and unexpected statuses are returned as errors, so an existing mention is
+    /// only dropped when the source answers `410 Gone`, `404` or without the link.
+    pub async fn check(&self, source: &str, target: &str) -> Result<SourceCheck> {
+        let url =
+            Url::parse(source).map_err(|e| PaiError::InvalidArgument(format!("Invalid source '{source}': {e}")))?;
+        if self.public_only && !has_public_host(&url) {
+            return Err(PaiError::Fetch(format!(
+                "Refusing to fetch {source}: not a public address"
+            )));
+        }
+
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| PaiError::Fetch(format!("Failed to fetch {source}: {}", error_chain(&e))))?;
+
+        let status = response.status();
+        if status == StatusCode::GONE || status == StatusCode::NOT_FOUND {
+            return Ok(SourceCheck::Missing);
+        }
+        if !status.is_success() {
+            return Err(PaiError::Fetch(format!("{source} answered HTTP {status}")));
+        }
+
+        let page = response.url().to_string();
+        let html = read_page(response, MAX_PAGE_BYTES).await?;
+
+        let target = Url::parse(target)
+            .map(String::from)
+            .unwrap_or_else(|_| target.to_string());
+        if html_links(&html, &page).contains(&target) {
+            Ok(SourceCheck::Links)
+        } else {
+            Ok(SourceCheck::Missing)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(source: &str, target: &str) -> WebmentionRequest {
+        WebmentionRequest { source: source.to_string(), target: target.to_string() }
+    }
+
+    #[test]
+    fn validates_source_and_target() {
+        assert!(request("https://a.example/reply", "https://blog.example/post")
+            .validate()
+            .is_ok());
+        assert!(request("https://a.example/reply", "https://a.example/reply")
+            .validate()
+            .is_err());
+        assert!(request("ftp://a.example/reply", "https://blog.example/post")
+            .validate()
+            .is_err());
+        assert!(request("https://a.example/reply", "not a url").validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn refuses_sources_on_private_addresses() {
+        let verifier = Verifier::default();
+        for source in ["http://127.0.0.1:9/reply", "http://localhost:9/reply"] {
+            let error = verifier.check(source, "https://blog.example/post").await.unwrap_err();
+            assert!(error.to_string().contains("public address"), "{source}: {error}");
+        }
+    }
+
+    #[test]
+    fn limits_pending_verifications() {
+        let verifier = Verifier::default();
+        let permits: Vec<_> = (0..MAX_PENDING).map_while(|_| verifier.reserve()).collect();
+        assert_eq!(permits.len(), MAX_PENDING);
+        assert!(verifier.reserve().is_none());
+        drop(permits);
+        assert!(verifier.reserve().is_some());
+    }
+
+    #[test]
+    fn parses_permalink_ids() {
+        let public_url = "https://pai.example.com";
+        assert_eq!(
+            permalink_id(
+                "https://pai.example.com/item/at%3A%2F%2Fdid%3Aplc%3Aabc%2Fpost%2F1",
+                public_url
+            )
+            .as_deref(),
+            Some("at://did:plc:abc/post/1")
+        );
+        assert_eq!(
+            permalink_id("https://pai.example.com/feeds/item/1", "https://pai.example.com/feeds/").as_deref(),
+            Some("1")
+        );
+        assert_eq!(permalink_id("https://pai.example.com/item/", public_url), None);
+        assert_eq!(permalink_id("https://blog.example/post", public_url), None);
+        assert_eq!(permalink_id("https://evil.example/item/1", public_url), None);
+        assert_eq!(permalink_id("http://pai.example.com/item/1", public_url), None);
+    }
+}
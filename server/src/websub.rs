@@ -0,0 +1,526 @@
+use crate::outbound::{self, error_chain, has_public_host};
+use axum::{
+    extract::Query,
+    http::{HeaderValue, Uri},
+};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use pai_core::{sign_payload, FeedQuery, ListFilter, PaiError, Result, WebSubSubscription};
+use reqwest::Url;
+use rss::extension::atom::{AtomExtension, AtomExtensionBuilder, Link};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Hub endpoint, relative to the server's base URL
+pub const HUB_PATH: &str = "/websub";
+
+/// The feed subscribers can follow, relative to the server's base URL
+pub const TOPIC_PATH: &str = "/rss.xml";
+
+/// Lease granted when the subscriber doesn't ask for one (10 days)
+const DEFAULT_LEASE_SECONDS: u64 = 10 * 24 * 60 * 60;
+
+/// Bounds for requested leases (5 minutes to 30 days)
+const MIN_LEASE_SECONDS: u64 = 5 * 60;
+const MAX_LEASE_SECONDS: u64 = 30 * 24 * 60 * 60;
+
+/// Secrets must be shorter than this many bytes
+const MAX_SECRET_LEN: usize = 200;
+
+/// Intents verified at once; further requests are turned away until one finishes
+pub const MAX_PENDING: usize = 16;
+
+/// Subscriptions a single callback can hold, across all topics
+pub const MAX_SUBSCRIPTIONS_PER_CALLBACK: usize = 10;
+
+/// Subscribers a single topic can have
+pub const MAX_SUBSCRIPTIONS_PER_TOPIC: usize = 100;
+
+/// Form body of a subscribe or unsubscribe request
+#[derive(Debug, Deserialize)]
+pub struct HubRequest {
+    #[serde(rename = "hub.mode")]
+    pub mode: String,
+    #[serde(rename = "hub.topic")]
+    pub topic: String,
+    #[serde(rename = "hub.callback")]
+    pub callback: String,
+    #[serde(rename = "hub.lease_seconds")]
+    pub lease_seconds: Option<u64>,
+    #[serde(rename = "hub.secret")]
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Subscribe,
+    Unsubscribe,
+}
+
+impl Mode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Subscribe => "subscribe",
+            Self::Unsubscribe => "unsubscribe",
+        }
+    }
+}
+
+/// A validated request whose intent still has to be verified with the subscriber
+#[derive(Debug, Clone)]
+pub struct Intent {
+    pub mode: Mode,
+    pub topic: String,
+    pub callback: String,
+    pub lease_seconds: u64,
+    pub secret: Option<String>,
+}
+
+impl HubRequest {
+    /// Checks the request can be honored before it is accepted for verification
+    ///
+    /// `public_url` is the server's configured base URL; only its own feed can be a topic.
+    pub fn validate(self, public_url: &str) -> Result<Intent> {
+        let mode = match self.mode.as_str() {
+            "subscribe" => Mode::Subscribe,
+            "unsubscribe" => Mode::Unsubscribe,
+            other => {
+                return Err(PaiError::InvalidArgument(format!(
+                    "Unsupported hub.mode '{other}'. Expected subscribe or unsubscribe."
+                )))
+            }
+        };
+
+        topic_filter(&self.topic, public_url)?;
+
+        let callback = Url::parse(&self.callback)
+            .map_err(|e| PaiError::InvalidArgument(format!("Invalid hub.callback '{}': {e}", self.callback)))?;
+        if !matches!(callback.scheme(), "http" | "https") {
+            return Err(PaiError::InvalidArgument(format!(
+                "Invalid hub.callback '{}': must be an http or https URL",
+                self.callback
+            )));
+        }
+
+        let secret = self.secret.filter(|secret| !secret.is_empty());
+        if secret.as_ref().is_some_and(|secret| secret.len() >= MAX_SECRET_LEN) {
+            return Err(PaiError::InvalidArgument(format!(
+                "hub.secret must be shorter than {MAX_SECRET_LEN} bytes"
+            )));
+        }
+
+        Ok(Intent {
+            mode,
+            topic: self.topic,
+            callback: self.callback,
+            lease_seconds: self
+                .lease_seconds
+                .unwrap_or(DEFAULT_LEASE_SECONDS)
+                .clamp(MIN_LEASE_SECONDS, MAX_LEASE_SECONDS),
+            secret,
+        })
+    }
+}
+
+impl Intent {
+    /// Checks that a new subscription stays within [`MAX_SUBSCRIPTIONS_PER_CALLBACK`] and
+    /// [`MAX_SUBSCRIPTIONS_PER_TOPIC`]; renewals and unsubscribes always pass
+    pub fn check_limits(&self, subscriptions: &[WebSubSubscription]) -> Result<()> {
+        let renewal = subscriptions
+            .iter()
+            .any(|s| s.topic == self.topic && s.callback == self.callback);
+        if self.mode == Mode::Unsubscribe || renewal {
+            return Ok(());
+        }
+
+        let for_callback = subscriptions.iter().filter(|s| s.callback == self.callback).count();
+        if for_callback >= MAX_SUBSCRIPTIONS_PER_CALLBACK {
+            return Err(PaiError::InvalidArgument(format!(
+                "hub.callback '{}' already has {MAX_SUBSCRIPTIONS_PER_CALLBACK} subscriptions",
+                self.callback
+            )));
+        }
+        let for_topic = subscriptions.iter().filter(|s| s.topic == self.topic).count();
+        if for_topic >= MAX_SUBSCRIPTIONS_PER_TOPIC {
+            return Err(PaiError::InvalidArgument(format!(
+                "hub.topic '{}' already has {MAX_SUBSCRIPTIONS_PER_TOPIC} subscribers",
+                self.topic
+            )));
+        }
+        Ok(())
+    }
+
+    /// The subscription to store once a subscribe intent is verified
+    pub fn into_subscription(self) -> WebSubSubscription {
+        let now = Utc::now();
+        WebSubSubscription {
+            topic: self.topic,
+            callback: self.callback,
+            secret: self.secret,
+            expires_at: (now + ChronoDuration::seconds(self.lease_seconds as i64)).to_rfc3339(),
+            verified_at: now.to_rfc3339(),
+        }
+    }
+}
+
+/// Whether the subscription's lease has not run out at `now`
+pub fn is_active(subscription: &WebSubSubscription, now: DateTime<Utc>) -> bool {
+    DateTime::parse_from_rfc3339(&subscription.expires_at).is_ok_and(|expires_at| expires_at > now)
+}
+
+/// Parses a topic URL into the filter its query string describes
+///
+/// Topics are this server's `/rss.xml` under `public_url`, optionally with the same filters
+/// `/api/feed` accepts, so subscribers can follow a single source.
+pub fn topic_filter(topic: &str, public_url: &str) -> Result<ListFilter> {
+    let url = Url::parse(topic).map_err(|e| PaiError::InvalidArgument(format!("Invalid hub.topic '{topic}': {e}")))?;
+    let feed = FeedLinks::new(public_url, None).topic;
+    let feed =
+        Url::parse(&feed).map_err(|e| PaiError::InvalidArgument(format!("Invalid public_url '{public_url}': {e}")))?;
+    let same_feed = url.scheme() == feed.scheme()
+        && url.host_str() == feed.host_str()
+        && url.port_or_known_default() == feed.port_or_known_default()
+        && url.path() == feed.path();
+    if !same_feed {
+        return Err(PaiError::InvalidArgument(format!(
+            "Unknown hub.topic '{topic}'. Only {feed} can be subscribed to."
+        )));
+    }
+
+    let uri: Uri = format!("{TOPIC_PATH}?{}", url.query().unwrap_or(""))
+        .parse()
+        .map_err(|e| PaiError::InvalidArgument(format!("Invalid hub.topic '{topic}': {e}")))?;
+    let Query(query) = Query::<FeedQuery>::try_from_uri(&uri)
+        .map_err(|e| PaiError::InvalidArgument(format!("Invalid hub.topic '{topic}': {e}")))?;
+    query.into_filter()
+}
+
+/// The hub and self links advertised by a feed, in its body and `Link` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedLinks {
+    pub hub: String,
+    pub topic: String,
+}
+
+impl FeedLinks {
+    /// Links for the feed served under `base_url` with the given raw query string
+    pub fn new(base_url: &str, query: Option<&str>) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+        let topic = match query.filter(|query| !query.is_empty()) {
+            Some(query) => format!("{base_url}{TOPIC_PATH}?{query}"),
+            None => format!("{base_url}{TOPIC_PATH}"),
+        };
+        Self { hub: format!("{base_url}{HUB_PATH}"), topic }
+    }
+
+    /// Links for a subscribed topic; the hub is the `/websub` endpoint next to its `/rss.xml`
+    pub fn for_topic(topic: &str) -> Self {
+        let base = topic.split('?').next().unwrap_or(topic);
+        let base_url = base.strip_suffix(TOPIC_PATH).unwrap_or(base);
+        Self { hub: format!("{base_url}{HUB_PATH}"), topic: topic.to_string() }
+    }
+
+    /// `<atom:link>` elements for an RSS channel
+    pub fn atom_extension(&self) -> AtomExtension {
+        let mut hub = Link::default();
+        hub.set_rel("hub");
+        hub.set_href(self.hub.as_str());
+
+        let mut topic = Link::default();
+        topic.set_rel("self");
+        topic.set_href(self.topic.as_str());
+        topic.set_mime_type(Some("application/rss+xml".to_string()));
+
+        AtomExtensionBuilder::default().links(vec![hub, topic]).build()
+    }
+
+    /// Value for an HTTP `Link` header
+    pub fn header_value(&self) -> Option<HeaderValue> {
+        HeaderValue::from_str(&format!(r#"<{}>; rel="hub", <{}>; rel="self""#, self.hub, self.topic)).ok()
+    }
+}
+
+/// Verifies subscriber intent and delivers feed updates
+///
+/// Anyone can name a callback, so the hub only connects to public addresses, checked after DNS
+/// resolution and again on every redirect, like the Webmention verifier.
+pub struct Hub {
+    client: reqwest::Client,
+    public_only: bool,
+    pending: Arc<Semaphore>,
+}
+
+impl Default for Hub {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+impl Hub {
+    fn new(public_only: bool) -> Self {
+        let client = outbound::client(concat!("pai/", env!("CARGO_PKG_VERSION"), " (WebSub hub)"), public_only);
+        Self { client, public_only, pending: Arc::new(Semaphore::new(MAX_PENDING)) }
+    }
+
+    /// A hub that also calls back loopback and private addresses, for tests against local subscribers
+    #[cfg(test)]
+    pub fn allowing_private_addresses() -> Self {
+        Self::new(false)
+    }
+
+    /// Reserves a verification slot, or `None` when [`MAX_PENDING`] intents are already being verified
+    pub fn reserve(&self) -> Option<OwnedSemaphorePermit> {
+        Arc::clone(&self.pending).try_acquire_owned().ok()
+    }
+
+    /// Checks that a callback resolves to public addresses before a request is accepted
+    pub async fn check_callback(&self, callback: &str) -> Result<()> {
+        if !self.public_only {
+            return Ok(());
+        }
+        let url = callback_url(callback)?;
+        outbound::ensure_public(&url)
+            .await
+            .map_err(|e| PaiError::InvalidArgument(format!("Invalid hub.callback: {e}")))
+    }
+
+    /// The callback as a URL, refused if it names a private address literal
+    ///
+    /// Host names are checked by the client's resolver when it connects.
+    fn target(&self, callback: &str) -> Result<Url> {
+        let url = callback_url(callback)?;
+        if self.public_only && !has_public_host(&url) {
+            return Err(PaiError::Fetch(format!(
+                "Refusing to call {callback}: not a public address"
+            )));
+        }
+        Ok(url)
+    }
+
+    /// Asks the subscriber to confirm the request by echoing a random challenge
+    ///
+    /// Returns `Ok(false)` if the callback answered but did not confirm.
+    pub async fn verify(&self, intent: &Intent) -> Result<bool> {
+        let challenge = challenge()?;
+        let mut url = self.target(&intent.callback)?;
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("hub.mode", intent.mode.as_str())
+                .append_pair("hub.topic", &intent.topic)
+                .append_pair("hub.challenge", &challenge);
+            if intent.mode == Mode::Subscribe {
+                query.append_pair("hub.lease_seconds", &intent.lease_seconds.to_string());
+            }
+        }
+
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| PaiError::Fetch(format!("Failed to verify {}: {}", intent.callback, error_chain(&e))))?;
+        if !response.status().is_success() {
+            return Ok(false);
+        }
+
+        let body = response
+            .text()
+            .await
+            .map_err(|e| PaiError::Fetch(format!("Failed to read verification response: {e}")))?;
+        Ok(body.trim() == challenge)
+    }
+
+    /// Sends the topic's current content to a subscriber
+    pub async fn deliver(&self, subscription: &WebSubSubscription, body: String) -> Result<()> {
+        let links = FeedLinks::for_topic(&subscription.topic);
+        let mut request = self
+            .client
+            .post(self.target(&subscription.callback)?)
+            .header(reqwest::header::CONTENT_TYPE, "application/rss+xml; charset=utf-8");
+        if let Some(value) = links.header_value() {
+            request = request.header(reqwest::header::LINK, value.as_bytes());
+        }
+        if let Some(secret) = &subscription.secret {
+            request = request.header("X-Hub-Signature", sign_payload(secret, body.as_bytes()));
+        }
+
+        let response = request.body(body).send().await.map_err(|e| {
+            PaiError::Fetch(format!(
+                "Failed to notify {}: {}",
+                subscription.callback,
+                error_chain(&e)
+            ))
+        })?;
+        if !response.status().is_success() {
+            return Err(PaiError::Fetch(format!(
+                "{} answered HTTP {}",
+                subscription.callback,
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn callback_url(callback: &str) -> Result<Url> {
+    Url::parse(callback).map_err(|e| PaiError::InvalidArgument(format!("Invalid hub.callback '{callback}': {e}")))
+}
+
+/// 128 bits from the operating system's random number generator, as hex
+fn challenge() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| PaiError::Io(std::io::Error::other(e.to_string())))?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pai_core::SourceKind;
+
+    const PUBLIC_URL: &str = "https://pai.example.com";
+
+    fn request(mode: &str, topic: &str) -> HubRequest {
+        HubRequest {
+            mode: mode.to_string(),
+            topic: topic.to_string(),
+            callback: "https://reader.example.com/push".to_string(),
+            lease_seconds: Some(1),
+            secret: None,
+        }
+    }
+
+    #[test]
+    fn validates_requests_and_clamps_leases() {
+        let intent = request("subscribe", "https://pai.example.com/rss.xml?source_kind=bluesky")
+            .validate(PUBLIC_URL)
+            .unwrap();
+        assert_eq!(intent.mode, Mode::Subscribe);
+        assert_eq!(intent.lease_seconds, MIN_LEASE_SECONDS);
+
+        assert!(request("publish", "https://pai.example.com/rss.xml")
+            .validate(PUBLIC_URL)
+            .is_err());
+        assert!(request("subscribe", "https://pai.example.com/api/feed")
+            .validate(PUBLIC_URL)
+            .is_err());
+        assert!(request("subscribe", "https://pai.example.com/rss.xml?limit=0")
+            .validate(PUBLIC_URL)
+            .is_err());
+
+        let mut ftp = request("subscribe", "https://pai.example.com/rss.xml");
+        ftp.callback = "ftp://reader.example.com/push".to_string();
+        assert!(ftp.validate(PUBLIC_URL).is_err());
+
+        for foreign in [
+            "https://evil.example.com/rss.xml",
+            "http://pai.example.com/rss.xml",
+            "https://pai.example.com:8443/rss.xml",
+            "https://pai.example.com/other/rss.xml",
+        ] {
+            assert!(request("subscribe", foreign).validate(PUBLIC_URL).is_err(), "{foreign}");
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_private_callbacks() {
+        let hub = Hub::default();
+        for callback in [
+            "http://localhost:9/push",
+            "http://127.0.0.1:8080/push",
+            "http://[::1]/push",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.0.0.2/push",
+        ] {
+            let error = hub.check_callback(callback).await.unwrap_err();
+            assert!(matches!(error, PaiError::InvalidArgument(_)), "{callback}: {error}");
+        }
+        assert!(Hub::allowing_private_addresses()
+            .check_callback("http://127.0.0.1:8080/push")
+            .await
+            .is_ok());
+
+        let intent = Intent {
+            mode: Mode::Subscribe,
+            topic: "https://pai.example.com/rss.xml".to_string(),
+            callback: "http://127.0.0.1:9/push".to_string(),
+            lease_seconds: MIN_LEASE_SECONDS,
+            secret: None,
+        };
+        let error = hub.verify(&intent).await.unwrap_err();
+        assert!(error.to_string().contains("public address"), "{error}");
+        let error = hub
+            .deliver(&intent.into_subscription(), String::new())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("public address"), "{error}");
+    }
+
+    #[test]
+    fn limits_pending_verifications_and_subscriptions() {
+        let hub = Hub::default();
+        let permits: Vec<_> = (0..MAX_PENDING).map_while(|_| hub.reserve()).collect();
+        assert_eq!(permits.len(), MAX_PENDING);
+        assert!(hub.reserve().is_none());
+        drop(permits);
+        assert!(hub.reserve().is_some());
+
+        let intent = |mode: &str, topic: &str, callback: &str| {
+            let mut request = request(mode, topic);
+            request.callback = callback.to_string();
+            request.validate(PUBLIC_URL).unwrap()
+        };
+        let callback = "https://reader.example.com/push";
+        let subscriptions: Vec<_> = (0..MAX_SUBSCRIPTIONS_PER_CALLBACK)
+            .map(|i| intent("subscribe", &format!("{PUBLIC_URL}/rss.xml?source_id={i}"), callback).into_subscription())
+            .collect();
+        let topic = format!("{PUBLIC_URL}/rss.xml?source_id=0");
+        let extra = format!("{PUBLIC_URL}/rss.xml?source_id=extra");
+        assert!(intent("subscribe", &extra, callback)
+            .check_limits(&subscriptions)
+            .is_err());
+        assert!(intent("subscribe", &topic, callback)
+            .check_limits(&subscriptions)
+            .is_ok());
+        assert!(intent("unsubscribe", &extra, callback)
+            .check_limits(&subscriptions)
+            .is_ok());
+
+        let subscribers: Vec<_> = (0..MAX_SUBSCRIPTIONS_PER_TOPIC)
+            .map(|i| intent("subscribe", &topic, &format!("https://reader{i}.example.com/push")).into_subscription())
+            .collect();
+        assert!(intent("subscribe", &topic, callback)
+            .check_limits(&subscribers)
+            .is_err());
+        assert!(intent("subscribe", &extra, callback).check_limits(&subscribers).is_ok());
+    }
+
+    #[test]
+    fn challenges_are_random_hex() {
+        let (first, second) = (challenge().unwrap(), challenge().unwrap());
+        assert_eq!(first.len(), 32);
+        assert!(first.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn topics_carry_feed_filters_and_hub_links() {
+        let topic = "https://pai.example.com/feeds/rss.xml?source_kind=bluesky&limit=5";
+        let filter = topic_filter(topic, "https://pai.example.com/feeds/").unwrap();
+        assert_eq!(filter.source_kind, Some(SourceKind::Bluesky));
+        assert_eq!(filter.limit, Some(5));
+
+        let links = FeedLinks::for_topic(topic);
+        assert_eq!(links.hub, "https://pai.example.com/feeds/websub");
+        assert_eq!(
+            links,
+            FeedLinks::new("https://pai.example.com/feeds/", Some("source_kind=bluesky&limit=5"))
+        );
+        assert_eq!(
+            FeedLinks::new("http://localhost:8080", None).topic,
+            "http://localhost:8080/rss.xml"
+        );
+    }
+}
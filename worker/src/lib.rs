@@ -1,5 +1,10 @@
-use pai_core::{CorsConfig, Item, ListFilter, SourceKind};
+use pai_core::{
+    cache_control, BearBlogConfig, BlueskyConfig, CacheValidators, Config, ConfigPaths, CorsConfig, ErrorResponse,
+    FeedQuery, FeedResponse, Item, LeafletConfig, ListFilter, RetentionConfig, RetentionRule, SourceCounts, SourceKind,
+    StatusResponse, SubstackConfig, SyncResponse, DEFAULT_CACHE_MAX_AGE,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use wasm_bindgen::JsValue;
 use worker::*;
 
@@ -44,6 +49,7 @@ struct Sources {
     bluesky: String,
     leaflet: String,
     bearblog: String,
+    feed: String,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -52,61 +58,17 @@ struct ScheduledSync {
     schedule: String,
 }
 
-#[derive(Deserialize)]
-struct SyncConfig {
-    substack: Option<SubstackConfig>,
-    bluesky: Option<BlueskyConfig>,
-    leaflet: Vec<LeafletConfig>,
-    bearblog: Vec<BearBlogConfig>,
-}
-
-#[derive(Deserialize)]
-struct SubstackConfig {
-    base_url: String,
-}
-
-#[derive(Deserialize)]
-struct BlueskyConfig {
-    handle: String,
-}
-
-#[derive(Deserialize)]
-struct LeafletConfig {
-    id: String,
-    base_url: String,
-}
-
-#[derive(Deserialize)]
-struct BearBlogConfig {
-    id: String,
-    base_url: String,
-}
-
-#[derive(Deserialize)]
-struct FeedParams {
-    source_kind: Option<SourceKind>,
-    source_id: Option<String>,
-    limit: Option<usize>,
-    since: Option<String>,
-    q: Option<String>,
-}
-
-#[derive(Serialize)]
-struct FeedResponse {
-    items: Vec<Item>,
-}
-
-#[derive(Serialize)]
-struct StatusResponse {
-    status: &'static str,
-    version: &'static str,
-    total_items: usize,
-    sources: std::collections::HashMap<String, usize>,
-}
-
 #[event(fetch)]
 async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
-    let cors_config = load_cors_config(&env);
+    // A broken config fails `/api/sync`, which needs the sources, but not the read-only routes or
+    // `/status`; CORS falls back to the defaults, which only let through requests without an `Origin`
+    let (cors_config, cache_max_age) = load_config(&env).map_or_else(
+        |e| {
+            console_error!("Worker configuration is invalid: {}", e);
+            (CorsConfig::default(), DEFAULT_CACHE_MAX_AGE)
+        },
+        |config| (config.cors, config.server.cache_max_age),
+    );
 
     if req.method() == Method::Options {
         return handle_preflight(&req, &cors_config);
@@ -118,7 +80,7 @@ async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
 
     let origin = req.headers().get("Origin").ok().flatten();
 
-    let router = Router::new();
+    let router = Router::with_data(cache_max_age);
     let mut response = router
         .get_async("/", |req, _ctx| async move {
             let url = req
@@ -146,19 +108,20 @@ async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
 
             Response::from_json(&docs)
         })
+        .get("/openapi.json", |_req, _ctx| Response::from_json(&pai_core::openapi()))
         .get_async("/api/feed", |req, ctx| async move { handle_feed(req, ctx).await })
-        .get_async("/api/item/:id", |_req, ctx| async move {
+        .get_async("/api/item/:id", |req, ctx| async move {
             let id = ctx
                 .param("id")
                 .ok_or_else(|| Error::RustError("Missing id parameter".into()))?;
-            handle_item(id, &ctx).await
+            handle_item(&req, id, &ctx).await
         })
         .post_async("/api/sync", |_req, ctx| async move {
             match run_sync(&ctx.env).await {
-                Ok(_) => Response::from_json(&serde_json::json!({
-                    "status": "success",
-                    "message": "Sync completed successfully"
-                })),
+                Ok(_) => Response::from_json(&SyncResponse {
+                    status: "success".to_string(),
+                    message: "Sync completed successfully".to_string(),
+                }),
                 Err(e) => Response::error(format!("Sync failed: {e}"), 500),
             }
         })
@@ -177,7 +140,7 @@ async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 .all()
                 .await?;
 
-            let mut sources = std::collections::HashMap::new();
+            let mut sources = BTreeMap::new();
             if let Ok(results) = sources_result.results::<serde_json::Value>() {
                 for result in results {
                     if let (Some(kind), Some(count)) = (
@@ -189,7 +152,14 @@ async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 }
             }
 
-            let status = StatusResponse { status: "ok", version: env!("CARGO_PKG_VERSION"), total_items, sources };
+            let status = StatusResponse {
+                status: "ok".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                uptime_seconds: None,
+                database_path: None,
+                total_items,
+                sources: SourceCounts::ByKind(sources),
+            };
             Response::from_json(&status)
         })
         .run(req, env)
@@ -210,38 +180,69 @@ async fn scheduled(_event: ScheduledEvent, env: Env, _ctx: ScheduleContext) {
     }
 }
 
-async fn handle_feed(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+async fn handle_feed(req: Request, ctx: RouteContext<u64>) -> Result<Response> {
     let url = req.url()?;
-    let params: FeedParams = serde_urlencoded::from_str(url.query().unwrap_or(""))
-        .map_err(|e| Error::RustError(format!("Invalid query parameters: {e}")))?;
-
-    let filter = ListFilter {
-        source_kind: params.source_kind,
-        source_id: params.source_id,
-        limit: Some(params.limit.unwrap_or(20)),
-        since: params.since,
-        query: params.q,
+    let query: FeedQuery = match serde_urlencoded::from_str(url.query().unwrap_or("")) {
+        Ok(query) => query,
+        Err(e) => return error_json(format!("Invalid query parameters: {e}"), 400),
+    };
+    let filter = match query.into_filter() {
+        Ok(filter) => filter,
+        Err(e) => return error_json(e.to_string(), 400),
     };
 
     let db = ctx.env.d1("DB")?;
     let items = query_items(&db, &filter).await?;
 
-    let response = FeedResponse { items };
-    Response::from_json(&response)
+    let validators = CacheValidators::for_items("feed", &items);
+    let response = FeedResponse { count: items.len(), items };
+    cached_json(&req, ctx.data, &validators, &response)
 }
 
-async fn handle_item(id: &str, ctx: &RouteContext<()>) -> Result<Response> {
+async fn handle_item(req: &Request, id: &str, ctx: &RouteContext<u64>) -> Result<Response> {
     let db = ctx.env.d1("DB")?;
     let stmt = db.prepare("SELECT * FROM items WHERE id = ?1").bind(&[id.into()])?;
 
     let result = stmt.first::<Item>(None).await?;
 
     match result {
-        Some(item) => Response::from_json(&item),
-        None => Response::error("Item not found", 404),
+        Some(item) => {
+            let validators = CacheValidators::for_items("item", std::slice::from_ref(&item));
+            cached_json(req, ctx.data, &validators, &item)
+        }
+        None => error_json(format!("Item '{id}' not found"), 404),
     }
 }
 
+/// Builds a JSON error response matching the HTTP server's error body
+fn error_json(message: String, status: u16) -> Result<Response> {
+    Ok(Response::from_json(&ErrorResponse { error: message })?.with_status(status))
+}
+
+/// Builds a JSON response with `ETag`, `Last-Modified` and `Cache-Control` headers
+///
+/// Answers with `304 Not Modified` when the request's conditional headers match `validators`.
+/// `max_age` is the configured `server.cache_max_age`, loaded once per request.
+fn cached_json<T: Serialize>(req: &Request, max_age: u64, validators: &CacheValidators, body: &T) -> Result<Response> {
+    let if_none_match = req.headers().get("If-None-Match")?;
+    let if_modified_since = req.headers().get("If-Modified-Since")?;
+
+    let mut response = if validators.is_not_modified(if_none_match.as_deref(), if_modified_since.as_deref()) {
+        Response::empty()?.with_status(304)
+    } else {
+        Response::from_json(body)?
+    };
+
+    let headers = response.headers_mut();
+    headers.set("ETag", &validators.etag)?;
+    if let Some(last_modified) = validators.last_modified_header() {
+        headers.set("Last-Modified", &last_modified)?;
+    }
+    headers.set("Cache-Control", &cache_control(max_age))?;
+
+    Ok(response)
+}
+
 async fn query_items(db: &D1Database, filter: &ListFilter) -> Result<Vec<Item>> {
     let mut query = String::from(
         "SELECT id, source_kind, source_id, author, title, summary, url, content_html, published_at, created_at FROM items WHERE 1=1"
@@ -286,12 +287,12 @@ async fn query_items(db: &D1Database, filter: &ListFilter) -> Result<Vec<Item>>
 }
 
 async fn run_sync(env: &Env) -> Result<()> {
-    let config = load_sync_config(env)?;
+    let Config { sources, retention, .. } = load_config(env)?;
 
     let db = env.d1("DB")?;
     let mut synced = 0;
 
-    if let Some(substack_config) = config.substack {
+    if let Some(substack_config) = sources.substack.filter(|c| c.enabled) {
         match sync_substack(&substack_config, &db).await {
             Ok(count) => {
                 console_log!("Synced {} items from Substack", count);
@@ -301,7 +302,7 @@ async fn run_sync(env: &Env) -> Result<()> {
         }
     }
 
-    if let Some(bluesky_config) = config.bluesky {
+    if let Some(bluesky_config) = sources.bluesky.filter(|c| c.enabled) {
         match sync_bluesky(&bluesky_config, &db).await {
             Ok(count) => {
                 console_log!("Synced {} items from Bluesky", count);
@@ -311,7 +312,7 @@ async fn run_sync(env: &Env) -> Result<()> {
         }
     }
 
-    for leaflet_config in config.leaflet {
+    for leaflet_config in sources.leaflet.into_iter().filter(|c| c.enabled) {
         match sync_leaflet(&leaflet_config, &db).await {
             Ok(count) => {
                 console_log!("Synced {} items from Leaflet ({})", count, leaflet_config.id);
@@ -321,7 +322,7 @@ async fn run_sync(env: &Env) -> Result<()> {
         }
     }
 
-    for bearblog_config in config.bearblog {
+    for bearblog_config in sources.bearblog.into_iter().filter(|c| c.enabled) {
         match sync_bearblog(&bearblog_config, &db).await {
             Ok(count) => {
                 console_log!("Synced {} items from BearBlog ({})", count, bearblog_config.id);
@@ -331,67 +332,162 @@ async fn run_sync(env: &Env) -> Result<()> {
         }
     }
 
+    for feed_config in sources.feed.into_iter().filter(|c| c.enabled) {
+        match sync_rss_feed(&feed_config.url, "feed", &feed_config.id, &db).await {
+            Ok(count) => {
+                console_log!("Synced {} items from feed ({})", count, feed_config.id);
+                synced += count;
+            }
+            Err(e) => console_error!("Feed sync failed for {}: {}", feed_config.id, e),
+        }
+    }
+
     console_log!("Sync completed: {} total items", synced);
+
+    if retention.after_sync {
+        match prune(&retention, &db).await {
+            Ok(count) => console_log!("Pruned {} items", count),
+            Err(e) => console_error!("Pruning failed: {}", e),
+        }
+    }
     Ok(())
 }
 
-fn load_sync_config(env: &Env) -> Result<SyncConfig> {
-    let substack = env
-        .var("SUBSTACK_URL")
-        .ok()
-        .map(|url| SubstackConfig { base_url: url.to_string() });
-
-    let bluesky = env
-        .var("BLUESKY_HANDLE")
-        .ok()
-        .map(|handle| BlueskyConfig { handle: handle.to_string() });
-
-    let leaflet = if let Ok(urls) = env.var("LEAFLET_URLS") {
-        urls.to_string()
-            .split(',')
-            .filter_map(|entry| {
-                let parts: Vec<&str> = entry.trim().splitn(2, ':').collect();
-                if parts.len() == 2 {
-                    Some(LeafletConfig { id: parts[0].to_string(), base_url: parts[1].to_string() })
-                } else {
-                    None
-                }
-            })
-            .collect()
-    } else {
-        Vec::new()
-    };
+/// Items read per D1 query while pruning
+const PRUNE_PAGE_SIZE: usize = 500;
 
-    let bearblog = if let Ok(urls) = env.var("BEARBLOG_URLS") {
-        urls.to_string()
-            .split(',')
-            .filter_map(|entry| {
-                let parts: Vec<&str> = entry.trim().splitn(2, ':').collect();
-                if parts.len() == 2 {
-                    Some(BearBlogConfig { id: parts[0].to_string(), base_url: parts[1].to_string() })
-                } else {
-                    None
-                }
-            })
-            .collect()
-    } else {
-        Vec::new()
-    };
+#[derive(Deserialize)]
+struct SourceRow {
+    source_kind: SourceKind,
+    source_id: String,
+}
 
-    Ok(SyncConfig { substack, bluesky, leaflet, bearblog })
+/// Delete the items the `[retention]` rules no longer keep, returning how many were deleted
+///
+/// D1 doesn't store tags, so every item counts as untagged: `keep_tags` never match, and items to
+/// keep have to be listed in `pinned`. Each source is read newest first, a page at a time.
+async fn prune(retention: &RetentionConfig, db: &D1Database) -> Result<usize> {
+    let sources: Vec<SourceRow> = db
+        .prepare("SELECT DISTINCT source_kind, source_id FROM items")
+        .all()
+        .await?
+        .results()?;
+
+    let now = chrono::Utc::now();
+    let mut pruned = 0;
+    for source in sources {
+        let Some(rule) = retention.rule_for(source.source_kind, &source.source_id) else { continue };
+        // Items on earlier pages that count toward `max_items`
+        let mut newer = 0;
+        let mut after = None;
+        loop {
+            let page = prune_page(db, &source, after.as_ref()).await?;
+            let Some(oldest) = page.last() else { break };
+            after = Some((oldest.published_at.clone(), oldest.id.clone()));
+
+            let page_rule =
+                RetentionRule { max_items: rule.max_items.map(|max| max.saturating_sub(newer)), ..rule.clone() };
+            newer += page.iter().filter(|item| !retention.keeps(rule, item)).count();
+            let statements = retention
+                .expired(&page_rule, &page, now)
+                .into_iter()
+                .map(|item| {
+                    db.prepare("DELETE FROM items WHERE id = ?1")
+                        .bind(&[item.id.clone().into()])
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if !statements.is_empty() {
+                pruned += statements.len();
+                db.batch(statements).await?;
+            }
+            if page.len() < PRUNE_PAGE_SIZE {
+                break;
+            }
+        }
+    }
+    Ok(pruned)
 }
 
-/// Load CORS configuration from environment variables
-fn load_cors_config(env: &Env) -> CorsConfig {
-    let allowed_origins = env
-        .var("CORS_ALLOWED_ORIGINS")
-        .ok()
-        .map(|origins| origins.to_string().split(',').map(|s| s.trim().to_string()).collect())
-        .unwrap_or_default();
+/// A page of a source's items, newest first, starting after the `(published_at, id)` of the last one read
+///
+/// Pages are keyed on the last item rather than an offset, so deleting from earlier pages doesn't
+/// skip any. Content is left out, as retention doesn't look at it.
+async fn prune_page(db: &D1Database, source: &SourceRow, after: Option<&(String, String)>) -> Result<Vec<Item>> {
+    let mut query = String::from(
+        "SELECT id, source_kind, source_id, author, title, summary, url, NULL AS content_html, published_at, created_at \
+         FROM items WHERE source_kind = ? AND source_id = ?",
+    );
+    let mut bindings = vec![source.source_kind.to_string().into(), source.source_id.clone().into()];
+    if let Some((published_at, id)) = after {
+        query.push_str(" AND (published_at < ? OR (published_at = ? AND id > ?))");
+        bindings.push(published_at.clone().into());
+        bindings.push(published_at.clone().into());
+        bindings.push(id.clone().into());
+    }
+    query.push_str(" ORDER BY published_at DESC, id LIMIT ?");
+    bindings.push((PRUNE_PAGE_SIZE as f64).into());
+
+    db.prepare(&query).bind(&bindings)?.all().await?.results()
+}
 
-    let dev_key = env.var("CORS_DEV_KEY").ok().map(|k| k.to_string());
+/// Load config from `PAI_*` bindings, the same keys `pai` reads from its environment
+///
+/// There are no config files here, so everything comes from `[vars]` and secrets in wrangler.toml.
+fn load_config(env: &Env) -> Result<Config> {
+    Config::load_layered(&ConfigPaths::default(), |name| {
+        env.var(name)
+            .ok()
+            .map(|value| value.to_string())
+            .or_else(|| legacy_var(env, name))
+    })
+    .map_err(|e| Error::RustError(e.to_string()))
+}
+
+/// Answers a `PAI_*` lookup from the variables the Worker read before it shared the config scheme
+///
+/// `SUBSTACK_URL`, `BLUESKY_HANDLE`, `LEAFLET_URLS`/`BEARBLOG_URLS` (`id:url,id:url`),
+/// `CORS_ALLOWED_ORIGINS`, `CORS_DEV_KEY` and `CACHE_MAX_AGE` keep working; sources they
+/// configure are enabled. A `PAI_*` binding for the same key takes precedence.
+fn legacy_var(env: &Env, name: &str) -> Option<String> {
+    let var = |legacy: &str| env.var(legacy).ok().map(|value| value.to_string());
+    match name {
+        "PAI_SOURCES__SUBSTACK__BASE_URL" => var("SUBSTACK_URL"),
+        "PAI_SOURCES__SUBSTACK__ENABLED" => var("SUBSTACK_URL").map(|_| "true".to_string()),
+        "PAI_SOURCES__BLUESKY__HANDLE" => var("BLUESKY_HANDLE"),
+        "PAI_SOURCES__BLUESKY__ENABLED" => var("BLUESKY_HANDLE").map(|_| "true".to_string()),
+        "PAI_CORS__ALLOWED_ORIGINS" => var("CORS_ALLOWED_ORIGINS"),
+        "PAI_CORS__DEV_KEY" => var("CORS_DEV_KEY"),
+        "PAI_SERVER__CACHE_MAX_AGE" => var("CACHE_MAX_AGE"),
+        _ => {
+            for (key, legacy) in [("LEAFLET", "LEAFLET_URLS"), ("BEARBLOG", "BEARBLOG_URLS")] {
+                let Some(rest) = name.strip_prefix(&format!("PAI_SOURCES__{key}__")) else { continue };
+                let (index, field) = rest.split_once("__")?;
+                let (id, base_url) = parse_id_urls(&var(legacy)?).into_iter().nth(index.parse().ok()?)?;
+                return match field {
+                    "ENABLED" => Some("true".to_string()),
+                    "ID" => Some(id),
+                    "BASE_URL" => Some(base_url),
+                    _ => None,
+                };
+            }
+            None
+        }
+    }
+}
 
-    CorsConfig { allowed_origins, dev_key }
+/// Parses `id:url,id:url`, skipping entries without an ID
+fn parse_id_urls(value: &str) -> Vec<(String, String)> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let parts: Vec<&str> = entry.trim().splitn(2, ':').collect();
+            if parts.len() == 2 {
+                Some((parts[0].to_string(), parts[1].to_string()))
+            } else {
+                None
+            }
+        })
+        .collect()
 }
 
 /// Check if request is authorized for CORS
@@ -609,8 +705,12 @@ async fn sync_leaflet(config: &LeafletConfig, db: &D1Database) -> Result<usize>
 
 async fn sync_bearblog(config: &BearBlogConfig, db: &D1Database) -> Result<usize> {
     let feed_url = format!("{}/feed/?type=rss", config.base_url.trim_end_matches('/'));
+    sync_rss_feed(&feed_url, "bearblog", &config.id, db).await
+}
 
-    let mut req = Request::new(&feed_url, Method::Get)?;
+/// Stores the items of an RSS feed under `source_kind` and `source_id`
+async fn sync_rss_feed(feed_url: &str, source_kind: &str, source_id: &str, db: &D1Database) -> Result<usize> {
+    let mut req = Request::new(feed_url, Method::Get)?;
     req.headers_mut()?.set("User-Agent", "pai-worker/0.1.0")?;
 
     let mut resp = Fetch::Request(req).send().await?;
@@ -644,8 +744,8 @@ async fn sync_bearblog(config: &BearBlogConfig, db: &D1Database) -> Result<usize
 
         stmt.bind(&[
             id.into(),
-            "bearblog".into(),
-            config.id.clone().into(),
+            source_kind.into(),
+            source_id.into(),
             author.map(|s| s.into()).unwrap_or(JsValue::NULL),
             title.map(|s| s.into()).unwrap_or(JsValue::NULL),
             summary.map(|s| s.into()).unwrap_or(JsValue::NULL),
@@ -726,6 +826,7 @@ mod tests {
         assert!(!docs.sources.bluesky.is_empty());
         assert!(!docs.sources.leaflet.is_empty());
         assert!(!docs.sources.bearblog.is_empty());
+        assert!(!docs.sources.feed.is_empty());
     }
 
     #[test]
@@ -824,17 +925,7 @@ mod tests {
     #[test]
     fn test_leaflet_config_parsing_multiple() {
         let urls = "id1:https://pub1.leaflet.pub,id2:https://pub2.leaflet.pub";
-        let configs: Vec<_> = urls
-            .split(',')
-            .filter_map(|entry| {
-                let parts: Vec<&str> = entry.trim().splitn(2, ':').collect();
-                if parts.len() == 2 {
-                    Some((parts[0].to_string(), parts[1].to_string()))
-                } else {
-                    None
-                }
-            })
-            .collect();
+        let configs = parse_id_urls(urls);
 
         assert_eq!(configs.len(), 2);
         assert_eq!(configs[0].0, "id1");
@@ -886,17 +977,7 @@ mod tests {
     #[test]
     fn test_bearblog_config_parsing_multiple() {
         let urls = "id1:https://blog1.bearblog.dev,id2:https://blog2.bearblog.dev";
-        let configs: Vec<_> = urls
-            .split(',')
-            .filter_map(|entry| {
-                let parts: Vec<&str> = entry.trim().splitn(2, ':').collect();
-                if parts.len() == 2 {
-                    Some((parts[0].to_string(), parts[1].to_string()))
-                } else {
-                    None
-                }
-            })
-            .collect();
+        let configs = parse_id_urls(urls);
 
         assert_eq!(configs.len(), 2);
         assert_eq!(configs[0].0, "id1");